@@ -0,0 +1,253 @@
+use clap::Parser;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use merged_lands::cli::Cli;
+use merged_lands::events::{CancellationToken, RunContext};
+use merged_lands::io::meta_schema::SeamSettings;
+use merged_lands::io::parsed_plugins::ParsedPlugin;
+use merged_lands::io::resolutions::Resolutions;
+use merged_lands::land::height_map::calculate_vertex_normals_map;
+use merged_lands::land::landscape_diff::LandscapeDiff;
+use merged_lands::land::terrain_map::{TerrainMap, Vec2};
+use merged_lands::merge::merge_strategy::MergeStrategy;
+use merged_lands::merge::relative_terrain_map::{recompute_vertex_normals, RelativeTerrainMap};
+use merged_lands::merge::resolve_conflict_strategy::ResolveConflictStrategy;
+use merged_lands::pipeline::{MergePipeline, MergeStage};
+use merged_lands::repair::seam_detection::repair_landmass_seams_until_fixed;
+use merged_lands::{merge_landmass_into, LandmassDiff};
+use std::ffi::OsStr;
+use std::sync::Arc;
+use tes3::esp::ObjectFlags;
+
+/// The number of cells along each side of the synthetic landmass.
+const GRID_SIZE: i32 = 8;
+
+/// The number of synthetic plugins merged by the end-to-end benchmark.
+const NUM_PLUGINS: i32 = 4;
+
+/// The environment variable with the path to a `Data Files` directory for the benchmark of
+/// real plugins. The plugins are read from the `Morrowind.ini` in the directory above it.
+/// If it is not set, the benchmark of real plugins is skipped.
+const DATA_FILES_ENV_VAR: &str = "MERGED_LANDS_BENCH_DATA_FILES";
+
+/// Returns the height map of the synthetic reference land at `coords`.
+fn reference_heights(coords: Vec2<i32>) -> TerrainMap<i32, 65> {
+    let mut terrain = [[0; 65]; 65];
+    for (y, row) in terrain.iter_mut().enumerate() {
+        for (x, height) in row.iter_mut().enumerate() {
+            let world_x = coords.x * 64 + x as i32;
+            let world_y = coords.y * 64 + y as i32;
+            *height = world_x * 2 - world_y;
+        }
+    }
+
+    terrain
+}
+
+/// Returns the height map of the synthetic plugin `seed` at `coords`. Each plugin adds a hill
+/// to the reference land, so plugins with nearby hills conflict with each other.
+fn plugin_heights(seed: i32, coords: Vec2<i32>) -> TerrainMap<i32, 65> {
+    const RADIUS: i32 = 24;
+
+    let center = Vec2::new(8 + (seed * 23) % 48, 56 - (seed * 17) % 48);
+
+    let mut terrain = reference_heights(coords);
+    for (y, row) in terrain.iter_mut().enumerate() {
+        for (x, height) in row.iter_mut().enumerate() {
+            let dx = x as i32 - center.x;
+            let dy = y as i32 - center.y;
+            let distance = dx * dx + dy * dy;
+            if distance < RADIUS * RADIUS {
+                *height += seed * (RADIUS * RADIUS - distance) / 16;
+            }
+        }
+    }
+
+    terrain
+}
+
+/// Creates a [LandscapeDiff] at `coords` with the height map and vertex normals of `plugin`.
+fn landscape_diff(
+    coords: Vec2<i32>,
+    reference: &TerrainMap<i32, 65>,
+    plugin: &TerrainMap<i32, 65>,
+) -> LandscapeDiff {
+    let height_map = RelativeTerrainMap::from_difference(reference, plugin);
+    let vertex_normals = LandscapeDiff::apply_mask(
+        &RelativeTerrainMap::from_difference(
+            &calculate_vertex_normals_map(reference),
+            &calculate_vertex_normals_map(plugin),
+        ),
+        Some(height_map.differences()),
+    );
+
+    LandscapeDiff {
+        coords,
+        flags: ObjectFlags::empty(),
+        height_map: Some(height_map),
+        vertex_normals: Some(vertex_normals),
+        world_map_data: None,
+        vertex_colors: None,
+        texture_indices: None,
+        plugins: Vec::new(),
+    }
+}
+
+/// Creates a [LandmassDiff] covering the synthetic landmass for the plugin `seed`.
+fn landmass_diff(seed: i32) -> LandmassDiff {
+    let plugin = Arc::new(ParsedPlugin::empty(&format!("Synthetic {}.esp", seed)));
+    let mut landmass = LandmassDiff::new(plugin);
+
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            let coords = Vec2::new(x, y);
+            let reference = reference_heights(coords);
+            let plugin = plugin_heights(seed + x + y, coords);
+            landmass.insert_land(landscape_diff(coords, &reference, &plugin));
+        }
+    }
+
+    landmass
+}
+
+/// Creates the [LandmassDiff] of merging every synthetic plugin without repairing seams.
+fn merged_landmass() -> LandmassDiff {
+    let mut merged = LandmassDiff::new(Arc::new(ParsedPlugin::empty("Merged Lands.esp")));
+    for seed in 1..=NUM_PLUGINS {
+        merge_landmass_into(
+            &mut merged,
+            &landmass_diff(seed),
+            None,
+            &Resolutions::default(),
+            &RunContext::default(),
+        )
+        .expect("safe");
+    }
+
+    merged
+}
+
+fn bench_from_difference(c: &mut Criterion) {
+    let coords = Vec2::new(0, 0);
+    let reference = reference_heights(coords);
+    let plugin = plugin_heights(1, coords);
+
+    c.bench_function("RelativeTerrainMap::from_difference", |b| {
+        b.iter(|| RelativeTerrainMap::from_difference(black_box(&reference), black_box(&plugin)))
+    });
+}
+
+fn bench_resolve_conflict_strategy(c: &mut Criterion) {
+    let coords = Vec2::new(0, 0);
+    let plugin = ParsedPlugin::empty("Synthetic.esp");
+    let reference = reference_heights(coords);
+    let lhs = RelativeTerrainMap::from_difference(&reference, &plugin_heights(1, coords));
+    let rhs = RelativeTerrainMap::from_difference(&reference, &plugin_heights(2, coords));
+    let strategy = ResolveConflictStrategy::default();
+
+    c.bench_function("ResolveConflictStrategy::apply", |b| {
+        b.iter(|| {
+            strategy.apply(
+                coords,
+                &plugin,
+                "height_map",
+                black_box(&lhs),
+                black_box(&rhs),
+                &RunContext::default(),
+            )
+        })
+    });
+}
+
+fn bench_recompute_vertex_normals(c: &mut Criterion) {
+    let coords = Vec2::new(0, 0);
+    let reference = reference_heights(coords);
+    let diff = landscape_diff(coords, &reference, &plugin_heights(1, coords));
+    let height_map = diff.height_map.expect("safe");
+    let vertex_normals = diff.vertex_normals.expect("safe");
+
+    c.bench_function("recompute_vertex_normals", |b| {
+        b.iter(|| recompute_vertex_normals(black_box(&height_map), Some(&vertex_normals), 0))
+    });
+}
+
+fn bench_seam_repair(c: &mut Criterion) {
+    let merged = merged_landmass();
+    let settings = SeamSettings::default();
+
+    c.bench_function("repair_landmass_seams_until_fixed", |b| {
+        b.iter_batched(
+            || merged.clone(),
+            |mut merged| repair_landmass_seams_until_fixed(&mut merged, &settings).expect("safe"),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_end_to_end(c: &mut Criterion) {
+    let landmasses = (1..=NUM_PLUGINS).map(landmass_diff).collect::<Vec<_>>();
+    let settings = SeamSettings::default();
+
+    let mut group = c.benchmark_group("end_to_end");
+    group.sample_size(10);
+    group.bench_function("merge_and_repair", |b| {
+        let context = RunContext::default();
+        b.iter(|| {
+            let mut merged = LandmassDiff::new(Arc::new(ParsedPlugin::empty("Merged Lands.esp")));
+            for landmass in landmasses.iter() {
+                merge_landmass_into(
+                    &mut merged,
+                    landmass,
+                    None,
+                    &Resolutions::default(),
+                    &context,
+                )
+                .expect("safe");
+            }
+
+            repair_landmass_seams_until_fixed(&mut merged, &settings).expect("safe");
+            merged
+        })
+    });
+    group.finish();
+}
+
+fn bench_real_plugins(c: &mut Criterion) {
+    let Some(data_files_dir) = std::env::var_os(DATA_FILES_ENV_VAR) else {
+        println!("Skipping real_plugins: {} is not set", DATA_FILES_ENV_VAR);
+        return;
+    };
+
+    // Any config file or resolutions in the working directory are not used.
+    let merged_lands_dir = std::env::temp_dir();
+    let cli = Cli::parse_from([
+        OsStr::new("merged_lands"),
+        OsStr::new("--merged-lands-dir"),
+        merged_lands_dir.as_os_str(),
+        OsStr::new("--data-files-dir"),
+        &data_files_dir,
+    ]);
+
+    let mut group = c.benchmark_group("real_plugins");
+    group.sample_size(10);
+    group.bench_function("parse_diff_and_merge", |b| {
+        b.iter(|| {
+            MergePipeline::new(&cli)
+                .with_reporting(false)
+                .stop_after(MergeStage::Merge)
+                .run(&CancellationToken::default())
+                .expect("safe")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_from_difference,
+    bench_resolve_conflict_strategy,
+    bench_recompute_vertex_normals,
+    bench_seam_repair,
+    bench_end_to_end,
+    bench_real_plugins
+);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![windows_subsystem = "windows"]
+
+use merged_lands::gui::run_gui;
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+/// Opens the graphical launcher.
+fn main() {
+    run_gui();
+}
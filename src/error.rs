@@ -0,0 +1,226 @@
+use crate::land::terrain_map::Vec2;
+use std::path::PathBuf;
+use thiserror::Error;
+
+// Functions inside of the library return an [anyhow::Error] with context for the log. The root
+// cause of the error is one of the types below, if it is known. Public functions such as
+// [crate::run] return an [Error] instead, so that callers can branch on the root cause.
+
+#[derive(Error, Debug)]
+/// The error returned by the public functions of the library. Each variant has the root cause
+/// of the error, if it is known, and the `context` of the error for the log.
+pub enum Error {
+    #[error("{context}")]
+    Io { context: String, error: IoError },
+    #[error("{context}")]
+    Parse { context: String, error: ParseError },
+    #[error("{context}")]
+    Merge { context: String, error: MergeError },
+    #[error("{context}")]
+    Seam { context: String, error: SeamError },
+    #[error("{context}")]
+    Other { context: String },
+}
+
+impl Error {
+    /// Replaces the `context` of the [Error].
+    fn with_context(mut self, new_context: String) -> Self {
+        match &mut self {
+            Self::Io { context, .. }
+            | Self::Parse { context, .. }
+            | Self::Merge { context, .. }
+            | Self::Seam { context, .. }
+            | Self::Other { context } => *context = new_context,
+        }
+
+        self
+    }
+}
+
+impl From<SeamError> for Error {
+    fn from(error: SeamError) -> Self {
+        Self::Seam {
+            context: error.to_string(),
+            error,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Finds the root cause of the error `e`. The `context` is each cause of `e` on one line.
+    fn from(e: anyhow::Error) -> Self {
+        let context = format!("{:#}", e);
+
+        let e = match e.downcast::<Error>() {
+            Ok(error) => return error.with_context(context),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<IoError>() {
+            Ok(error) => return Self::Io { context, error },
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ParseError>() {
+            Ok(error) => return Self::Parse { context, error },
+            Err(e) => e,
+        };
+        let e = match e.downcast::<MergeError>() {
+            Ok(error) => return Self::Merge { context, error },
+            Err(e) => e,
+        };
+        match e.downcast::<SeamError>() {
+            Ok(error) => Self::Seam { context, error },
+            Err(_) => Self::Other { context },
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+/// A file or directory could not be found or accessed.
+pub enum IoError {
+    #[error("The `{}` directory does not exist", .0.to_string_lossy())]
+    DirectoryNotFound(PathBuf),
+    #[error("File {} does not exist", .0.to_string_lossy())]
+    FileNotFound(PathBuf),
+    #[error("Plugin {plugin} does not exist in `{}` directory", .data_files.to_string_lossy())]
+    PluginNotFound { plugin: String, data_files: PathBuf },
+}
+
+#[derive(Error, Debug)]
+/// A file exists, but its contents are invalid.
+pub enum ParseError {
+    #[error("Failed to load records from plugin {plugin}")]
+    CorruptPlugin {
+        plugin: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Not a land diff file")]
+    NotLandDiff,
+    #[error("Unsupported land diff version {0}")]
+    UnsupportedLandDiffVersion(u16),
+    #[error("Unexpected end of file at byte {0}")]
+    UnexpectedEndOfFile(usize),
+    #[error("Invalid index {index} for a {size}x{size} grid")]
+    InvalidGridIndex { index: usize, size: usize },
+    #[error("Texture index {0} has no LTEX record")]
+    UnknownTexture(u16),
+    #[error("Unsupported config file {}", .0.to_string_lossy())]
+    UnsupportedConfig(PathBuf),
+    #[error("Invalid config value {field} = {value}, expected {expected}")]
+    InvalidConfigValue {
+        field: &'static str,
+        value: usize,
+        expected: &'static str,
+    },
+    #[error("Unsupported meta database {0}")]
+    UnsupportedMetaDatabase(String),
+    #[error("The `profile` field must be a string")]
+    InvalidProfileField,
+    #[error("Unknown profile {0}")]
+    UnknownProfile(String),
+    #[error("Invalid order override `{0}`, expected `A.esp < B.esp`")]
+    InvalidOrderOverride(String),
+    #[error("Order overrides contain a cycle involving {0}")]
+    OrderOverrideCycle(String),
+    #[error("Invalid cell `{0}`, expected `x,y`")]
+    InvalidExcludedCell(String),
+    #[error("Invalid meta file {meta}")]
+    InvalidMeta {
+        meta: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Unsupported meta file version")]
+    UnsupportedMetaVersion,
+    #[error("Unsupported bundle {}", .0.to_string_lossy())]
+    UnsupportedBundle(PathBuf),
+    #[error("Plugin {plugin} has CRC32 {actual}, but the bundle was saved with CRC32 {expected}")]
+    BundleMismatch {
+        plugin: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+#[derive(Error, Debug)]
+/// The plugins could not be merged, or the merged land does not support the request.
+pub enum MergeError {
+    #[error("Found {0} LAND records with texture indices without an LTEX record")]
+    MissingTextures(usize),
+    #[error(
+        "Plugin {plugin} uses texture indices that may be defined by its missing masters {masters}"
+    )]
+    MissingTextureMasters { plugin: String, masters: String },
+    #[error("Plugin {plugin} has masters {masters} that are not in the load order")]
+    MissingMasters { plugin: String, masters: String },
+    #[error("Plugin {0} is a forbidden master of the output")]
+    ForbiddenMaster(String),
+    #[error("No LAND record exists at ({}, {})", .0.x, .0.y)]
+    NoLandRecord(Vec2<i32>),
+    #[error("No plugins modify the LAND record at ({}, {})", .0.x, .0.y)]
+    NoModifiedLandRecord(Vec2<i32>),
+    #[error("The LAND record at ({}, {}) has no {value}", .coords.x, .coords.y)]
+    MissingValue { coords: Vec2<i32>, value: String },
+    #[error("Plugins {plugins} each add LAND at ({}, {}) where no master has LAND", .coords.x, .coords.y)]
+    ContestedNewLand { coords: Vec2<i32>, plugins: String },
+    #[error("Self-test checks failed: {0}")]
+    SelfTestFailed(String),
+    #[error("Saved plugin {plugin} has {num_cells} cells with heights that differ from the merge")]
+    InvalidOutput { plugin: String, num_cells: usize },
+    #[error("The LAND record at ({}, {}) broke an invariant of the merge: {invariant}. This is a bug", .coords.x, .coords.y)]
+    InvariantViolation {
+        coords: Vec2<i32>,
+        invariant: &'static str,
+    },
+    #[error("Vertex ({}, {}) is outside of the {size}x{size} {value}", .vertex.x, .vertex.y)]
+    VertexOutOfBounds {
+        vertex: Vec2<i32>,
+        size: usize,
+        value: String,
+    },
+}
+
+#[derive(Error, Debug)]
+/// An invariant of seam repair was violated. This is a bug.
+pub enum SeamError {
+    #[error("Cells ({}, {}) and ({}, {}) do not share a seam", .lhs.x, .lhs.y, .rhs.x, .rhs.y)]
+    NotAdjacent { lhs: Vec2<i32>, rhs: Vec2<i32> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn root_causes_are_kept_with_their_context() {
+        let result: anyhow::Result<()> =
+            Err(anyhow!(IoError::FileNotFound(PathBuf::from("A.esp"))));
+        let error = Error::from(result.context("Unable to load plugin A.esp").unwrap_err());
+        assert!(matches!(
+            &error,
+            Error::Io {
+                error: IoError::FileNotFound(_),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Unable to load plugin A.esp: File A.esp does not exist"
+        );
+
+        // An [Error] returned through a function of the library keeps its root cause.
+        let result: anyhow::Result<()> = Err(error.into());
+        let error = Error::from(result.context("Unable to merge").unwrap_err());
+        assert!(matches!(error, Error::Io { .. }));
+        assert_eq!(
+            error.to_string(),
+            "Unable to merge: Unable to load plugin A.esp: File A.esp does not exist"
+        );
+
+        assert!(matches!(
+            Error::from(anyhow!("Unknown")),
+            Error::Other { .. }
+        ));
+    }
+}
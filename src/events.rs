@@ -0,0 +1,229 @@
+use crate::land::terrain_map::Vec2;
+use crate::timings::{TimedPhase, Timings};
+use log::{info, trace, warn};
+use owo_colors::OwoColorize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+/// An event emitted while running the tool. Front-ends receive each [MergeEvent] of a run with
+/// [RunContext::with_events], e.g. to show progress in a GUI. The command line logs them with
+/// [log_event].
+pub enum MergeEvent {
+    /// A `phase` of the run started, e.g. `Merging Lands`.
+    PhaseStarted { phase: &'static str },
+    /// The LAND records of the `plugin` were compared to the reference land.
+    /// `index` counts from `1` to `total`.
+    PluginProcessed {
+        plugin: String,
+        index: usize,
+        total: usize,
+    },
+    /// The `plugin` conflicts with an earlier plugin in the `value` of the cell at `coords`.
+    ConflictFound {
+        plugin: String,
+        coords: Vec2<i32>,
+        value: String,
+        num_minor: usize,
+        num_major: usize,
+    },
+    /// `completed` of `total` steps of the `phase` are done.
+    Progress {
+        phase: &'static str,
+        completed: usize,
+        total: usize,
+    },
+    /// The run was cancelled by a [CancellationToken] during the `phase`.
+    Cancelled { phase: &'static str },
+}
+
+impl MergeEvent {
+    /// Returns the progress of a [MergeEvent::Progress] as a percentage, or [None] for other events.
+    pub fn percent(&self) -> Option<f32> {
+        match self {
+            Self::Progress {
+                completed, total, ..
+            } => Some(100. * (*completed as f32) / (*total).max(1) as f32),
+            _ => None,
+        }
+    }
+}
+
+/// A function receiving each [MergeEvent].
+type Subscriber = Arc<dyn Fn(&MergeEvent) + Send + Sync>;
+
+#[derive(Clone, Default)]
+/// The [CancellationToken] of a run, the subscribers receiving each [MergeEvent] of the run, and
+/// the [Timings] of the run, if they are recorded.
+/// It is passed down to each step of the run, so that runs at the same time, e.g. by two hosts
+/// embedding the tool, do not receive each other's events or add to each other's [Timings].
+pub struct RunContext {
+    cancel: CancellationToken,
+    subscribers: Vec<Subscriber>,
+    timings: Option<Arc<Timings>>,
+}
+
+impl RunContext {
+    /// Creates a [RunContext] for a run stopped by the `cancel` token, without any subscribers.
+    pub fn new(cancel: CancellationToken) -> Self {
+        Self {
+            cancel,
+            subscribers: Vec::new(),
+            timings: None,
+        }
+    }
+
+    /// Calls `subscriber` with each [MergeEvent] of the run, after any earlier subscribers.
+    pub fn with_events(mut self, subscriber: impl Fn(&MergeEvent) + Send + Sync + 'static) -> Self {
+        self.subscribers.push(Arc::new(subscriber));
+        self
+    }
+
+    /// Returns a [RunContext] with the same subscribers for a run stopped by the `cancel` token.
+    pub fn with_cancellation_token(&self, cancel: &CancellationToken) -> Self {
+        Self {
+            cancel: cancel.clone(),
+            subscribers: self.subscribers.clone(),
+            timings: self.timings.clone(),
+        }
+    }
+
+    /// Returns a [RunContext] with the same subscribers that records the time of each phase of
+    /// the run to the `timings`.
+    pub fn with_timings(&self, timings: &Arc<Timings>) -> Self {
+        Self {
+            timings: Some(timings.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the [CancellationToken] of the run.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Returns `true` if the [CancellationToken] of the run was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Sends the [MergeEvent] to each subscriber of the run.
+    pub fn emit(&self, event: MergeEvent) {
+        for subscriber in self.subscribers.iter() {
+            subscriber(&event);
+        }
+    }
+
+    /// Runs `f` and adds the time it took to the `phase` of the [Timings] of the run. See
+    /// [Timings::time_phase]. Without [Timings], `f` is only called, since [std::time::Instant::now]
+    /// panics on platforms without a clock, e.g. in a web page.
+    pub fn time_phase<T>(&self, phase: TimedPhase, f: impl FnOnce() -> T) -> T {
+        match self.timings.as_ref() {
+            Some(timings) => timings.time_phase(phase, f),
+            None => f(),
+        }
+    }
+
+    /// Runs `f` and adds the time it took to the `plugin` in the `phase` of the [Timings] of the
+    /// run. See [Timings::time_plugin].
+    pub fn time_plugin<T>(&self, phase: TimedPhase, plugin: &str, f: impl FnOnce() -> T) -> T {
+        match self.timings.as_ref() {
+            Some(timings) => timings.time_plugin(phase, plugin, f),
+            None => f(),
+        }
+    }
+}
+
+/// The subscriber used by the command line. Logs each [MergeEvent].
+pub fn log_event(event: &MergeEvent) {
+    match event {
+        MergeEvent::PhaseStarted { phase } => info!(":: {} ::", phase),
+        MergeEvent::PluginProcessed {
+            plugin,
+            index,
+            total,
+        } => trace!("Processed {} ({}/{})", plugin, index, total),
+        MergeEvent::ConflictFound {
+            plugin,
+            coords,
+            value,
+            num_minor,
+            num_major,
+        } => trace!(
+            "({:>4}, {:>4}) {:<15} | {:<50} | {} minor, {} major conflicts",
+            coords.x,
+            coords.y,
+            value,
+            plugin,
+            num_minor,
+            num_major
+        ),
+        MergeEvent::Progress { phase, .. } => {
+            trace!("{} {:.0}%", phase, event.percent().expect("safe"))
+        }
+        MergeEvent::Cancelled { phase } => {
+            warn!("{}", format!("Cancelled during {}", phase.bold()).yellow())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A token shared with the host of a run. The run stops at the next phase or plugin
+/// boundary after [CancellationToken::cancel] is called from any thread.
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Requests that the run using this token stops.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if [CancellationToken::cancel] was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Returns a subscriber counting each [MergeEvent] in `count`.
+    fn counter(count: &Arc<AtomicUsize>) -> impl Fn(&MergeEvent) + Send + Sync + 'static {
+        let count = count.clone();
+        move |_| {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn runs_only_receive_their_own_events() {
+        let first_count = Arc::new(AtomicUsize::new(0));
+        let second_count = Arc::new(AtomicUsize::new(0));
+        let first = RunContext::default().with_events(counter(&first_count));
+        let second = RunContext::default().with_events(counter(&second_count));
+
+        first.emit(MergeEvent::PhaseStarted { phase: "First" });
+        first.emit(MergeEvent::PhaseStarted { phase: "First" });
+        second.emit(MergeEvent::PhaseStarted { phase: "Second" });
+        assert_eq!(first_count.load(Ordering::Relaxed), 2);
+        assert_eq!(second_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn subscribers_are_kept_with_another_cancellation_token() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::default();
+        let context = RunContext::default()
+            .with_events(counter(&count))
+            .with_cancellation_token(&cancel);
+
+        cancel.cancel();
+        context.emit(MergeEvent::Cancelled { phase: "Testing" });
+        assert!(context.is_cancelled());
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,470 @@
+use crate::cli::Cli;
+use crate::error::Error;
+use crate::events::{log_event, CancellationToken, MergeEvent, RunContext};
+use crate::init_log;
+use crate::io::artifacts::ArtifactSink;
+use crate::io::file_provider::{FileProvider, StdFileProvider};
+use crate::io::locate::locate_data_files_dir;
+use crate::io::paths::extended_length_path;
+use crate::io::report::{CellConflict, Report};
+use crate::io::save_to_image::{diff_image_name, merged_image_name};
+use crate::land::height_map::format_height;
+use crate::merge_all;
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use eframe::egui;
+use egui::{ColorImage, TextureFilter, TextureHandle};
+use itertools::Itertools;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A merge running on a worker thread, started by the [Launcher].
+struct RunningMerge {
+    cancel: CancellationToken,
+    handle: JoinHandle<Result<Report, Error>>,
+}
+
+/// The images of the [CellConflict] selected in the summary.
+struct ConflictImages {
+    conflict: usize,
+    images: Vec<(String, TextureHandle)>,
+}
+
+/// A minimal graphical front-end for merging with [merge_all]. The options are turned into
+/// command line arguments, so the merge behaves exactly like the command line.
+struct Launcher {
+    merged_lands_dir: String,
+    data_files_dir: String,
+    output_file: String,
+    /// Each plugin in `data_files_dir` and whether it is merged.
+    /// If empty, the plugins are read from `Morrowind.ini` instead.
+    plugins: Vec<(String, bool)>,
+    diff_masters: bool,
+    remove_cell_records: bool,
+    regenerate_world_map: bool,
+    add_debug_vertex_colors: bool,
+    add_debug_normals: bool,
+    shadow_strategies: bool,
+    survival_images: bool,
+    skip_major_conflict_cells: bool,
+    /// The latest [MergeEvent] of the running merge, as text.
+    status: Arc<Mutex<String>>,
+    /// Repaints the [Launcher] when the `status` changes.
+    ctx: egui::Context,
+    is_log_initialized: bool,
+    running: Option<RunningMerge>,
+    result: Option<Result<Report, String>>,
+    selected: Option<ConflictImages>,
+}
+
+/// Returns the text shown in the [Launcher] for the `event`, or [None] if it is not shown.
+fn describe_event(event: &MergeEvent) -> Option<String> {
+    match event {
+        MergeEvent::PhaseStarted { phase } => Some(phase.to_string()),
+        MergeEvent::PluginProcessed {
+            plugin,
+            index,
+            total,
+        } => Some(format!("Processed {} ({}/{})", plugin, index, total)),
+        MergeEvent::Progress { phase, .. } => {
+            Some(format!("{} {:.0}%", phase, event.percent().expect("safe")))
+        }
+        MergeEvent::Cancelled { phase } => Some(format!("Cancelled during {}", phase)),
+        MergeEvent::ConflictFound { .. } => None,
+    }
+}
+
+/// Returns the `Data Files` folder in the working directory if it exists, and otherwise the
+/// `Data Files` folder of a Morrowind installation, if one is found.
+fn default_data_files_dir() -> String {
+    let local_dir = Path::new("Data Files");
+    if StdFileProvider.is_dir(local_dir).unwrap_or(false) {
+        return local_dir.to_string_lossy().to_string();
+    }
+
+    locate_data_files_dir(&StdFileProvider)
+        .map(|(_, dir)| dir)
+        .unwrap_or_else(|| local_dir.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the name of each `.esm` and `.esp` file in the `data_files` directory, sorted by name.
+fn find_plugins(files: &dyn FileProvider, data_files: &Path) -> Result<Vec<String>> {
+    let entries = files.read_dir(data_files).with_context(|| {
+        anyhow!(
+            "Unable to read `Data Files` directory `{}`",
+            data_files.to_string_lossy()
+        )
+    })?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|path| {
+            path.extension().map_or(false, |ext| {
+                ext.eq_ignore_ascii_case("esm") || ext.eq_ignore_ascii_case("esp")
+            })
+        })
+        .filter_map(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+        .sorted_by_key(|name| name.to_lowercase())
+        .collect())
+}
+
+/// Loads the PNG at `file_path` as a texture named `name`.
+fn load_texture(ctx: &egui::Context, name: &str, file_path: &Path) -> Result<TextureHandle> {
+    let image = StdFileProvider
+        .read(file_path)
+        .and_then(|bytes| Ok(image::load_from_memory(&bytes)?))
+        .with_context(|| anyhow!("Unable to open image {}", file_path.to_string_lossy()))?
+        .to_rgba8();
+
+    let size = [image.width() as usize, image.height() as usize];
+    let image = ColorImage::from_rgba_unmultiplied(size, image.as_flat_samples().as_slice());
+    Ok(ctx.load_texture(name, image, TextureFilter::Nearest))
+}
+
+impl Launcher {
+    /// Creates a [Launcher] that repaints the `cc` when the status of the running merge changes.
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        Self {
+            merged_lands_dir: ".".to_string(),
+            data_files_dir: default_data_files_dir(),
+            output_file: "Merged Lands.esp".to_string(),
+            plugins: Vec::new(),
+            diff_masters: false,
+            remove_cell_records: false,
+            regenerate_world_map: false,
+            add_debug_vertex_colors: false,
+            add_debug_normals: false,
+            shadow_strategies: false,
+            survival_images: false,
+            skip_major_conflict_cells: false,
+            status: Arc::new(Mutex::new(String::new())),
+            ctx: cc.egui_ctx.clone(),
+            is_log_initialized: false,
+            running: None,
+            result: None,
+            selected: None,
+        }
+    }
+
+    /// Returns the command line arguments for the selected options.
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "merged_lands".to_string(),
+            "--merged-lands-dir".to_string(),
+            self.merged_lands_dir.clone(),
+            "--data-files-dir".to_string(),
+            self.data_files_dir.clone(),
+            "--output-file".to_string(),
+            self.output_file.clone(),
+        ];
+
+        let flags = [
+            (self.diff_masters, "--diff-masters"),
+            (self.remove_cell_records, "--remove-cell-records"),
+            (self.regenerate_world_map, "--regenerate-world-map"),
+            (self.add_debug_vertex_colors, "--add-debug-vertex-colors"),
+            (self.add_debug_normals, "--add-debug-normals"),
+            (self.shadow_strategies, "--shadow-strategies"),
+            (self.survival_images, "--survival-images"),
+            (
+                self.skip_major_conflict_cells,
+                "--skip-major-conflict-cells",
+            ),
+        ];
+
+        args.extend(
+            flags
+                .into_iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, flag)| flag.to_string()),
+        );
+
+        args.extend(
+            self.plugins
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(plugin, _)| plugin.clone()),
+        );
+
+        args
+    }
+
+    /// Starts merging on a worker thread with the selected options.
+    fn start(&mut self) -> Result<()> {
+        let cli = Cli::try_parse_from(self.args()).with_context(|| anyhow!("Invalid options"))?;
+
+        // The logger can only be initialized once, so later runs share the first log file.
+        if !self.is_log_initialized {
+            init_log(&cli);
+            self.is_log_initialized = true;
+        }
+
+        let cancel = CancellationToken::default();
+        let context = RunContext::new(cancel.clone())
+            .with_events(log_event)
+            .with_events({
+                let status = self.status.clone();
+                let ctx = self.ctx.clone();
+                move |event| {
+                    if let Some(text) = describe_event(event) {
+                        *status.lock().expect("safe") = text;
+                        ctx.request_repaint();
+                    }
+                }
+            });
+
+        let handle = std::thread::Builder::new()
+            .stack_size(cli.stack_size())
+            .spawn(move || merge_all(&cli, &context))
+            .with_context(|| anyhow!("Unable to create worker thread"))?;
+
+        self.result = None;
+        self.selected = None;
+        self.running = Some(RunningMerge { cancel, handle });
+        Ok(())
+    }
+
+    /// Stores the result of the running merge once the worker thread finishes.
+    fn poll(&mut self) {
+        if !self
+            .running
+            .as_ref()
+            .map_or(false, |running| running.handle.is_finished())
+        {
+            return;
+        }
+
+        let running = self.running.take().expect("safe");
+        self.result = Some(match running.handle.join() {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("The worker thread panicked".to_string()),
+        });
+    }
+
+    /// Returns the directory containing the conflict images.
+    fn conflicts_dir(&self) -> PathBuf {
+        let merged_lands_dir = extended_length_path(Path::new(&self.merged_lands_dir));
+        match ArtifactSink::conflicts_dir(&StdFileProvider, &merged_lands_dir) {
+            ArtifactSink::Directory { directory, .. } => directory,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Loads the images of the `conflict` at index `idx` in the [Report].
+    fn select_conflict(&mut self, ctx: &egui::Context, idx: usize, conflict: &CellConflict) {
+        let conflicts_dir = self.conflicts_dir();
+        let images = [diff_image_name(conflict), merged_image_name(conflict)]
+            .into_iter()
+            .filter_map(|name| {
+                let texture = load_texture(ctx, &name, &conflicts_dir.join(&name))
+                    .map_err(|e| *self.status.lock().expect("safe") = format!("{:?}", e))
+                    .ok()?;
+                Some((name, texture))
+            })
+            .collect();
+
+        self.selected = Some(ConflictImages {
+            conflict: idx,
+            images,
+        });
+    }
+
+    /// Shows the directories, plugins, and toggles.
+    fn show_options(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("directories").show(ui, |ui| {
+            ui.label("Merged Lands directory");
+            ui.text_edit_singleline(&mut self.merged_lands_dir);
+            ui.end_row();
+
+            ui.label("Data Files directory");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.data_files_dir);
+                if ui.button("Load plugins").clicked() {
+                    match find_plugins(&StdFileProvider, Path::new(&self.data_files_dir)) {
+                        Ok(plugins) => {
+                            self.plugins = plugins.into_iter().map(|name| (name, true)).collect()
+                        }
+                        Err(e) => *self.status.lock().expect("safe") = format!("{:?}", e),
+                    }
+                }
+            });
+            ui.end_row();
+
+            ui.label("Output file");
+            ui.text_edit_singleline(&mut self.output_file);
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.checkbox(&mut self.diff_masters, "Merge masters like plugins");
+        ui.checkbox(&mut self.remove_cell_records, "Remove CELL records");
+        ui.checkbox(&mut self.regenerate_world_map, "Regenerate world map");
+        ui.checkbox(&mut self.add_debug_vertex_colors, "Add debug vertex colors");
+        ui.checkbox(&mut self.add_debug_normals, "Add debug normals");
+        ui.checkbox(&mut self.shadow_strategies, "Compare conflict strategies");
+        ui.checkbox(&mut self.survival_images, "Save survival images");
+        ui.checkbox(
+            &mut self.skip_major_conflict_cells,
+            "Skip cells with major conflicts",
+        );
+
+        ui.separator();
+        if self.plugins.is_empty() {
+            ui.label("Plugins are read from Morrowind.ini unless loaded above.");
+        } else {
+            ui.horizontal(|ui| {
+                if ui.button("Select all").clicked() {
+                    self.plugins
+                        .iter_mut()
+                        .for_each(|(_, enabled)| *enabled = true);
+                }
+                if ui.button("Select none").clicked() {
+                    self.plugins
+                        .iter_mut()
+                        .for_each(|(_, enabled)| *enabled = false);
+                }
+            });
+
+            egui::ScrollArea::vertical()
+                .id_source("plugins")
+                .show(ui, |ui| {
+                    for (plugin, enabled) in self.plugins.iter_mut() {
+                        ui.checkbox(enabled, plugin.as_str());
+                    }
+                });
+        }
+    }
+
+    /// Shows the summary of the [Report], with a clickable list of flagged conflicts.
+    fn show_report(&mut self, ui: &mut egui::Ui, report: &Report) {
+        if let Some(phase) = report.cancelled.as_ref() {
+            ui.label(format!("Cancelled during {}", phase));
+        }
+
+        ui.label(format!("{} warnings", report.warnings.len()));
+        ui.label(format!("{} repaired seams", report.repaired_seams.len()));
+        ui.label(format!(
+            "{} conflicts, {} flagged",
+            report.conflicts.len(),
+            report
+                .conflicts
+                .iter()
+                .filter(|conflict| conflict.flagged)
+                .count()
+        ));
+
+        ui.collapsing(format!("{} clusters", report.clusters.len()), |ui| {
+            for cluster in report.clusters.iter() {
+                ui.label(&cluster.summary);
+            }
+        });
+
+        ui.separator();
+        let selected = self.selected.as_ref().map(|images| images.conflict);
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_source("conflicts")
+            .show(ui, |ui| {
+                for (idx, conflict) in report.conflicts.iter().enumerate() {
+                    if !conflict.flagged {
+                        continue;
+                    }
+
+                    let [x, y] = conflict.coords;
+                    let mut text = format!(
+                        "({}, {}) {} | {} | {} minor, {} major",
+                        x,
+                        y,
+                        conflict.value,
+                        conflict.plugin,
+                        conflict.num_minor,
+                        conflict.num_major
+                    );
+                    if let Some(max_difference) = conflict.max_difference {
+                        text.push_str(&format!(", up to {}", format_height(max_difference)));
+                    }
+
+                    if ui.selectable_label(selected == Some(idx), text).clicked() {
+                        clicked = Some(idx);
+                    }
+                }
+            });
+
+        if let Some(idx) = clicked {
+            self.select_conflict(ui.ctx(), idx, &report.conflicts[idx]);
+        }
+    }
+}
+
+impl eframe::App for Launcher {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll();
+
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(running) = self.running.as_ref() {
+                    ui.spinner();
+                    if ui.button("Cancel").clicked() {
+                        running.cancel.cancel();
+                    }
+                } else if ui.button("Merge").clicked() {
+                    if let Err(e) = self.start() {
+                        self.result = Some(Err(format!("{:?}", e)));
+                    }
+                }
+
+                ui.label(self.status.lock().expect("safe").as_str());
+            });
+        });
+
+        egui::SidePanel::left("options").show(ctx, |ui| {
+            ui.add_enabled_ui(self.running.is_none(), |ui| self.show_options(ui));
+        });
+
+        if let Some(selected) = self.selected.as_ref() {
+            egui::SidePanel::right("images").show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_source("images")
+                    .show(ui, |ui| {
+                        for (name, texture) in selected.images.iter() {
+                            ui.label(name);
+                            ui.image(texture.id(), texture.size_vec2());
+                        }
+                    });
+            });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| match self.result.take() {
+            None => {
+                ui.heading("Merged Lands");
+                ui.label("Choose the options on the left, then press Merge.");
+            }
+            Some(Err(e)) => {
+                ui.heading("Merge failed");
+                ui.label(&e);
+                self.result = Some(Err(e));
+            }
+            Some(Ok(report)) => {
+                ui.heading("Summary");
+                self.show_report(ui, &report);
+                self.result = Some(Ok(report));
+            }
+        });
+
+        if self.running.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+    }
+}
+
+/// Opens the launcher window and blocks until it is closed.
+pub fn run_gui() {
+    eframe::run_native(
+        "Merged Lands",
+        eframe::NativeOptions::default(),
+        Box::new(|cc| Box::new(Launcher::new(cc))),
+    );
+}
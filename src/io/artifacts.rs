@@ -0,0 +1,152 @@
+use crate::io::file_provider::{FileProvider, FileWriter};
+use anyhow::{anyhow, Context, Result};
+use image::{DynamicImage, ImageOutputFormat};
+use log::warn;
+use owo_colors::OwoColorize;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// The destination of report artifacts, e.g. conflict images.
+pub enum ArtifactSink {
+    /// Each artifact is saved as a file in the `directory`.
+    Directory {
+        directory: PathBuf,
+        files: &'static dyn FileProvider,
+    },
+    /// Each artifact is saved as an entry in a single zip archive.
+    Archive {
+        file_path: PathBuf,
+        writer: Mutex<ZipWriter<Box<dyn FileWriter>>>,
+    },
+    /// No artifact is saved, e.g. for a [crate::pipeline::MergePipeline] without reporting.
+    Discard,
+}
+
+impl ArtifactSink {
+    /// Creates an [ArtifactSink] that saves files in the `directory` with the [FileProvider].
+    pub fn directory(files: &'static dyn FileProvider, directory: PathBuf) -> Self {
+        Self::Directory { directory, files }
+    }
+
+    /// Creates an [ArtifactSink] that saves files in the `Conflicts` folder of `merged_lands_dir`.
+    pub fn conflicts_dir(files: &'static dyn FileProvider, merged_lands_dir: &Path) -> Self {
+        Self::directory(files, merged_lands_dir.join("Conflicts"))
+    }
+
+    /// Creates an [ArtifactSink] that saves entries to a new zip archive at `file_path`.
+    pub fn archive(files: &dyn FileProvider, file_path: &Path) -> Result<Self> {
+        let file = files.create(file_path).with_context(|| {
+            anyhow!(
+                "Unable to create report archive {}",
+                file_path.to_string_lossy()
+            )
+        })?;
+
+        Ok(Self::Archive {
+            file_path: file_path.to_path_buf(),
+            writer: Mutex::new(ZipWriter::new(file)),
+        })
+    }
+
+    /// Returns `true` if an artifact named `file_name` can be saved.
+    /// Logs a warning that the artifact will not be saved otherwise.
+    pub fn can_save(&self, file_name: &str) -> Result<bool> {
+        let (directory, files) = match self {
+            Self::Directory { directory, files } => (directory, files),
+            Self::Archive { .. } => return Ok(true),
+            Self::Discard => return Ok(false),
+        };
+
+        let exists = files.exists(directory).with_context(|| {
+            anyhow!("Unable to find `{}` directory", directory.to_string_lossy())
+        })?;
+
+        if !exists {
+            warn!(
+                "{} {}",
+                format!(
+                    "Unable to save file {}",
+                    directory.join(file_name).to_string_lossy().bold()
+                )
+                .yellow(),
+                format!(
+                    "because the `{}` directory does not exist",
+                    directory.to_string_lossy()
+                )
+                .yellow()
+            );
+        }
+
+        Ok(exists)
+    }
+
+    /// Saves `bytes` as an artifact named `file_name`.
+    pub fn save_bytes(&self, file_name: &str, bytes: &[u8]) -> Result<()> {
+        match self {
+            Self::Directory { directory, files } => {
+                let file_path = directory.join(file_name);
+                files.write(&file_path, bytes).with_context(|| {
+                    anyhow!("Unable to save file {}", file_path.to_string_lossy())
+                })?;
+            }
+            Self::Archive { file_path, writer } => {
+                let mut writer = writer.lock().expect("safe");
+
+                // PNG files are already compressed.
+                let compression_method = if file_name.ends_with(".png") {
+                    CompressionMethod::Stored
+                } else {
+                    CompressionMethod::Deflated
+                };
+
+                let options = FileOptions::default().compression_method(compression_method);
+
+                writer
+                    .start_file(file_name, options)
+                    .and_then(|_| writer.write_all(bytes).map_err(Into::into))
+                    .with_context(|| {
+                        anyhow!(
+                            "Unable to save {} to report archive {}",
+                            file_name,
+                            file_path.to_string_lossy()
+                        )
+                    })?;
+            }
+            Self::Discard => {}
+        }
+
+        Ok(())
+    }
+
+    /// Saves `img` as a PNG artifact named `file_name`.
+    pub fn save_image(&self, file_name: &str, img: &DynamicImage) -> Result<()> {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, ImageOutputFormat::Png)
+            .with_context(|| anyhow!("Unable to save image file {}", file_name))?;
+
+        self.save_bytes(file_name, bytes.get_ref())
+    }
+
+    /// Completes the [ArtifactSink]. This must be called to produce a valid zip archive.
+    pub fn finish(self) -> Result<()> {
+        let Self::Archive { file_path, writer } = self else {
+            return Ok(());
+        };
+
+        writer
+            .into_inner()
+            .expect("safe")
+            .finish()
+            .with_context(|| {
+                anyhow!(
+                    "Unable to finish report archive {}",
+                    file_path.to_string_lossy()
+                )
+            })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+use crate::error::{IoError, ParseError};
+use crate::io::artifacts::ArtifactSink;
+use crate::io::file_provider::FileProvider;
+use crate::io::land_diff::{land_diff_file_name, load_land_diff, save_land_diffs};
+use crate::io::meta_schema::{MetaType, PluginMeta};
+use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::land::textures::KnownTextures;
+use crate::{Landmass, LandmassDiff};
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+use log::{debug, trace, warn};
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tes3::esp::LandscapeTexture;
+
+/// The name of the [Bundle] manifest saved next to the land diff files of the bundle.
+pub const BUNDLE_FILE_NAME: &str = "bundle.toml";
+
+#[derive(Serialize, Deserialize, Debug)]
+/// A master that formed the reference land when a [Bundle] was saved.
+struct BundleMaster {
+    /// The name of the master.
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// The CRC32 of the master, if it could be read.
+    crc32: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// A texture added by a [BundlePlugin], so that the land diff can be read without the plugin.
+struct BundleTexture {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    file_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// A plugin of a [Bundle] and the land diff file of its changes to the reference land.
+struct BundlePlugin {
+    /// The name of the plugin.
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// The CRC32 of the plugin, if it could be read. Patches have no CRC32.
+    crc32: Option<u32>,
+    /// The name of the land diff file, relative to the [Bundle] manifest.
+    land_diff: String,
+    #[serde(default)]
+    /// The textures last added or modified by the plugin.
+    textures: Vec<BundleTexture>,
+    #[serde(default)]
+    /// The [PluginMeta] of the plugin.
+    meta: PluginMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// The manifest of the land diff files of every plugin of a merge, in load order.
+struct Bundle {
+    /// The version of the tool that saved the [Bundle].
+    tool_version: String,
+    #[serde(default)]
+    masters: Vec<BundleMaster>,
+    #[serde(default)]
+    plugins: Vec<BundlePlugin>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "version")]
+/// A versioned [Bundle].
+enum VersionedBundle {
+    #[serde(rename = "0")]
+    /// Initial release.
+    V0(Bundle),
+    #[serde(other)]
+    /// An unknown version.
+    Unsupported,
+}
+
+/// Formats an optional CRC32 for an error message.
+fn crc32_string(crc32: Option<u32>) -> String {
+    crc32.map_or_else(|| "none".to_string(), |crc32| format!("{:08x}", crc32))
+}
+
+/// Saves a land diff file of each [LandmassDiff] in `landmasses` with [save_land_diffs], and a
+/// `bundle.toml` manifest with the CRC32 of each plugin and master in `parsed_plugins`.
+/// The bundle can be merged on another machine with only the masters by using `--bundle`.
+pub fn save_bundle(
+    artifacts: &ArtifactSink,
+    parsed_plugins: &ParsedPlugins,
+    landmasses: &[LandmassDiff],
+    known_textures: &KnownTextures,
+) -> Result<()> {
+    save_land_diffs(artifacts, landmasses, known_textures)?;
+
+    let masters = parsed_plugins
+        .masters
+        .iter()
+        .map(|master| BundleMaster {
+            name: master.name.clone(),
+            crc32: master.crc32,
+        })
+        .collect_vec();
+
+    let plugins = landmasses
+        .iter()
+        .map(|landmass| {
+            let plugin = &landmass.plugin;
+            let crc32 = (plugin.meta.meta_type != MetaType::LandDiff)
+                .then_some(plugin.crc32)
+                .flatten();
+
+            let textures = known_textures
+                .sorted()
+                .filter(|texture| texture.plugin.name == plugin.name)
+                .map(|texture| BundleTexture {
+                    id: texture.id().clone(),
+                    file_name: texture.file_name().map(str::to_string),
+                })
+                .collect();
+
+            BundlePlugin {
+                name: plugin.name.clone(),
+                crc32,
+                land_diff: land_diff_file_name(&plugin.name),
+                textures,
+                meta: plugin.meta.clone(),
+            }
+        })
+        .collect_vec();
+
+    let bundle = VersionedBundle::V0(Bundle {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        masters,
+        plugins,
+    });
+
+    let text = toml::to_string(&bundle)
+        .with_context(|| anyhow!("Unable to serialize {}", BUNDLE_FILE_NAME))?;
+
+    trace!("Saving {}", BUNDLE_FILE_NAME);
+    artifacts.save_bytes(BUNDLE_FILE_NAME, text.as_bytes())
+}
+
+/// Loads the land diff file of each plugin of the `bundle.toml` manifest at `file_path`.
+/// Each master of the bundle must exist in the `data_files` directory with the same CRC32,
+/// so that the land diffs are applied to the same `reference` [Landmass] they were saved from.
+/// Plugins of the bundle that are also in `parsed_plugins` are skipped if their CRC32 matches.
+/// Textures of the bundle missing from the [KnownTextures] are added. Each [LandmassDiff] is
+/// inserted into the `modded_landmasses` at its position in the load order of the bundle, i.e.
+/// after the [LandmassDiff] of the plugin before it. Returns the number of [LandmassDiff] inserted.
+pub fn load_bundle(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    file_path: &Path,
+    parsed_plugins: &ParsedPlugins,
+    reference: &Landmass,
+    known_textures: &mut KnownTextures,
+    modded_landmasses: &mut Vec<LandmassDiff>,
+) -> Result<usize> {
+    let text = files
+        .read_to_string(file_path)
+        .with_context(|| anyhow!("Unable to read bundle {}", file_path.to_string_lossy()))?;
+
+    let bundle = match toml::from_str::<VersionedBundle>(&text)
+        .with_context(|| anyhow!("Failed to parse bundle {}", file_path.to_string_lossy()))?
+    {
+        VersionedBundle::V0(bundle) => bundle,
+        VersionedBundle::Unsupported => {
+            bail!(ParseError::UnsupportedBundle(file_path.to_path_buf()))
+        }
+    };
+
+    debug!(
+        "Loading bundle {} with {} masters and {} plugins saved by v{}",
+        file_path.to_string_lossy(),
+        bundle.masters.len(),
+        bundle.plugins.len(),
+        bundle.tool_version
+    );
+
+    for master in bundle.masters.iter() {
+        let Some(local) = parsed_plugins
+            .masters
+            .iter()
+            .find(|local| local.name.eq_ignore_ascii_case(&master.name))
+        else {
+            bail!(IoError::PluginNotFound {
+                plugin: master.name.clone(),
+                data_files: data_files.to_path_buf(),
+            });
+        };
+
+        let crc32 = local.crc32;
+        if crc32 != master.crc32 {
+            bail!(ParseError::BundleMismatch {
+                plugin: master.name.clone(),
+                expected: crc32_string(master.crc32),
+                actual: crc32_string(crc32),
+            });
+        }
+    }
+
+    for master in parsed_plugins.masters.iter().filter(|local| {
+        !bundle
+            .masters
+            .iter()
+            .any(|master| master.name.eq_ignore_ascii_case(&local.name))
+    }) {
+        warn!(
+            "{} {}",
+            format!("Master {} is not in the bundle", master.name.bold()).yellow(),
+            "and may change the land the bundle is merged into".yellow()
+        );
+    }
+
+    let bundle_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut num_loaded = 0;
+    // The index in the `modded_landmasses` after the last plugin of the bundle.
+    let mut insert_idx = 0;
+    for plugin in bundle.plugins {
+        let local = parsed_plugins
+            .masters
+            .iter()
+            .chain(parsed_plugins.plugins.iter())
+            .find(|local| local.name.eq_ignore_ascii_case(&plugin.name));
+
+        if let Some(local) = local {
+            let crc32 = local.crc32;
+            if crc32 != plugin.crc32 {
+                bail!(ParseError::BundleMismatch {
+                    plugin: plugin.name,
+                    expected: crc32_string(plugin.crc32),
+                    actual: crc32_string(crc32),
+                });
+            }
+
+            trace!("Using local plugin {} instead of the bundle", plugin.name);
+            if let Some(local_idx) = modded_landmasses
+                .iter()
+                .position(|landmass| landmass.plugin.name.eq_ignore_ascii_case(&plugin.name))
+            {
+                insert_idx = insert_idx.max(local_idx + 1);
+            }
+
+            continue;
+        }
+
+        let parsed_plugin = Arc::new(ParsedPlugin::from_bundle(&plugin.name, plugin.meta));
+        for texture in plugin.textures {
+            if known_textures.get(&texture.id).is_none() {
+                known_textures.add_bundled_texture(
+                    &parsed_plugin,
+                    &LandscapeTexture {
+                        id: texture.id,
+                        file_name: texture.file_name,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        let mut landmass = load_land_diff(
+            files,
+            &bundle_dir.join(&plugin.land_diff),
+            reference,
+            known_textures,
+        )?;
+        landmass.plugin = parsed_plugin;
+        modded_landmasses.insert(insert_idx, landmass);
+        insert_idx += 1;
+        num_loaded += 1;
+    }
+
+    Ok(num_loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::file_provider::MemoryFileProvider;
+    use crate::land::terrain_map::LandData;
+    use crate::test_support::{
+        parsed_plugins, plugin, with_main_thread_stack, LandmassBuilder, CELL,
+    };
+    use filetime::FileTime;
+    use std::path::PathBuf;
+
+    /// Returns a [LandmassDiff] of the plugin named `name` with a hill at [CELL].
+    fn landmass(name: &str) -> LandmassDiff {
+        LandmassBuilder::new(plugin(name))
+            .hill(CELL, 4, 0)
+            .build_diff(LandData::all())
+    }
+
+    #[test]
+    fn bundled_plugins_are_merged_at_their_position_in_the_load_order() {
+        with_main_thread_stack(|| {
+            let files: &'static MemoryFileProvider = Box::leak(Box::default());
+            let data_files = Path::new("Data Files");
+            for name in ["Master.esm", "Local.esp"] {
+                files.insert(data_files.join(name), name.into(), FileTime::zero());
+            }
+
+            let parsed_plugins =
+                parsed_plugins(vec![plugin("Master.esm")], vec![plugin("Local.esp")]);
+
+            let bundled = ["First.esp", "Local.esp", "Last.esp"].map(landmass);
+            let artifacts = ArtifactSink::directory(files, PathBuf::from("Export"));
+            let known_textures = KnownTextures::new();
+            save_bundle(&artifacts, &parsed_plugins, &bundled, &known_textures).unwrap();
+
+            let mut modded_landmasses = vec![landmass("Other.esp"), landmass("Local.esp")];
+            let num_loaded = load_bundle(
+                files,
+                data_files,
+                &Path::new("Export").join(BUNDLE_FILE_NAME),
+                &parsed_plugins,
+                &Landmass::new(parsed_plugins.masters[0].clone()),
+                &mut KnownTextures::new(),
+                &mut modded_landmasses,
+            )
+            .unwrap();
+
+            assert_eq!(num_loaded, 2);
+            assert_eq!(
+                modded_landmasses
+                    .iter()
+                    .map(|landmass| landmass.plugin.name.as_str())
+                    .collect_vec(),
+                ["First.esp", "Other.esp", "Local.esp", "Last.esp"]
+            );
+        });
+    }
+}
@@ -0,0 +1,233 @@
+use crate::io::report::{CellConflict, PatchDirection, PluginPairScore};
+use crate::land::grid_access::SquareGridIterator;
+use crate::land::terrain_map::{land_data_named, Vec2};
+use crate::LandmassDiff;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// The weight of a vertex with a major conflict in a [PluginPairScore], relative to a minor one.
+const MAJOR_WEIGHT: usize = 10;
+
+/// The fraction of the vertices changed by both plugins of a pair that must be changed in the
+/// same direction, or in opposite directions, for a [PatchDirection] other than independent.
+const DIRECTION_THRESHOLD: f32 = 0.8;
+
+#[derive(Default)]
+/// How the height changes of an earlier and a later plugin overlap in the cells both modify.
+pub struct EditOverlap {
+    /// The number of vertices only changed by the earlier plugin.
+    num_earlier_only: usize,
+    /// The number of vertices only changed by the later plugin.
+    num_later_only: usize,
+    /// The number of vertices changed in the same direction, at least as far by the later plugin.
+    num_later_further: usize,
+    /// The number of vertices changed in the same direction, further by the earlier plugin.
+    num_earlier_further: usize,
+    /// The number of vertices changed in opposite directions.
+    num_opposed: usize,
+}
+
+impl EditOverlap {
+    /// Returns the [PatchDirection] of the plugins. One plugin refines the other if it changes
+    /// nearly every vertex that the other changes, mostly in the same direction and further.
+    fn direction(&self) -> PatchDirection {
+        let num_same = self.num_later_further + self.num_earlier_further;
+        let num_both = num_same + self.num_opposed;
+        if num_both == 0 {
+            return PatchDirection::Unknown;
+        }
+
+        let fraction = |count: usize, total: usize| count as f32 / total.max(1) as f32;
+        let num_earlier = num_both + self.num_earlier_only;
+        let num_later = num_both + self.num_later_only;
+        if fraction(self.num_opposed, num_both) >= DIRECTION_THRESHOLD {
+            PatchDirection::Opposed
+        } else if fraction(self.num_later_further, num_both) >= DIRECTION_THRESHOLD
+            && fraction(num_both, num_earlier) >= DIRECTION_THRESHOLD
+        {
+            PatchDirection::LaterRefinesEarlier
+        } else if fraction(self.num_earlier_further, num_both) >= DIRECTION_THRESHOLD
+            && fraction(num_both, num_later) >= DIRECTION_THRESHOLD
+        {
+            PatchDirection::EarlierRefinesLater
+        } else {
+            PatchDirection::Independent
+        }
+    }
+}
+
+/// Compares the height changes of each pair of the `modded_landmasses` in each cell that both
+/// modify, and adds them to the [EditOverlap] of the pair, keyed by the earlier and later plugin.
+/// The `modded_landmasses` must be in load order.
+pub fn compare_plugin_edits(
+    modded_landmasses: &[LandmassDiff],
+    overlaps: &mut HashMap<(String, String), EditOverlap>,
+) {
+    for (idx, earlier) in modded_landmasses.iter().enumerate() {
+        for later in modded_landmasses[idx + 1..].iter() {
+            for (coords, earlier_land) in earlier.land.iter() {
+                let Some(later_land) = later.land.get(coords) else {
+                    continue;
+                };
+
+                let (Some(earlier_heights), Some(later_heights)) = (
+                    earlier_land.height_map.as_ref(),
+                    later_land.height_map.as_ref(),
+                ) else {
+                    continue;
+                };
+
+                let overlap = overlaps
+                    .entry((earlier.plugin.name.clone(), later.plugin.name.clone()))
+                    .or_default();
+                for coords in earlier_heights.iter_grid() {
+                    let lhs = earlier_heights.get_difference(coords);
+                    let rhs = later_heights.get_difference(coords);
+                    match (lhs, rhs) {
+                        (0, 0) => {}
+                        (_, 0) => overlap.num_earlier_only += 1,
+                        (0, _) => overlap.num_later_only += 1,
+                        _ if lhs.signum() != rhs.signum() => overlap.num_opposed += 1,
+                        _ if rhs.abs() >= lhs.abs() => overlap.num_later_further += 1,
+                        _ => overlap.num_earlier_further += 1,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The conflicts attributed to a pair of plugins while scoring.
+#[derive(Default)]
+struct PairConflicts {
+    num_minor: usize,
+    num_major: usize,
+    cells: HashSet<Vec2<i32>>,
+    major_cells: HashMap<String, HashSet<Vec2<i32>>>,
+}
+
+/// Returns a [PluginPairScore] for each pair of plugins that modify the same value of a cell
+/// with a [CellConflict], ordered by descending score. The conflict of a plugin is attributed
+/// to every earlier plugin that modified the same value of the cell in the `merged` land.
+/// The [PatchDirection] of each pair is found from its [EditOverlap] in the `overlaps`.
+pub fn score_plugin_pairs(
+    merged: &LandmassDiff,
+    conflicts: &[CellConflict],
+    overlaps: &HashMap<(String, String), EditOverlap>,
+) -> Vec<PluginPairScore> {
+    let mut pairs: HashMap<(String, String), PairConflicts> = HashMap::new();
+
+    for conflict in conflicts
+        .iter()
+        .filter(|conflict| conflict.num_minor + conflict.num_major > 0)
+    {
+        let coords = Vec2::from(conflict.coords);
+        let Some(land) = merged.land.get(&coords) else {
+            continue;
+        };
+
+        let Some(data) = land_data_named(&conflict.value) else {
+            continue;
+        };
+
+        let Some(position) = land
+            .plugins
+            .iter()
+            .position(|(plugin, _)| plugin.name == conflict.plugin)
+        else {
+            continue;
+        };
+
+        let others = land.plugins[..position]
+            .iter()
+            .filter(|(_, modified_data)| modified_data.contains(data))
+            .map(|(plugin, _)| plugin.name.clone())
+            .unique();
+
+        for other in others {
+            let pair = pairs.entry((other, conflict.plugin.clone())).or_default();
+            pair.num_minor += conflict.num_minor;
+            pair.num_major += conflict.num_major;
+            pair.cells.insert(coords);
+            if conflict.num_major > 0 {
+                pair.major_cells
+                    .entry(conflict.value.clone())
+                    .or_default()
+                    .insert(coords);
+            }
+        }
+    }
+
+    pairs
+        .into_iter()
+        .map(|((lhs, rhs), pair)| {
+            let major_cells: BTreeMap<String, usize> = pair
+                .major_cells
+                .into_iter()
+                .map(|(value, cells)| (value, cells.len()))
+                .collect();
+
+            let worst = major_cells
+                .iter()
+                .sorted_by_key(|(value, num_cells)| (Reverse(**num_cells), *value))
+                .next();
+
+            let suggestion = match worst {
+                Some((value, num_cells)) => format!(
+                    "Consider a patch for {} + {}: {} cells with major {} conflicts",
+                    lhs, rhs, num_cells, value
+                ),
+                None => format!(
+                    "{} + {} have only minor conflicts in {} cells",
+                    lhs,
+                    rhs,
+                    pair.cells.len()
+                ),
+            };
+
+            let direction = overlaps
+                .get(&(lhs.clone(), rhs.clone()))
+                .map(EditOverlap::direction)
+                .unwrap_or_default();
+
+            let direction_suggestion = match direction {
+                PatchDirection::Unknown => None,
+                PatchDirection::LaterRefinesEarlier => Some(format!(
+                    "{} mostly extends the height changes of {}, so it can overwrite them",
+                    rhs, lhs
+                )),
+                PatchDirection::EarlierRefinesLater => Some(format!(
+                    "{} mostly extends the height changes of {}, so {} should be patched to include them",
+                    lhs, rhs, rhs
+                )),
+                PatchDirection::Opposed => Some(format!(
+                    "{} and {} change heights in opposite directions, so a patch must choose between them",
+                    lhs, rhs
+                )),
+                PatchDirection::Independent => Some(format!(
+                    "{} and {} make independent height changes, so a patch should combine them",
+                    lhs, rhs
+                )),
+            };
+
+            PluginPairScore {
+                plugins: [lhs, rhs],
+                score: pair.num_major * MAJOR_WEIGHT + pair.num_minor,
+                num_cells: pair.cells.len(),
+                num_minor: pair.num_minor,
+                num_major: pair.num_major,
+                major_cells,
+                suggestion,
+                direction,
+                direction_suggestion,
+            }
+        })
+        .sorted_by(|lhs, rhs| {
+            rhs.score
+                .cmp(&lhs.score)
+                .then_with(|| lhs.plugins.cmp(&rhs.plugins))
+        })
+        .collect()
+}
@@ -0,0 +1,167 @@
+use crate::io::report::{CellConflict, ConflictCluster};
+use crate::land::terrain_map::Vec2;
+use crate::merge::cells::ModifiedCell;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use std::cmp::{Ordering, Reverse};
+use std::collections::VecDeque;
+
+/// Returns the coordinates of the 8 cells surrounding `coords`.
+/// Diagonal cells are included because they share a corner of the height map.
+fn neighbors(coords: Vec2<i32>) -> impl Iterator<Item = Vec2<i32>> {
+    (-1..=1)
+        .cartesian_product(-1..=1)
+        .filter(|(x, y)| *x != 0 || *y != 0)
+        .map(move |(x, y)| Vec2::new(coords.x + x, coords.y + y))
+}
+
+/// Orders `(key, count)` pairs by descending count, then by key.
+fn most_frequent_first<K: Ord>(lhs: &(K, usize), rhs: &(K, usize)) -> Ordering {
+    rhs.1.cmp(&lhs.1).then(lhs.0.cmp(&rhs.0))
+}
+
+/// Returns the most common of the `names`, preferring the first in sorted order on a tie.
+fn most_common<'a>(names: impl Iterator<Item = &'a str>) -> Option<(&'a str, usize)> {
+    names
+        .counts()
+        .into_iter()
+        .sorted_by(most_frequent_first)
+        .next()
+}
+
+/// Returns a name for the area covering the `cells`. Named cells take priority over regions.
+/// If only some of the `cells` are named, the area is the outskirts of the most common name.
+fn cluster_name(cells: &[Vec2<i32>], modified_cells: &HashMap<Vec2<i32>, ModifiedCell>) -> String {
+    let cells = cells
+        .iter()
+        .filter_map(|coords| modified_cells.get(coords))
+        .map(|cell| &cell.inner)
+        .collect_vec();
+
+    let names = cells
+        .iter()
+        .map(|cell| cell.id.as_str())
+        .filter(|id| !id.is_empty());
+
+    if let Some((name, count)) = most_common(names) {
+        return if count == cells.len() {
+            name.to_string()
+        } else {
+            format!("{} outskirts", name)
+        };
+    }
+
+    let regions = cells.iter().filter_map(|cell| cell.region.as_deref());
+    most_common(regions)
+        .map(|(region, _)| region.to_string())
+        .unwrap_or_else(|| "Wilderness".to_string())
+}
+
+/// Creates a [ConflictCluster] of the contiguous `cells` and their flagged `conflicts`.
+fn create_cluster(
+    cells: Vec<Vec2<i32>>,
+    conflicts: &HashMap<Vec2<i32>, Vec<&CellConflict>>,
+    modified_cells: &HashMap<Vec2<i32>, ModifiedCell>,
+) -> ConflictCluster {
+    let name = cluster_name(&cells, modified_cells);
+
+    let min = Vec2::new(
+        cells.iter().map(|coords| coords.x).min().expect("safe"),
+        cells.iter().map(|coords| coords.y).min().expect("safe"),
+    );
+    let max = Vec2::new(
+        cells.iter().map(|coords| coords.x).max().expect("safe"),
+        cells.iter().map(|coords| coords.y).max().expect("safe"),
+    );
+
+    let cell_conflicts = cells
+        .iter()
+        .flat_map(|coords| conflicts.get(coords).expect("safe"))
+        .collect_vec();
+
+    let plugins = cell_conflicts
+        .iter()
+        .map(|conflict| conflict.plugin.clone())
+        .unique()
+        .collect_vec();
+
+    let num_minor = cell_conflicts
+        .iter()
+        .map(|conflict| conflict.num_minor)
+        .sum();
+    let num_major = cell_conflicts
+        .iter()
+        .map(|conflict| conflict.num_major)
+        .sum();
+
+    let mut totals: HashMap<(&str, &str), usize> = HashMap::new();
+    for conflict in cell_conflicts.iter() {
+        *totals.entry((&conflict.value, "Minor")).or_default() += conflict.num_minor;
+        *totals.entry((&conflict.value, "Major")).or_default() += conflict.num_major;
+    }
+
+    let (value, kind) = totals
+        .into_iter()
+        .sorted_by(most_frequent_first)
+        .map(|(key, _)| key)
+        .next()
+        .expect("safe");
+
+    let summary = format!(
+        "Cluster: {}, {} cells, plugins {}, mostly {} {}",
+        name,
+        cells.len(),
+        plugins.join("/"),
+        value,
+        kind
+    );
+
+    ConflictCluster {
+        name,
+        min: min.into(),
+        max: max.into(),
+        cells: cells.into_iter().map(Into::into).collect(),
+        plugins,
+        num_minor,
+        num_major,
+        summary,
+    }
+}
+
+/// Groups the cells of each flagged [CellConflict] into a [ConflictCluster] of contiguous cells.
+/// Each [ConflictCluster] is named from the [ModifiedCell] records of its cells.
+pub fn cluster_conflicts(
+    conflicts: &[CellConflict],
+    modified_cells: &HashMap<Vec2<i32>, ModifiedCell>,
+) -> Vec<ConflictCluster> {
+    let mut flagged: HashMap<Vec2<i32>, Vec<&CellConflict>> = HashMap::new();
+    for conflict in conflicts.iter().filter(|conflict| conflict.flagged) {
+        let coords = Vec2::new(conflict.coords[0], conflict.coords[1]);
+        flagged.entry(coords).or_default().push(conflict);
+    }
+
+    let mut visited = HashSet::new();
+    let mut clusters = Vec::new();
+    for start in flagged.keys().sorted_by_key(|coords| (coords.x, coords.y)) {
+        if !visited.insert(*start) {
+            continue;
+        }
+
+        let mut cells = Vec::new();
+        let mut queue = VecDeque::from([*start]);
+        while let Some(coords) = queue.pop_front() {
+            cells.push(coords);
+            for neighbor in neighbors(coords) {
+                if flagged.contains_key(&neighbor) && visited.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        cells.sort_by_key(|coords| (coords.x, coords.y));
+        clusters.push(create_cluster(cells, &flagged, modified_cells));
+    }
+
+    clusters.sort_by_key(|cluster| Reverse(cluster.cells.len()));
+    clusters
+}
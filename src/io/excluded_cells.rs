@@ -0,0 +1,62 @@
+use crate::error::ParseError;
+use crate::io::file_provider::FileProvider;
+use crate::land::terrain_map::Vec2;
+use anyhow::{anyhow, bail, Context, Result};
+use hashbrown::HashSet;
+use log::debug;
+use std::path::Path;
+
+/// Parses the coordinates of a cell on `line` of an exclusion list, formatted as `x,y`. The
+/// coordinates may be wrapped in parentheses, as printed by the in-game console.
+/// Returns [None] for a blank line or a comment.
+fn parse_excluded_cell(line: &str) -> Result<Option<Vec2<i32>>> {
+    let text = line.trim();
+    if text.is_empty() || text.starts_with('#') {
+        return Ok(None);
+    }
+
+    let coords = text
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split_once(',')
+        .and_then(|(x, y)| Some(Vec2::new(x.trim().parse().ok()?, y.trim().parse().ok()?)));
+    match coords {
+        Some(coords) => Ok(Some(coords)),
+        None => bail!(ParseError::InvalidExcludedCell(text.to_string())),
+    }
+}
+
+/// Loads the coordinates of each cell in the exclusion list at `file_path`.
+/// Each line of the file contains the coordinates of one cell, formatted as `x,y`.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_excluded_cells(
+    files: &dyn FileProvider,
+    file_path: &Path,
+) -> Result<HashSet<Vec2<i32>>> {
+    let text = files.read_to_string(file_path).with_context(|| {
+        anyhow!(
+            "Unable to read exclusion list {}",
+            file_path.to_string_lossy()
+        )
+    })?;
+
+    let mut excluded_cells = HashSet::new();
+    for (idx, line) in text.lines().enumerate() {
+        let coords = parse_excluded_cell(line).with_context(|| {
+            anyhow!(
+                "Failed to parse line {} of exclusion list {}",
+                idx + 1,
+                file_path.to_string_lossy()
+            )
+        })?;
+        excluded_cells.extend(coords);
+    }
+
+    debug!(
+        "Excluding {} cells listed in {}",
+        excluded_cells.len(),
+        file_path.to_string_lossy()
+    );
+
+    Ok(excluded_cells)
+}
@@ -0,0 +1,997 @@
+use crate::io::artifacts::ArtifactSink;
+use crate::io::save_to_image::{save_world_map, ImageOptions, SaveToImage};
+use crate::land::grid_access::SquareGridIterator;
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::{LandData, Vec2, LAND_DATA_NAMES};
+use crate::land::textures::KnownTextures;
+use crate::LandmassDiff;
+use anyhow::{anyhow, Context, Result};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::{info, trace};
+use serde::Serialize;
+use std::fmt::Write as _;
+
+/// The name of the JSON file saved by [TerrainStatistics::save].
+const STATISTICS_JSON_FILE_NAME: &str = "distant_land.json";
+
+/// The name of the CSV file saved by [TerrainStatistics::save].
+const STATISTICS_CSV_FILE_NAME: &str = "distant_land.csv";
+
+/// The name of the JSON file saved by [CellMap::save].
+const CELL_MAP_FILE_NAME: &str = "cell_map.json";
+
+/// The size of a cell in world units.
+const CELL_SIZE: i32 = 8192;
+
+/// The distance between two vertices of a height map in world units.
+const VERTEX_SPACING: i32 = 128;
+
+#[derive(Serialize, Debug)]
+/// Statistics of a single modified cell in the merged landmass.
+pub struct CellStatistics {
+    /// The `x` coordinate of the cell.
+    pub x: i32,
+    /// The `y` coordinate of the cell.
+    pub y: i32,
+    /// The highest vertex of the cell in world units, if the cell has a height map.
+    pub max_height: Option<i32>,
+    /// The world position of the highest vertex of the cell as `[x, y]`, if the cell has a
+    /// height map.
+    pub peak: Option<[i32; 2]>,
+    /// The lowest vertex of the cell in world units, if the cell has a height map.
+    pub min_height: Option<i32>,
+    /// The names of the data modified in the cell, e.g. `height_map`.
+    pub modified_data: Vec<&'static str>,
+    /// The plugins that modified the cell.
+    pub plugins: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+/// Statistics of the merged landmass for regenerating distant land, e.g. with MGE XE.
+pub struct TerrainStatistics {
+    /// The [CellStatistics] of each modified cell, ordered by `x` and `y` coordinates.
+    pub cells: Vec<CellStatistics>,
+}
+
+/// Returns the names of each portion of the [LandData].
+fn land_data_names(data: LandData) -> Vec<&'static str> {
+    LAND_DATA_NAMES
+        .into_iter()
+        .filter(|(flag, _)| data.contains(*flag))
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Returns a CSV field for `value`, quoting it if necessary.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl CellStatistics {
+    /// Creates [CellStatistics] for the `land` if it is modified. The heights are only known if
+    /// the `land` has a height map, e.g. not for cells that only change textures.
+    fn from_land(land: &LandscapeDiff) -> Option<Self> {
+        let modified_data = land.modified_data();
+        if modified_data.is_empty() {
+            return None;
+        }
+
+        let mut peak = None;
+        let mut min_height = None;
+        if let Some(height_map) = land.height_map.as_ref() {
+            for coords in height_map.iter_grid() {
+                let height = height_map.get_value(coords);
+                min_height =
+                    Some(min_height.map_or(height, |min_height: i32| min_height.min(height)));
+                if peak.map_or(true, |(max_height, _)| height > max_height) {
+                    peak = Some((height, coords));
+                }
+            }
+        }
+
+        let plugins = land
+            .plugins
+            .iter()
+            .filter(|(_, modified_data)| !modified_data.is_empty())
+            .map(|(plugin, _)| plugin.name.clone())
+            .unique()
+            .collect_vec();
+
+        Some(Self {
+            x: land.coords.x,
+            y: land.coords.y,
+            max_height: peak.map(|(max_height, _)| max_height),
+            peak: peak.map(|(_, peak_coords)| {
+                [
+                    land.coords.x * CELL_SIZE + peak_coords.x as i32 * VERTEX_SPACING,
+                    land.coords.y * CELL_SIZE + peak_coords.y as i32 * VERTEX_SPACING,
+                ]
+            }),
+            min_height,
+            modified_data: land_data_names(modified_data),
+            plugins,
+        })
+    }
+}
+
+impl TerrainStatistics {
+    /// Creates [TerrainStatistics] for each modified cell of the `merged` [LandmassDiff].
+    pub fn from_landmass(merged: &LandmassDiff) -> Self {
+        Self {
+            cells: merged
+                .sorted()
+                .filter_map(|(_, land)| CellStatistics::from_land(land))
+                .collect(),
+        }
+    }
+
+    /// Returns the [TerrainStatistics] as CSV with one row per cell.
+    /// Plugins are separated by `;` within the `plugins` column. The heights of a cell without a
+    /// height map are left empty.
+    fn to_csv(&self) -> String {
+        let mut text = String::new();
+        writeln!(
+            text,
+            "x,y,max_height,peak_x,peak_y,min_height,modified_data,plugins"
+        )
+        .expect("safe");
+
+        let optional =
+            |value: Option<i32>| value.map(|value| value.to_string()).unwrap_or_default();
+        for cell in self.cells.iter() {
+            writeln!(
+                text,
+                "{},{},{},{},{},{},{},{}",
+                cell.x,
+                cell.y,
+                optional(cell.max_height),
+                optional(cell.peak.map(|peak| peak[0])),
+                optional(cell.peak.map(|peak| peak[1])),
+                optional(cell.min_height),
+                csv_field(&cell.modified_data.join(";")),
+                csv_field(&cell.plugins.join(";"))
+            )
+            .expect("safe");
+        }
+
+        text
+    }
+
+    /// Saves the [TerrainStatistics] as JSON and CSV to the [ArtifactSink].
+    pub fn save(&self, artifacts: &ArtifactSink) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)
+            .with_context(|| anyhow!("Unable to serialize terrain statistics"))?;
+
+        if !artifacts.can_save(STATISTICS_JSON_FILE_NAME)? {
+            return Ok(());
+        }
+
+        trace!(
+            "Saving {} and {} with {} cells",
+            STATISTICS_JSON_FILE_NAME,
+            STATISTICS_CSV_FILE_NAME,
+            self.cells.len()
+        );
+
+        artifacts.save_bytes(STATISTICS_JSON_FILE_NAME, &bytes)?;
+        artifacts.save_bytes(STATISTICS_CSV_FILE_NAME, self.to_csv().as_bytes())
+    }
+}
+
+#[derive(Serialize, Debug)]
+/// A single cell of the merged landmass in the [CellMap].
+pub struct CellMapEntry {
+    /// The `x` coordinate of the cell.
+    pub x: i32,
+    /// The `y` coordinate of the cell.
+    pub y: i32,
+    /// `true` if any plugin modified the LAND of the cell.
+    pub modified: bool,
+    /// The names of the data modified in the cell, e.g. `height_map`.
+    pub modified_data: Vec<&'static str>,
+    /// The last plugin that modified the cell, or the master of the cell if none did.
+    pub owner: String,
+    /// The master of the cell and each plugin that modified it, in load order.
+    pub plugins: Vec<String>,
+    /// The coordinates of each cell of the merged landmass sharing an edge with the cell, in the
+    /// order north, east, south, and west.
+    pub neighbors: Vec<[i32; 2]>,
+}
+
+#[derive(Serialize, Debug, Default)]
+/// Which cells exist in the merged landmass, their neighbors, and the plugins that own them,
+/// e.g. for navigation mesh or region planning tools.
+pub struct CellMap {
+    /// The [CellMapEntry] of each cell, ordered by `x` and `y` coordinates.
+    pub cells: Vec<CellMapEntry>,
+}
+
+impl CellMap {
+    /// Creates a [CellMap] of every cell of the `merged` [LandmassDiff].
+    pub fn from_landmass(merged: &LandmassDiff) -> Self {
+        let cells = merged
+            .sorted()
+            .map(|(coords, land)| {
+                let modified_data = land.modified_data();
+                let plugins = land
+                    .plugins
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, (_, modified_data))| *idx == 0 || !modified_data.is_empty())
+                    .map(|(_, (plugin, _))| plugin.name.clone())
+                    .unique()
+                    .collect_vec();
+
+                let neighbors = [(0, 1), (1, 0), (0, -1), (-1, 0)]
+                    .into_iter()
+                    .map(|(x, y)| Vec2::new(coords.x + x, coords.y + y))
+                    .filter(|neighbor| merged.land.contains_key(neighbor))
+                    .map(|neighbor| [neighbor.x, neighbor.y])
+                    .collect();
+
+                CellMapEntry {
+                    x: coords.x,
+                    y: coords.y,
+                    modified: !modified_data.is_empty(),
+                    modified_data: land_data_names(modified_data),
+                    owner: plugins.last().cloned().unwrap_or_default(),
+                    plugins,
+                    neighbors,
+                }
+            })
+            .collect();
+
+        Self { cells }
+    }
+
+    /// Saves the [CellMap] as JSON to the [ArtifactSink].
+    pub fn save(&self, artifacts: &ArtifactSink) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)
+            .with_context(|| anyhow!("Unable to serialize cell map"))?;
+
+        if !artifacts.can_save(CELL_MAP_FILE_NAME)? {
+            return Ok(());
+        }
+
+        trace!(
+            "Saving {} with {} cells",
+            CELL_MAP_FILE_NAME,
+            self.cells.len()
+        );
+
+        artifacts.save_bytes(CELL_MAP_FILE_NAME, &bytes)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// The format of the merged land saved by the `export` command.
+pub enum ExportFormat {
+    /// Simplified meshes of each block of cells, saved as `.obj` by [save_lod_meshes].
+    LodObj,
+    /// A raw height map for TESAnnwyn, saved by [save_tesannwyn_raw].
+    TesannwynRaw,
+    /// The changes of each plugin to the land of its masters, saved by [crate::io::land_diff::save_land_diffs].
+    LandDiff,
+    /// The land diffs of [Self::LandDiff] and a manifest of the CRC32 of every input, saved by
+    /// [crate::io::bundle::save_bundle].
+    Bundle,
+}
+
+#[derive(Copy, Clone, Debug)]
+/// Options controlling the meshes saved by [save_lod_meshes].
+pub struct LodOptions {
+    /// The number of cells along each side of a block. Each block is saved as a separate mesh.
+    pub block_size: usize,
+    /// The largest difference in world units between the simplified mesh and the height map.
+    pub tolerance: i32,
+}
+
+/// The number of vertices along each side of a cell, excluding the vertices shared with the next cell.
+const CELL_VERTICES: usize = 64;
+
+/// The heights of the vertices of a block of cells. Vertices in missing cells are `None`.
+struct BlockHeights {
+    size: usize,
+    heights: Vec<Option<i32>>,
+}
+
+impl BlockHeights {
+    /// Returns the height at `x` and `y`, if any.
+    fn get(&self, x: usize, y: usize) -> Option<i32> {
+        self.heights[y * self.size + x]
+    }
+}
+
+/// A quad of the vertices of a [BlockHeights] from `x0` and `y0` to `x1` and `y1`.
+type Quad = (usize, usize, usize, usize);
+
+/// A simplified mesh of a block of cells.
+#[derive(Default)]
+struct LodMesh {
+    vertices: Vec<[usize; 2]>,
+    indices: HashMap<[usize; 2], usize>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl LodMesh {
+    /// Returns the index of the vertex at `coords`, adding it if necessary.
+    fn vertex(&mut self, coords: [usize; 2]) -> usize {
+        let next = self.vertices.len();
+        let index = *self.indices.entry(coords).or_insert(next);
+        if index == next {
+            self.vertices.push(coords);
+        }
+        index
+    }
+
+    /// Adds the quad from `x0` and `y0` to `x1` and `y1` as two triangles facing up.
+    fn push_quad(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let a = self.vertex([x0, y0]);
+        let b = self.vertex([x1, y0]);
+        let c = self.vertex([x1, y1]);
+        let d = self.vertex([x0, y1]);
+        self.triangles.push([a, b, c]);
+        self.triangles.push([a, c, d]);
+    }
+
+    /// Adds the `quad` so that it shares every vertex on its sides with the quads next to it.
+    /// A vertex for which `is_vertex` returns `true` that lies on a side of the `quad`, e.g. a
+    /// corner of a smaller quad next to it, would otherwise leave a crack at a T-junction. If
+    /// there are such vertices, the `quad` is split into a fan of triangles around its center.
+    fn push_stitched_quad(&mut self, quad: Quad, is_vertex: impl Fn(usize, usize) -> bool) {
+        let (x0, y0, x1, y1) = quad;
+
+        // The vertices on the sides of the quad, counterclockwise from `x0` and `y0`.
+        let sides = (x0..x1)
+            .map(|x| [x, y0])
+            .chain((y0..y1).map(|y| [x1, y]))
+            .chain((x0 + 1..=x1).rev().map(|x| [x, y1]))
+            .chain((y0 + 1..=y1).rev().map(|y| [x0, y]))
+            .filter(|[x, y]| is_corner(quad, *x, *y) || is_vertex(*x, *y))
+            .collect_vec();
+
+        if sides.len() == 4 {
+            self.push_quad(x0, y0, x1, y1);
+            return;
+        }
+
+        let center = self.vertex([(x0 + x1) / 2, (y0 + y1) / 2]);
+        for (a, b) in sides.into_iter().circular_tuple_windows() {
+            let a = self.vertex(a);
+            let b = self.vertex(b);
+            self.triangles.push([center, a, b]);
+        }
+    }
+}
+
+/// Returns `true` if `x` and `y` is a corner of the `quad`.
+fn is_corner((x0, y0, x1, y1): Quad, x: usize, y: usize) -> bool {
+    (x == x0 || x == x1) && (y == y0 || y == y1)
+}
+
+/// Returns `true` if the quad from `x0` and `y0` to `x1` and `y1` matches every height within it
+/// to within the `tolerance` when interpolating between its corners. Returns `false` if any
+/// height within it is missing.
+fn is_flat_enough(
+    block: &BlockHeights,
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+    tolerance: i32,
+) -> bool {
+    let (Some(h00), Some(h10), Some(h01), Some(h11)) = (
+        block.get(x0, y0),
+        block.get(x1, y0),
+        block.get(x0, y1),
+        block.get(x1, y1),
+    ) else {
+        return false;
+    };
+
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let Some(height) = block.get(x, y) else {
+                return false;
+            };
+
+            let tx = (x - x0) as f32 / (x1 - x0) as f32;
+            let ty = (y - y0) as f32 / (y1 - y0) as f32;
+            let expected = (h00 as f32 * (1. - tx) + h10 as f32 * tx) * (1. - ty)
+                + (h01 as f32 * (1. - tx) + h11 as f32 * tx) * ty;
+
+            if (height as f32 - expected).abs() > tolerance as f32 {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Adds the quad from `x0` and `y0` to `x1` and `y1` to the `quads`, splitting it into
+/// 4 quads until each quad is within the `tolerance` or covers a single vertex spacing.
+fn simplify_quad(
+    quads: &mut Vec<Quad>,
+    block: &BlockHeights,
+    (x0, y0, x1, y1): Quad,
+    tolerance: i32,
+) {
+    let is_leaf = x1 - x0 <= 1 && y1 - y0 <= 1;
+    if is_leaf || is_flat_enough(block, x0, y0, x1, y1, tolerance) {
+        let is_complete = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+            .iter()
+            .all(|(x, y)| block.get(*x, *y).is_some());
+        if is_complete {
+            quads.push((x0, y0, x1, y1));
+        }
+        return;
+    }
+
+    let xs = if x1 - x0 > 1 {
+        vec![(x0, (x0 + x1) / 2), ((x0 + x1) / 2, x1)]
+    } else {
+        vec![(x0, x1)]
+    };
+
+    let ys = if y1 - y0 > 1 {
+        vec![(y0, (y0 + y1) / 2), ((y0 + y1) / 2, y1)]
+    } else {
+        vec![(y0, y1)]
+    };
+
+    for (y0, y1) in ys.iter() {
+        for (x0, x1) in xs.iter() {
+            simplify_quad(quads, block, (*x0, *y0, *x1, *y1), tolerance);
+        }
+    }
+}
+
+/// Returns the [BlockHeights] of the block of cells with `block_coords` in the [LandmassDiff].
+fn block_heights(
+    merged: &LandmassDiff,
+    block_coords: Vec2<i32>,
+    block_size: usize,
+) -> BlockHeights {
+    let size = block_size * CELL_VERTICES + 1;
+    let mut heights = vec![None; size * size];
+
+    for cell_y in 0..block_size {
+        for cell_x in 0..block_size {
+            let coords = Vec2::new(
+                block_coords.x * block_size as i32 + cell_x as i32,
+                block_coords.y * block_size as i32 + cell_y as i32,
+            );
+
+            let Some(height_map) = merged
+                .land
+                .get(&coords)
+                .and_then(|land| land.height_map.as_ref())
+            else {
+                continue;
+            };
+
+            for vertex in height_map.iter_grid() {
+                let x = cell_x * CELL_VERTICES + vertex.x;
+                let y = cell_y * CELL_VERTICES + vertex.y;
+                heights[y * size + x] = Some(height_map.get_value(vertex));
+            }
+        }
+    }
+
+    BlockHeights { size, heights }
+}
+
+/// Returns the [LodMesh] of the `block` as a Wavefront `.obj` file. Coordinates are in world
+/// units with `z` up, offset so that the block with coordinates `block_coords` is in place.
+fn to_obj(
+    mesh: &LodMesh,
+    block: &BlockHeights,
+    block_coords: Vec2<i32>,
+    block_size: usize,
+) -> String {
+    let origin_x = block_coords.x * block_size as i32 * CELL_SIZE;
+    let origin_y = block_coords.y * block_size as i32 * CELL_SIZE;
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "# Merged Lands LOD mesh of cells ({}, {}) to ({}, {})",
+        block_coords.x * block_size as i32,
+        block_coords.y * block_size as i32,
+        (block_coords.x + 1) * block_size as i32 - 1,
+        (block_coords.y + 1) * block_size as i32 - 1
+    )
+    .expect("safe");
+
+    for [x, y] in mesh.vertices.iter() {
+        writeln!(
+            text,
+            "v {} {} {}",
+            origin_x + *x as i32 * VERTEX_SPACING,
+            origin_y + *y as i32 * VERTEX_SPACING,
+            block.get(*x, *y).expect("safe")
+        )
+        .expect("safe");
+    }
+
+    for [a, b, c] in mesh.triangles.iter() {
+        writeln!(text, "f {} {} {}", a + 1, b + 1, c + 1).expect("safe");
+    }
+
+    text
+}
+
+/// The simplified [LodMesh] of a block of cells.
+struct LodBlock {
+    coords: Vec2<i32>,
+    heights: BlockHeights,
+    mesh: LodMesh,
+}
+
+/// Returns the simplified [LodBlock] of each block of cells in the `merged` [LandmassDiff] with
+/// any triangles. Each block is simplified first, so that the quads of every block can be
+/// stitched to the corners of smaller quads next to them, including in neighboring blocks.
+fn lod_blocks(merged: &LandmassDiff, options: &LodOptions) -> Vec<LodBlock> {
+    assert!(options.block_size > 0, "block_size must be > 0");
+
+    let block_size = options.block_size as i32;
+    let blocks = merged
+        .land
+        .keys()
+        .map(|coords| {
+            Vec2::new(
+                coords.x.div_euclid(block_size),
+                coords.y.div_euclid(block_size),
+            )
+        })
+        .unique()
+        .sorted_by_key(|coords| (coords.x, coords.y))
+        .map(|block_coords| {
+            let heights = block_heights(merged, block_coords, options.block_size);
+            let mut quads = Vec::new();
+            let size = heights.size - 1;
+            simplify_quad(&mut quads, &heights, (0, 0, size, size), options.tolerance);
+            (block_coords, heights, quads)
+        })
+        .collect_vec();
+
+    // The corners of the quads of every block, counted in vertices from the block at (0, 0).
+    let block_vertices = (options.block_size * CELL_VERTICES) as i32;
+    let world_vertex = |block_coords: Vec2<i32>, x: usize, y: usize| {
+        Vec2::new(
+            block_coords.x * block_vertices + x as i32,
+            block_coords.y * block_vertices + y as i32,
+        )
+    };
+
+    let vertices: HashSet<Vec2<i32>> = blocks
+        .iter()
+        .flat_map(|(block_coords, _, quads)| {
+            quads.iter().flat_map(move |(x0, y0, x1, y1)| {
+                [(*x0, *y0), (*x1, *y0), (*x0, *y1), (*x1, *y1)]
+                    .map(|(x, y)| world_vertex(*block_coords, x, y))
+            })
+        })
+        .collect();
+
+    blocks
+        .into_iter()
+        .filter(|(_, _, quads)| !quads.is_empty())
+        .map(|(block_coords, heights, quads)| {
+            let mut mesh = LodMesh::default();
+            for quad in quads {
+                mesh.push_stitched_quad(quad, |x, y| {
+                    vertices.contains(&world_vertex(block_coords, x, y))
+                });
+            }
+
+            LodBlock {
+                coords: block_coords,
+                heights,
+                mesh,
+            }
+        })
+        .collect()
+}
+
+/// Saves a simplified mesh of each block of cells in the `merged` [LandmassDiff] as a
+/// `lod_{x}_{y}.obj` file, where `x` and `y` are the coordinates of the block.
+/// Quads of different sizes are stitched together, so there are no cracks between them.
+pub fn save_lod_meshes(
+    artifacts: &ArtifactSink,
+    merged: &LandmassDiff,
+    options: &LodOptions,
+) -> Result<()> {
+    for block in lod_blocks(merged, options) {
+        let file_name = format!("lod_{}_{}.obj", block.coords.x, block.coords.y);
+        trace!(
+            "Saving {} with {} vertices and {} triangles",
+            file_name,
+            block.mesh.vertices.len(),
+            block.mesh.triangles.len()
+        );
+
+        let text = to_obj(
+            &block.mesh,
+            &block.heights,
+            block.coords,
+            options.block_size,
+        );
+        artifacts.save_bytes(&file_name, text.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// The name of the height map saved by [save_tesannwyn_raw].
+const TESANNWYN_RAW_FILE_NAME: &str = "tesannwyn.raw";
+
+/// The name of the file with the size and offset of the height map saved by [save_tesannwyn_raw].
+const TESANNWYN_PARAMETERS_FILE_NAME: &str = "tesannwyn.txt";
+
+/// The number of world units per unit of a TESAnnwyn height map.
+const TESANNWYN_HEIGHT_SCALE: i32 = 8;
+
+/// The height in world units used for vertices of cells without LAND.
+const TESANNWYN_MISSING_HEIGHT: i32 = -2048;
+
+/// Saves the height map of the `merged` [LandmassDiff] as a raw file for TESAnnwyn with signed,
+/// little-endian values of `bits` bits. Each cell is 64x64 values, since the last row and column
+/// of each cell is shared with the next cell. Rows are ordered from north to south. The size of
+/// the height map, the coordinates of its south-west cell, and the `bits` are saved next to it,
+/// since they are needed to import it. Cells in the `outliers` are left out, so that a far away
+/// cell does not enlarge the height map.
+pub fn save_tesannwyn_raw(
+    artifacts: &ArtifactSink,
+    merged: &LandmassDiff,
+    outliers: &HashSet<Vec2<i32>>,
+    bits: u8,
+) -> Result<()> {
+    assert!(bits == 16 || bits == 32, "bits must be 16 or 32");
+
+    let cells = merged
+        .land
+        .iter()
+        .filter(|(coords, _)| !outliers.contains(*coords))
+        .collect_vec();
+
+    let Some(min_x) = cells.iter().map(|(coords, _)| coords.x).min() else {
+        return Ok(());
+    };
+
+    let max_x = cells
+        .iter()
+        .map(|(coords, _)| coords.x)
+        .max()
+        .expect("safe");
+    let min_y = cells
+        .iter()
+        .map(|(coords, _)| coords.y)
+        .min()
+        .expect("safe");
+    let max_y = cells
+        .iter()
+        .map(|(coords, _)| coords.y)
+        .max()
+        .expect("safe");
+
+    let cells_x = (max_x - min_x + 1) as usize;
+    let cells_y = (max_y - min_y + 1) as usize;
+    let width = cells_x * CELL_VERTICES;
+    let height = cells_y * CELL_VERTICES;
+
+    let mut heights = vec![TESANNWYN_MISSING_HEIGHT / TESANNWYN_HEIGHT_SCALE; width * height];
+    for (coords, land) in cells {
+        let Some(height_map) = land.height_map.as_ref() else {
+            continue;
+        };
+
+        let cell_x = (coords.x - min_x) as usize * CELL_VERTICES;
+        let cell_row = (max_y - coords.y) as usize * CELL_VERTICES;
+        for vertex in height_map.iter_grid() {
+            if vertex.x == CELL_VERTICES || vertex.y == CELL_VERTICES {
+                continue;
+            }
+
+            let row = cell_row + (CELL_VERTICES - 1 - vertex.y);
+            heights[row * width + cell_x + vertex.x] =
+                height_map.get_value(vertex) / TESANNWYN_HEIGHT_SCALE;
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(width * height * bits as usize / 8);
+    for value in heights {
+        if bits == 16 {
+            let value = value.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        } else {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    let mut parameters = String::new();
+    writeln!(parameters, "bits = {}", bits).expect("safe");
+    writeln!(parameters, "width = {}", width).expect("safe");
+    writeln!(parameters, "height = {}", height).expect("safe");
+    writeln!(parameters, "x_offset = {}", min_x).expect("safe");
+    writeln!(parameters, "y_offset = {}", min_y).expect("safe");
+
+    trace!("Saving {}", TESANNWYN_RAW_FILE_NAME);
+    artifacts.save_bytes(TESANNWYN_RAW_FILE_NAME, &bytes)?;
+    artifacts.save_bytes(TESANNWYN_PARAMETERS_FILE_NAME, parameters.as_bytes())?;
+
+    info!(
+        "Saved {} with {}x{} cells ({}x{} values) starting at cell ({}, {}) in the south-west",
+        TESANNWYN_RAW_FILE_NAME, cells_x, cells_y, width, height, min_x, min_y
+    );
+
+    Ok(())
+}
+
+/// The name of the table of cells saved by [save_reference_snapshot].
+const REFERENCE_CELLS_FILE_NAME: &str = "reference_cells.csv";
+
+/// The name of the table of LTEX records saved by [save_reference_snapshot].
+const REFERENCE_TEXTURES_FILE_NAME: &str = "reference_textures.csv";
+
+/// Saves a snapshot of the `reference` [LandmassDiff] merged from the masters before any plugin.
+/// This includes an image of the height map of each cell as `height_map_{x}_{y}.png`, an image of
+/// the world map, a table of each cell with the master it came from, and a table of the LTEX
+/// records in the [KnownTextures]. Cells in the `outliers` are left out of the world map.
+pub fn save_reference_snapshot(
+    artifacts: &ArtifactSink,
+    reference: &LandmassDiff,
+    outliers: &HashSet<Vec2<i32>>,
+    known_textures: &KnownTextures,
+    options: &ImageOptions,
+) -> Result<()> {
+    let mut cells = String::new();
+    writeln!(cells, "x,y,min_height,max_height,data,plugin").expect("safe");
+
+    for (coords, land) in reference.sorted() {
+        let (min_height, max_height) = match land.height_map.as_ref() {
+            Some(height_map) => {
+                let file_name = format!("height_map_{}_{}.png", coords.x, coords.y);
+                height_map.save_to_image(artifacts, &file_name, options);
+
+                let (min_height, max_height) = height_map
+                    .iter_grid()
+                    .map(|coords| height_map.get_value(coords))
+                    .minmax()
+                    .into_option()
+                    .expect("safe");
+                (min_height.to_string(), max_height.to_string())
+            }
+            None => (String::new(), String::new()),
+        };
+
+        let plugin = land
+            .plugins
+            .first()
+            .map(|(plugin, _)| plugin.name.as_str())
+            .unwrap_or_default();
+
+        writeln!(
+            cells,
+            "{},{},{},{},{},{}",
+            coords.x,
+            coords.y,
+            min_height,
+            max_height,
+            csv_field(&land_data_names(land.present_data()).join(";")),
+            csv_field(plugin)
+        )
+        .expect("safe");
+    }
+
+    save_world_map(artifacts, reference, outliers, options);
+
+    let mut textures = String::new();
+    writeln!(textures, "index,id,file_name,plugin").expect("safe");
+
+    for texture in known_textures.sorted() {
+        writeln!(
+            textures,
+            "{},{},{},{}",
+            texture.index().as_u16(),
+            csv_field(texture.id()),
+            csv_field(texture.file_name().unwrap_or_default()),
+            csv_field(&texture.plugin.name)
+        )
+        .expect("safe");
+    }
+
+    trace!(
+        "Saving {} with {} cells and {} with {} textures",
+        REFERENCE_CELLS_FILE_NAME,
+        reference.land.len(),
+        REFERENCE_TEXTURES_FILE_NAME,
+        known_textures.len()
+    );
+
+    artifacts.save_bytes(REFERENCE_CELLS_FILE_NAME, cells.as_bytes())?;
+    artifacts.save_bytes(REFERENCE_TEXTURES_FILE_NAME, textures.as_bytes())
+}
+
+/// The name of the table of texture remaps saved by [save_texture_remaps].
+const TEXTURE_REMAPS_FILE_NAME: &str = "texture_remaps.csv";
+
+/// Saves a table of each [crate::land::textures::TextureRemap] of the [KnownTextures], across
+/// both the registration of the LTEX records of each plugin and the final compaction.
+/// Textures removed by the compaction have an empty `new_index`.
+pub fn save_texture_remaps(artifacts: &ArtifactSink, known_textures: &KnownTextures) -> Result<()> {
+    if !artifacts.can_save(TEXTURE_REMAPS_FILE_NAME)? {
+        return Ok(());
+    }
+
+    let mut remaps = String::new();
+    writeln!(remaps, "stage,plugin,original_index,new_index,id,file_name").expect("safe");
+
+    for remap in known_textures.remaps() {
+        writeln!(
+            remaps,
+            "{},{},{},{},{},{}",
+            remap.stage.name(),
+            csv_field(&remap.plugin),
+            remap.original_index,
+            remap
+                .new_index
+                .map(|index| index.to_string())
+                .unwrap_or_default(),
+            csv_field(&remap.id),
+            csv_field(remap.file_name.as_deref().unwrap_or_default())
+        )
+        .expect("safe");
+    }
+
+    trace!(
+        "Saving {} with {} texture remaps",
+        TEXTURE_REMAPS_FILE_NAME,
+        known_textures.remaps().len()
+    );
+
+    artifacts.save_bytes(TEXTURE_REMAPS_FILE_NAME, remaps.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::file_provider::{FileProvider, MemoryFileProvider};
+    use crate::test_support::{plugin, with_main_thread_stack, LandmassBuilder, CELL};
+    use std::path::{Path, PathBuf};
+
+    /// Returns a [LandmassDiff] with a flat cell at `(0, 0)` and a cell at `(1, 0)` that is flat
+    /// except for a bump, so that only the second cell is split into small quads.
+    fn bumpy_landmass() -> LandmassDiff {
+        let mut bumpy = [[800; 65]; 65];
+        bumpy[20][12] += 400;
+
+        LandmassBuilder::new(plugin("Merged.esp"))
+            .heights(CELL, &[[800; 65]; 65], 1)
+            .heights(Vec2::new(1, 0), &bumpy, 1)
+            .build_diff(LandData::all())
+    }
+
+    #[test]
+    fn cells_that_only_change_textures_have_statistics() {
+        let merged = LandmassBuilder::new(plugin("Merged.esp"))
+            .heights(CELL, &[[800; 65]; 65], 1)
+            .build_diff(LandData::TEXTURES);
+
+        let statistics = with_main_thread_stack(|| TerrainStatistics::from_landmass(&merged));
+        let [cell] = statistics.cells.as_slice() else {
+            panic!("expected 1 cell but found {}", statistics.cells.len());
+        };
+
+        assert_eq!(cell.modified_data, vec!["texture_indices"]);
+        assert_eq!(cell.max_height, None);
+        assert!(statistics
+            .to_csv()
+            .ends_with("\n0,0,,,,,texture_indices,\n"));
+    }
+
+    #[test]
+    fn tesannwyn_parameters_are_saved_next_to_the_height_map() {
+        let files: &'static MemoryFileProvider = Box::leak(Box::default());
+        let artifacts = ArtifactSink::directory(files, PathBuf::from("Export"));
+        let merged = with_main_thread_stack(bumpy_landmass);
+        save_tesannwyn_raw(&artifacts, &merged, &HashSet::new(), 16).unwrap();
+
+        let raw = files
+            .read(&Path::new("Export").join(TESANNWYN_RAW_FILE_NAME))
+            .unwrap();
+        assert_eq!(raw.len(), 2 * CELL_VERTICES * CELL_VERTICES * 2);
+
+        let parameters = files
+            .read_to_string(&Path::new("Export").join(TESANNWYN_PARAMETERS_FILE_NAME))
+            .unwrap();
+        assert_eq!(
+            parameters,
+            "bits = 16\nwidth = 128\nheight = 64\nx_offset = 0\ny_offset = 0\n"
+        );
+    }
+
+    /// Returns each triangle of the `blocks` with its vertices counted from the block at `(0, 0)`.
+    fn world_triangles(blocks: &[LodBlock], block_size: usize) -> Vec<[[i64; 2]; 3]> {
+        let block_vertices = (block_size * CELL_VERTICES) as i64;
+        blocks
+            .iter()
+            .flat_map(|block| {
+                block.mesh.triangles.iter().map(|triangle| {
+                    triangle.map(|index| {
+                        let [x, y] = block.mesh.vertices[index];
+                        [
+                            block.coords.x as i64 * block_vertices + x as i64,
+                            block.coords.y as i64 * block_vertices + y as i64,
+                        ]
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Returns `true` if `vertex` lies on the edge from `a` to `b`, excluding its ends.
+    fn is_inside_edge(vertex: [i64; 2], a: [i64; 2], b: [i64; 2]) -> bool {
+        let cross = (b[0] - a[0]) * (vertex[1] - a[1]) - (b[1] - a[1]) * (vertex[0] - a[0]);
+        let dot = (vertex[0] - a[0]) * (b[0] - a[0]) + (vertex[1] - a[1]) * (b[1] - a[1]);
+        let length = (b[0] - a[0]).pow(2) + (b[1] - a[1]).pow(2);
+        cross == 0 && dot > 0 && dot < length
+    }
+
+    #[test]
+    fn meshes_have_no_t_junctions() {
+        for block_size in [1, 2] {
+            let blocks = with_main_thread_stack(|| {
+                lod_blocks(
+                    &bumpy_landmass(),
+                    &LodOptions {
+                        block_size,
+                        tolerance: 0,
+                    },
+                )
+            });
+
+            let triangles = world_triangles(&blocks, block_size);
+            let vertices: HashSet<[i64; 2]> = triangles.iter().flatten().copied().collect();
+            for triangle in triangles.iter() {
+                for (a, b) in triangle.iter().circular_tuple_windows() {
+                    let t_junction = vertices
+                        .iter()
+                        .find(|vertex| is_inside_edge(**vertex, *a, *b));
+                    assert_eq!(t_junction, None, "edge {:?} to {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn meshes_cover_every_cell_once() {
+        let blocks = with_main_thread_stack(|| {
+            lod_blocks(
+                &bumpy_landmass(),
+                &LodOptions {
+                    block_size: 1,
+                    tolerance: 0,
+                },
+            )
+        });
+
+        // Twice the area of each triangle, which is positive if it faces up.
+        let areas = world_triangles(&blocks, 1)
+            .into_iter()
+            .map(|[a, b, c]| (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]))
+            .collect_vec();
+
+        assert!(areas.iter().all(|area| *area > 0));
+        assert_eq!(
+            areas.iter().sum::<i64>(),
+            2 * 2 * (CELL_VERTICES * CELL_VERTICES) as i64
+        );
+    }
+}
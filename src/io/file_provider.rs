@@ -0,0 +1,402 @@
+use crate::error::IoError;
+use crate::io::paths::extended_length_path;
+use anyhow::{anyhow, bail, Context, Result};
+use filetime::FileTime;
+use hashbrown::HashMap;
+use log::warn;
+use owo_colors::OwoColorize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Reads and writes the plugins, meta files, and config files used by the tool.
+/// The [StdFileProvider] uses the file system, but other providers can read files from
+/// archives or from memory, e.g. with the [MemoryFileProvider].
+pub trait FileProvider: Send + Sync {
+    /// Returns `true` if a file or directory exists at `path`.
+    fn exists(&self, path: &Path) -> Result<bool>;
+
+    /// Returns the bytes of the file at `path`.
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Returns the text of the file at `path`.
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        let bytes = self.read(path)?;
+        String::from_utf8(bytes)
+            .with_context(|| anyhow!("File {} is not valid UTF-8", path.to_string_lossy()))
+    }
+
+    /// Returns the path of each file in the directory at `path`, in no particular order.
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Returns the size in bytes of the file at `path`.
+    fn file_size(&self, path: &Path) -> Result<u64>;
+
+    /// Returns the last modified time of the file at `path`.
+    fn modified_time(&self, path: &Path) -> Result<FileTime>;
+
+    /// Sets the last modified time of the file at `path`.
+    fn set_modified_time(&self, path: &Path, time: FileTime) -> Result<()>;
+
+    /// Replaces the file at `path` with the `bytes`. If writing fails, the file is unchanged.
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()>;
+
+    /// Creates or truncates the file at `path` and returns a [FileWriter] for it. Unlike
+    /// [FileProvider::write], the file is written while it is created, e.g. for a log file.
+    fn create(&self, path: &Path) -> Result<Box<dyn FileWriter>>;
+
+    /// Returns an error if the existing file at `path` cannot be opened for writing, e.g. because
+    /// it is open in another program. Succeeds if there is no file at `path`.
+    fn check_writable(&self, path: &Path) -> Result<()>;
+
+    /// Returns `true` if a directory exists at `path`.
+    fn is_dir(&self, path: &Path) -> Result<bool>;
+
+    /// Creates the directory at `path` and any of its parents that do not exist.
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Returns the canonical form of the `path`, so that equal paths refer to the same file.
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// A file opened for writing by [FileProvider::create].
+pub trait FileWriter: Write + Seek + Send {}
+
+impl<T: Write + Seek + Send> FileWriter for T {}
+
+/// A [FileProvider] for the file system. Each path is converted with [extended_length_path],
+/// so that long paths can be used on Windows.
+pub struct StdFileProvider;
+
+impl FileProvider for StdFileProvider {
+    fn exists(&self, path: &Path) -> Result<bool> {
+        let path = &extended_length_path(path);
+        Ok(path.try_exists()?)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = &extended_length_path(path);
+        Ok(fs::read(path)?)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = &extended_length_path(path);
+        Ok(fs::read_dir(path)?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect())
+    }
+
+    fn file_size(&self, path: &Path) -> Result<u64> {
+        let path = &extended_length_path(path);
+        Ok(filesize::file_real_size(path)?)
+    }
+
+    fn modified_time(&self, path: &Path) -> Result<FileTime> {
+        let path = &extended_length_path(path);
+        let metadata = path.metadata()?;
+        Ok(FileTime::from_last_modification_time(&metadata))
+    }
+
+    fn set_modified_time(&self, path: &Path, time: FileTime) -> Result<()> {
+        let path = &extended_length_path(path);
+        Ok(filetime::set_file_mtime(path, time)?)
+    }
+
+    /// Writes a temporary `.tmp` copy of the file at `path`, then renames the copy over the file.
+    /// If writing fails, the temporary copy is removed.
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        let path = &extended_length_path(path);
+        let mut tmp_file_name = path.file_name().expect("safe").to_os_string();
+        tmp_file_name.push(".tmp");
+        let tmp_file_path = path.with_file_name(tmp_file_name);
+
+        if let Err(e) = fs::write(&tmp_file_path, bytes) {
+            if tmp_file_path.exists() {
+                fs::remove_file(&tmp_file_path).unwrap_or_else(|e| {
+                    warn!(
+                        "{} {}",
+                        format!(
+                            "Unable to remove file {}",
+                            tmp_file_path.to_string_lossy().bold()
+                        )
+                        .yellow(),
+                        format!("due to: {:?}", e.bold()).yellow()
+                    );
+                });
+            }
+
+            return Err(e.into());
+        }
+
+        fs::rename(&tmp_file_path, path).with_context(|| {
+            anyhow!(
+                "Unable to replace file {} with {}",
+                path.to_string_lossy(),
+                tmp_file_path.to_string_lossy()
+            )
+        })
+    }
+
+    fn create(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        let path = &extended_length_path(path);
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn check_writable(&self, path: &Path) -> Result<()> {
+        let path = &extended_length_path(path);
+        if !path.try_exists()? {
+            return Ok(());
+        }
+
+        OpenOptions::new().append(true).open(path)?;
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &Path) -> Result<bool> {
+        let path = &extended_length_path(path);
+        Ok(path.is_dir())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let path = &extended_length_path(path);
+        Ok(fs::create_dir_all(path)?)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        let path = &extended_length_path(path);
+        Ok(fs::canonicalize(path)?)
+    }
+}
+
+/// A file stored by the [MemoryFileProvider].
+struct MemoryFile {
+    /// The contents of the file.
+    bytes: Vec<u8>,
+    /// The last modified time of the file.
+    modified_time: FileTime,
+}
+
+#[derive(Default)]
+/// A [FileProvider] that keeps every file in memory. Directories exist if they contain a file.
+pub struct MemoryFileProvider {
+    /// Each file, by its path.
+    files: Arc<RwLock<HashMap<PathBuf, MemoryFile>>>,
+}
+
+/// A [FileWriter] created by the [MemoryFileProvider]. The file is replaced with the bytes
+/// written so far whenever the writer is flushed or dropped.
+struct MemoryFileWriter {
+    /// The files of the [MemoryFileProvider].
+    files: Arc<RwLock<HashMap<PathBuf, MemoryFile>>>,
+    /// The path of the file.
+    path: PathBuf,
+    /// The bytes written so far.
+    cursor: Cursor<Vec<u8>>,
+}
+
+impl Write for MemoryFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.cursor.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut files = self.files.write().expect("safe");
+        let file = files
+            .entry(self.path.clone())
+            .or_insert_with(|| MemoryFile {
+                bytes: Vec::new(),
+                modified_time: FileTime::zero(),
+            });
+        file.bytes = self.cursor.get_ref().clone();
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFileWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+impl Drop for MemoryFileWriter {
+    fn drop(&mut self) {
+        self.flush().expect("safe");
+    }
+}
+
+impl MemoryFileProvider {
+    /// Creates a [MemoryFileProvider] without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file at `path` with the `bytes` and the `modified_time`.
+    pub fn insert(&self, path: impl Into<PathBuf>, bytes: Vec<u8>, modified_time: FileTime) {
+        self.files.write().expect("safe").insert(
+            path.into(),
+            MemoryFile {
+                bytes,
+                modified_time,
+            },
+        );
+    }
+
+    /// Removes the file at `path`, if it exists.
+    pub fn remove(&self, path: &Path) {
+        self.files.write().expect("safe").remove(path);
+    }
+
+    /// Calls `f` with the file at `path`, or returns an `Err` if there is no file.
+    fn with_file<T>(&self, path: &Path, f: impl FnOnce(&MemoryFile) -> T) -> Result<T> {
+        let files = self.files.read().expect("safe");
+        match files.get(path) {
+            Some(file) => Ok(f(file)),
+            None => bail!(IoError::FileNotFound(path.to_path_buf())),
+        }
+    }
+}
+
+impl FileProvider for MemoryFileProvider {
+    fn exists(&self, path: &Path) -> Result<bool> {
+        let files = self.files.read().expect("safe");
+        Ok(files.keys().any(|file_path| file_path.starts_with(path)))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.with_file(path, |file| file.bytes.clone())
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let files = self.files.read().expect("safe");
+        Ok(files
+            .keys()
+            .filter(|file_path| file_path.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn file_size(&self, path: &Path) -> Result<u64> {
+        self.with_file(path, |file| file.bytes.len() as u64)
+    }
+
+    fn modified_time(&self, path: &Path) -> Result<FileTime> {
+        self.with_file(path, |file| file.modified_time)
+    }
+
+    fn set_modified_time(&self, path: &Path, time: FileTime) -> Result<()> {
+        let mut files = self.files.write().expect("safe");
+        match files.get_mut(path) {
+            Some(file) => file.modified_time = time,
+            None => bail!(IoError::FileNotFound(path.to_path_buf())),
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the file at `path`, keeping its last modified time. New files are given the
+    /// earliest last modified time, since the time may be unknown, e.g. on the web.
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        let modified_time = self
+            .modified_time(path)
+            .unwrap_or_else(|_| FileTime::zero());
+        self.insert(path, bytes.to_vec(), modified_time);
+        Ok(())
+    }
+
+    /// Creates an empty file at `path`, keeping its last modified time like [Self::write].
+    fn create(&self, path: &Path) -> Result<Box<dyn FileWriter>> {
+        self.write(path, &[])?;
+        Ok(Box::new(MemoryFileWriter {
+            files: self.files.clone(),
+            path: path.to_path_buf(),
+            cursor: Cursor::new(Vec::new()),
+        }))
+    }
+
+    /// Does nothing, since files in memory cannot be opened by another program.
+    fn check_writable(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &Path) -> Result<bool> {
+        let files = self.files.read().expect("safe");
+        Ok(files
+            .keys()
+            .any(|file_path| file_path != path && file_path.starts_with(path)))
+    }
+
+    /// Does nothing, since directories exist if they contain a file.
+    fn create_dir_all(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_and_closed_files_are_writable() {
+        let dir =
+            std::env::temp_dir().join(format!("merged_lands_writable_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Merged Lands.esp");
+
+        let files = StdFileProvider;
+        let missing = files.check_writable(&path);
+        files.write(&path, b"TES3").unwrap();
+        let closed = files.check_writable(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(missing.is_ok());
+        assert!(closed.is_ok());
+    }
+
+    #[test]
+    fn unicode_plugin_names_are_read_and_written() {
+        let dir = std::env::temp_dir().join(format!("merged_lands_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Tamriel_Ünïcödé_Ærø_日本.esp");
+
+        let files = StdFileProvider;
+        files.write(&path, b"TES3").unwrap();
+        let exists = files.exists(&path).unwrap();
+        let bytes = files.read(&path).unwrap();
+        let listed = files.read_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(exists);
+        assert_eq!(bytes, b"TES3");
+        assert_eq!(
+            listed
+                .iter()
+                .map(|path| path.file_name().unwrap().to_os_string())
+                .collect::<Vec<_>>(),
+            vec![path.file_name().unwrap().to_os_string()]
+        );
+    }
+
+    #[test]
+    fn memory_files_are_written_while_they_are_created() {
+        let files = MemoryFileProvider::new();
+        let dir = Path::new("Merged Lands");
+        let path = dir.join("merged_lands.log");
+        files.create_dir_all(dir).unwrap();
+
+        let mut writer = files.create(&path).unwrap();
+        assert_eq!(files.read(&path).unwrap(), b"");
+        writer.write_all(b"INFO").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(files.read(&path).unwrap(), b"INFO");
+        writer.write_all(b" DEBUG").unwrap();
+        drop(writer);
+        assert_eq!(files.read(&path).unwrap(), b"INFO DEBUG");
+
+        assert!(files.is_dir(dir).unwrap());
+        assert!(!files.is_dir(&path).unwrap());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::error::ParseError;
+use crate::io::file_provider::FileProvider;
+use crate::io::meta_schema::{GlobalConfig, VersionedGlobalConfig};
+use anyhow::{anyhow, bail, Context, Result};
+use log::trace;
+use std::path::Path;
+use toml::value::Table;
+use toml::Value;
+
+/// Parses the [GlobalConfig] at `file_path`. If the file does not exist,
+/// then a default [GlobalConfig] is returned instead.
+pub fn parse_global_config(files: &dyn FileProvider, file_path: &Path) -> Result<GlobalConfig> {
+    let exists = files
+        .exists(file_path)
+        .with_context(|| anyhow!("Unable to find config file {}", file_path.to_string_lossy()))?;
+
+    if !exists {
+        trace!("No config file {}", file_path.to_string_lossy());
+        return Ok(GlobalConfig::default());
+    }
+
+    let text = files
+        .read_to_string(file_path)
+        .with_context(|| anyhow!("Failed to read config file {}", file_path.to_string_lossy()))?;
+
+    let config = toml::from_str::<VersionedGlobalConfig>(&text).with_context(|| {
+        anyhow!(
+            "Failed to parse config file {}",
+            file_path.to_string_lossy()
+        )
+    })?;
+
+    match config {
+        VersionedGlobalConfig::V0(config) => {
+            check_config_values(&config).with_context(|| {
+                anyhow!(
+                    "Failed to parse config file {}",
+                    file_path.to_string_lossy()
+                )
+            })?;
+            trace!("Parsed config file {}", file_path.to_string_lossy());
+            Ok(*config)
+        }
+        VersionedGlobalConfig::Unsupported => {
+            bail!(ParseError::UnsupportedConfig(file_path.to_path_buf()))
+        }
+    }
+}
+
+/// Fails if a value of the [GlobalConfig] is out of range.
+fn check_config_values(config: &GlobalConfig) -> Result<(), ParseError> {
+    let checks: [(&'static str, usize, bool, &'static str); 3] = [
+        (
+            "seams.min_corner_neighbors",
+            config.seams.min_corner_neighbors,
+            (1..=4).contains(&config.seams.min_corner_neighbors),
+            "1 to 4",
+        ),
+        (
+            "seams.max_repair_passes",
+            config.seams.max_repair_passes,
+            config.seams.max_repair_passes >= 1,
+            "at least 1",
+        ),
+        (
+            "outlier_distance",
+            config.outlier_distance,
+            config.outlier_distance >= 1,
+            "at least 1",
+        ),
+    ];
+
+    match checks.into_iter().find(|(_, _, is_valid, _)| !is_valid) {
+        Some((field, value, _, expected)) => Err(ParseError::InvalidConfigValue {
+            field,
+            value,
+            expected,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Copies each field of `overrides` into `table`. Fields that are tables in both
+/// are merged recursively so that only the innermost values are replaced.
+fn merge_tables(table: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(inner)), Value::Table(value)) => merge_tables(inner, value),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns the contents of a meta file after inheriting the fields of the
+/// profile in the [GlobalConfig] named by the `profile` field, if any.
+pub fn inherit_profile(meta: Table, config: &GlobalConfig) -> Result<Table> {
+    let profile_name = match meta.get("profile") {
+        None => return Ok(meta),
+        Some(Value::String(profile_name)) => profile_name,
+        Some(_) => bail!(ParseError::InvalidProfileField),
+    };
+
+    let Some(profile) = config.profiles.get(profile_name) else {
+        bail!(ParseError::UnknownProfile(profile_name.clone()));
+    };
+
+    let mut table = profile.clone();
+    merge_tables(&mut table, meta);
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_config_values_are_rejected() {
+        assert!(check_config_values(&GlobalConfig::default()).is_ok());
+
+        let mut config = GlobalConfig::default();
+        config.seams.min_corner_neighbors = 5;
+        assert!(matches!(
+            check_config_values(&config),
+            Err(ParseError::InvalidConfigValue {
+                field: "seams.min_corner_neighbors",
+                ..
+            })
+        ));
+
+        let config = GlobalConfig {
+            outlier_distance: 0,
+            ..GlobalConfig::default()
+        };
+        assert!(check_config_values(&config).is_err());
+    }
+}
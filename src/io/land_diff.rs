@@ -0,0 +1,611 @@
+use crate::error::ParseError;
+use crate::io::artifacts::ArtifactSink;
+use crate::io::file_provider::FileProvider;
+use crate::io::meta_schema::{GlobalConfig, MetaDatabase, MetaType};
+use crate::io::parsed_plugins::ParsedPlugin;
+use crate::land::conversions::{texture_indices, vertex_colors, vertex_normals, world_map_data};
+use crate::land::grid_access::{Index2D, SquareGridIterator};
+use crate::land::height_map::try_calculate_height_map;
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::{TerrainMap, Vec2, Vec3};
+use crate::land::textures::{IndexVTEX, KnownTextures};
+use crate::merge::relative_terrain_map::{OptionalTerrainMap, RelativeTerrainMap};
+use crate::merge::relative_to::RelativeTo;
+use crate::{Landmass, LandmassDiff};
+use anyhow::{anyhow, bail, Context, Result};
+use hashbrown::HashMap;
+use itertools::Itertools;
+use log::{trace, warn};
+use owo_colors::OwoColorize;
+use std::path::Path;
+use std::sync::Arc;
+use tes3::esp::Landscape;
+
+/// The magic bytes at the start of every land diff file.
+const LAND_DIFF_MAGIC: &[u8; 8] = b"LANDDIFF";
+
+/// The version of the land diff format written by [write_land_diff].
+/// See `docs/land_diff.md` for a description of the format.
+const LAND_DIFF_VERSION: u16 = 1;
+
+/// The file extension of land diff files.
+pub const LAND_DIFF_EXTENSION: &str = "landdiff";
+
+/// Flags for each kind of data included in a cell of a land diff.
+const HEIGHT_MAP: u8 = 0x01;
+const VERTEX_NORMALS: u8 = 0x02;
+const WORLD_MAP_DATA: u8 = 0x04;
+const VERTEX_COLORS: u8 = 0x08;
+const TEXTURE_INDICES: u8 = 0x10;
+
+/// Writes little-endian values to a buffer.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn vec3(&mut self, value: Vec3<i32>) {
+        self.i32(value.x);
+        self.i32(value.y);
+        self.i32(value.z);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u16(value.len().try_into().expect("string too long"));
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+}
+
+/// Reads little-endian values from a buffer.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let Some(bytes) = self.bytes.get(self.pos..self.pos + N) else {
+            bail!(ParseError::UnexpectedEndOfFile(self.pos));
+        };
+
+        self.pos += N;
+        Ok(bytes.try_into().expect("safe"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(u8::from_le_bytes(self.take()?))
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3<i32>> {
+        Ok(Vec3::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        let Some(bytes) = self.bytes.get(self.pos..self.pos + len) else {
+            bail!(ParseError::UnexpectedEndOfFile(self.pos));
+        };
+
+        self.pos += len;
+        String::from_utf8(bytes.to_vec())
+            .with_context(|| anyhow!("Invalid string at byte {}", self.pos - len))
+    }
+}
+
+/// Writes the number of differences in the [RelativeTerrainMap] followed by the index and
+/// value of each difference. The value is created by `value`.
+fn write_differences<U: RelativeTo, V, const T: usize>(
+    writer: &mut Writer,
+    map: &RelativeTerrainMap<U, T>,
+    mut value: impl FnMut(&RelativeTerrainMap<U, T>, Index2D) -> Result<V>,
+    mut write: impl FnMut(&mut Writer, V),
+) -> Result<()> {
+    let coords = map
+        .iter_grid()
+        .filter(|coords| map.has_difference(*coords))
+        .collect::<Vec<_>>();
+
+    writer.u32(coords.len() as u32);
+    for coords in coords {
+        writer.u16((coords.y * T + coords.x) as u16);
+        let value = value(map, coords)?;
+        write(writer, value);
+    }
+
+    Ok(())
+}
+
+/// Reads the differences written by [write_differences] and applies each with `apply`.
+fn read_differences<'a, U: RelativeTo, V, const T: usize>(
+    reader: &mut Reader<'a>,
+    map: &mut RelativeTerrainMap<U, T>,
+    mut read: impl FnMut(&mut Reader<'a>) -> Result<V>,
+    mut apply: impl FnMut(&mut RelativeTerrainMap<U, T>, Index2D, V),
+) -> Result<()> {
+    let count = reader.u32()?;
+    for _ in 0..count {
+        let index = reader.u16()? as usize;
+        if index >= T * T {
+            bail!(ParseError::InvalidGridIndex { index, size: T });
+        }
+
+        let value = read(reader)?;
+        apply(map, Index2D::new(index % T, index / T), value);
+    }
+
+    Ok(())
+}
+
+/// Creates a land diff file from the [LandmassDiff] of a plugin.
+/// Texture indices are saved by the ID of the texture in the [KnownTextures].
+/// Returns an error if a texture index is not in the [KnownTextures].
+pub fn write_land_diff(landmass: &LandmassDiff, known_textures: &KnownTextures) -> Result<Vec<u8>> {
+    let texture_ids: HashMap<IndexVTEX, &String> = known_textures
+        .sorted()
+        .map(|texture| (IndexVTEX::from(texture.index()), texture.id()))
+        .collect();
+
+    let mut textures: Vec<&String> = Vec::new();
+    let mut texture_table: HashMap<IndexVTEX, u16> = HashMap::new();
+
+    let mut cells = Writer::default();
+    let mut num_cells = 0;
+    for (coords, land) in landmass.sorted() {
+        let mut kinds = 0;
+        for (flag, is_some) in [
+            (HEIGHT_MAP, land.height_map.is_some()),
+            (VERTEX_NORMALS, land.vertex_normals.is_some()),
+            (WORLD_MAP_DATA, land.world_map_data.is_some()),
+            (VERTEX_COLORS, land.vertex_colors.is_some()),
+            (TEXTURE_INDICES, land.texture_indices.is_some()),
+        ] {
+            if is_some {
+                kinds |= flag;
+            }
+        }
+
+        if kinds == 0 {
+            continue;
+        }
+
+        num_cells += 1;
+        cells.i32(coords.x);
+        cells.i32(coords.y);
+        cells.u8(kinds);
+
+        if let Some(map) = land.height_map.as_ref() {
+            write_differences(
+                &mut cells,
+                map,
+                |map, coords| Ok(map.get_difference(coords)),
+                Writer::i32,
+            )?;
+        }
+
+        if let Some(map) = land.vertex_normals.as_ref() {
+            write_differences(
+                &mut cells,
+                map,
+                |map, coords| Ok(map.get_difference(coords)),
+                Writer::vec3,
+            )?;
+        }
+
+        if let Some(map) = land.world_map_data.as_ref() {
+            write_differences(
+                &mut cells,
+                map,
+                |map, coords| Ok(map.get_difference(coords)),
+                Writer::i32,
+            )?;
+        }
+
+        if let Some(map) = land.vertex_colors.as_ref() {
+            write_differences(
+                &mut cells,
+                map,
+                |map, coords| Ok(map.get_difference(coords)),
+                Writer::vec3,
+            )?;
+        }
+
+        if let Some(map) = land.texture_indices.as_ref() {
+            write_differences(
+                &mut cells,
+                map,
+                |map, coords| {
+                    let index = map.get_value(coords);
+                    if index == IndexVTEX::default() {
+                        return Ok(0);
+                    }
+
+                    if let Some(id) = texture_table.get(&index) {
+                        return Ok(*id);
+                    }
+
+                    let Some(texture_id) = texture_ids.get(&index) else {
+                        bail!(ParseError::UnknownTexture(index.as_u16()));
+                    };
+
+                    textures.push(*texture_id);
+                    texture_table.insert(index, textures.len() as u16);
+                    Ok(textures.len() as u16)
+                },
+                Writer::u16,
+            )?;
+        }
+    }
+
+    let mut writer = Writer::default();
+    writer.bytes.extend_from_slice(LAND_DIFF_MAGIC);
+    writer.u16(LAND_DIFF_VERSION);
+    writer.string(&landmass.plugin.name);
+
+    writer.u32(textures.len() as u32);
+    for id in textures {
+        writer.string(id);
+    }
+
+    writer.u32(num_cells);
+    writer.bytes.extend_from_slice(&cells.bytes);
+
+    Ok(writer.bytes)
+}
+
+/// Returns the [RelativeTerrainMap] of the `reference` [TerrainMap], or of a default
+/// [TerrainMap] if there is no `reference`.
+fn empty_map<U: RelativeTo, const T: usize>(
+    reference: Option<TerrainMap<U, T>>,
+) -> RelativeTerrainMap<U, T> {
+    RelativeTerrainMap::empty(reference.unwrap_or([[Default::default(); T]; T]))
+}
+
+/// Parses a land diff file created by [write_land_diff]. The differences are applied to the
+/// `reference` [Landmass], and texture IDs are found in the [KnownTextures].
+pub fn read_land_diff(
+    bytes: &[u8],
+    reference: &Landmass,
+    known_textures: &KnownTextures,
+) -> Result<LandmassDiff> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if &reader.take::<8>()? != LAND_DIFF_MAGIC {
+        bail!(ParseError::NotLandDiff);
+    }
+
+    let version = reader.u16()?;
+    if version != LAND_DIFF_VERSION {
+        bail!(ParseError::UnsupportedLandDiffVersion(version));
+    }
+
+    let plugin_name = reader.string()?;
+    let num_textures = reader.u32()?;
+    let mut textures = Vec::with_capacity(num_textures as usize);
+    for _ in 0..num_textures {
+        let id = reader.string()?;
+        let index = known_textures
+            .get(&id)
+            .map(|texture| IndexVTEX::from(texture.index()));
+        if index.is_none() {
+            warn!(
+                "{} {}",
+                format!("Unknown texture {}", id.bold()).yellow(),
+                format!("in land diff of {}", plugin_name.bold()).yellow()
+            );
+        }
+
+        textures.push(index);
+    }
+
+    let mut landmass = LandmassDiff::new(Arc::new(ParsedPlugin::from_land_diff(&plugin_name)));
+
+    let num_cells = reader.u32()?;
+    for _ in 0..num_cells {
+        let coords = Vec2::new(reader.i32()?, reader.i32()?);
+        let kinds = reader.u8()?;
+        let reference_land: Option<&Landscape> = reference.land.get(&coords);
+
+        let mut height_map: OptionalTerrainMap<i32, 65> = None;
+        if kinds & HEIGHT_MAP != 0 {
+            let mut map = empty_map(reference_land.and_then(try_calculate_height_map));
+            read_differences(&mut reader, &mut map, Reader::i32, |map, coords, delta| {
+                map.set_difference(coords, delta)
+            })?;
+            height_map = Some(map);
+        }
+
+        let mut normals: OptionalTerrainMap<Vec3<i8>, 65> = None;
+        if kinds & VERTEX_NORMALS != 0 {
+            let mut map = empty_map(reference_land.and_then(vertex_normals));
+            read_differences(&mut reader, &mut map, Reader::vec3, |map, coords, delta| {
+                map.set_difference(coords, delta)
+            })?;
+            normals = Some(map);
+        }
+
+        let mut world_map: OptionalTerrainMap<u8, 9> = None;
+        if kinds & WORLD_MAP_DATA != 0 {
+            let mut map = empty_map(reference_land.and_then(world_map_data));
+            read_differences(&mut reader, &mut map, Reader::i32, |map, coords, delta| {
+                map.set_difference(coords, delta)
+            })?;
+            world_map = Some(map);
+        }
+
+        let mut colors: OptionalTerrainMap<Vec3<u8>, 65> = None;
+        if kinds & VERTEX_COLORS != 0 {
+            let mut map = empty_map(reference_land.and_then(vertex_colors));
+            read_differences(&mut reader, &mut map, Reader::vec3, |map, coords, delta| {
+                map.set_difference(coords, delta)
+            })?;
+            colors = Some(map);
+        }
+
+        let mut textures_map: OptionalTerrainMap<IndexVTEX, 16> = None;
+        if kinds & TEXTURE_INDICES != 0 {
+            let mut map = empty_map(reference_land.and_then(texture_indices));
+            let mut is_valid = true;
+            read_differences(&mut reader, &mut map, Reader::u16, |map, coords, index| {
+                let texture = match index {
+                    0 => Some(IndexVTEX::default()),
+                    index => textures.get(index as usize - 1).copied().flatten(),
+                };
+
+                match texture {
+                    Some(texture) => map.set_value(coords, texture),
+                    None => is_valid = false,
+                }
+            })?;
+
+            if is_valid {
+                textures_map = Some(map);
+            } else {
+                warn!(
+                    "{} {}",
+                    format!("Skipping texture indices of ({}, {})", coords.x, coords.y).yellow(),
+                    format!(
+                        "in land diff of {} with unknown textures",
+                        plugin_name.bold()
+                    )
+                    .yellow()
+                );
+            }
+        }
+
+        landmass.land.insert(
+            coords,
+            LandscapeDiff {
+                coords,
+                flags: reference_land.map(|land| land.flags).unwrap_or_default(),
+                height_map,
+                vertex_normals: normals,
+                world_map_data: world_map,
+                vertex_colors: colors,
+                texture_indices: textures_map,
+                plugins: Vec::new(),
+            },
+        );
+    }
+
+    trace!(
+        "Read {} cells from land diff of {}",
+        landmass.land.len(),
+        plugin_name
+    );
+
+    Ok(landmass)
+}
+
+/// Returns the name of the land diff file saved for the plugin named `plugin_name`.
+pub fn land_diff_file_name(plugin_name: &str) -> String {
+    let file_stem = Path::new(plugin_name)
+        .file_stem()
+        .expect("safe")
+        .to_string_lossy();
+    format!("{}.{}", file_stem, LAND_DIFF_EXTENSION)
+}
+
+/// Saves a land diff file of each [LandmassDiff] in `landmasses`, named after the plugin.
+pub fn save_land_diffs(
+    artifacts: &ArtifactSink,
+    landmasses: &[LandmassDiff],
+    known_textures: &KnownTextures,
+) -> Result<()> {
+    for landmass in landmasses {
+        let file_name = land_diff_file_name(&landmass.plugin.name);
+
+        trace!("Saving {}", file_name);
+        let bytes = write_land_diff(landmass, known_textures)
+            .with_context(|| anyhow!("Unable to create land diff of {}", landmass.plugin.name))?;
+        artifacts.save_bytes(&file_name, &bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Loads the land diff file at `file_path` with [read_land_diff].
+pub fn load_land_diff(
+    files: &dyn FileProvider,
+    file_path: &Path,
+    reference: &Landmass,
+    known_textures: &KnownTextures,
+) -> Result<LandmassDiff> {
+    let bytes = files
+        .read(file_path)
+        .with_context(|| anyhow!("Unable to read land diff {}", file_path.to_string_lossy()))?;
+
+    read_land_diff(&bytes, reference, known_textures)
+        .with_context(|| anyhow!("Invalid land diff {}", file_path.to_string_lossy()))
+}
+
+/// Loads each `.landdiff` patch file in `patches_dir`, ordered by file name, as a synthetic
+/// [ParsedPlugin] with any [crate::io::meta_schema::PluginMeta] found next to it.
+pub fn load_patches(
+    files: &dyn FileProvider,
+    patches_dir: &Path,
+    config: &GlobalConfig,
+    database: &MetaDatabase,
+    reference: &Landmass,
+    known_textures: &KnownTextures,
+) -> Result<Vec<LandmassDiff>> {
+    let exists = files.exists(patches_dir).with_context(|| {
+        anyhow!(
+            "Unable to find patches directory {}",
+            patches_dir.to_string_lossy()
+        )
+    })?;
+
+    if !exists {
+        trace!("No patches directory {}", patches_dir.to_string_lossy());
+        return Ok(Vec::new());
+    }
+
+    let entries = files.read_dir(patches_dir).with_context(|| {
+        anyhow!(
+            "Unable to read patches directory {}",
+            patches_dir.to_string_lossy()
+        )
+    })?;
+
+    let file_paths = entries
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext.eq_ignore_ascii_case(LAND_DIFF_EXTENSION))
+        })
+        .sorted()
+        .collect_vec();
+
+    let mut patches = Vec::new();
+    for file_path in file_paths {
+        let patch_name = file_path.file_name().expect("safe").to_string_lossy();
+        let mut patch = load_land_diff(files, &file_path, reference, known_textures)?;
+        patch.plugin = Arc::new(ParsedPlugin::patch(
+            files,
+            patches_dir,
+            &patch_name,
+            config,
+            database,
+        )?);
+        patches.push(patch);
+    }
+
+    Ok(patches)
+}
+
+/// Inserts each patch in `patches` into the `modded_landmasses`, in order. A patch with
+/// `load_after` is inserted after the named plugin and any patches already inserted after it.
+/// Otherwise, the patch is merged after every plugin.
+pub fn insert_patches(modded_landmasses: &mut Vec<LandmassDiff>, patches: Vec<LandmassDiff>) {
+    for patch in patches {
+        let position = patch
+            .plugin
+            .meta
+            .load_after
+            .as_ref()
+            .and_then(|load_after| {
+                let position = modded_landmasses
+                    .iter()
+                    .position(|landmass| landmass.plugin.name.eq_ignore_ascii_case(load_after));
+
+                if position.is_none() {
+                    warn!(
+                        "{} {}",
+                        format!(
+                            "Patch {} loads after {}",
+                            patch.plugin.name.bold(),
+                            load_after.bold()
+                        )
+                        .yellow(),
+                        "which does not modify any LAND and will be merged last".yellow()
+                    );
+                }
+
+                position
+            });
+
+        let Some(mut position) = position else {
+            modded_landmasses.push(patch);
+            continue;
+        };
+
+        position += 1;
+        while modded_landmasses.get(position).map_or(false, |landmass| {
+            landmass.plugin.meta.meta_type == MetaType::LandDiff
+        }) {
+            position += 1;
+        }
+
+        trace!(
+            "Merging patch {} after {}",
+            patch.plugin.name,
+            modded_landmasses[position - 1].plugin.name
+        );
+        modded_landmasses.insert(position, patch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::land::terrain_map::LandData;
+    use crate::test_support::{plugin, with_main_thread_stack, LandmassBuilder, CELL};
+
+    /// Returns a [LandmassDiff] of `Mod.esp` with texture index `texture` at [CELL].
+    fn landmass_with_texture(texture: u16) -> LandmassDiff {
+        LandmassBuilder::new(plugin("Mod.esp"))
+            .hill(CELL, 4, texture)
+            .build_diff(LandData::all())
+    }
+
+    #[test]
+    fn unknown_textures_are_an_error() {
+        let error = write_land_diff(&landmass_with_texture(7), &KnownTextures::new()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::UnknownTexture(7))
+        ));
+    }
+
+    #[test]
+    fn land_diffs_are_not_masters() {
+        with_main_thread_stack(|| {
+            let bytes = write_land_diff(&landmass_with_texture(0), &KnownTextures::new()).unwrap();
+            let reference = Landmass::new(plugin("Master.esm"));
+            let landmass = read_land_diff(&bytes, &reference, &KnownTextures::new()).unwrap();
+
+            assert_eq!(landmass.plugin.name, "Mod.esp");
+            assert_eq!(landmass.plugin.meta.meta_type, MetaType::LandDiff);
+            assert!(landmass.land.contains_key(&CELL));
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use crate::io::file_provider::FileProvider;
+use log::trace;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Where [locate_data_files_dir] found a Morrowind installation.
+pub enum InstallSource {
+    /// The `Installed Path` written to the registry by the retail installer.
+    Registry,
+    /// A Steam library folder listed in `libraryfolders.vdf`.
+    Steam,
+    /// A GOG installation, found in the registry or a default GOG folder.
+    Gog,
+}
+
+/// The GOG product ID of Morrowind.
+const GOG_PRODUCT_ID: &str = "1435828767";
+
+/// Returns the string `value` of the registry `key` in `HKEY_LOCAL_MACHINE` or `HKEY_CURRENT_USER`.
+#[cfg(windows)]
+fn registry_value(key: &str, value: &str) -> Option<String> {
+    use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+    use winreg::RegKey;
+
+    [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER]
+        .into_iter()
+        .find_map(|hkey| {
+            RegKey::predef(hkey)
+                .open_subkey(key)
+                .ok()?
+                .get_value(value)
+                .ok()
+        })
+}
+
+/// Returns [None]. Only Windows has a registry.
+#[cfg(not(windows))]
+fn registry_value(_key: &str, _value: &str) -> Option<String> {
+    None
+}
+
+/// Returns the paths in the `value` of the `SOFTWARE` registry `key`. Installers write to the
+/// 32-bit view of the registry on 64-bit Windows, so both views are read.
+fn registry_paths(key: &str, value: &str) -> Vec<PathBuf> {
+    [
+        format!(r"SOFTWARE\WOW6432Node\{}", key),
+        format!(r"SOFTWARE\{}", key),
+    ]
+    .iter()
+    .filter_map(|key| registry_value(key, value))
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Returns each directory that may contain a Steam installation.
+fn steam_dirs() -> Vec<PathBuf> {
+    let mut dirs = registry_paths(r"Valve\Steam", "InstallPath");
+    dirs.extend(registry_value(r"Software\Valve\Steam", "SteamPath").map(PathBuf::from));
+
+    if cfg!(windows) {
+        dirs.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
+    } else if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        dirs.push(home.join(".steam").join("steam"));
+        dirs.push(home.join(".local").join("share").join("Steam"));
+    }
+
+    dirs
+}
+
+/// Returns each library folder listed in the `libraryfolders.vdf` file of the `steam_dir`.
+/// The `steam_dir` itself is always a library folder.
+fn steam_library_dirs(files: &dyn FileProvider, steam_dir: &Path) -> Vec<PathBuf> {
+    let mut library_dirs = vec![steam_dir.to_path_buf()];
+
+    let vdf_path = steam_dir.join("steamapps").join("libraryfolders.vdf");
+    let Ok(text) = files.read_to_string(&vdf_path) else {
+        return library_dirs;
+    };
+
+    // Each library is listed as `"path"  "C:\\SteamLibrary"` with escaped backslashes.
+    let path_regex = Regex::new(r#""path"\s+"((?:[^"\\]|\\.)*)""#).expect("safe");
+    library_dirs.extend(
+        path_regex
+            .captures_iter(&text)
+            .map(|captures| PathBuf::from(captures[1].replace(r"\\", r"\"))),
+    );
+
+    library_dirs
+}
+
+/// Returns each directory that may contain a Morrowind installation, in order of preference.
+fn install_dirs(files: &dyn FileProvider) -> Vec<(InstallSource, PathBuf)> {
+    let mut install_dirs = Vec::new();
+
+    for dir in registry_paths(r"Bethesda Softworks\Morrowind", "Installed Path") {
+        install_dirs.push((InstallSource::Registry, dir));
+    }
+
+    for steam_dir in steam_dirs() {
+        for library_dir in steam_library_dirs(files, &steam_dir) {
+            let dir = library_dir
+                .join("steamapps")
+                .join("common")
+                .join("Morrowind");
+            install_dirs.push((InstallSource::Steam, dir));
+        }
+    }
+
+    for dir in registry_paths(&format!(r"GOG.com\Games\{}", GOG_PRODUCT_ID), "path") {
+        install_dirs.push((InstallSource::Gog, dir));
+    }
+
+    if cfg!(windows) {
+        install_dirs.push((InstallSource::Gog, PathBuf::from(r"C:\GOG Games\Morrowind")));
+        install_dirs.push((
+            InstallSource::Gog,
+            PathBuf::from(r"C:\Program Files (x86)\GOG Galaxy\Games\Morrowind"),
+        ));
+    }
+
+    install_dirs
+}
+
+/// Returns the `Data Files` directory of a Morrowind installation and the [InstallSource] it was
+/// found in. The registry is checked first, then each Steam library folder, then GOG.
+/// Files are read with the [FileProvider].
+pub fn locate_data_files_dir(files: &dyn FileProvider) -> Option<(InstallSource, PathBuf)> {
+    install_dirs(files)
+        .into_iter()
+        .map(|(source, dir)| (source, dir.join("Data Files")))
+        .find(|(source, data_files)| {
+            let exists = files.is_dir(data_files).unwrap_or(false);
+            trace!(
+                "Checking {:?} installation at {}: {}",
+                source,
+                data_files.to_string_lossy(),
+                if exists { "found" } else { "missing" }
+            );
+            exists
+        })
+}
+
+/// Returns the `Data Files` directory for a `path` dropped onto the executable.
+/// For an `.ini` file, this is the `Data Files` folder next to it. For a folder, this is the
+/// `Data Files` folder inside it if one exists, and otherwise the folder itself.
+/// Files are read with the [FileProvider].
+pub fn dropped_data_files_dir(files: &dyn FileProvider, path: &Path) -> Option<PathBuf> {
+    let is_dir = |path: &Path| files.is_dir(path).unwrap_or(false);
+    let is_file = |path: &Path| files.exists(path).unwrap_or(false) && !is_dir(path);
+
+    let is_ini = path
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("ini"));
+
+    let data_files = if is_file(path) && is_ini {
+        path.parent()?.join("Data Files")
+    } else if is_dir(path) {
+        let nested = path.join("Data Files");
+        if is_dir(&nested) {
+            nested
+        } else {
+            path.to_path_buf()
+        }
+    } else {
+        return None;
+    };
+
+    is_dir(&data_files).then_some(data_files)
+}
@@ -0,0 +1,171 @@
+use crate::error::ParseError;
+use crate::io::file_provider::FileProvider;
+use crate::io::meta_schema::{MetaDatabase, MetaDatabaseEntry, VersionedMetaDatabase};
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+use log::{trace, warn};
+use owo_colors::OwoColorize;
+use std::path::Path;
+use toml::value::Table;
+use toml::Value;
+
+/// The [MetaDatabase] bundled with the tool.
+const BUNDLED_META_DATABASE: &str = include_str!("meta_db.toml");
+
+/// Parses a [MetaDatabase] from the `text` of the file `name`.
+fn parse_meta_database(name: &str, text: &str) -> Result<MetaDatabase> {
+    let database = toml::from_str::<VersionedMetaDatabase>(text)
+        .with_context(|| anyhow!("Failed to parse meta database {}", name))?;
+
+    match database {
+        VersionedMetaDatabase::V0(mut database) => {
+            // Like embedded meta files, entries may leave out the `version` of the meta file.
+            for entry in database.plugins.iter_mut() {
+                entry
+                    .meta
+                    .entry("version")
+                    .or_insert_with(|| Value::String("0".to_string()));
+            }
+
+            trace!(
+                "Parsed {} entries from meta database {}",
+                database.plugins.len(),
+                name
+            );
+            Ok(database)
+        }
+        VersionedMetaDatabase::Unsupported => {
+            bail!(ParseError::UnsupportedMetaDatabase(name.to_string()))
+        }
+    }
+}
+
+impl MetaDatabase {
+    /// Returns the [MetaDatabase] bundled with the tool.
+    pub fn bundled() -> Result<Self> {
+        parse_meta_database("(bundled)", BUNDLED_META_DATABASE)
+    }
+
+    /// Returns a [MetaDatabase] containing the entries of every `.toml` file in `dir`.
+    pub fn from_dir(files: &dyn FileProvider, dir: &Path) -> Result<Self> {
+        let entries = files.read_dir(dir).with_context(|| {
+            anyhow!(
+                "Unable to read meta database directory `{}`",
+                dir.to_string_lossy()
+            )
+        })?;
+
+        let file_paths = entries
+            .into_iter()
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("toml"))
+            })
+            .sorted()
+            .collect_vec();
+
+        let mut plugins = Vec::new();
+        for file_path in file_paths {
+            let name = file_path.to_string_lossy();
+            let text = files
+                .read_to_string(&file_path)
+                .with_context(|| anyhow!("Failed to read meta database {}", name))?;
+            plugins.extend(parse_meta_database(&name, &text)?.plugins);
+        }
+
+        Ok(Self { plugins })
+    }
+
+    /// Returns the meta file contents of the [MetaDatabaseEntry] matching the plugin named
+    /// `plugin_name` in the `data_files` directory, if any. The `crc32` of the plugin is only
+    /// calculated if it is [None] and an entry needs it.
+    pub fn find(
+        &self,
+        files: &dyn FileProvider,
+        data_files: &Path,
+        plugin_name: &str,
+        crc32: Option<u32>,
+    ) -> Option<&Table> {
+        let mut crc32 = crc32.map(Some);
+
+        for entry in self
+            .plugins
+            .iter()
+            .filter(|entry| entry.name.eq_ignore_ascii_case(plugin_name))
+        {
+            let Some(expected) = entry.crc32.as_ref() else {
+                return Some(&entry.meta);
+            };
+
+            let actual = crc32.get_or_insert_with(|| plugin_crc32(files, data_files, plugin_name));
+            match actual {
+                Some(actual) if matches_crc32(entry, *actual) => return Some(&entry.meta),
+                _ => trace!(
+                    "Skipping meta database entry for {} with CRC32 {}",
+                    plugin_name,
+                    expected
+                ),
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns `true` if the `crc32` of the [MetaDatabaseEntry] matches `actual`.
+fn matches_crc32(entry: &MetaDatabaseEntry, actual: u32) -> bool {
+    entry
+        .crc32
+        .as_ref()
+        .and_then(|expected| u32::from_str_radix(expected.trim_start_matches("0x"), 16).ok())
+        == Some(actual)
+}
+
+/// Calculates the CRC32 of the plugin named `plugin_name` in the `data_files` directory.
+pub fn plugin_crc32(files: &dyn FileProvider, data_files: &Path, plugin_name: &str) -> Option<u32> {
+    match files.read(&data_files.join(plugin_name)) {
+        Ok(bytes) => Some(crc32fast::hash(&bytes)),
+        Err(e) => {
+            warn!(
+                "{} {}",
+                format!("Unable to calculate CRC32 of plugin {}", plugin_name.bold()).yellow(),
+                format!("due to: {:?}", e.bold()).yellow()
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::meta_schema::VersionedPluginMeta;
+
+    #[test]
+    fn bundled_entries_parse_as_plugin_meta() {
+        let database = MetaDatabase::bundled().unwrap();
+        assert!(!database.plugins.is_empty());
+
+        for entry in database.plugins.iter() {
+            let meta = Value::Table(entry.meta.clone())
+                .try_into::<VersionedPluginMeta>()
+                .unwrap_or_else(|e| panic!("entry {} does not parse: {:?}", entry.name, e));
+            assert!(
+                matches!(meta, VersionedPluginMeta::V0(_)),
+                "entry {} has an unsupported version",
+                entry.name
+            );
+        }
+    }
+
+    #[test]
+    fn entries_keep_an_explicit_version() {
+        let text =
+            "version = \"0\"\n[[plugins]]\nname = \"A.esp\"\n[plugins.meta]\nversion = \"1\"\n";
+        let database = parse_meta_database("test", text).unwrap();
+        assert_eq!(
+            database.plugins[0].meta.get("version"),
+            Some(&Value::String("1".to_string()))
+        );
+    }
+}
@@ -1,7 +1,9 @@
+use crate::land::terrain_map::Vec2;
 use serde::{Deserialize, Serialize};
-use std::default::default;
+use std::collections::{BTreeMap, HashMap};
+use toml::value::Table;
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default, Clone)]
 /// The type of the `.mergedlands.toml` meta file.
 pub enum MetaType {
     #[default]
@@ -11,6 +13,9 @@ pub enum MetaType {
     Patch,
     /// A marker [MetaType] so that the tool can ignore previous `Merged Lands.esp` results.
     MergedLands,
+    /// A synthetic plugin created by the tool for a `.landdiff` patch file.
+    /// These are never added as masters of the output file.
+    LandDiff,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default, Copy, Clone)]
@@ -27,7 +32,7 @@ pub enum ConflictStrategy {
     Ignore,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 /// The [MergeSettings] control how a part of a plugin should be processed.
 pub struct MergeSettings {
     #[serde(default = "default_bool_true")]
@@ -36,24 +41,71 @@ pub struct MergeSettings {
     #[serde(default)]
     /// The [ConflictStrategy] to use for any conflicts found during a merge.
     pub conflict_strategy: ConflictStrategy,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    /// If not empty, changes from the plugin are only kept in cells inside any of the [CellBounds].
+    pub cells: Vec<CellBounds>,
 }
 
 impl Default for MergeSettings {
-    /// The default [MergeSettings] are `included: true` and
-    /// the [ConflictStrategy::Auto] `conflict_strategy`.
+    /// The default [MergeSettings] are `included: true`, the [ConflictStrategy::Auto]
+    /// `conflict_strategy`, and no [CellBounds].
     fn default() -> Self {
         Self {
             included: true,
-            conflict_strategy: default(),
+            conflict_strategy: Default::default(),
+            cells: Vec::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+impl MergeSettings {
+    /// Returns `true` if changes from the plugin are kept in the cell at `coords`.
+    pub fn includes(&self, coords: Vec2<i32>) -> bool {
+        self.included
+            && (self.cells.is_empty() || self.cells.iter().any(|bounds| bounds.contains(coords)))
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+/// The cells from `min` to `max` coordinates, inclusive, e.g. `{ min = [-3, 5], max = [0, 8] }`.
+pub struct CellBounds {
+    pub min: [i32; 2],
+    pub max: [i32; 2],
+}
+
+impl CellBounds {
+    /// Returns `true` if the cell at `coords` is inside of the [CellBounds].
+    pub fn contains(&self, coords: Vec2<i32>) -> bool {
+        (self.min[0]..=self.max[0]).contains(&coords.x)
+            && (self.min[1]..=self.max[1]).contains(&coords.y)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
 /// A meta file describing how a plugin should be processed.
 pub struct PluginMeta {
     /// The [MetaType] of this plugin.
     pub meta_type: MetaType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// The name of a profile in the [GlobalConfig] that this plugin inherits [MergeSettings] from.
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// The name of a plugin or patch that a `.landdiff` patch is merged after.
+    /// If not provided, the patch is merged after every plugin. Ignored for other plugins.
+    pub load_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// If set, every height of the plugin is multiplied by `height_scale` before it is compared to
+    /// its masters, e.g. `0.125` for a plugin converted with heights 8 times too large.
+    pub height_scale: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// If set, `height_offset` world units are added to every height of the plugin after it is
+    /// scaled by `height_scale` and before it is compared to its masters.
+    pub height_offset: Option<i32>,
     #[serde(skip_serializing_if = "skip_default")]
     #[serde(default)]
     /// The [MergeSettings] for the height map and associated vertex normals.
@@ -70,6 +122,11 @@ pub struct PluginMeta {
     #[serde(default)]
     /// The [MergeSettings] for the world map data.
     pub world_map_data: MergeSettings,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    /// The IDs of LTEX records whose file name from this plugin is kept, even if a later plugin
+    /// changes it, e.g. `["Grass", "Rock"]`.
+    pub texture_authority: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -78,18 +135,352 @@ pub struct PluginMeta {
 pub(in crate::io) enum VersionedPluginMeta {
     #[serde(rename = "0")]
     /// Initial release.
-    V0(PluginMeta),
+    V0(Box<PluginMeta>),
+    #[serde(other)]
+    /// An unknown version.
+    Unsupported,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// The global `merged_lands.toml` config file.
+pub struct GlobalConfig {
+    #[serde(default = "default_base_masters")]
+    /// The masters that always form the reference land.
+    pub base_masters: Vec<String>,
+    #[serde(default)]
+    /// If `diff_masters` is `true` then any masters not in `base_masters` are merged like plugins.
+    pub diff_masters: bool,
+    #[serde(default)]
+    /// If `adjust_output_mtime` is `true` then an output file in the `Data Files` directory that
+    /// would load before any plugin has its last modified time moved after the last plugin.
+    /// Otherwise, a warning is logged and the previous last modified time is kept.
+    pub adjust_output_mtime: bool,
+    #[serde(default)]
+    /// Each override `"A.esp < B.esp"` moves `A.esp` before `B.esp` in the load order.
+    /// Overrides are applied after plugins are sorted, keeping the sorted order where possible.
+    pub order_overrides: Vec<String>,
+    #[serde(default)]
+    /// Named profiles containing any fields of a [PluginMeta], e.g. `[profiles.name.height_map]`.
+    /// A [PluginMeta] with `profile = "name"` inherits these fields unless it overrides them.
+    pub profiles: HashMap<String, Table>,
+    #[serde(default)]
+    /// The [SeamSettings] used when repairing seams between cells.
+    pub seams: SeamSettings,
+    #[serde(default)]
+    /// The [MissingTexturePolicy] for texture indices without a matching LTEX record.
+    pub missing_textures: MissingTexturePolicy,
+    #[serde(default)]
+    /// Directories searched for meta files that are not in the `Data Files` directory, in order.
+    /// Each directory may contain meta files, or mod folders containing meta files, e.g. the
+    /// `mods` directory of Mod Organizer 2.
+    pub meta_search_paths: Vec<String>,
+    #[serde(default)]
+    /// The [NewLandPolicy] for cells where more than one plugin adds LAND that no master has.
+    pub new_land: NewLandPolicy,
+    #[serde(default)]
+    /// The [AveragingKernel] for heights modified by three or more plugins.
+    pub averaging_kernel: AveragingKernel,
+    #[serde(default)]
+    /// The [DuplicateRecordPolicy] for plugins with more than one LAND record for the same cell.
+    pub duplicate_records: DuplicateRecordPolicy,
+    #[serde(default)]
+    /// The [MetaErrorPolicy] for meta files that cannot be parsed.
+    pub on_meta_error: MetaErrorPolicy,
+    #[serde(default)]
+    /// Each LTEX record ID mapped to the name of the plugin whose file name is kept for it, like
+    /// the `texture_authority` of the [PluginMeta] of that plugin.
+    pub texture_authorities: BTreeMap<String, String>,
+    #[serde(default)]
+    /// The number of unmodified vertices next to modified vertices in a cell that blend the
+    /// recalculated vertex normals into the original vertex normals. If `0`, the original
+    /// vertex normals are used for every unmodified vertex.
+    pub normal_blend_radius: usize,
+    #[serde(default)]
+    /// The [OutlierCellPolicy] for cells far away from the rest of the merged land.
+    pub outlier_cells: OutlierCellPolicy,
+    #[serde(default = "default_outlier_distance")]
+    /// The distance in cells from the rest of the merged land beyond which a group of cells is
+    /// handled with the [OutlierCellPolicy].
+    pub outlier_distance: usize,
+    #[serde(default)]
+    /// If `ignore_world_map_only_edits` is `true` then LAND records of plugins that only change
+    /// the world map data are not merged, so these cells are not modified and these plugins are
+    /// not masters of the output.
+    pub ignore_world_map_only_edits: bool,
+    #[serde(default)]
+    /// The [MissingMasterPolicy] for plugins with a master that is not in the load order.
+    pub missing_masters: MissingMasterPolicy,
+    #[serde(default)]
+    /// Plugins that must not be masters of the output, e.g. a patch by another author that the
+    /// output may not depend on when it is distributed.
+    pub forbidden_masters: Vec<String>,
+    #[serde(default)]
+    /// The [ForbiddenMasterPolicy] for plugins in the load order listed in `forbidden_masters`.
+    pub on_forbidden_master: ForbiddenMasterPolicy,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a LAND record using a texture index that does not match any LTEX record
+/// in the same plugin. Morrowind renders these vertices with an unpredictable texture.
+pub enum MissingTexturePolicy {
+    #[default]
+    /// The texture index is replaced by the default texture.
+    UseDefault,
+    /// The merge fails, listing each plugin and cell with a missing texture.
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a cell where more than one plugin adds LAND that no master has, e.g. two mods
+/// adding islands in the same empty ocean. Every vertex of new land differs from the flat
+/// reference, so any overlap between these plugins is a conflict.
+pub enum NewLandPolicy {
+    #[default]
+    /// The LAND of each plugin is merged like any other cell.
+    Resolve,
+    /// Only the LAND of the first plugin in the load order is merged.
+    FirstWins,
+    /// The merge fails, listing the first cell with new LAND from more than one plugin.
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// How a height modified by three or more plugins with the [ConflictStrategy::Resolve] strategy
+/// is resolved. Averaging each plugin with the merge of the previous plugins weighs the last
+/// plugins most, e.g. the last of three plugins counts for half of the result.
+pub enum AveragingKernel {
+    #[default]
+    /// Each plugin is averaged with the merge of the previous plugins.
+    Pairwise,
+    /// The median of the changes of every plugin is used.
+    Median,
+    /// The mean of the changes of every plugin is used, without the smallest and largest change.
+    TrimmedMean,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a plugin that has more than one LAND record for the same cell. Only one of
+/// these records is loaded by the game, but malformed plugins created by some tools contain them.
+pub enum DuplicateRecordPolicy {
+    #[default]
+    /// Only the last LAND record in the plugin is merged.
+    LastWins,
+    /// Only the first LAND record in the plugin is merged.
+    FirstWins,
+    /// Each value of the LAND records is taken from the last record in the plugin that has it,
+    /// as if each record was in a separate plugin loaded after the previous one.
+    Merge,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a meta file that cannot be parsed, e.g. a typo in a `.mergedlands.toml` file
+/// or an unsupported version. The plugin is merged with the default [PluginMeta] unless the
+/// merge fails.
+pub enum MetaErrorPolicy {
+    /// The meta file is skipped without logging the error.
+    Ignore,
+    #[default]
+    /// The meta file is skipped, and the error is logged with its TOML diagnostics.
+    Warn,
+    /// The merge fails with the error and its TOML diagnostics.
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+/// Settings for repairing seams between cells, e.g. `[seams]` in the [GlobalConfig].
+pub struct SeamSettings {
+    #[serde(default = "default_min_corner_neighbors")]
+    /// Corners shared by fewer than `min_corner_neighbors` cells with a height map are not
+    /// averaged. At the edge of the world, averaging the few cells that exist can pull
+    /// coastal cliffs toward a single neighbor. The sides of a skipped corner are still
+    /// repaired, so the two cells of each side agree at the corner.
+    pub min_corner_neighbors: usize,
+    #[serde(default = "default_max_repair_passes")]
+    /// Repairing a corner can introduce a new seam along a side, or vice versa. Seams are
+    /// repaired again until no seams are found or `max_repair_passes` is reached.
+    pub max_repair_passes: usize,
+    #[serde(default)]
+    /// The number of rows in each cell, including the seam, that the correction of a side seam
+    /// is spread over. If `0` or `1`, only the vertices on the seam are moved.
+    pub blend_band: usize,
+    #[serde(default)]
+    /// The [BlendProfile] used to spread the correction over the `blend_band`.
+    pub blend_profile: BlendProfile,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// How the correction of a seam falls off with distance from the seam.
+pub enum BlendProfile {
+    #[default]
+    /// The correction falls off linearly.
+    Linear,
+    /// The correction falls off with a smoothstep curve, avoiding a crease at the edge of the band.
+    Smoothstep,
+}
+
+impl Default for SeamSettings {
+    /// The default [SeamSettings] repair every corner.
+    fn default() -> Self {
+        Self {
+            min_corner_neighbors: 1,
+            max_repair_passes: 8,
+            blend_band: 0,
+            blend_profile: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "version")]
+/// A versioned [GlobalConfig].
+pub(in crate::io) enum VersionedGlobalConfig {
+    #[serde(rename = "0")]
+    /// Initial release.
+    V0(Box<GlobalConfig>),
+    #[serde(other)]
+    /// An unknown version.
+    Unsupported,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// A [MetaDatabaseEntry] provides the contents of a meta file for a known plugin.
+pub struct MetaDatabaseEntry {
+    /// The `name` of the plugin, ignoring case.
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    /// If set, the entry is only used if the CRC32 of the plugin file matches, e.g. `"0A1B2C3D"`.
+    pub crc32: Option<String>,
+    /// The contents of the meta file, e.g. `[plugins.meta.height_map]`.
+    pub meta: Table,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with cells of the merged land far away from every other cell, e.g. a test cell at
+/// (1000, 1000). Images and exports stitched from every cell grow with the distance between the
+/// farthest cells, so a single outlier can make them enormous.
+pub enum OutlierCellPolicy {
+    /// The cells are kept everywhere, including stitched images and exports.
+    Include,
+    #[default]
+    /// The cells are kept in the output, but left out of stitched images and exports.
+    Warn,
+    /// The cells are left out of the output and of stitched images and exports.
+    Exclude,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a plugin whose master is not in the load order. The LAND of the plugin is
+/// diffed against land without the changes of that master, so its conflicts may be misleading.
+pub enum MissingMasterPolicy {
+    #[default]
+    /// The plugin is merged, and the missing master is listed as a warning.
+    Warn,
+    /// The plugin is left out of the merge, along with any plugin that depends on it.
+    Skip,
+    /// The merge fails, naming the first plugin with missing masters.
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+/// What to do with a plugin listed in the `forbidden_masters` of the [GlobalConfig] that has
+/// records the output could depend on. Plugins without these records are never masters.
+pub enum ForbiddenMasterPolicy {
+    #[default]
+    /// The plugin is left out of the merge, so none of its records are in the output.
+    Drop,
+    /// The merge fails, naming the first of these plugins.
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+/// A database of [MetaDatabaseEntry] for plugins without a meta file.
+pub struct MetaDatabase {
+    #[serde(default)]
+    /// Each [MetaDatabaseEntry] in the database.
+    pub plugins: Vec<MetaDatabaseEntry>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "version")]
+/// A versioned [MetaDatabase].
+pub(in crate::io) enum VersionedMetaDatabase {
+    #[serde(rename = "0")]
+    /// Initial release.
+    V0(MetaDatabase),
     #[serde(other)]
     /// An unknown version.
     Unsupported,
 }
 
+impl Default for GlobalConfig {
+    /// The default [GlobalConfig] uses the base game masters for `base_masters`.
+    fn default() -> Self {
+        Self {
+            base_masters: vec![
+                "Morrowind.esm".to_string(),
+                "Tribunal.esm".to_string(),
+                "Bloodmoon.esm".to_string(),
+            ],
+            diff_masters: false,
+            adjust_output_mtime: false,
+            order_overrides: Default::default(),
+            profiles: Default::default(),
+            seams: Default::default(),
+            missing_textures: Default::default(),
+            meta_search_paths: Default::default(),
+            new_land: Default::default(),
+            averaging_kernel: Default::default(),
+            duplicate_records: Default::default(),
+            on_meta_error: Default::default(),
+            texture_authorities: Default::default(),
+            normal_blend_radius: 0,
+            outlier_cells: Default::default(),
+            outlier_distance: 32,
+            ignore_world_map_only_edits: false,
+            missing_masters: Default::default(),
+            forbidden_masters: Default::default(),
+            on_forbidden_master: Default::default(),
+        }
+    }
+}
+
+/// Helper function providing the default `min_corner_neighbors` of [SeamSettings].
+fn default_min_corner_neighbors() -> usize {
+    SeamSettings::default().min_corner_neighbors
+}
+
+/// Helper function providing the default `max_repair_passes` of [SeamSettings].
+fn default_max_repair_passes() -> usize {
+    SeamSettings::default().max_repair_passes
+}
+
+/// Helper function providing the default `outlier_distance` of [GlobalConfig].
+fn default_outlier_distance() -> usize {
+    GlobalConfig::default().outlier_distance
+}
+
+/// Helper function providing the default `base_masters` of [GlobalConfig], i.e. the masters of
+/// the base game.
+fn default_base_masters() -> Vec<String> {
+    GlobalConfig::default().base_masters
+}
+
 /// Helper function providing a default `true` value.
 fn default_bool_true() -> bool {
     true
 }
 
-/// A function that returns `true` if the `field` is equal to [default()].
+/// A function that returns `true` if the `field` is equal to [Default::default()].
 fn skip_default<T: Default + PartialEq>(field: &T) -> bool {
     field == &T::default()
 }
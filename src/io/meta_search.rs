@@ -0,0 +1,108 @@
+use crate::io::file_provider::FileProvider;
+use crate::io::paths::extended_length_path;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use log::{debug, trace, warn};
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+
+/// The suffix of every meta file name, e.g. `Plugin.mergedlands.toml`.
+const META_SUFFIX: &str = ".mergedlands.toml";
+
+/// Returns `true` if the `path` is named like a meta file, ignoring case.
+fn is_meta_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase().ends_with(META_SUFFIX))
+        .unwrap_or(false)
+}
+
+#[derive(Default)]
+/// The meta files found outside of the `Data Files` directory, e.g. inside the mod folders of
+/// a mod manager that does not flatten the mods into the `Data Files` directory.
+pub struct MetaSearchPaths {
+    /// The path of each meta file, by lowercase file name.
+    meta_files: HashMap<String, PathBuf>,
+}
+
+impl MetaSearchPaths {
+    /// Finds the meta files in each of the `search_paths`. A search path is either a directory
+    /// containing meta files, or a directory of mod folders containing meta files, e.g. the
+    /// `mods` directory of Mod Organizer 2. If more than one meta file has the same name,
+    /// the first one found is used.
+    pub fn new(files: &dyn FileProvider, search_paths: &[String]) -> Self {
+        let mut meta_search_paths = Self::default();
+
+        for search_path in search_paths.iter() {
+            let dir = extended_length_path(Path::new(search_path));
+            let entries = match files.read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    warn!(
+                        "{} {}",
+                        format!("Ignoring meta search path {}", search_path.bold()).yellow(),
+                        format!("due to: {:?}", e.bold()).yellow()
+                    );
+                    continue;
+                }
+            };
+
+            for entry in entries.into_iter().sorted() {
+                if is_meta_file(&entry) {
+                    meta_search_paths.insert(entry);
+                    continue;
+                }
+
+                // Anything else is assumed to be a mod folder. Files fail to be read as directories.
+                let Ok(mod_entries) = files.read_dir(&entry) else {
+                    continue;
+                };
+
+                for mod_entry in mod_entries.into_iter().sorted() {
+                    if is_meta_file(&mod_entry) {
+                        meta_search_paths.insert(mod_entry);
+                    }
+                }
+            }
+        }
+
+        debug!(
+            "Found {} meta files in {} meta search paths",
+            meta_search_paths.meta_files.len(),
+            search_paths.len()
+        );
+
+        meta_search_paths
+    }
+
+    /// Adds the meta file at `path`, unless a meta file with the same name was already found.
+    fn insert(&mut self, path: PathBuf) {
+        let name = path
+            .file_name()
+            .expect("safe")
+            .to_string_lossy()
+            .to_lowercase();
+
+        match self.meta_files.get(&name) {
+            Some(existing) => warn!(
+                "{} {}",
+                format!("Ignoring meta file {}", path.to_string_lossy().bold()).yellow(),
+                format!(
+                    "because {} was found first",
+                    existing.to_string_lossy().bold()
+                )
+                .yellow()
+            ),
+            None => {
+                trace!("Found meta file {}", path.to_string_lossy());
+                self.meta_files.insert(name, path);
+            }
+        }
+    }
+
+    /// Returns the path of the meta file named `meta_name`, ignoring case, if it was found.
+    pub fn find(&self, meta_name: &str) -> Option<&Path> {
+        self.meta_files
+            .get(&meta_name.to_lowercase())
+            .map(PathBuf::as_path)
+    }
+}
@@ -1,4 +1,26 @@
+pub mod artifacts;
+pub mod bitmap_font;
+pub mod bundle;
+pub mod compatibility;
+pub mod conflict_clusters;
+pub mod excluded_cells;
+pub mod export;
+pub mod file_provider;
+pub mod global_config;
+pub mod land_diff;
+pub mod locate;
+pub mod meta_database;
 pub mod meta_schema;
+pub mod meta_search;
+pub mod new_land;
 pub mod parsed_plugins;
+pub mod paths;
+pub mod preset;
+pub mod report;
+pub mod resolutions;
+pub mod run_history;
 pub mod save_to_image;
+pub mod save_to_numeric;
 pub mod save_to_plugin;
+pub mod signature;
+pub mod validation;
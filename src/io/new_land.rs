@@ -0,0 +1,68 @@
+use crate::io::report::{NewLandCell, NewLandCollision};
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
+
+/// Returns a [NewLandCollision] for each set of plugins that add new LAND to the same cells,
+/// ordered by descending number of cells. Averaging unrelated land, e.g. two islands placed in the
+/// same empty ocean, cannot produce a useful result, so each collision is logged prominently.
+pub fn find_new_land_collisions(new_land: &[NewLandCell]) -> Vec<NewLandCollision> {
+    let collisions = new_land
+        .iter()
+        .filter(|cell| cell.plugins.len() > 1)
+        .into_group_map_by(|cell| cell.plugins.iter().sorted().cloned().collect_vec())
+        .into_iter()
+        .map(|(plugins, cells)| {
+            let cells = cells
+                .iter()
+                .map(|cell| cell.coords)
+                .sorted_by_key(|coords| (coords[0], coords[1]))
+                .collect_vec();
+
+            let min = [
+                cells.iter().map(|coords| coords[0]).min().expect("safe"),
+                cells.iter().map(|coords| coords[1]).min().expect("safe"),
+            ];
+            let max = [
+                cells.iter().map(|coords| coords[0]).max().expect("safe"),
+                cells.iter().map(|coords| coords[1]).max().expect("safe"),
+            ];
+
+            let summary = format!(
+                "{} each add new LAND to {} cells from ({}, {}) to ({}, {})",
+                plugins.join(" and "),
+                cells.len(),
+                min[0],
+                min[1],
+                max[0],
+                max[1]
+            );
+
+            NewLandCollision {
+                plugins,
+                min,
+                max,
+                cells,
+                summary,
+            }
+        })
+        .sorted_by(|lhs, rhs| {
+            rhs.cells
+                .len()
+                .cmp(&lhs.cells.len())
+                .then(lhs.plugins.cmp(&rhs.plugins))
+        })
+        .collect_vec();
+
+    for collision in collisions.iter() {
+        warn!(
+            "{} {}",
+            format!("New land collision: {}.", collision.summary)
+                .bold()
+                .bright_red(),
+            "These plugins are probably incompatible".bright_red()
+        );
+    }
+
+    collisions
+}
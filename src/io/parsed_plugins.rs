@@ -1,54 +1,124 @@
 use crate::cli::SortOrder;
-use crate::io::meta_schema::{PluginMeta, VersionedPluginMeta};
+use crate::error::{IoError, ParseError};
+use crate::events::RunContext;
+use crate::io::file_provider::FileProvider;
+use crate::io::global_config::inherit_profile;
+use crate::io::meta_schema::{
+    DuplicateRecordPolicy, GlobalConfig, MetaDatabase, MetaErrorPolicy, MetaType, PluginMeta,
+    VersionedPluginMeta,
+};
+use crate::io::meta_search::MetaSearchPaths;
+use crate::io::report::{LandAnomaly, LoadOrderEntry, OrderSource, ReportWarning};
+use crate::land::conversions::{coordinates, included_data};
+use crate::land::terrain_map::LandData;
+use crate::timings::TimedPhase;
 use anyhow::{anyhow, bail, Context, Result};
-use filetime::FileTime;
 use itertools::Itertools;
-use log::{error, trace, warn};
+use log::{debug, error, info, trace, warn};
 use owo_colors::OwoColorize;
 use regex::Regex;
-use std::default::default;
-use std::fs;
-use std::fs::File;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
-use std::io::{BufRead, BufReader, Lines};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::Arc;
-use tes3::esp::{Cell, Header, Landscape, LandscapeTexture, Plugin, TES3Object};
+use tes3::esp::{
+    Cell, Header, Landscape, LandscapeFlags, LandscapeTexture, ObjectFlags, Plugin, Region,
+    TES3Object,
+};
+use toml::value::Table;
+use toml::Value;
+
+/// Returns `true` if records with the `tag` are needed for merging LAND records.
+fn is_parsed_tag(tag: [u8; 4]) -> bool {
+    matches!(
+        &tag,
+        Header::TAG | LandscapeTexture::TAG | Landscape::TAG | Cell::TAG | Region::TAG
+    )
+}
+
+/// Removes every interior [Cell] from the `plugin`, since only exterior cells have LAND records.
+fn retain_exterior_cells(plugin: &mut Plugin) {
+    plugin.objects.retain(|object| match object {
+        TES3Object::Cell(cell) => cell.is_exterior(),
+        _ => true,
+    });
+}
 
 /// Parse a [Plugin] named `plugin_name` from the `data_files` directory.
-fn parse_records(data_files: &Path, plugin_name: &str) -> Result<Plugin> {
-    ParsedPlugins::check_dir_exists(data_files)
+/// Returns the [Plugin] and the CRC32 of the plugin file.
+fn parse_records(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    plugin_name: &str,
+) -> Result<(Plugin, u32)> {
+    ParsedPlugins::check_dir_exists(files, data_files)
         .with_context(|| anyhow!("Unable to find plugin {}", plugin_name))?;
 
-    let file_path: PathBuf = [data_files, Path::new(plugin_name)].iter().collect();
+    let file_path = data_files.join(plugin_name);
+    let exists = files
+        .exists(&file_path)
+        .with_context(|| anyhow!("Unable to find plugin {}", plugin_name))?;
+
+    if !exists {
+        bail!(IoError::PluginNotFound {
+            plugin: plugin_name.to_string(),
+            data_files: data_files.to_path_buf(),
+        });
+    }
+
+    let bytes = files
+        .read(&file_path)
+        .with_context(|| anyhow!("Unable to read plugin {}", plugin_name))?;
 
     let mut plugin = Plugin::new();
     plugin
-        .load_path_filtered(file_path, |tag| {
-            matches!(
-                &tag,
-                Header::TAG | LandscapeTexture::TAG | Landscape::TAG | Cell::TAG
-            )
-        })
-        .with_context(|| anyhow!("Failed to load records from plugin {}", plugin_name))?;
+        .load_bytes_filtered(&bytes, is_parsed_tag)
+        .map_err(|source| ParseError::CorruptPlugin {
+            plugin: plugin_name.to_string(),
+            source,
+        })?;
 
-    plugin.objects.retain(|object| match object {
-        TES3Object::Cell(cell) => cell.is_exterior(),
-        _ => true,
-    });
+    retain_exterior_cells(&mut plugin);
+    Ok((plugin, crc32fast::hash(&bytes)))
+}
+
+/// The characters of Windows-1252 from `0x80` to `0x9F`. Unused bytes are mapped to
+/// the control characters with the same value.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}', '\u{017D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}', '\u{017E}', '\u{0178}',
+];
 
-    Ok(plugin)
+/// Decodes a `line` as UTF-8, or as Windows-1252 if it is not valid UTF-8.
+/// `Morrowind.ini` is saved with the encoding of the game, so plugin names with
+/// accented characters are usually not valid UTF-8.
+fn decode_line(line: &[u8]) -> String {
+    match std::str::from_utf8(line) {
+        Ok(line) => line.to_string(),
+        Err(_) => line
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                _ => byte as char,
+            })
+            .collect(),
+    }
 }
 
-/// Open `filename` and return an iterator for the lines in the file.
-fn read_lines(filename: &Path) -> Result<Lines<BufReader<File>>> {
-    let file = File::open(filename).with_context(|| {
+/// Open `filename` and return the lines in the file, decoded with [decode_line].
+fn read_lines(files: &dyn FileProvider, filename: &Path) -> Result<Vec<String>> {
+    let bytes = files.read(filename).with_context(|| {
         anyhow!(
             "Unable to open file {} for reading",
             filename.to_string_lossy()
         )
     })?;
-    Ok(BufReader::new(file).lines())
+    Ok(bytes
+        .split(|&byte| byte == b'\n')
+        .map(decode_line)
+        .collect())
 }
 
 /// Returns `true` if `path` ends with `.esm`, ignoring case.
@@ -67,6 +137,7 @@ pub fn is_esp(path: impl AsRef<Path>) -> bool {
 
 /// Sorts `plugin_list` by using the last modified date of the files in `data_files`.
 pub fn sort_plugins(
+    files: &dyn FileProvider,
     data_files: &Path,
     plugin_list: &mut [String],
     sort_order: SortOrder,
@@ -75,39 +146,388 @@ pub fn sort_plugins(
         return Ok(());
     }
 
-    ParsedPlugins::check_dir_exists(data_files)
+    ParsedPlugins::check_dir_exists(files, data_files)
         .with_context(|| anyhow!("Unable to sort load order with last modified date"))?;
 
     for plugin_name in plugin_list.iter() {
-        let file_path: PathBuf = [data_files, Path::new(plugin_name)].iter().collect();
-        file_path
-            .metadata()
-            .map(|metadata| FileTime::from_last_modification_time(&metadata))
+        files
+            .modified_time(&data_files.join(plugin_name))
             .with_context(|| anyhow!("Unable to find plugin {}", plugin_name))?;
     }
 
     let order = |plugin_name: &str| {
         // Order by modified time, with ESMs given priority.
         let is_esm = is_esm(plugin_name);
-        let file_path: PathBuf = [data_files, Path::new(plugin_name)].iter().collect();
-        let last_modified_time = file_path
-            .metadata()
-            .map(|metadata| FileTime::from_last_modification_time(&metadata))
+        let last_modified_time = files
+            .modified_time(&data_files.join(plugin_name))
             .expect("safe");
         (!is_esm, last_modified_time)
     };
 
-    plugin_list.sort_by(|a, b| order(a).cmp(&order(b)));
+    plugin_list.sort_by_key(|plugin_name| order(plugin_name));
 
     Ok(())
 }
 
+/// Parses an order override `A.esp < B.esp` into the names of the plugins `(A.esp, B.esp)`.
+fn parse_order_override(text: &str) -> Result<(&str, &str)> {
+    let Some((before, after)) = text.split_once('<') else {
+        bail!(ParseError::InvalidOrderOverride(text.to_string()));
+    };
+
+    let (before, after) = (before.trim(), after.trim());
+    if before.is_empty() || after.is_empty() || after.contains('<') {
+        bail!(ParseError::InvalidOrderOverride(text.to_string()));
+    }
+
+    Ok((before, after))
+}
+
+/// Reorders `plugin_list` so that the first plugin of each of the `overrides` comes before the
+/// second. Plugins keep their current order unless an override requires otherwise.
+/// Overrides that name unknown plugins or would load an ESP before an ESM are ignored.
+/// Returns the names of the plugins in the applied overrides.
+pub fn apply_order_overrides(
+    plugin_list: &mut Vec<String>,
+    overrides: &[String],
+) -> Result<Vec<String>> {
+    if overrides.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let index_of = |name: &str| {
+        plugin_list
+            .iter()
+            .position(|plugin_name| plugin_name.eq_ignore_ascii_case(name))
+    };
+
+    let mut edges = Vec::new();
+    for text in overrides {
+        let (before, after) = parse_order_override(text)?;
+        let (Some(before), Some(after)) = (index_of(before), index_of(after)) else {
+            warn!(
+                "{} {}",
+                format!("Ignoring order override {}", text.bold()).yellow(),
+                "because a plugin is not in the load order".yellow()
+            );
+            continue;
+        };
+
+        if is_esm(&plugin_list[after]) && !is_esm(&plugin_list[before]) {
+            warn!(
+                "{} {}",
+                format!("Ignoring order override {}", text.bold()).yellow(),
+                "because ESMs always load before ESPs".yellow()
+            );
+            continue;
+        }
+
+        trace!("Applying order override {}", text);
+        edges.push((before, after));
+    }
+
+    // Each plugin is placed once every plugin ordered before it has been placed.
+    // The earliest plugin that can be placed is always chosen to keep the current order.
+    let mut num_before = vec![0; plugin_list.len()];
+    for &(_, after) in edges.iter() {
+        num_before[after] += 1;
+    }
+
+    let mut is_placed = vec![false; plugin_list.len()];
+    let mut order = Vec::with_capacity(plugin_list.len());
+    while order.len() < plugin_list.len() {
+        let Some(next) =
+            (0..plugin_list.len()).find(|&idx| !is_placed[idx] && num_before[idx] == 0)
+        else {
+            let cycle = (0..plugin_list.len())
+                .filter(|&idx| !is_placed[idx] && num_before[idx] > 0)
+                .map(|idx| plugin_list[idx].as_str())
+                .join(", ");
+            bail!(ParseError::OrderOverrideCycle(cycle));
+        };
+
+        is_placed[next] = true;
+        order.push(next);
+        for &(before, after) in edges.iter() {
+            if before == next {
+                num_before[after] -= 1;
+            }
+        }
+    }
+
+    let overridden = edges
+        .iter()
+        .flat_map(|&(before, after)| [before, after])
+        .unique()
+        .map(|idx| plugin_list[idx].clone())
+        .collect_vec();
+
+    *plugin_list = order
+        .into_iter()
+        .map(|idx| plugin_list[idx].clone())
+        .collect_vec();
+
+    Ok(overridden)
+}
+
+/// Returns the [OrderSource] of each plugin in the `plugin_list` sorted with the [SortOrder].
+/// Plugins in `overridden` were moved by an order override.
+fn order_sources(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    plugin_list: &[String],
+    sort_order: SortOrder,
+    overridden: &[String],
+) -> Vec<OrderSource> {
+    let last_modified_time =
+        |plugin_name: &str| files.modified_time(&data_files.join(plugin_name)).ok();
+
+    let first_esp_time = plugin_list
+        .iter()
+        .filter(|plugin_name| !is_esm(plugin_name))
+        .filter_map(|plugin_name| last_modified_time(plugin_name))
+        .min();
+
+    plugin_list
+        .iter()
+        .map(|plugin_name| {
+            if overridden
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(plugin_name))
+            {
+                OrderSource::Override
+            } else if matches!(sort_order, SortOrder::None) {
+                OrderSource::Listed
+            } else if is_esm(plugin_name)
+                && last_modified_time(plugin_name) > first_esp_time
+                && first_esp_time.is_some()
+            {
+                OrderSource::EsmPriority
+            } else {
+                OrderSource::ModifiedTime
+            }
+        })
+        .collect_vec()
+}
+
 /// Returns a `name` describing a meta file by replacing the extension with `.mergedlands.toml`.
 pub fn meta_name(name: &str) -> String {
     let file_name_without_extension = Path::new(&name).file_stem().unwrap().to_string_lossy();
     format!("{}.mergedlands.toml", file_name_without_extension)
 }
 
+/// Returns each [LandAnomaly] of the `land`. The `max_texture_index` is the largest texture index
+/// of any LTEX record the `land` may use, or [None] if there are no LTEX records.
+fn land_anomalies(land: &Landscape, max_texture_index: Option<u16>) -> Vec<LandAnomaly> {
+    let flags = land.landscape_flags;
+    let uses_heights = flags.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS);
+    let uses_colors = flags.contains(LandscapeFlags::USES_VERTEX_COLORS);
+    let uses_textures = flags.contains(LandscapeFlags::USES_TEXTURES);
+
+    let mut anomalies = Vec::new();
+    let mut check = |is_anomaly: bool, anomaly: LandAnomaly| {
+        if is_anomaly {
+            anomalies.push(anomaly);
+        }
+    };
+
+    check(
+        uses_heights && land.vertex_heights.is_none(),
+        LandAnomaly::MissingVertexHeights,
+    );
+    check(
+        uses_heights && land.vertex_normals.is_none(),
+        LandAnomaly::MissingVertexNormals,
+    );
+    check(
+        uses_colors && land.vertex_colors.is_none(),
+        LandAnomaly::MissingVertexColors,
+    );
+    check(
+        uses_textures && land.texture_indices.is_none(),
+        LandAnomaly::MissingTextureIndices,
+    );
+    check(
+        LandData::from(flags).contains(LandData::WORLD_MAP) && land.world_map_data.is_none(),
+        LandAnomaly::MissingWorldMapData,
+    );
+    check(
+        !uses_heights && land.vertex_heights.is_some(),
+        LandAnomaly::UnflaggedVertexHeights,
+    );
+    check(
+        !uses_colors && land.vertex_colors.is_some(),
+        LandAnomaly::UnflaggedVertexColors,
+    );
+    check(
+        !uses_textures && land.texture_indices.is_some(),
+        LandAnomaly::UnflaggedTextureIndices,
+    );
+    check(
+        land.vertex_heights
+            .as_ref()
+            .map_or(false, |heights| !heights.offset.is_finite()),
+        LandAnomaly::NonFiniteHeightOffset,
+    );
+    check(
+        land.texture_indices.as_ref().map_or(false, |indices| {
+            indices
+                .data
+                .iter()
+                .flatten()
+                .any(|idx| *idx > max_texture_index.unwrap_or_default())
+        }),
+        LandAnomaly::TextureIndexOutOfBounds,
+    );
+
+    anomalies
+}
+
+/// The line in the description of a [Header] that starts a meta file embedded in the plugin.
+const EMBEDDED_META_MARKER: &str = "[mergedlands]";
+
+/// Returns the contents of the meta file embedded in the description of the [Header] of the
+/// `records`, if any. The meta file is the text after the [EMBEDDED_META_MARKER] line, e.g.
+/// `[mergedlands]` followed by `texture_indices.conflict_strategy = "Ignore"`.
+/// The `version` may be left out to save space in the description.
+fn embedded_meta(records: &Plugin) -> Option<Result<Table>> {
+    let header = records.objects_of_type::<Header>().next()?;
+    let description = header.description.0.trim_end_matches('\0');
+
+    let (_, text) = description.split_once(EMBEDDED_META_MARKER)?;
+    let meta = toml::from_str::<Table>(text)
+        .with_context(|| anyhow!("Failed to parse embedded meta file contents."))
+        .map(|mut meta| {
+            meta.entry("version")
+                .or_insert_with(|| Value::String("0".to_string()));
+            meta
+        });
+
+    Some(meta)
+}
+
+/// Returns a `name` with ` (suffix)` appended to the file stem.
+fn suffixed_name(name: &str, suffix: &str) -> String {
+    let path = Path::new(&name);
+    let file_name_without_extension = path.file_stem().unwrap().to_string_lossy();
+    match path.extension() {
+        Some(extension) => format!(
+            "{} ({}).{}",
+            file_name_without_extension,
+            suffix,
+            extension.to_string_lossy()
+        ),
+        None => format!("{} ({})", file_name_without_extension, suffix),
+    }
+}
+
+/// Returns a `name` for a cleaned copy of a plugin by appending ` (Clean)` to the file stem.
+pub fn cleaned_name(name: &str) -> String {
+    suffixed_name(name, "Clean")
+}
+
+/// Returns a `name` for a debug copy of a plugin by appending ` (Debug)` to the file stem.
+pub fn debug_name(name: &str) -> String {
+    suffixed_name(name, "Debug")
+}
+
+/// Parse the [PluginMeta] for the plugin named `plugin_name` from the `data_files` directory.
+/// If no meta file exists, the meta file found in the [MetaSearchPaths] is used instead,
+/// followed by the meta file embedded in the `parsed` records, and the entry for the plugin in
+/// the [MetaDatabase], which is matched by the CRC32 of the `parsed` plugin file. Any profile named by the meta file is inherited from the [GlobalConfig].
+/// Returns [None] if no meta file exists, or if the meta file cannot be parsed and the
+/// [MetaErrorPolicy] of the [GlobalConfig] is not [MetaErrorPolicy::Fail].
+fn parse_meta(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    plugin_name: &str,
+    parsed: Option<(&Plugin, u32)>,
+    config: &GlobalConfig,
+    database: &MetaDatabase,
+    meta_search_paths: &MetaSearchPaths,
+) -> Result<Option<PluginMeta>> {
+    let meta_name = meta_name(plugin_name);
+
+    let text = files
+        .read_to_string(&data_files.join(&meta_name))
+        .or_else(|e| match meta_search_paths.find(&meta_name) {
+            Some(file_path) => {
+                trace!("Using meta file {}", file_path.to_string_lossy());
+                files.read_to_string(file_path)
+            }
+            None => Err(e),
+        });
+
+    let meta = match text {
+        Ok(text) => toml::from_str::<Table>(&text)
+            .with_context(|| anyhow!("Failed to parse meta file contents.")),
+        Err(_) => match parsed.and_then(|(records, _)| embedded_meta(records)) {
+            Some(meta) => {
+                trace!("Using meta file embedded in {}", plugin_name);
+                meta
+            }
+            None => {
+                let Some(meta) = database.find(
+                    files,
+                    data_files,
+                    plugin_name,
+                    parsed.map(|(_, crc32)| crc32),
+                ) else {
+                    return Ok(None);
+                };
+
+                trace!("Using meta database entry for {}", plugin_name);
+                Ok(meta.clone())
+            }
+        },
+    };
+
+    let data = meta
+        .and_then(|meta| {
+            inherit_profile(meta, config)
+                .with_context(|| anyhow!("Unable to inherit profile for meta file."))
+        })
+        .and_then(|meta| {
+            Value::Table(meta)
+                .try_into::<VersionedPluginMeta>()
+                .with_context(|| anyhow!("Failed to parse meta file contents."))
+        });
+
+    let e = match data {
+        Ok(VersionedPluginMeta::V0(meta)) => {
+            trace!("Parsed meta file {}", meta_name);
+            return Ok(Some(*meta));
+        }
+        Ok(VersionedPluginMeta::Unsupported) => anyhow!(ParseError::UnsupportedMetaVersion),
+        Err(e) => e,
+    };
+
+    match config.on_meta_error {
+        MetaErrorPolicy::Ignore => {
+            trace!("Ignoring meta file {} due to: {:?}", meta_name, e);
+            Ok(None)
+        }
+        MetaErrorPolicy::Warn => {
+            warn!(
+                "{} {}",
+                format!(
+                    "Using default settings for {} instead of meta file {}",
+                    plugin_name.bold(),
+                    meta_name.bold()
+                )
+                .yellow(),
+                format!("due to: {:?}", e.bold()).yellow()
+            );
+            Ok(None)
+        }
+        MetaErrorPolicy::Fail => Err(ParseError::InvalidMeta {
+            meta: meta_name,
+            source: e,
+        }
+        .into()),
+    }
+}
+
 /// A [ParsedPlugin] is the `name`, [Plugin] records, and any [PluginMeta] data.
 pub struct ParsedPlugin {
     /// The `name` of the plugin.
@@ -116,6 +536,9 @@ pub struct ParsedPlugin {
     pub records: Plugin,
     /// The parsed [PluginMeta], or a default if no meta file was found.
     pub meta: PluginMeta,
+    /// The CRC32 of the plugin file, calculated once when it is parsed.
+    /// [None] for plugins without a file in the `data_files` directory, e.g. patches.
+    pub crc32: Option<u32>,
 }
 
 impl ParsedPlugin {
@@ -124,8 +547,105 @@ impl ParsedPlugin {
         Self {
             name: name.to_string(),
             records: Plugin::new(),
-            meta: default(),
+            meta: Default::default(),
+            crc32: None,
+        }
+    }
+
+    /// Parses the plugin named `plugin_name` from the `data_files` directory,
+    /// along with any [PluginMeta] found next to it, in the [MetaSearchPaths], or embedded in it.
+    /// Any `texture_authorities` of the [GlobalConfig] for the plugin are added to the [PluginMeta].
+    pub fn load(
+        files: &dyn FileProvider,
+        data_files: &Path,
+        plugin_name: &str,
+        config: &GlobalConfig,
+        database: &MetaDatabase,
+        meta_search_paths: &MetaSearchPaths,
+    ) -> Result<Self> {
+        let (records, crc32) = parse_records(files, data_files, plugin_name)?;
+        let mut meta = parse_meta(
+            files,
+            data_files,
+            plugin_name,
+            Some((&records, crc32)),
+            config,
+            database,
+            meta_search_paths,
+        )?;
+
+        for (id, _) in config
+            .texture_authorities
+            .iter()
+            .filter(|(_, authority)| authority.eq_ignore_ascii_case(plugin_name))
+        {
+            trace!("Using {} as the authority for texture {}", plugin_name, id);
+            meta.get_or_insert_with(Default::default)
+                .texture_authority
+                .push(id.clone());
         }
+
+        let mut plugin = Self::from(plugin_name, records, meta);
+        plugin.crc32 = Some(crc32);
+        Ok(plugin)
+    }
+
+    /// Returns a synthetic [ParsedPlugin] for the `.landdiff` patch named `patch_name` in the
+    /// `patches_dir` directory, along with any [PluginMeta] found next to it.
+    pub fn patch(
+        files: &dyn FileProvider,
+        patches_dir: &Path,
+        patch_name: &str,
+        config: &GlobalConfig,
+        database: &MetaDatabase,
+    ) -> Result<Self> {
+        let mut meta = parse_meta(
+            files,
+            patches_dir,
+            patch_name,
+            None,
+            config,
+            database,
+            &MetaSearchPaths::default(),
+        )?
+        .unwrap_or_default();
+        meta.meta_type = MetaType::LandDiff;
+        Ok(Self::from(patch_name, Plugin::new(), Some(meta)))
+    }
+
+    /// Returns a [ParsedPlugin] named `name` for a land diff file, e.g. from `--land-diff`.
+    /// Like a patch, its cells are attributed to the `name`, but it is never a master of the output.
+    pub fn from_land_diff(name: &str) -> Self {
+        let meta = PluginMeta {
+            meta_type: MetaType::LandDiff,
+            ..Default::default()
+        };
+        Self::from(name, Plugin::new(), Some(meta))
+    }
+
+    /// Returns a [ParsedPlugin] named `name` for a land diff of a [crate::io::bundle] with the
+    /// [PluginMeta] the plugin had when the bundle was saved.
+    pub fn from_bundle(name: &str, meta: PluginMeta) -> Self {
+        Self::from(name, Plugin::new(), Some(meta))
+    }
+
+    /// Returns `true` if the plugin has LTEX records but no LAND records, e.g. a texture replacer
+    /// that only changes the file names of existing textures.
+    pub fn is_ltex_only(&self) -> bool {
+        self.records
+            .objects_of_type::<LandscapeTexture>()
+            .any(|_| true)
+            && !self.records.objects_of_type::<Landscape>().any(|_| true)
+    }
+
+    /// Returns the names of the masters listed in the [Header] of the plugin.
+    pub fn masters(&self) -> Vec<String> {
+        self.records
+            .objects_of_type::<Header>()
+            .next()
+            .and_then(|header| header.masters.as_ref())
+            .map(|masters| masters.iter().map(|(name, _)| name.clone()).collect_vec())
+            .unwrap_or_default()
     }
 
     /// Creates a [ParsedPlugin]. If `meta` is [None], a default [PluginMeta] is created.
@@ -133,7 +653,8 @@ impl ParsedPlugin {
         Self {
             name: name.to_string(),
             records,
-            meta: meta.unwrap_or_else(default),
+            meta: meta.unwrap_or_default(),
+            crc32: None,
         }
     }
 }
@@ -160,15 +681,17 @@ pub struct ParsedPlugins {
     /// The ordered list of `.esp` files.
     /// These will be created for creating each [crate::LandmassDiff].
     pub plugins: Vec<Arc<ParsedPlugin>>,
+    /// Each plugin in the resolved load order, including masters.
+    pub load_order: Vec<LoadOrderEntry>,
 }
 
 /// Returns a [Vec] of plugin names by reading the `.ini` file located at
 /// `path`. Each plugin name is checked for existence in `data_files`.
-fn read_ini_file(data_files: &Path, path: &Path) -> Result<Vec<String>> {
-    ParsedPlugins::check_dir_exists(data_files)
+fn read_ini_file(files: &dyn FileProvider, data_files: &Path, path: &Path) -> Result<Vec<String>> {
+    ParsedPlugins::check_dir_exists(files, data_files)
         .with_context(|| anyhow!("Unable to parse plugins from ini file"))?;
 
-    let lines = read_lines(path).with_context(|| anyhow!("Unable to read Morrowind.ini"))?;
+    let lines = read_lines(files, path).with_context(|| anyhow!("Unable to read Morrowind.ini"))?;
 
     let mut all_plugins = Vec::new();
 
@@ -177,7 +700,7 @@ fn read_ini_file(data_files: &Path, path: &Path) -> Result<Vec<String>> {
 
     let mut is_game_files = false;
     for line in lines
-        .flatten()
+        .into_iter()
         .map(|line| line.trim().to_string())
         .filter(|line| !line.is_empty() && !line.starts_with(';'))
     {
@@ -201,8 +724,7 @@ fn read_ini_file(data_files: &Path, path: &Path) -> Result<Vec<String>> {
                         .trim_start_matches(QUOTE_CHARS)
                         .trim_end_matches(QUOTE_CHARS);
 
-                    let file_path: PathBuf = [data_files, Path::new(plugin_name)].iter().collect();
-                    match file_path.try_exists() {
+                    match files.exists(&data_files.join(plugin_name)) {
                         Ok(true) => all_plugins.push(plugin_name.to_string()),
                         Ok(false) => error!(
                             "{} {}",
@@ -230,14 +752,14 @@ fn read_ini_file(data_files: &Path, path: &Path) -> Result<Vec<String>> {
 impl ParsedPlugins {
     /// Helper function for returning an `Err` if the `data_files` does not exist
     /// or is otherwise inaccessible.
-    pub fn check_dir_exists(dir: impl AsRef<Path>) -> Result<()> {
+    pub fn check_dir_exists(files: &dyn FileProvider, dir: impl AsRef<Path>) -> Result<()> {
         let path = dir.as_ref();
-        let exists = path
-            .try_exists()
+        let exists = files
+            .exists(path)
             .with_context(|| anyhow!("Unable to find `{}` directory", path.to_string_lossy()))?;
 
         if !exists {
-            bail!("The `{}` directory does not exist", path.to_string_lossy());
+            bail!(IoError::DirectoryNotFound(path.to_path_buf()));
         }
 
         Ok(())
@@ -246,12 +768,19 @@ impl ParsedPlugins {
     /// Creates a new [ParsedPlugins] from the `data_files` directory.
     /// If `plugin_names` is [None], then the `.ini` file will be read from
     /// the parent directory above `data_files` and used for the list instead.
+    /// The time to parse each plugin is added to the [Timings] of the [RunContext].
+    ///
+    /// [Timings]: crate::timings::Timings
     pub fn new(
+        files: &dyn FileProvider,
         data_files: &Path,
         plugin_names: Option<&[String]>,
         sort_order: SortOrder,
+        config: &GlobalConfig,
+        database: &MetaDatabase,
+        context: &RunContext,
     ) -> Result<Self> {
-        ParsedPlugins::check_dir_exists(data_files)
+        ParsedPlugins::check_dir_exists(files, data_files)
             .with_context(|| anyhow!("Unable to parse plugins"))?;
 
         let mut all_plugins = plugin_names
@@ -275,11 +804,9 @@ impl ParsedPlugins {
                     )
                 })?;
 
-                let file_path: PathBuf = [parent_directory, Path::new("Morrowind.ini")]
-                    .iter()
-                    .collect();
+                let file_path = parent_directory.join("Morrowind.ini");
 
-                let plugin_names = read_ini_file(data_files, &file_path)
+                let plugin_names = read_ini_file(files, data_files, &file_path)
                     .with_context(|| anyhow!("Unable to parse plugins from Morrowind.ini"))?;
 
                 trace!(
@@ -291,44 +818,38 @@ impl ParsedPlugins {
             })
             .with_context(|| anyhow!("Unable to parse plugins"))?;
 
-        sort_plugins(data_files, &mut all_plugins, sort_order)
+        sort_plugins(files, data_files, &mut all_plugins, sort_order)
             .with_context(|| anyhow!("Unknown load order for plugins"))?;
 
+        let overridden = apply_order_overrides(&mut all_plugins, &config.order_overrides)
+            .with_context(|| anyhow!("Unable to apply order overrides"))?;
+
+        let sources = order_sources(files, data_files, &all_plugins, sort_order, &overridden);
+        let meta_search_paths = MetaSearchPaths::new(files, &config.meta_search_paths);
+
         let mut masters = Vec::new();
         let mut plugins = Vec::new();
+        let mut load_order = Vec::new();
 
-        for plugin_name in all_plugins {
-            match parse_records(data_files, &plugin_name) {
-                Ok(records) => {
-                    let meta_name = meta_name(&plugin_name);
-                    let meta_file_path: PathBuf =
-                        [data_files, Path::new(&meta_name)].iter().collect();
-
-                    let data = fs::read_to_string(meta_file_path)
-                        .with_context(|| anyhow!("Failed to read meta file."))
-                        .and_then(|text| {
-                            toml::from_str::<VersionedPluginMeta>(&text)
-                                .with_context(|| anyhow!("Failed to parse meta file contents."))
-                        });
+        for (plugin_name, source) in all_plugins.into_iter().zip(sources) {
+            let parsed_plugin = context.time_plugin(TimedPhase::Parsing, &plugin_name, || {
+                ParsedPlugin::load(
+                    files,
+                    data_files,
+                    &plugin_name,
+                    config,
+                    database,
+                    &meta_search_paths,
+                )
+            });
+            match parsed_plugin {
+                Ok(parsed_plugin) => {
+                    load_order.push(LoadOrderEntry {
+                        plugin: plugin_name.clone(),
+                        source,
+                    });
 
-                    let meta = match data {
-                        Ok(VersionedPluginMeta::V0(meta)) => {
-                            trace!("Parsed meta file {}", meta_name);
-                            Some(meta)
-                        }
-                        Ok(VersionedPluginMeta::Unsupported) => {
-                            error!(
-                                "{}",
-                                format!("Unsupported plugin meta file {}", meta_name.bold())
-                                    .bright_red()
-                            );
-                            None
-                        }
-                        // TODO(dvd): #refactor Is there a TOML error we could be printing here?
-                        Err(_) => None,
-                    };
-
-                    let parsed_plugin = Arc::new(ParsedPlugin::from(&plugin_name, records, meta));
+                    let parsed_plugin = Arc::new(parsed_plugin);
                     if is_esm(&plugin_name) {
                         masters.push(parsed_plugin);
                     } else {
@@ -345,6 +866,618 @@ impl ParsedPlugins {
             }
         }
 
-        Ok(Self { masters, plugins })
+        info!("Resolved load order:");
+        for (idx, entry) in load_order.iter().enumerate() {
+            info!("{:>4} | {:<50} | {:?}", idx, entry.plugin, entry.source);
+        }
+
+        Ok(Self {
+            masters,
+            plugins,
+            load_order,
+        })
+    }
+
+    /// Parses the plugin named `plugin_name` from the `data_files` directory, and each master in
+    /// its header as a master, in the order of the header. The plugin is the only plugin.
+    pub fn with_masters_of(
+        files: &dyn FileProvider,
+        data_files: &Path,
+        plugin_name: &str,
+        config: &GlobalConfig,
+        database: &MetaDatabase,
+    ) -> Result<Self> {
+        let meta_search_paths = MetaSearchPaths::new(files, &config.meta_search_paths);
+        let plugin = Arc::new(
+            ParsedPlugin::load(
+                files,
+                data_files,
+                plugin_name,
+                config,
+                database,
+                &meta_search_paths,
+            )
+            .with_context(|| anyhow!("Unable to parse plugin {}", plugin_name))?,
+        );
+
+        let masters = plugin
+            .masters()
+            .iter()
+            .map(|master| {
+                ParsedPlugin::load(
+                    files,
+                    data_files,
+                    master,
+                    config,
+                    database,
+                    &meta_search_paths,
+                )
+                .map(Arc::new)
+            })
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| anyhow!("Unable to parse masters of plugin {}", plugin.name))?;
+
+        debug!("Found {} masters for {}", masters.len(), plugin.name);
+
+        let load_order = masters
+            .iter()
+            .chain([&plugin])
+            .map(|plugin| LoadOrderEntry {
+                plugin: plugin.name.clone(),
+                source: OrderSource::Listed,
+            })
+            .collect();
+
+        Ok(Self {
+            masters,
+            plugins: vec![plugin],
+            load_order,
+        })
+    }
+
+    /// Splits the `masters` into the `base_masters` and all other masters, in order.
+    pub fn split_base_masters(
+        &self,
+        base_masters: &[String],
+    ) -> (Vec<Arc<ParsedPlugin>>, Vec<Arc<ParsedPlugin>>) {
+        let (reference_masters, other_masters): (Vec<_>, Vec<_>) =
+            self.masters.iter().cloned().partition(|master| {
+                base_masters
+                    .iter()
+                    .any(|base_master| base_master.eq_ignore_ascii_case(&master.name))
+            });
+
+        if reference_masters.is_empty() {
+            warn!(
+                "{}",
+                format!(
+                    "None of the base masters {} are in the load order",
+                    base_masters.join(", ")
+                )
+                .yellow()
+            );
+        }
+
+        (reference_masters, other_masters)
+    }
+
+    /// Returns a [ReportWarning] for each plugin that loads before one of its masters, or
+    /// whose master is not in the load order. Diffs of these plugins may be misleading.
+    pub fn check_master_order(&self) -> Vec<ReportWarning> {
+        let load_order = self.masters.iter().chain(self.plugins.iter()).collect_vec();
+
+        let mut warnings = Vec::new();
+        for (plugin_idx, plugin) in load_order.iter().enumerate() {
+            for master in plugin.masters() {
+                let master_idx = load_order
+                    .iter()
+                    .position(|other| other.name.eq_ignore_ascii_case(&master));
+
+                match master_idx {
+                    Some(master_idx) if master_idx < plugin_idx => {}
+                    Some(_) => {
+                        warn!(
+                            "{} {}",
+                            format!("Plugin {}", plugin.name.bold()).yellow(),
+                            format!("loads before its master {}", master.bold()).yellow()
+                        );
+
+                        warnings.push(ReportWarning::LoadsBeforeMaster {
+                            plugin: plugin.name.clone(),
+                            master,
+                        });
+                    }
+                    None => {
+                        warn!(
+                            "{} {}",
+                            format!("Plugin {}", plugin.name.bold()).yellow(),
+                            format!("has master {} that is not in the load order", master.bold())
+                                .yellow()
+                        );
+
+                        warnings.push(ReportWarning::MissingMaster {
+                            plugin: plugin.name.clone(),
+                            master,
+                        });
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Removes each plugin with a master that is not in the load order, including masters, from
+    /// the load order. Plugins depending on a removed plugin are removed as well.
+    /// Returns a [ReportWarning] for each removed plugin, in load order.
+    pub fn skip_plugins_with_missing_masters(&mut self) -> Vec<ReportWarning> {
+        let mut skipped = BTreeMap::new();
+        loop {
+            let missing = self
+                .masters
+                .iter()
+                .chain(self.plugins.iter())
+                .filter_map(|plugin| {
+                    let masters = plugin
+                        .masters()
+                        .into_iter()
+                        .filter(|master| self.find(master).is_none())
+                        .collect_vec();
+                    (!masters.is_empty()).then(|| (plugin.name.to_lowercase(), masters))
+                })
+                .collect_vec();
+
+            if missing.is_empty() {
+                break;
+            }
+
+            for (name, masters) in missing {
+                skipped.insert(name, masters);
+            }
+
+            self.masters
+                .retain(|plugin| !skipped.contains_key(&plugin.name.to_lowercase()));
+            self.plugins
+                .retain(|plugin| !skipped.contains_key(&plugin.name.to_lowercase()));
+        }
+
+        let mut warnings = Vec::new();
+        self.load_order.retain(|entry| {
+            let Some(masters) = skipped.remove(&entry.plugin.to_lowercase()) else {
+                return true;
+            };
+
+            warn!(
+                "{} {}",
+                format!("Skipping plugin {}", entry.plugin.bold()).yellow(),
+                format!(
+                    "since its masters {} are not in the load order",
+                    masters.join(", ").bold()
+                )
+                .yellow()
+            );
+
+            warnings.push(ReportWarning::SkippedForMissingMasters {
+                plugin: entry.plugin.clone(),
+                masters,
+            });
+
+            false
+        });
+
+        warnings
+    }
+
+    /// Returns each plugin in the load order named in `forbidden_masters` that could be a master
+    /// of the output, along with the number of its records that could be merged.
+    /// Patches and plugins with only a [Header] are never masters, so these are not returned.
+    pub fn find_forbidden_masters(
+        &self,
+        forbidden_masters: &[String],
+    ) -> Vec<(Arc<ParsedPlugin>, usize)> {
+        self.masters
+            .iter()
+            .chain(self.plugins.iter())
+            .filter(|plugin| {
+                plugin.meta.meta_type != MetaType::LandDiff
+                    && forbidden_masters
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&plugin.name))
+            })
+            .map(|plugin| {
+                let num_records = plugin
+                    .records
+                    .objects
+                    .iter()
+                    .filter(|object| !matches!(object, TES3Object::Header(_)))
+                    .count();
+                (plugin.clone(), num_records)
+            })
+            .filter(|(_, num_records)| *num_records > 0)
+            .collect()
+    }
+
+    /// Removes the plugin with the `name` from the load order, if any.
+    pub fn remove(&mut self, name: &str) {
+        self.masters
+            .retain(|plugin| !plugin.name.eq_ignore_ascii_case(name));
+        self.plugins
+            .retain(|plugin| !plugin.name.eq_ignore_ascii_case(name));
+        self.load_order
+            .retain(|entry| !entry.plugin.eq_ignore_ascii_case(name));
+    }
+
+    /// Returns a [ReportWarning] for each LAND record flagged as ignored or blocked.
+    /// Ignored LAND records are skipped when merging and blocked LAND records are merged
+    /// without the flag.
+    pub fn check_land_flags(&self) -> Vec<ReportWarning> {
+        let mut warnings = Vec::new();
+        for plugin in self.masters.iter().chain(self.plugins.iter()) {
+            for land in plugin.records.objects_of_type::<Landscape>() {
+                let coords = coordinates(land);
+
+                if land.flags.contains(ObjectFlags::IGNORED) {
+                    warn!(
+                        "{} {}",
+                        format!("Plugin {}", plugin.name.bold()).yellow(),
+                        format!(
+                            "has ignored LAND record at ({}, {}) that will be skipped",
+                            coords.x, coords.y
+                        )
+                        .yellow()
+                    );
+
+                    warnings.push(ReportWarning::IgnoredLand {
+                        plugin: plugin.name.clone(),
+                        x: coords.x,
+                        y: coords.y,
+                    });
+                } else if land.flags.contains(ObjectFlags::BLOCKED) {
+                    warn!(
+                        "{} {}",
+                        format!("Plugin {}", plugin.name.bold()).yellow(),
+                        format!(
+                            "has blocked LAND record at ({}, {}) that will be merged",
+                            coords.x, coords.y
+                        )
+                        .yellow()
+                    );
+
+                    warnings.push(ReportWarning::BlockedLand {
+                        plugin: plugin.name.clone(),
+                        x: coords.x,
+                        y: coords.y,
+                    });
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns the [ParsedPlugin] with the `name` in the load order, if any.
+    pub fn find(&self, name: &str) -> Option<&Arc<ParsedPlugin>> {
+        self.masters
+            .iter()
+            .chain(self.plugins.iter())
+            .find(|plugin| plugin.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns each master of the `plugin` in the load order, and the name of each master
+    /// that is not. The masters of each master are included as well.
+    fn find_masters(&self, plugin: &ParsedPlugin) -> (Vec<Arc<ParsedPlugin>>, Vec<String>) {
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+        let mut visited = HashSet::from([plugin.name.to_lowercase()]);
+        let mut pending = VecDeque::from_iter(plugin.masters().into_iter().rev());
+        while let Some(name) = pending.pop_front() {
+            if !visited.insert(name.to_lowercase()) {
+                continue;
+            }
+
+            match self.find(&name) {
+                Some(master) => {
+                    pending.extend(master.masters().into_iter().rev());
+                    found.push(master.clone());
+                }
+                None => missing.push(name),
+            }
+        }
+
+        (found, missing)
+    }
+
+    /// Returns each master of the `plugin` that may define the LTEX records used by its LAND
+    /// records, in the order they are searched. Later masters in the [Header] are searched before
+    /// earlier ones, and each master before its own masters. The load order does not matter.
+    pub fn texture_dependencies(&self, plugin: &ParsedPlugin) -> Vec<Arc<ParsedPlugin>> {
+        self.find_masters(plugin).0
+    }
+
+    /// Returns the texture index of each LTEX record in the `plugin` or in its
+    /// [Self::texture_dependencies], and the name of each master of the `plugin` that is not in
+    /// the load order. The texture index of an LTEX record is its index plus one.
+    fn known_texture_indices(&self, plugin: &ParsedPlugin) -> (HashSet<u16>, Vec<String>) {
+        let (dependencies, missing_masters) = self.find_masters(plugin);
+        let known_indices = plugin
+            .records
+            .objects_of_type::<LandscapeTexture>()
+            .chain(dependencies.iter().flat_map(|master| {
+                master
+                    .records
+                    .objects_of_type::<LandscapeTexture>()
+                    .filter(|texture| !texture.flags.contains(ObjectFlags::DELETED))
+            }))
+            .filter_map(|texture| texture.index)
+            .filter_map(|index| u16::try_from(index + 1).ok())
+            .collect();
+
+        (known_indices, missing_masters)
+    }
+
+    /// Returns a [ReportWarning] for each LAND record using a texture index that does not match
+    /// any LTEX record in the same plugin or in its [Self::texture_dependencies]. Index `0` is the
+    /// default texture and always matches.
+    pub fn check_texture_indices(&self) -> Vec<ReportWarning> {
+        let mut warnings = Vec::new();
+        for plugin in self.masters.iter().chain(self.plugins.iter()) {
+            let (known_indices, missing_masters) = self.known_texture_indices(plugin);
+
+            for land in plugin.records.objects_of_type::<Landscape>() {
+                if land.flags.contains(ObjectFlags::IGNORED) {
+                    continue;
+                }
+
+                let Some(texture_indices) = land.texture_indices.as_ref() else {
+                    continue;
+                };
+
+                let indices = texture_indices
+                    .data
+                    .iter()
+                    .flatten()
+                    .filter(|idx| **idx != 0 && !known_indices.contains(idx))
+                    .copied()
+                    .sorted()
+                    .dedup()
+                    .collect_vec();
+
+                if indices.is_empty() {
+                    continue;
+                }
+
+                let coords = coordinates(land);
+                if !missing_masters.is_empty() {
+                    warn!(
+                        "{} {}",
+                        format!("Plugin {}", plugin.name.bold()).yellow(),
+                        format!(
+                            "has LAND record at ({}, {}) with {} texture indices that may be defined by missing masters {}",
+                            coords.x,
+                            coords.y,
+                            indices.len(),
+                            missing_masters.join(", ")
+                        )
+                        .yellow()
+                    );
+
+                    warnings.push(ReportWarning::MissingTextureMaster {
+                        plugin: plugin.name.clone(),
+                        masters: missing_masters.clone(),
+                        x: coords.x,
+                        y: coords.y,
+                        indices,
+                    });
+                    continue;
+                }
+
+                warn!(
+                    "{} {}",
+                    format!("Plugin {}", plugin.name.bold()).yellow(),
+                    format!(
+                        "has LAND record at ({}, {}) with {} texture indices without an LTEX record",
+                        coords.x,
+                        coords.y,
+                        indices.len()
+                    )
+                    .yellow()
+                );
+
+                warnings.push(ReportWarning::MissingTexture {
+                    plugin: plugin.name.clone(),
+                    x: coords.x,
+                    y: coords.y,
+                    indices,
+                });
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns a [ReportWarning] for each cell with more than one LAND record in the same plugin.
+    /// The records are merged according to the [DuplicateRecordPolicy] `policy`.
+    pub fn check_duplicate_land_records(
+        &self,
+        policy: DuplicateRecordPolicy,
+    ) -> Vec<ReportWarning> {
+        let mut warnings = Vec::new();
+        for plugin in self.masters.iter().chain(self.plugins.iter()) {
+            let mut records: BTreeMap<(i32, i32), Vec<(usize, &Landscape)>> = BTreeMap::new();
+            for (idx, land) in plugin
+                .records
+                .objects_of_type::<Landscape>()
+                .enumerate()
+                .filter(|(_, land)| !land.flags.contains(ObjectFlags::IGNORED))
+            {
+                let coords = coordinates(land);
+                records
+                    .entry((coords.x, coords.y))
+                    .or_default()
+                    .push((idx, land));
+            }
+
+            for ((x, y), lands) in records.into_iter().filter(|(_, lands)| lands.len() > 1) {
+                warn!(
+                    "{} {}",
+                    format!("Plugin {}", plugin.name.bold()).yellow(),
+                    format!(
+                        "has {} LAND records at ({}, {}) that will be merged with {:?}: {}",
+                        lands.len(),
+                        x,
+                        y,
+                        policy,
+                        lands
+                            .iter()
+                            .map(|(idx, land)| format!(
+                                "#{} with {:?}",
+                                idx + 1,
+                                included_data(land)
+                            ))
+                            .join(", ")
+                    )
+                    .yellow()
+                );
+
+                warnings.push(ReportWarning::DuplicateLandRecords {
+                    plugin: plugin.name.clone(),
+                    x,
+                    y,
+                    count: lands.len(),
+                    policy,
+                });
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns a [ReportWarning] for each plugin with LAND records whose flags do not match
+    /// their sub-records, or whose values are invalid. See [LandAnomaly].
+    pub fn check_land_records(&self) -> Vec<ReportWarning> {
+        let mut warnings = Vec::new();
+        for plugin in self.masters.iter().chain(self.plugins.iter()) {
+            let (known_indices, missing_masters) = self.known_texture_indices(plugin);
+            // Without every master, the largest texture index is unknown.
+            let max_texture_index = if missing_masters.is_empty() {
+                known_indices.into_iter().max()
+            } else {
+                Some(u16::MAX)
+            };
+
+            let mut anomalies: BTreeMap<LandAnomaly, usize> = BTreeMap::new();
+            for land in plugin.records.objects_of_type::<Landscape>() {
+                if land.flags.contains(ObjectFlags::DELETED) {
+                    continue;
+                }
+
+                for anomaly in land_anomalies(land, max_texture_index) {
+                    let coords = coordinates(land);
+                    debug!(
+                        "Plugin {} has LAND record at ({}, {}) with anomaly {:?}",
+                        plugin.name, coords.x, coords.y, anomaly
+                    );
+                    *anomalies.entry(anomaly).or_default() += 1;
+                }
+            }
+
+            if anomalies.is_empty() {
+                continue;
+            }
+
+            warn!(
+                "{} {}",
+                format!("Plugin {}", plugin.name.bold()).yellow(),
+                format!(
+                    "has {} LAND record anomalies: {}",
+                    anomalies.values().sum::<usize>(),
+                    anomalies
+                        .iter()
+                        .map(|(anomaly, count)| format!("{:?} x{}", anomaly, count))
+                        .join(", ")
+                )
+                .yellow()
+            );
+
+            warnings.push(ReportWarning::LandAnomalies {
+                plugin: plugin.name.clone(),
+                anomalies,
+            });
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        landscape, landscape_texture, master_heights, parsed_plugins, PluginBuilder, CELL,
+    };
+    use tes3::esp::TES3Object;
+
+    /// Returns [ParsedPlugins] with a plugin with a LAND record at [CELL] with the `flags`.
+    fn plugins_with_land(flags: ObjectFlags) -> ParsedPlugins {
+        let plugin = PluginBuilder::new("Flagged.esp")
+            .record(TES3Object::Landscape(Landscape {
+                flags,
+                ..Default::default()
+            }))
+            .build();
+
+        parsed_plugins(Vec::new(), vec![plugin])
+    }
+
+    #[test]
+    fn land_flags_are_reported_per_flag() {
+        for (flags, ignored, blocked) in [
+            (ObjectFlags::empty(), false, false),
+            (ObjectFlags::IGNORED, true, false),
+            (ObjectFlags::BLOCKED, false, true),
+            // Ignored LAND records are skipped, so they are not reported as blocked too.
+            (ObjectFlags::IGNORED | ObjectFlags::BLOCKED, true, false),
+        ] {
+            let warnings = plugins_with_land(flags).check_land_flags();
+            assert_eq!(warnings.len(), (ignored || blocked) as usize, "{:?}", flags);
+            assert_eq!(
+                warnings
+                    .iter()
+                    .any(|warning| matches!(warning, ReportWarning::IgnoredLand { .. })),
+                ignored,
+                "{:?}",
+                flags
+            );
+            assert_eq!(
+                warnings
+                    .iter()
+                    .any(|warning| matches!(warning, ReportWarning::BlockedLand { .. })),
+                blocked,
+                "{:?}",
+                flags
+            );
+        }
+    }
+
+    #[test]
+    fn texture_indices_are_checked_against_the_ltex_records() {
+        for (texture, out_of_bounds) in [(0, false), (1, false), (2, true)] {
+            let plugin = PluginBuilder::new("Textured.esp")
+                .record(TES3Object::LandscapeTexture(landscape_texture("Grass", 0)))
+                .record(TES3Object::Landscape(landscape(
+                    CELL,
+                    &master_heights(CELL),
+                    texture,
+                )))
+                .build();
+
+            let plugins = parsed_plugins(Vec::new(), vec![plugin]);
+
+            let reported = plugins.check_land_records().iter().any(|warning| {
+                matches!(warning, ReportWarning::LandAnomalies { anomalies, .. }
+                    if anomalies.contains_key(&LandAnomaly::TextureIndexOutOfBounds))
+            });
+            assert_eq!(reported, out_of_bounds, "{}", texture);
+        }
     }
 }
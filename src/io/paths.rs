@@ -0,0 +1,114 @@
+use crate::io::file_provider::FileProvider;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::path::{Path, PathBuf};
+
+/// Returns an absolute, extended-length version of `path` on Windows, i.e. prefixed with `\\?\`.
+/// This lifts the `MAX_PATH` limit of 260 characters, which deeply nested mod manager
+/// directories can exceed. Paths that cannot be converted are returned unchanged.
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let Ok(absolute) = std::env::current_dir().map(|dir| dir.join(path)) else {
+        return path.to_path_buf();
+    };
+
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return absolute;
+    };
+
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut extended = OsString::from(r"\\?\");
+            extended.push(prefix.as_os_str());
+            extended
+        }
+        Prefix::UNC(server, share) => {
+            let mut extended = OsString::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended
+        }
+        // Verbatim and device paths are used as is.
+        _ => return absolute,
+    };
+
+    // Extended-length paths are not normalized by Windows, so `.` and `..` must be removed.
+    let mut normal_components = Vec::new();
+    for component in components {
+        match component {
+            Component::Normal(name) => normal_components.push(name),
+            Component::ParentDir => {
+                normal_components.pop();
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+
+    if normal_components.is_empty() {
+        // The root of a drive must end with a separator, e.g. `\\?\C:\`.
+        extended.push(r"\");
+    }
+
+    for name in normal_components {
+        extended.push(r"\");
+        extended.push(name);
+    }
+
+    PathBuf::from(extended)
+}
+
+/// Returns `path` unchanged. Only Windows limits the length of paths.
+#[cfg(not(windows))]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Creates the directory `dir` and any missing parents with the [FileProvider] if it does not exist.
+pub fn create_dir_if_missing(files: &dyn FileProvider, dir: &Path) -> Result<()> {
+    let exists = files
+        .exists(dir)
+        .with_context(|| anyhow!("Unable to find `{}` directory", dir.to_string_lossy()))?;
+
+    if !exists {
+        files
+            .create_dir_all(dir)
+            .with_context(|| anyhow!("Unable to create `{}` directory", dir.to_string_lossy()))?;
+        info!("Created `{}` directory", dir.to_string_lossy());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNICODE_PLUGIN: &str = "Tamriel_Ünïcödé_Ærø_日本.esp";
+
+    #[test]
+    fn unicode_plugin_names_are_kept() {
+        let path = Path::new("Data Files").join(UNICODE_PLUGIN);
+        let extended = extended_length_path(&path);
+
+        assert_eq!(
+            extended.file_name().and_then(|name| name.to_str()),
+            Some(UNICODE_PLUGIN)
+        );
+        assert!(extended.ends_with(&path));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unicode_plugin_names_are_extended_on_windows() {
+        let extended = extended_length_path(&Path::new(r"C:\Data Files").join(UNICODE_PLUGIN));
+        assert_eq!(
+            extended,
+            PathBuf::from(format!(r"\\?\C:\Data Files\{}", UNICODE_PLUGIN))
+        );
+    }
+}
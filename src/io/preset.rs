@@ -0,0 +1,74 @@
+use crate::io::meta_schema::ConflictStrategy;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// A named bundle of [PresetSettings] for running the tool without any other arguments,
+/// e.g. from an automated mod list installer.
+pub enum Preset {
+    /// Uses the conflict strategies from the meta files and flags more conflicts.
+    Conservative,
+    /// Resolves every conflict by merging both sides and flags fewer conflicts.
+    Aggressive,
+    /// Keeps the change from the last plugin in load order, like the game would.
+    VanillaFriendly,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+/// The settings that change the result of a run of the tool.
+pub struct PresetSettings {
+    /// If [Some], this [ConflictStrategy] is used for every plugin instead of its meta file.
+    pub conflict_strategy: Option<ConflictStrategy>,
+    /// A conflict is flagged if at least this fraction of the vertices of a cell have minor conflicts.
+    pub minor_conflict_fraction: f32,
+    /// A conflict is flagged if at least this fraction of the vertices of a cell have major conflicts.
+    pub major_conflict_fraction: f32,
+    /// `true` if images of flagged conflicts are saved.
+    pub save_images: bool,
+    /// `true` if the output is identical for identical inputs, i.e. the description of the
+    /// output does not include the time it was generated.
+    pub deterministic: bool,
+}
+
+impl Default for PresetSettings {
+    /// The default [PresetSettings] are used when no [Preset] is selected.
+    fn default() -> Self {
+        Self {
+            conflict_strategy: None,
+            minor_conflict_fraction: 0.02,
+            major_conflict_fraction: 0.001,
+            save_images: true,
+            deterministic: false,
+        }
+    }
+}
+
+impl Preset {
+    /// Returns the [PresetSettings] of the [Preset]. Every [Preset] skips saving images and
+    /// produces deterministic output, since nobody is watching an automated run.
+    pub fn settings(self) -> PresetSettings {
+        let defaults = PresetSettings {
+            save_images: false,
+            deterministic: true,
+            ..PresetSettings::default()
+        };
+
+        match self {
+            Preset::Conservative => PresetSettings {
+                minor_conflict_fraction: 0.01,
+                major_conflict_fraction: 0.0005,
+                ..defaults
+            },
+            Preset::Aggressive => PresetSettings {
+                conflict_strategy: Some(ConflictStrategy::Resolve),
+                minor_conflict_fraction: 0.05,
+                major_conflict_fraction: 0.005,
+                ..defaults
+            },
+            Preset::VanillaFriendly => PresetSettings {
+                conflict_strategy: Some(ConflictStrategy::Overwrite),
+                ..defaults
+            },
+        }
+    }
+}
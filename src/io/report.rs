@@ -0,0 +1,597 @@
+use crate::io::artifacts::ArtifactSink;
+use crate::io::meta_schema::{
+    AveragingKernel, ConflictStrategy, DuplicateRecordPolicy, OutlierCellPolicy,
+};
+use crate::io::preset::{Preset, PresetSettings};
+use anyhow::{anyhow, Context, Result};
+use hashbrown::HashSet;
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The name of the [Report] file saved to the [ArtifactSink].
+const REPORT_FILE_NAME: &str = "report.json";
+
+#[derive(Serialize, Debug, Default)]
+/// A machine readable summary of a run of the tool.
+pub struct Report {
+    /// The [Preset] selected for the run, if any.
+    pub preset: Option<Preset>,
+    /// The [PresetSettings] used by the run.
+    pub settings: PresetSettings,
+    /// Each [NewLandCollision] between plugins adding new LAND to the same cells.
+    /// These are listed first, since no conflict strategy can merge them.
+    pub new_land_collisions: Vec<NewLandCollision>,
+    /// Each [ReportWarning] found during the run.
+    pub warnings: Vec<ReportWarning>,
+    /// Each [RepairedSeam] changed after merging.
+    pub repaired_seams: Vec<RepairedSeam>,
+    /// Each plugin in the resolved load order.
+    pub load_order: Vec<LoadOrderEntry>,
+    /// Each [CellConflict] between a plugin and the merged land.
+    pub conflicts: Vec<CellConflict>,
+    /// Each [ConflictCluster] of contiguous cells with flagged conflicts.
+    pub clusters: Vec<ConflictCluster>,
+    /// The [PluginPairScore] of each pair of plugins with conflicts, from most to least severe.
+    pub plugin_pairs: Vec<PluginPairScore>,
+    /// The phase the run was cancelled during, if it did not finish.
+    pub cancelled: Option<String>,
+    /// Each [StrategyDivergence] found by a shadow evaluation, if enabled.
+    pub strategy_divergences: Vec<StrategyDivergence>,
+    /// The [DiffBaseline] the `conflicts` were found with.
+    pub diff_baseline: DiffBaseline,
+    /// Each [ProvenanceSummary] of the merged land, if recorded.
+    pub provenance: Vec<ProvenanceSummary>,
+    /// Each [SkippedCell] left out of the output because of major conflicts, if enabled.
+    pub skipped_cells: Vec<SkippedCell>,
+    /// Each [ExcludedCell] left out of the output because it is in the exclusion list.
+    pub excluded_cells: Vec<ExcludedCell>,
+    /// Each [NewLandCell] with LAND added by plugins where no master has LAND.
+    pub new_land: Vec<NewLandCell>,
+    /// Each [TextureHistory] of an LTEX record with a file name changed by a later plugin.
+    pub textures: Vec<TextureHistory>,
+    /// Each [StrayVertexNormals] removed from the merged land.
+    pub stray_vertex_normals: Vec<StrayVertexNormals>,
+    /// Each [DuplicateLand] between plugins with identical LAND records.
+    pub duplicate_land: Vec<DuplicateLand>,
+    /// Each [WorldMapOnlyEdits] of a plugin with LAND records ignored by the
+    /// `ignore_world_map_only_edits` setting.
+    pub world_map_only_edits: Vec<WorldMapOnlyEdits>,
+    /// The [HeightEncoding] of the cells of the output.
+    pub height_encoding: HeightEncoding,
+    /// Each [MultiWayConflict] with heights modified by three or more plugins.
+    pub multi_way_conflicts: Vec<MultiWayConflict>,
+    /// The [RunChanges] since the previous run, if it was saved.
+    pub changes: Option<RunChanges>,
+    /// Each group of [OutlierCells] far away from the rest of the merged land.
+    pub outlier_cells: Vec<OutlierCells>,
+    /// The one line summary of the merge, once the output is saved.
+    pub summary: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+/// A group of `cells` of the merged land far away from every other cell, e.g. a test cell.
+/// The `plugins` are the masters and plugins of the cells, and the [OutlierCellPolicy] is how
+/// the cells were handled.
+pub struct OutlierCells {
+    pub cells: Vec<[i32; 2]>,
+    pub plugins: Vec<String>,
+    pub policy: OutlierCellPolicy,
+}
+
+#[derive(Serialize, Debug, Default)]
+/// The changes of the conflicts and the cells of the merged land since the previous run.
+/// Conflicts are matched by plugin, cell, and value.
+pub struct RunChanges {
+    /// Each [CellConflict] that did not exist in the previous run.
+    pub new_conflicts: Vec<CellConflict>,
+    /// Each [CellConflict] of the previous run that no longer exists.
+    pub resolved_conflicts: Vec<CellConflict>,
+    /// The coordinates of each cell of the merged land that did not exist in the previous run.
+    pub added_cells: Vec<[i32; 2]>,
+    /// The coordinates of each cell of the merged land of the previous run that no longer exists.
+    pub removed_cells: Vec<[i32; 2]>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// The land each plugin is compared with to find its [CellConflict].
+pub enum DiffBaseline {
+    /// The land of the masters.
+    #[default]
+    Reference,
+    /// The merged land at the position of the plugin in the load order, i.e. the land that
+    /// the plugin replaces in-game.
+    Predecessor,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// A `plugin` in the resolved load order and the [OrderSource] of its position.
+pub struct LoadOrderEntry {
+    pub plugin: String,
+    pub source: OrderSource,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// The reason a plugin is at its position in the resolved load order.
+pub enum OrderSource {
+    /// The order the plugins were listed in, either as arguments or in `Morrowind.ini`.
+    Listed,
+    /// The last modified date of the plugin.
+    ModifiedTime,
+    /// The plugin is an ESM modified after an ESP, but ESMs always load before ESPs.
+    EsmPriority,
+    /// An order override in the config file.
+    Override,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The conflicts in the `value` of the cell at `coords` between the `plugin` and the merged land.
+pub struct CellConflict {
+    pub plugin: String,
+    pub coords: [i32; 2],
+    pub value: String,
+    pub num_minor: usize,
+    pub num_major: usize,
+    /// `true` if there are enough conflicts to save images of the cell.
+    pub flagged: bool,
+    /// The largest difference in world units between the heights of the `plugin` and the merged
+    /// land, for conflicts in the `height_map`.
+    #[serde(default)]
+    pub max_difference: Option<i32>,
+    /// The `max_difference` in meters.
+    #[serde(default)]
+    pub max_difference_meters: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// A named area of contiguous `cells` with a flagged [CellConflict].
+pub struct ConflictCluster {
+    /// The name of the area, taken from the names or regions of its cells.
+    pub name: String,
+    /// The smallest coordinates of any of the `cells`.
+    pub min: [i32; 2],
+    /// The largest coordinates of any of the `cells`.
+    pub max: [i32; 2],
+    pub cells: Vec<[i32; 2]>,
+    /// The plugins with a flagged [CellConflict] in any of the `cells`.
+    pub plugins: Vec<String>,
+    pub num_minor: usize,
+    pub num_major: usize,
+    /// A one line summary of the cluster, e.g. for sharing in a bug report.
+    pub summary: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// How compatible the LAND of two `plugins` is, in load order. A higher `score` is less compatible.
+pub struct PluginPairScore {
+    pub plugins: [String; 2],
+    /// The number of vertices with a major conflict, weighted above the minor conflicts.
+    pub score: usize,
+    /// The number of cells with a conflict between the `plugins`.
+    pub num_cells: usize,
+    pub num_minor: usize,
+    pub num_major: usize,
+    /// The number of cells with a major conflict, by value.
+    pub major_cells: BTreeMap<String, usize>,
+    /// Advice for the pair, e.g. for list builders.
+    pub suggestion: String,
+    /// The [PatchDirection] of the pair, from the direction of their height changes.
+    pub direction: PatchDirection,
+    /// Advice for which plugin of the pair should patch the other, if known.
+    pub direction_suggestion: Option<String>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Which plugin of a [PluginPairScore] should patch the other, from how their height changes
+/// overlap in the cells both modify. This is a heuristic.
+pub enum PatchDirection {
+    #[default]
+    /// The plugins do not both change the heights of any vertex.
+    Unknown,
+    /// The later plugin changes nearly every height the earlier plugin changes, in the same
+    /// direction and further, so it already includes the changes and can overwrite them.
+    LaterRefinesEarlier,
+    /// The earlier plugin changes nearly every height the later plugin changes, in the same
+    /// direction and further, so the later plugin should be patched to include its changes.
+    EarlierRefinesLater,
+    /// The plugins change the same heights in opposite directions, so a patch must choose.
+    Opposed,
+    /// The plugins make mostly independent changes, so a patch should combine them.
+    Independent,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// The results of every [ConflictStrategy] for a conflict in the `value` of the cell at `coords`
+/// between the `plugin` and the merged land, found by a shadow evaluation.
+pub struct StrategyDivergence {
+    pub plugin: String,
+    pub coords: [i32; 2],
+    pub value: String,
+    /// The [ConflictStrategy] used by the merge, which is the one forced by the preset if any,
+    /// and may be [ConflictStrategy::Auto].
+    pub strategy: ConflictStrategy,
+    /// The disagreement between each pair of strategies.
+    pub pairs: Vec<StrategyPairDivergence>,
+    /// `true` if any pair of strategies has enough major disagreements to need human attention.
+    pub disagree_strongly: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// The number of vertices where the results of the `lhs` and `rhs` [ConflictStrategy] disagree.
+pub struct StrategyPairDivergence {
+    pub lhs: ConflictStrategy,
+    pub rhs: ConflictStrategy,
+    pub num_minor: usize,
+    pub num_major: usize,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` left out of the output because of major conflicts. The game uses the
+/// LAND record of the `winner`, i.e. the last plugin in the load order that modifies the cell.
+pub struct SkippedCell {
+    pub coords: [i32; 2],
+    pub winner: String,
+    /// The plugins with a major conflict in the cell.
+    pub plugins: Vec<String>,
+    /// The values with a major conflict in the cell.
+    pub values: Vec<String>,
+    /// The total number of major conflicts of the `plugins`.
+    pub num_major: usize,
+}
+
+#[derive(Serialize, Debug, Default)]
+/// Whether the heights of each cell of the output are saved without loss in its LAND record.
+pub struct HeightEncoding {
+    /// The number of cells with heights saved without loss.
+    pub num_lossless: usize,
+    /// The total number of vertices of the `clamped` cells saved at a different height.
+    pub num_affected_vertices: usize,
+    /// Each [ClampedHeights] cell, ordered by `x` and `y` coordinates.
+    pub clamped: Vec<ClampedHeights>,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` with heights too steep to be saved without loss. Each difference between
+/// neighboring vertices of a LAND record must fit in a byte, so steeper differences are clamped.
+pub struct ClampedHeights {
+    pub coords: [i32; 2],
+    /// The number of differences between neighboring vertices that were clamped.
+    pub num_clamped_gradients: usize,
+    /// The number of vertices saved at a different height.
+    pub num_affected_vertices: usize,
+    /// The largest difference in world units between a saved height and the merged height.
+    pub max_difference: i32,
+    /// The `max_difference` in meters.
+    pub max_difference_meters: f32,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` left out of the output because it is in the exclusion list. The game uses
+/// the LAND record of the `winner`, i.e. the last plugin in the load order that modifies the cell.
+pub struct ExcludedCell {
+    pub coords: [i32; 2],
+    pub winner: String,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` with LAND added by `plugins` where no master has LAND.
+pub struct NewLandCell {
+    pub coords: [i32; 2],
+    /// The plugins that add LAND to the cell, in load order.
+    pub plugins: Vec<String>,
+    /// The `plugins` whose LAND is merged, depending on the
+    /// [crate::io::meta_schema::NewLandPolicy].
+    pub merged: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` with `num_vertices` heights modified by three or more of the `plugins`, and
+/// at most `max_plugins` at a single vertex. The heights are resolved with the `kernel`.
+pub struct MultiWayConflict {
+    pub coords: [i32; 2],
+    pub plugins: Vec<String>,
+    pub num_vertices: usize,
+    pub max_plugins: usize,
+    pub kernel: AveragingKernel,
+}
+
+#[derive(Serialize, Debug)]
+/// A cell at `coords` with modified vertex normals at `vertices` without a modified height.
+/// The modified vertex normals were removed, since they cannot match the height map.
+pub struct StrayVertexNormals {
+    pub coords: [i32; 2],
+    pub vertices: Vec<[usize; 2]>,
+    /// The plugins that modified the vertex normals of the `vertices`.
+    pub plugins: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+/// The LTEX record `id` and the `file_name` from the `plugin` used in the output.
+pub struct TextureHistory {
+    pub id: String,
+    pub file_name: String,
+    pub plugin: String,
+    /// The `plugin`, if it is the authority for the LTEX record. Later changes are ignored.
+    pub authority: Option<String>,
+    /// Each [TextureChange] of the file name, in load order.
+    pub changes: Vec<TextureChange>,
+}
+
+#[derive(Serialize, Debug)]
+/// A `plugin` that changed the file name of an LTEX record to `file_name`.
+pub struct TextureChange {
+    pub plugin: String,
+    pub file_name: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+/// The `cells` where each of the `plugins` adds new LAND, e.g. two landmass mods placing land
+/// in the same empty ocean.
+pub struct NewLandCollision {
+    pub plugins: Vec<String>,
+    /// The smallest coordinates of any of the `cells`.
+    pub min: [i32; 2],
+    /// The largest coordinates of any of the `cells`.
+    pub max: [i32; 2],
+    pub cells: Vec<[i32; 2]>,
+    /// A one line summary of the collision, e.g. for sharing in a bug report.
+    pub summary: String,
+}
+
+#[derive(Serialize, Debug)]
+/// The `cells` where each of the `plugins` has an identical LAND record, e.g. because a
+/// compilation re-ships the LAND of another mod. Only the LAND of the `merged` plugin, the last
+/// of the `plugins` in load order, is merged, so the others have no conflicts in these cells.
+pub struct DuplicateLand {
+    pub plugins: Vec<String>,
+    pub merged: String,
+    pub cells: Vec<[i32; 2]>,
+}
+
+#[derive(Serialize, Debug)]
+/// The `cells` where the LAND of the `plugin` only changes the world map data, which are not merged.
+pub struct WorldMapOnlyEdits {
+    pub plugin: String,
+    pub cells: Vec<[i32; 2]>,
+}
+
+#[derive(Serialize, Debug)]
+/// The number of vertices of the `value` of the cell at `coords` changed by each plugin
+/// and by repairs after merging.
+pub struct ProvenanceSummary {
+    pub coords: [i32; 2],
+    pub value: String,
+    /// The number of vertices with the value merged from each plugin, by name.
+    pub plugins: BTreeMap<String, usize>,
+    /// The number of vertices merged over a change by an earlier plugin.
+    pub num_conflicts: usize,
+    /// The number of vertices changed by repairs after merging.
+    pub num_repaired: usize,
+}
+
+#[derive(Serialize, Debug)]
+/// A seam along a side or corner shared by `cells` that was repaired after merging.
+pub struct RepairedSeam {
+    /// The coordinates of each cell sharing the seam.
+    pub cells: Vec<[i32; 2]>,
+    /// The number of vertices on the seam that were moved.
+    pub vertices_moved: usize,
+    /// The largest change in height in world units of any vertex on the seam.
+    pub max_delta: usize,
+    /// The `max_delta` in meters.
+    pub max_delta_meters: f32,
+    /// The plugins that modified the height map of any of the `cells`.
+    pub plugins: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+/// An inconsistency between the flags and the sub-records of a LAND record.
+pub enum LandAnomaly {
+    /// The LAND record is flagged as using vertex heights, but has no height map.
+    MissingVertexHeights,
+    /// The LAND record is flagged as using vertex normals, but has no vertex normals.
+    MissingVertexNormals,
+    /// The LAND record is flagged as using vertex colors, but has no vertex colors.
+    MissingVertexColors,
+    /// The LAND record is flagged as using textures, but has no texture indices.
+    MissingTextureIndices,
+    /// The LAND record is flagged as using world map data, but has no world map data.
+    MissingWorldMapData,
+    /// The LAND record has a height map, but is not flagged as using vertex heights.
+    UnflaggedVertexHeights,
+    /// The LAND record has vertex colors, but is not flagged as using vertex colors.
+    UnflaggedVertexColors,
+    /// The LAND record has texture indices, but is not flagged as using textures.
+    UnflaggedTextureIndices,
+    /// The offset of the height map is infinite or NaN.
+    NonFiniteHeightOffset,
+    /// A texture index is larger than the texture index of any LTEX record in the plugin or its
+    /// masters. These are also reported as [ReportWarning::MissingTexture].
+    TextureIndexOutOfBounds,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// A problem found during the run that may affect the merged result.
+pub enum ReportWarning {
+    /// The `plugin` loads before its `master`, so its diffs are computed against the wrong land.
+    LoadsBeforeMaster { plugin: String, master: String },
+    /// The `master` of the `plugin` is not in the load order.
+    MissingMaster { plugin: String, master: String },
+    /// The `plugin` is left out of the merge, since its `masters` are missing or were left out.
+    SkippedForMissingMasters {
+        plugin: String,
+        masters: Vec<String>,
+    },
+    /// The `plugin` is left out of the merge, since it must not be a master of the output.
+    /// None of its `num_records` LAND, LTEX, CELL, and REGN records are merged.
+    DroppedForbiddenMaster { plugin: String, num_records: usize },
+    /// The LAND record at `x` and `y` in the `plugin` is flagged as ignored and will be skipped.
+    IgnoredLand { plugin: String, x: i32, y: i32 },
+    /// The LAND record at `x` and `y` in the `plugin` is flagged as blocked. The flag is cleared.
+    BlockedLand { plugin: String, x: i32, y: i32 },
+    /// The LAND record at `x` and `y` in the `plugin` uses texture `indices` that do not match
+    /// any LTEX record in the `plugin` or its masters. These are replaced by the default texture.
+    MissingTexture {
+        plugin: String,
+        x: i32,
+        y: i32,
+        indices: Vec<u16>,
+    },
+    /// The LAND record at `x` and `y` in the `plugin` uses texture `indices` that do not match
+    /// any LTEX record in the `plugin` or its masters, and the `masters` that may define them
+    /// are not in the load order. These are replaced by the default texture.
+    MissingTextureMaster {
+        plugin: String,
+        masters: Vec<String>,
+        x: i32,
+        y: i32,
+        indices: Vec<u16>,
+    },
+    /// The LAND records of the `plugin` are not internally consistent. The number of LAND
+    /// records with each [LandAnomaly] is listed in `anomalies`. These records may lose data
+    /// when merged, e.g. a height map that is present but not flagged is ignored.
+    LandAnomalies {
+        plugin: String,
+        anomalies: BTreeMap<LandAnomaly, usize>,
+    },
+    /// The `plugin` has `count` LAND records at `x` and `y`. Only one is merged, or each value
+    /// is merged from the last record that has it, depending on the [DuplicateRecordPolicy].
+    DuplicateLandRecords {
+        plugin: String,
+        x: i32,
+        y: i32,
+        count: usize,
+        policy: DuplicateRecordPolicy,
+    },
+    /// The merged CELL record at `x` and `y` has `flags` that differ from the last `plugin` that
+    /// modified it, because each flag is merged from every plugin instead of using the last plugin.
+    CellFlagConflict {
+        plugin: String,
+        x: i32,
+        y: i32,
+        flags: Vec<String>,
+    },
+    /// The merged CELL record at `x` and `y` has a `region` set by the `plugin` that no plugin in
+    /// the load order has a REGN record for.
+    MissingRegion {
+        plugin: String,
+        x: i32,
+        y: i32,
+        region: String,
+    },
+    /// Plugins set the CELL record at `x` and `y` to different regions than the masters and each
+    /// other. Each [PluginRegion] is listed in load order, and the last one is the merged `region`.
+    RegionConflict {
+        x: i32,
+        y: i32,
+        region: String,
+        plugins: Vec<PluginRegion>,
+    },
+    /// The seam shared by the `cells` remains after the last seam repair pass, because seam repair
+    /// did not converge. Another pass would move `num_vertices` by up to `max_delta` world units.
+    UnrepairedSeam {
+        cells: Vec<[i32; 2]>,
+        num_vertices: usize,
+        max_delta: usize,
+    },
+    /// The estimated peak memory of the merge is more than the memory available when it started.
+    LowMemory {
+        estimated_bytes: u64,
+        available_bytes: u64,
+        suggestion: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+/// The `region` a `plugin` sets for a CELL record.
+pub struct PluginRegion {
+    pub plugin: String,
+    pub region: String,
+}
+
+impl Report {
+    /// Returns each [CellConflict] resolved by the merge, i.e. without the conflicts in the
+    /// `skipped_cells` and `excluded_cells` that are left to the game.
+    pub fn resolved_conflicts(&self) -> impl Iterator<Item = &CellConflict> {
+        let unresolved_cells = self
+            .skipped_cells
+            .iter()
+            .map(|cell| cell.coords)
+            .chain(self.excluded_cells.iter().map(|cell| cell.coords))
+            .collect::<HashSet<_>>();
+
+        self.conflicts
+            .iter()
+            .filter(move |conflict| !unresolved_cells.contains(&conflict.coords))
+    }
+
+    /// Saves the [Report] as JSON to the [ArtifactSink].
+    pub fn save(&self, artifacts: &ArtifactSink) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)
+            .with_context(|| anyhow!("Unable to serialize report"))?;
+
+        if !artifacts.can_save(REPORT_FILE_NAME)? {
+            return Ok(());
+        }
+
+        trace!(
+            "Saving {} with {} warnings and {} repaired seams",
+            REPORT_FILE_NAME,
+            self.warnings.len(),
+            self.repaired_seams.len()
+        );
+        artifacts.save_bytes(REPORT_FILE_NAME, &bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflict(coords: [i32; 2], num_minor: usize, num_major: usize) -> CellConflict {
+        CellConflict {
+            plugin: "Plugin.esp".to_string(),
+            coords,
+            value: "height_map".to_string(),
+            num_minor,
+            num_major,
+            flagged: false,
+            max_difference: None,
+            max_difference_meters: None,
+        }
+    }
+
+    #[test]
+    fn conflicts_of_skipped_and_excluded_cells_are_not_resolved() {
+        let report = Report {
+            conflicts: vec![
+                conflict([0, 0], 1, 2),
+                conflict([1, 0], 3, 4),
+                conflict([2, 0], 5, 6),
+            ],
+            skipped_cells: vec![SkippedCell {
+                coords: [1, 0],
+                winner: "Plugin.esp".to_string(),
+                plugins: vec!["Plugin.esp".to_string()],
+                values: vec!["height_map".to_string()],
+                num_major: 4,
+            }],
+            excluded_cells: vec![ExcludedCell {
+                coords: [2, 0],
+                winner: "Plugin.esp".to_string(),
+            }],
+            ..Report::default()
+        };
+
+        let resolved = report
+            .resolved_conflicts()
+            .map(|conflict| conflict.coords)
+            .collect::<Vec<_>>();
+        assert_eq!(resolved, vec![[0, 0]]);
+    }
+}
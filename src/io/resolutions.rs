@@ -0,0 +1,211 @@
+use crate::io::artifacts::ArtifactSink;
+use crate::io::file_provider::FileProvider;
+use crate::io::meta_schema::ConflictStrategy;
+use crate::io::report::CellConflict;
+use crate::land::terrain_map::Vec2;
+use anyhow::{anyhow, Context, Result};
+use hashbrown::{HashMap, HashSet};
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The name of the [ResolutionsFile] template saved to the [ArtifactSink].
+const RESOLUTIONS_FILE_NAME: &str = "resolutions.toml";
+
+/// The comment at the top of the [ResolutionsFile] template.
+const RESOLUTIONS_HEADER: &str = "\
+# Each major conflict of the last run. Change `choice` to decide how the conflict is merged:
+#   auto    - use the conflict strategy of the plugin.
+#   lhs     - keep the land merged before the plugin, i.e. drop the change of the plugin.
+#   rhs     - use the change of the plugin.
+#   average - merge both sides.
+#   skip    - leave the cell out of the output, so the game uses the last plugin in load order.
+# Copy this file next to the config file to use it on the next run.
+
+";
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+/// How a major conflict between a plugin and the merged land is resolved.
+pub enum ResolutionChoice {
+    #[default]
+    /// Use the [ConflictStrategy] of the plugin.
+    Auto,
+    /// Keep the merged land, i.e. [ConflictStrategy::Ignore].
+    Lhs,
+    /// Use the plugin, i.e. [ConflictStrategy::Overwrite].
+    Rhs,
+    /// Merge both sides, i.e. [ConflictStrategy::Resolve].
+    Average,
+    /// Leave the cell out of the output.
+    Skip,
+}
+
+impl ResolutionChoice {
+    /// Returns the [ConflictStrategy] of the [ResolutionChoice], if it changes the merge.
+    pub fn conflict_strategy(self) -> Option<ConflictStrategy> {
+        match self {
+            ResolutionChoice::Auto | ResolutionChoice::Skip => None,
+            ResolutionChoice::Lhs => Some(ConflictStrategy::Ignore),
+            ResolutionChoice::Rhs => Some(ConflictStrategy::Overwrite),
+            ResolutionChoice::Average => Some(ConflictStrategy::Resolve),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The [ResolutionChoice] for the `value` of the `cell` merged from the `plugin`.
+pub struct Resolution {
+    pub plugin: String,
+    pub cell: [i32; 2],
+    pub value: String,
+    #[serde(default)]
+    /// The number of major conflicts when the template was saved. Only for reference.
+    pub num_major: usize,
+    #[serde(default)]
+    pub choice: ResolutionChoice,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// The contents of a `resolutions.toml` file.
+struct ResolutionsFile {
+    #[serde(default)]
+    resolution: Vec<Resolution>,
+}
+
+#[derive(Default, Clone)]
+/// The [ResolutionChoice] of each plugin, cell, and value from a `resolutions.toml` file.
+pub struct Resolutions {
+    choices: HashMap<(String, Vec2<i32>, String), ResolutionChoice>,
+}
+
+impl Resolutions {
+    /// Parses the [Resolutions] at `file_path`. If the file does not exist,
+    /// then empty [Resolutions] are returned instead.
+    pub fn load(files: &dyn FileProvider, file_path: &Path) -> Result<Self> {
+        let exists = files.exists(file_path).with_context(|| {
+            anyhow!(
+                "Unable to find resolutions file {}",
+                file_path.to_string_lossy()
+            )
+        })?;
+
+        if !exists {
+            trace!("No resolutions file {}", file_path.to_string_lossy());
+            return Ok(Self::default());
+        }
+
+        let text = files.read_to_string(file_path).with_context(|| {
+            anyhow!(
+                "Failed to read resolutions file {}",
+                file_path.to_string_lossy()
+            )
+        })?;
+
+        let file = toml::from_str::<ResolutionsFile>(&text).with_context(|| {
+            anyhow!(
+                "Failed to parse resolutions file {}",
+                file_path.to_string_lossy()
+            )
+        })?;
+
+        let choices: HashMap<_, _> = file
+            .resolution
+            .into_iter()
+            .filter(|resolution| resolution.choice != ResolutionChoice::Auto)
+            .map(|resolution| {
+                (
+                    (
+                        resolution.plugin,
+                        Vec2::from(resolution.cell),
+                        resolution.value,
+                    ),
+                    resolution.choice,
+                )
+            })
+            .collect();
+
+        debug!(
+            "Parsed {} resolutions from {}",
+            choices.len(),
+            file_path.to_string_lossy()
+        );
+
+        Ok(Self { choices })
+    }
+
+    /// Returns the [ResolutionChoice] for the `value` of the cell at `coords` merged from the `plugin`.
+    pub fn choice(&self, plugin: &str, coords: Vec2<i32>, value: &str) -> ResolutionChoice {
+        self.choices
+            .get(&(plugin.to_string(), coords, value.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the [ConflictStrategy] for the `value` of the cell at `coords` merged from
+    /// the `plugin`, if the [ResolutionChoice] changes the merge.
+    pub fn conflict_strategy(
+        &self,
+        plugin: &str,
+        coords: Vec2<i32>,
+        value: &str,
+    ) -> Option<ConflictStrategy> {
+        if self.choices.is_empty() {
+            return None;
+        }
+
+        self.choice(plugin, coords, value).conflict_strategy()
+    }
+
+    /// Returns the coordinates of each cell with a [ResolutionChoice::Skip].
+    pub fn skipped_cells(&self) -> HashSet<Vec2<i32>> {
+        self.choices
+            .iter()
+            .filter(|(_, choice)| **choice == ResolutionChoice::Skip)
+            .map(|((_, coords, _), _)| *coords)
+            .collect()
+    }
+
+    /// Saves a template `resolutions.toml` file to the [ArtifactSink] with a [Resolution] for
+    /// each [CellConflict] with major conflicts. Any existing [ResolutionChoice] is kept.
+    pub fn save_template(
+        &self,
+        artifacts: &ArtifactSink,
+        conflicts: &[CellConflict],
+    ) -> Result<()> {
+        let file = ResolutionsFile {
+            resolution: conflicts
+                .iter()
+                .filter(|conflict| conflict.num_major > 0)
+                .map(|conflict| Resolution {
+                    plugin: conflict.plugin.clone(),
+                    cell: conflict.coords,
+                    value: conflict.value.clone(),
+                    num_major: conflict.num_major,
+                    choice: self.choice(
+                        &conflict.plugin,
+                        Vec2::from(conflict.coords),
+                        &conflict.value,
+                    ),
+                })
+                .collect(),
+        };
+
+        if file.resolution.is_empty() || !artifacts.can_save(RESOLUTIONS_FILE_NAME)? {
+            return Ok(());
+        }
+
+        let text = toml::to_string(&file)
+            .with_context(|| anyhow!("Unable to serialize {}", RESOLUTIONS_FILE_NAME))?;
+
+        trace!(
+            "Saving {} with {} major conflicts",
+            RESOLUTIONS_FILE_NAME,
+            file.resolution.len()
+        );
+        artifacts.save_bytes(
+            RESOLUTIONS_FILE_NAME,
+            format!("{}{}", RESOLUTIONS_HEADER, text).as_bytes(),
+        )
+    }
+}
@@ -0,0 +1,138 @@
+use crate::io::artifacts::ArtifactSink;
+use crate::io::file_provider::FileProvider;
+use crate::io::report::{CellConflict, RunChanges};
+use crate::LandmassDiff;
+use anyhow::{anyhow, Context, Result};
+use hashbrown::HashSet;
+use log::{info, trace, warn};
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::path::Path;
+
+/// The name of the file saved by [RunSnapshot::save] in the `merged_lands_dir`.
+const SNAPSHOT_FILE_NAME: &str = "last_run.json";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// The conflicts and cells of the merged land of a run, saved to compare with the next run.
+pub struct RunSnapshot {
+    /// Each [CellConflict] of the run.
+    pub conflicts: Vec<CellConflict>,
+    /// The coordinates of each modified cell of the merged land, ordered by `x` and `y`
+    /// coordinates.
+    pub cells: Vec<[i32; 2]>,
+}
+
+/// Returns the key identifying the same [CellConflict] in two runs.
+fn conflict_key(conflict: &CellConflict) -> (String, [i32; 2], String) {
+    (
+        conflict.plugin.clone(),
+        conflict.coords,
+        conflict.value.clone(),
+    )
+}
+
+/// Returns each item of `lhs` without an item with the same `key` in `rhs`.
+fn missing_from<T: Clone, K: Eq + Hash>(lhs: &[T], rhs: &[T], key: impl Fn(&T) -> K) -> Vec<T> {
+    let keys: HashSet<K> = rhs.iter().map(&key).collect();
+    lhs.iter()
+        .filter(|item| !keys.contains(&key(item)))
+        .cloned()
+        .collect()
+}
+
+impl RunSnapshot {
+    /// Creates a [RunSnapshot] of the `conflicts` and the modified cells of the `merged`
+    /// [LandmassDiff].
+    pub fn new(conflicts: &[CellConflict], merged: &LandmassDiff) -> Self {
+        Self {
+            conflicts: conflicts.to_vec(),
+            cells: merged
+                .sorted()
+                .filter(|(_, land)| land.is_modified())
+                .map(|(coords, _)| [coords.x, coords.y])
+                .collect(),
+        }
+    }
+
+    /// Loads the [RunSnapshot] saved by the previous run in the `merged_lands_dir`, if any.
+    /// A snapshot that cannot be read is ignored with a warning, since it only affects the report.
+    pub fn load(files: &dyn FileProvider, merged_lands_dir: &Path) -> Option<Self> {
+        let file_path = merged_lands_dir.join(SNAPSHOT_FILE_NAME);
+        if !files.exists(&file_path).unwrap_or(false) {
+            trace!("No previous run {}", file_path.to_string_lossy());
+            return None;
+        }
+
+        files
+            .read_to_string(&file_path)
+            .and_then(|text| serde_json::from_str(&text).map_err(Into::into))
+            .with_context(|| {
+                anyhow!(
+                    "Unable to read previous run {}",
+                    file_path.to_string_lossy()
+                )
+            })
+            .map_err(|e| warn!("{}", format!("{:?}", e).yellow()))
+            .ok()
+    }
+
+    /// Saves the [RunSnapshot] to the [ArtifactSink], for comparison with the next run.
+    pub fn save(&self, artifacts: &ArtifactSink) -> Result<()> {
+        let bytes = serde_json::to_vec(self)
+            .with_context(|| anyhow!("Unable to serialize snapshot of run"))?;
+
+        if !artifacts.can_save(SNAPSHOT_FILE_NAME)? {
+            return Ok(());
+        }
+
+        trace!(
+            "Saving {} with {} conflicts and {} cells",
+            SNAPSHOT_FILE_NAME,
+            self.conflicts.len(),
+            self.cells.len()
+        );
+        artifacts.save_bytes(SNAPSHOT_FILE_NAME, &bytes)
+    }
+
+    /// Returns the [RunChanges] from the `previous` [RunSnapshot] to this one.
+    pub fn changes_since(&self, previous: &RunSnapshot) -> RunChanges {
+        let changes = RunChanges {
+            new_conflicts: missing_from(&self.conflicts, &previous.conflicts, conflict_key),
+            resolved_conflicts: missing_from(&previous.conflicts, &self.conflicts, conflict_key),
+            added_cells: missing_from(&self.cells, &previous.cells, |coords| *coords),
+            removed_cells: missing_from(&previous.cells, &self.cells, |coords| *coords),
+        };
+
+        info!(
+            "Since the last run: {} new conflicts, {} resolved conflicts, {} cells added, {} cells removed",
+            changes.new_conflicts.len(),
+            changes.resolved_conflicts.len(),
+            changes.added_cells.len(),
+            changes.removed_cells.len()
+        );
+
+        let mut flagged_cells: BTreeMap<&str, HashSet<[i32; 2]>> = BTreeMap::new();
+        for conflict in changes
+            .new_conflicts
+            .iter()
+            .filter(|conflict| conflict.flagged)
+        {
+            flagged_cells
+                .entry(&conflict.plugin)
+                .or_default()
+                .insert(conflict.coords);
+        }
+
+        for (plugin, cells) in flagged_cells {
+            info!(
+                "New flagged conflicts of {} in {} cells",
+                plugin,
+                cells.len()
+            );
+        }
+
+        changes
+    }
+}
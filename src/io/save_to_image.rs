@@ -1,66 +1,126 @@
+use crate::io::artifacts::ArtifactSink;
+use crate::io::bitmap_font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::io::meta_schema::ConflictStrategy;
 use crate::io::parsed_plugins::ParsedPlugin;
+use crate::io::preset::PresetSettings;
+use crate::io::report::{CellConflict, NewLandCell, NewLandCollision};
+use crate::io::save_to_numeric::{numeric_dump, NumericFormat, NumericValue};
 use crate::land::grid_access::{GridAccessor2D, Index2D, SquareGridIterator};
+use crate::land::height_map::{format_height, world_units_to_meters};
 use crate::land::landscape_diff::LandscapeDiff;
-use crate::land::terrain_map::{Vec2, Vec3};
+use crate::land::terrain_map::{TerrainMap, Vec2, Vec3};
 use crate::merge::conflict::{ConflictResolver, ConflictType};
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::merge::relative_to::RelativeTo;
 use crate::LandmassDiff;
 use anyhow::{anyhow, Context, Result};
-use image::imageops::FilterType;
-use image::{DynamicImage, ImageBuffer, Luma, Pixel, Rgb};
-use log::{error, trace, warn};
+use hashbrown::HashSet;
+use image::imageops::{replace, FilterType};
+use image::{DynamicImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage};
+use itertools::Itertools;
+use log::{error, info, trace};
 use owo_colors::OwoColorize;
-use std::default::default;
 use std::ops::{Deref, DerefMut};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-const DEFAULT_SCALE_FACTOR: usize = 4;
+/// The distance between two vertices of a height map in world units.
+const VERTEX_SPACING: f32 = 128.;
 
-/// Saves `img` to `file_name` after resizing by `scale_factor`.
+#[derive(Copy, Clone, Debug)]
+/// Options controlling how images are rendered.
+pub struct ImageOptions {
+    /// The direction of the light in a hillshade, in degrees clockwise from north.
+    pub light_azimuth: f32,
+    /// The angle of the light in a hillshade, in degrees above the horizon.
+    pub light_altitude: f32,
+    /// The factor to resize images by. Must be > 0.
+    pub scale_factor: usize,
+    /// The [FilterType] used when resizing images.
+    pub filter: FilterType,
+    /// If set, the un-scaled data of each `MERGED` image is also saved as a PNG and
+    /// as a numeric dump in this [NumericFormat].
+    pub numeric_format: Option<NumericFormat>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            light_azimuth: 315.,
+            light_altitude: 45.,
+            scale_factor: 4,
+            filter: FilterType::Nearest,
+            numeric_format: None,
+        }
+    }
+}
+
+/// Resizes `img` by the `scale_factor` in [ImageOptions] using the `filter`.
+fn resize_image<const T: usize, I>(img: I, options: &ImageOptions) -> DynamicImage
+where
+    DynamicImage: From<I>,
+{
+    assert!(options.scale_factor > 0, "scale_factor must be > 0");
+
+    let img = DynamicImage::from(img);
+    if options.scale_factor == 1 {
+        return img;
+    }
+
+    img.resize_exact(
+        (T * options.scale_factor) as u32,
+        (T * options.scale_factor) as u32,
+        options.filter,
+    )
+}
+
+/// Saves `img` to `file_name` after resizing by the `scale_factor` in [ImageOptions].
 fn save_resized_image<const T: usize, I>(
     img: I,
-    file_path: &Path,
-    scale_factor: usize,
+    artifacts: &ArtifactSink,
+    file_name: &str,
+    options: &ImageOptions,
 ) -> Result<()>
 where
     DynamicImage: From<I>,
 {
-    let directory = file_path.parent().expect("safe");
-
-    let exists = directory
-        .try_exists()
-        .with_context(|| anyhow!("Unable to find `{}` directory", directory.to_string_lossy()))?;
-
-    if !exists {
-        warn!(
-            "{} {}",
-            format!(
-                "Unable to save image file {}",
-                file_path.to_string_lossy().bold()
-            )
-            .yellow(),
-            format!(
-                "because the `{}` directory does not exist",
-                directory.to_string_lossy()
-            )
-            .yellow()
-        );
+    if !artifacts.can_save(file_name)? {
+        return Ok(());
+    }
+
+    artifacts.save_image(file_name, &resize_image::<T, _>(img, options))
+}
 
+/// Saves the un-scaled `img` and a numeric dump of the `terrain` alongside `file_name`
+/// if a [NumericFormat] is set in [ImageOptions].
+fn save_raw_data<U: NumericValue, const T: usize, I>(
+    img: I,
+    terrain: &TerrainMap<U, T>,
+    artifacts: &ArtifactSink,
+    file_name: &str,
+    options: &ImageOptions,
+) -> Result<()>
+where
+    DynamicImage: From<I>,
+{
+    let Some(numeric_format) = options.numeric_format else {
+        return Ok(());
+    };
+
+    if !artifacts.can_save(file_name)? {
         return Ok(());
     }
 
-    assert!(scale_factor > 0, "scale_factor must be > 0");
-    DynamicImage::from(img)
-        .resize_exact(
-            (T * scale_factor) as u32,
-            (T * scale_factor) as u32,
-            FilterType::Nearest,
-        )
-        .save(file_path)
-        .with_context(|| anyhow!("Unable to save image file {}", file_path.to_string_lossy()))?;
+    let file_stem = Path::new(file_name)
+        .file_stem()
+        .expect("safe")
+        .to_string_lossy();
+
+    artifacts.save_image(&format!("{}_RAW.png", file_stem), &DynamicImage::from(img))?;
 
-    Ok(())
+    let (extension, bytes) = numeric_dump(terrain, numeric_format)
+        .with_context(|| anyhow!("Unable to save numeric dump of {}", file_name))?;
+
+    artifacts.save_bytes(&format!("{}.{}", file_stem, extension), &bytes)
 }
 
 impl<P, Container> GridAccessor2D<P> for ImageBuffer<P, Container>
@@ -79,24 +139,24 @@ where
 
 /// Types implementing [SaveToImage] support a method [SaveToImage::save_to_image].
 pub trait SaveToImage {
-    /// Save an image to `file_name`.
-    fn save_to_image(&self, file_path: &Path);
+    /// Save an image to `file_name` in the [ArtifactSink].
+    fn save_to_image(&self, artifacts: &ArtifactSink, file_name: &str, options: &ImageOptions);
 }
 
 impl<const T: usize> SaveToImage for RelativeTerrainMap<Vec3<i8>, T> {
-    fn save_to_image(&self, _file_path: &Path) {
+    fn save_to_image(&self, _artifacts: &ArtifactSink, _file_name: &str, _options: &ImageOptions) {
         // Ignore
     }
 }
 
 impl<const T: usize> SaveToImage for RelativeTerrainMap<u16, T> {
-    fn save_to_image(&self, _file_path: &Path) {
+    fn save_to_image(&self, _artifacts: &ArtifactSink, _file_name: &str, _options: &ImageOptions) {
         // Ignore
     }
 }
 
 impl<const T: usize> SaveToImage for RelativeTerrainMap<Vec3<u8>, T> {
-    fn save_to_image(&self, file_path: &Path) {
+    fn save_to_image(&self, artifacts: &ArtifactSink, file_name: &str, options: &ImageOptions) {
         let mut img = ImageBuffer::new(T as u32, T as u32);
 
         for coords in self.iter_grid() {
@@ -104,7 +164,17 @@ impl<const T: usize> SaveToImage for RelativeTerrainMap<Vec3<u8>, T> {
             *img.get_mut(coords) = Rgb::from([new.x, new.y, new.z]);
         }
 
-        save_resized_image::<T, _>(img, file_path, DEFAULT_SCALE_FACTOR)
+        save_raw_data(
+            img.clone(),
+            &self.to_terrain(),
+            artifacts,
+            file_name,
+            options,
+        )
+        .map_err(|e| error!("{}", e.bold().bright_red()))
+        .ok();
+
+        save_resized_image::<T, _>(img, artifacts, file_name, options)
             .map_err(|e| error!("{}", e.bold().bright_red()))
             .ok();
     }
@@ -127,34 +197,54 @@ where
     (min_value, max_value)
 }
 
+/// Scales the `value` from the range `min_value` to `max_value` to the range 0 to 1.
+/// Every value is scaled to 0 if the range is empty, e.g. for a flat [RelativeTerrainMap].
+fn normalize(value: f32, min_value: f32, max_value: f32) -> f32 {
+    let range = max_value - min_value;
+    if range > 0. {
+        (value - min_value) / range
+    } else {
+        0.
+    }
+}
+
 impl<const T: usize> SaveToImage for RelativeTerrainMap<u8, T> {
-    fn save_to_image(&self, file_path: &Path) {
+    fn save_to_image(&self, artifacts: &ArtifactSink, file_name: &str, options: &ImageOptions) {
         let mut img = ImageBuffer::new(T as u32, T as u32);
 
         let (min_value, max_value) = calculate_min_max(self);
 
         for coords in self.iter_grid() {
             let value = self.get_value(coords) as f32;
-            let scaled = (value - min_value) as f32 / (max_value - min_value);
+            let scaled = normalize(value, min_value, max_value);
             *img.get_mut(coords) = Luma::from([(scaled * 255.) as u8]);
         }
 
-        save_resized_image::<T, _>(img, file_path, DEFAULT_SCALE_FACTOR)
+        save_raw_data(
+            img.clone(),
+            &self.to_terrain(),
+            artifacts,
+            file_name,
+            options,
+        )
+        .map_err(|e| error!("{}", e.bold().bright_red()))
+        .ok();
+
+        save_resized_image::<T, _>(img, artifacts, file_name, options)
             .map_err(|e| error!("{}", e.bold().bright_red()))
             .ok();
     }
 }
 
 impl<const T: usize> SaveToImage for RelativeTerrainMap<i32, T> {
-    fn save_to_image(&self, file_path: &Path) {
+    fn save_to_image(&self, artifacts: &ArtifactSink, file_name: &str, options: &ImageOptions) {
         let mut img = ImageBuffer::new(T as u32, T as u32);
 
-        let (min_value, max_value) = calculate_min_max(self);
+        let terrain = self.to_terrain();
+        let shaded = hillshade(&terrain, options);
 
         for coords in self.iter_grid() {
-            let value = self.get_value(coords) as f32;
-            let scaled = (value - min_value) as f32 / (max_value - min_value);
-            let as_u8 = (scaled * 255.) as u8;
+            let as_u8 = shaded.get(coords).0[0];
             if self.has_difference(coords) {
                 *img.get_mut(coords) = Rgb::from([
                     (as_u8 as f32 * 0.98) as u8,
@@ -166,88 +256,122 @@ impl<const T: usize> SaveToImage for RelativeTerrainMap<i32, T> {
             }
         }
 
-        save_resized_image::<T, _>(img, file_path, DEFAULT_SCALE_FACTOR)
+        if options.numeric_format.is_some() {
+            let mut raw_img = ImageBuffer::new(T as u32, T as u32);
+
+            let (min_value, max_value) = calculate_min_max(self);
+
+            for coords in self.iter_grid() {
+                let value = self.get_value(coords) as f32;
+                let scaled = normalize(value, min_value, max_value);
+                *raw_img.get_mut(coords) = Luma::from([(scaled * u16::MAX as f32) as u16]);
+            }
+
+            save_raw_data(raw_img, &terrain, artifacts, file_name, options)
+                .map_err(|e| error!("{}", e.bold().bright_red()))
+                .ok();
+        }
+
+        save_resized_image::<T, _>(img, artifacts, file_name, options)
             .map_err(|e| error!("{}", e.bold().bright_red()))
             .ok();
     }
 }
 
-/// Saves an image of the conflicts between the `lhs` [RelativeTerrainMap] and
-/// the `rhs` [RelativeTerrainMap] if any exist.
-pub fn save_image<U: RelativeTo + ConflictResolver, const T: usize>(
-    merged_lands_dir: &Path,
-    coords: Vec2<i32>,
-    plugin: &ParsedPlugin,
-    value: &str,
-    lhs: Option<&RelativeTerrainMap<U, T>>,
-    rhs: Option<&RelativeTerrainMap<U, T>>,
-) where
-    RelativeTerrainMap<U, T>: SaveToImage,
-{
-    let Some(lhs) = lhs else {
-        return;
-    };
-
-    let Some(rhs) = rhs else {
-        return;
-    };
-
-    let mut diff_img = ImageBuffer::new(T as u32, T as u32);
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// The result of comparing a single vertex of the merged land to a plugin.
+enum VertexConflict {
+    /// The plugin does not modify the vertex.
+    Unmodified,
+    /// The plugin modifies the vertex and the merged land matches it.
+    Modified,
+    /// The merged land differs from the plugin by a [ConflictType::Minor].
+    Minor,
+    /// The merged land differs from the plugin by a [ConflictType::Major].
+    Major,
+}
 
-    let mut num_major_conflicts = 0;
-    let mut num_minor_conflicts = 0;
+/// Returns the [VertexConflict] at each coordinate between the `lhs` [RelativeTerrainMap] of
+/// the merged land and the `rhs` [RelativeTerrainMap] of a plugin.
+fn classify_vertices<U: RelativeTo + ConflictResolver, const T: usize>(
+    lhs: &RelativeTerrainMap<U, T>,
+    rhs: &RelativeTerrainMap<U, T>,
+) -> TerrainMap<VertexConflict, T> {
+    let mut conflicts = [[VertexConflict::Unmodified; T]; T];
 
-    let params = default();
+    let params = Default::default();
 
     for coords in lhs.iter_grid() {
+        if !rhs.has_difference(coords) {
+            continue;
+        }
+
         let actual = lhs.get_value(coords);
         let expected = rhs.get_value(coords);
-        let has_difference = rhs.has_difference(coords);
 
-        // TODO(dvd): #feature Use a gradient so that smaller conflicts can be seen.
-        match actual.average(expected, &params) {
-            None => {
-                let color = if has_difference {
-                    Rgb::from([0, 255u8, 0])
-                } else {
-                    Rgb::from([0, 0, 0])
-                };
+        *conflicts.get_mut(coords) = match actual.average(expected, &params) {
+            None => VertexConflict::Modified,
+            Some(ConflictType::Minor(_)) => VertexConflict::Minor,
+            Some(ConflictType::Major(_)) => VertexConflict::Major,
+        };
+    }
 
-                *diff_img.get_mut(coords) = color;
-            }
-            Some(ConflictType::Minor(_)) => {
-                let color = if has_difference {
-                    num_minor_conflicts += 1;
-                    Rgb::from([255u8, 255u8, 0])
-                } else {
-                    Rgb::from([0, 0, 0])
-                };
+    conflicts
+}
 
-                *diff_img.get_mut(coords) = color;
-            }
-            Some(ConflictType::Major(_)) => {
-                let color = if has_difference {
-                    num_major_conflicts += 1;
-                    Rgb::from([255u8, 0, 0])
-                } else {
-                    Rgb::from([0, 0, 0])
-                };
+/// Returns the largest difference in world units between the `lhs` height map of the merged land
+/// and the `rhs` height map of a plugin, over each vertex modified by the plugin.
+fn max_height_difference<const T: usize>(
+    lhs: Option<&RelativeTerrainMap<i32, T>>,
+    rhs: Option<&RelativeTerrainMap<i32, T>>,
+) -> Option<i32> {
+    let (Some(lhs), Some(rhs)) = (lhs, rhs) else {
+        return None;
+    };
 
-                *diff_img.get_mut(coords) = color;
-            }
-        }
-    }
+    lhs.iter_grid()
+        .filter(|coords| rhs.has_difference(*coords))
+        .map(|coords| (lhs.get_value(coords) - rhs.get_value(coords)).abs())
+        .max()
+}
 
-    if num_minor_conflicts == 0 && num_major_conflicts == 0 {
-        return;
+/// Counts the conflicts between the `lhs` [RelativeTerrainMap] and the `rhs` [RelativeTerrainMap]
+/// of the `value` in the cell at `coords`. Returns a [CellConflict] if any exist.
+/// The `max_difference` in world units is only known for height maps.
+fn analyze_conflicts<U: RelativeTo + ConflictResolver, const T: usize>(
+    coords: Vec2<i32>,
+    plugin: &ParsedPlugin,
+    value: &str,
+    lhs: Option<&RelativeTerrainMap<U, T>>,
+    rhs: Option<&RelativeTerrainMap<U, T>>,
+    max_difference: Option<i32>,
+    settings: &PresetSettings,
+) -> Option<CellConflict> {
+    let (Some(lhs), Some(rhs)) = (lhs, rhs) else {
+        return None;
+    };
+
+    let conflicts = classify_vertices(lhs, rhs);
+    let num_minor = conflicts
+        .iter()
+        .flatten()
+        .filter(|conflict| **conflict == VertexConflict::Minor)
+        .count();
+    let num_major = conflicts
+        .iter()
+        .flatten()
+        .filter(|conflict| **conflict == VertexConflict::Major)
+        .count();
+
+    if num_minor == 0 && num_major == 0 {
+        return None;
     }
 
-    // TODO(dvd): #mvp Read thresholds from config.
-    let minor_conflict_threshold = (T * T) as f32 * 0.02;
-    let major_conflict_threshold = (T * T) as f32 * 0.001;
+    let minor_conflict_threshold = (T * T) as f32 * settings.minor_conflict_fraction;
+    let major_conflict_threshold = (T * T) as f32 * settings.major_conflict_fraction;
 
-    let mut should_skip = num_minor_conflicts < minor_conflict_threshold as usize
-        && num_major_conflicts < major_conflict_threshold as usize;
+    let mut should_skip = num_minor < minor_conflict_threshold as usize
+        && num_major < major_conflict_threshold as usize;
 
     // TODO(dvd): #mvp Configure this too.
     if value == "vertex_colors" || value == "vertex_normals" {
@@ -255,13 +379,16 @@ pub fn save_image<U: RelativeTo + ConflictResolver, const T: usize>(
     }
 
     trace!(
-        "({:>4}, {:>4}) {:<15} | {:<50} | {:>4} Major | {:>4} Minor{}",
+        "({:>4}, {:>4}) {:<15} | {:<50} | {:>4} Major | {:>4} Minor{}{}",
         coords.x,
         coords.y,
         value,
         plugin.name,
-        num_major_conflicts,
-        num_minor_conflicts,
+        num_major,
+        num_minor,
+        max_difference
+            .map(|max_difference| format!(" | Max = {}", format_height(max_difference)))
+            .unwrap_or_default(),
         if should_skip {
             "".to_string()
         } else {
@@ -269,91 +396,876 @@ pub fn save_image<U: RelativeTo + ConflictResolver, const T: usize>(
         }
     );
 
-    if should_skip {
+    Some(CellConflict {
+        plugin: plugin.name.clone(),
+        coords: coords.into(),
+        value: value.to_string(),
+        num_minor,
+        num_major,
+        flagged: !should_skip,
+        max_difference,
+        max_difference_meters: max_difference.map(world_units_to_meters),
+    })
+}
+
+/// Returns a [CellConflict] for each value of the [LandscapeDiff] `plugin` that conflicts
+/// with the [LandscapeDiff] `reference`.
+fn analyze_landscape_conflicts(
+    parsed_plugin: &ParsedPlugin,
+    reference: &LandscapeDiff,
+    plugin: &LandscapeDiff,
+    settings: &PresetSettings,
+) -> Vec<CellConflict> {
+    let coords = reference.coords;
+    [
+        analyze_conflicts(
+            coords,
+            parsed_plugin,
+            "height_map",
+            reference.height_map.as_ref(),
+            plugin.height_map.as_ref(),
+            max_height_difference(reference.height_map.as_ref(), plugin.height_map.as_ref()),
+            settings,
+        ),
+        analyze_conflicts(
+            coords,
+            parsed_plugin,
+            "vertex_normals",
+            reference.vertex_normals.as_ref(),
+            plugin.vertex_normals.as_ref(),
+            None,
+            settings,
+        ),
+        analyze_conflicts(
+            coords,
+            parsed_plugin,
+            "world_map_data",
+            reference.world_map_data.as_ref(),
+            plugin.world_map_data.as_ref(),
+            None,
+            settings,
+        ),
+        analyze_conflicts(
+            coords,
+            parsed_plugin,
+            "vertex_colors",
+            reference.vertex_colors.as_ref(),
+            plugin.vertex_colors.as_ref(),
+            None,
+            settings,
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Returns a [CellConflict] for each value of each cell in the [LandmassDiff] `plugin` that
+/// conflicts with the [LandmassDiff] `reference`. Conflicts are flagged with the thresholds in
+/// the [PresetSettings]. No images are saved.
+pub fn analyze_landmass_conflicts(
+    reference: &LandmassDiff,
+    plugin: &LandmassDiff,
+    settings: &PresetSettings,
+) -> Vec<CellConflict> {
+    plugin
+        .sorted()
+        .flat_map(|(coords, land)| {
+            let merged_land = reference.land.get(coords).expect("safe");
+            analyze_landscape_conflicts(&plugin.plugin, merged_land, land, settings)
+        })
+        .collect()
+}
+
+/// Returns a [CellConflict] for each value of each cell in each of the `modded_landmasses` that
+/// conflicts with the land of the last earlier plugin to modify the cell, i.e. the land that the
+/// plugin overwrites if the plugins are not merged. Each cell of the `overwritten` [LandmassDiff]
+/// is replaced with the [LandscapeDiff] of the plugin, and lists every plugin that modified it in
+/// load order. Unlike [analyze_landmass_conflicts], no merged land is needed.
+pub fn analyze_overwrite_conflicts(
+    modded_landmasses: &[LandmassDiff],
+    overwritten: &mut LandmassDiff,
+    settings: &PresetSettings,
+) -> Vec<CellConflict> {
+    let mut conflicts = Vec::new();
+    for modded_landmass in modded_landmasses {
+        for (coords, land) in modded_landmass.sorted() {
+            let mut plugins = Vec::new();
+            if let Some(previous) = overwritten.land.get_mut(coords) {
+                conflicts.extend(analyze_landscape_conflicts(
+                    &modded_landmass.plugin,
+                    previous,
+                    land,
+                    settings,
+                ));
+                plugins = std::mem::take(&mut previous.plugins);
+            }
+
+            plugins.push((modded_landmass.plugin.clone(), land.modified_data()));
+            let mut land = land.clone();
+            land.plugins = plugins;
+            overwritten.land.insert(*coords, land);
+        }
+    }
+
+    conflicts
+}
+
+/// Returns the name of the image showing the vertices of the [CellConflict].
+pub fn diff_image_name(conflict: &CellConflict) -> String {
+    let [x, y] = conflict.coords;
+    format!(
+        "{}_{}_{}_DIFF_{}.png",
+        conflict.value, x, y, conflict.plugin
+    )
+}
+
+/// Returns the name of the image showing the merged value of the [CellConflict].
+pub fn merged_image_name(conflict: &CellConflict) -> String {
+    let [x, y] = conflict.coords;
+    format!("{}_{}_{}_MERGED.png", conflict.value, x, y)
+}
+
+/// Saves an image of the conflicts between the `lhs` [RelativeTerrainMap] and
+/// the `rhs` [RelativeTerrainMap], and an image of the `lhs` [RelativeTerrainMap].
+fn render_conflict<U: RelativeTo + ConflictResolver, const T: usize>(
+    artifacts: &ArtifactSink,
+    conflict: &CellConflict,
+    lhs: Option<&RelativeTerrainMap<U, T>>,
+    rhs: Option<&RelativeTerrainMap<U, T>>,
+    options: &ImageOptions,
+) where
+    RelativeTerrainMap<U, T>: SaveToImage,
+{
+    let (Some(lhs), Some(rhs)) = (lhs, rhs) else {
         return;
+    };
+
+    // TODO(dvd): #feature Use a gradient so that smaller conflicts can be seen.
+    let conflicts = classify_vertices(lhs, rhs);
+    let mut diff_img = ImageBuffer::new(T as u32, T as u32);
+    for coords in lhs.iter_grid() {
+        *diff_img.get_mut(coords) = match conflicts.get(coords) {
+            VertexConflict::Unmodified => Rgb::from([0, 0, 0]),
+            VertexConflict::Modified => Rgb::from([0, 255u8, 0]),
+            VertexConflict::Minor => Rgb::from([255u8, 255u8, 0]),
+            VertexConflict::Major => Rgb::from([255u8, 0, 0]),
+        };
     }
 
     {
-        let file_name = format!(
-            "{}_{}_{}_DIFF_{}.png",
-            value, coords.x, coords.y, plugin.name,
-        );
+        let file_name = diff_image_name(conflict);
+        let mut diff_img = resize_image::<T, _>(diff_img, options).into_rgb8();
+        if let Some(max_difference) = conflict.max_difference {
+            draw_caption(
+                &mut diff_img,
+                &format!("max {}", format_height(max_difference)),
+            );
+        }
 
-        let file_path: PathBuf = [
-            merged_lands_dir,
-            Path::new("Conflicts"),
-            &PathBuf::from(file_name),
-        ]
-        .iter()
-        .collect();
+        artifacts
+            .can_save(&file_name)
+            .and_then(|can_save| {
+                if !can_save {
+                    return Ok(());
+                }
 
-        save_resized_image::<T, _>(diff_img, &file_path, DEFAULT_SCALE_FACTOR)
+                artifacts.save_image(&file_name, &DynamicImage::from(diff_img))
+            })
             .map_err(|e| error!("{}", e.bold().bright_red()))
             .ok();
     }
 
     {
-        let file_name = format!("{}_{}_{}_MERGED.png", value, coords.x, coords.y);
-        let file_path: PathBuf = [
-            merged_lands_dir,
-            Path::new("Conflicts"),
-            &PathBuf::from(file_name),
-        ]
+        let file_name = merged_image_name(conflict);
+        lhs.save_to_image(artifacts, &file_name, options);
+    }
+}
+
+/// Draws the `caption` in the top left corner of the `img`, unless it is too small to fit.
+fn draw_caption(img: &mut RgbImage, caption: &str) {
+    const PADDING: u32 = 2;
+    if text_width(caption, 1) + 2 * PADDING > img.width() {
+        return;
+    }
+
+    draw_text(
+        img,
+        PADDING,
+        PADDING,
+        caption,
+        1,
+        Rgb::from([255u8, 255u8, 255u8]),
+    );
+}
+
+/// Saves images for each flagged [CellConflict] between the [LandmassDiff] `reference` and
+/// the [LandmassDiff] in `plugins` named by the [CellConflict].
+pub fn render_conflict_images(
+    artifacts: &ArtifactSink,
+    reference: &LandmassDiff,
+    plugins: &[LandmassDiff],
+    conflicts: &[CellConflict],
+    options: &ImageOptions,
+) {
+    for conflict in conflicts.iter().filter(|conflict| conflict.flagged) {
+        let coords = Vec2::from(conflict.coords);
+
+        let Some(plugin) = plugins
+            .iter()
+            .find(|plugin| plugin.plugin.name == conflict.plugin)
+        else {
+            continue;
+        };
+
+        let (Some(lhs), Some(rhs)) = (reference.land.get(&coords), plugin.land.get(&coords)) else {
+            continue;
+        };
+
+        match conflict.value.as_str() {
+            "height_map" => render_conflict(
+                artifacts,
+                conflict,
+                lhs.height_map.as_ref(),
+                rhs.height_map.as_ref(),
+                options,
+            ),
+            "vertex_normals" => render_conflict(
+                artifacts,
+                conflict,
+                lhs.vertex_normals.as_ref(),
+                rhs.vertex_normals.as_ref(),
+                options,
+            ),
+            "world_map_data" => render_conflict(
+                artifacts,
+                conflict,
+                lhs.world_map_data.as_ref(),
+                rhs.world_map_data.as_ref(),
+                options,
+            ),
+            "vertex_colors" => render_conflict(
+                artifacts,
+                conflict,
+                lhs.vertex_colors.as_ref(),
+                rhs.vertex_colors.as_ref(),
+                options,
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// Renders the `height_map` as a hillshade lit from the direction in [ImageOptions].
+/// The image has the same orientation as the other images of a cell.
+pub fn hillshade<const T: usize>(
+    height_map: &TerrainMap<i32, T>,
+    options: &ImageOptions,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let mut img = ImageBuffer::new(T as u32, T as u32);
+
+    let zenith = (90. - options.light_altitude).to_radians();
+    let azimuth = (360. - options.light_azimuth + 90.).to_radians();
+
+    for coords in height_map.iter_grid() {
+        // On the edge of the cell, reuse the nearest row or column.
+        let x0 = coords.x.saturating_sub(1);
+        let x1 = (coords.x + 1).min(T - 1);
+        let y0 = coords.y.saturating_sub(1);
+        let y1 = (coords.y + 1).min(T - 1);
+
+        let dx = (height_map.get(Index2D::new(x1, coords.y))
+            - height_map.get(Index2D::new(x0, coords.y))) as f32
+            / ((x1 - x0) as f32 * VERTEX_SPACING);
+
+        // The height map increases to the north, but a hillshade expects the slope to the south.
+        let dy = (height_map.get(Index2D::new(coords.x, y0))
+            - height_map.get(Index2D::new(coords.x, y1))) as f32
+            / ((y1 - y0) as f32 * VERTEX_SPACING);
+
+        let slope = dx.hypot(dy).atan();
+        let aspect = dy.atan2(-dx);
+
+        let shade =
+            zenith.cos() * slope.cos() + zenith.sin() * slope.sin() * (azimuth - aspect).cos();
+
+        *img.get_mut(coords) = Luma::from([(shade.max(0.) * 255.) as u8]);
+    }
+
+    img
+}
+
+/// Saves the hillshades of each height map in `previews` side-by-side, in order.
+pub fn save_strategy_previews<const T: usize>(
+    artifacts: &ArtifactSink,
+    coords: Vec2<i32>,
+    previews: &[(ConflictStrategy, TerrainMap<i32, T>)],
+    options: &ImageOptions,
+) -> Result<()> {
+    let file_name = format!("height_map_{}_{}_PREVIEW.png", coords.x, coords.y);
+
+    if previews.is_empty() || !artifacts.can_save(&file_name)? {
+        return Ok(());
+    }
+
+    let panel_size = (T * options.scale_factor) as u32;
+    let mut img = ImageBuffer::new(panel_size * previews.len() as u32, panel_size);
+
+    for (idx, (strategy, height_map)) in previews.iter().enumerate() {
+        let panel = resize_image::<T, _>(hillshade(height_map, options), options).into_luma8();
+
+        replace(&mut img, &panel, (idx as u32 * panel_size) as i64, 0);
+        info!("Panel {} | {:?}", idx + 1, strategy);
+    }
+
+    artifacts.save_image(&file_name, &DynamicImage::from(img))
+}
+
+/// The name of the image of the world map saved by [save_world_map].
+const WORLD_MAP_FILE_NAME: &str = "world_map.png";
+
+/// Returns the color of a `value` of the world map data. Values below 0 are water.
+fn world_map_color(value: i8) -> Rgb<u8> {
+    let lerp = |from: [u8; 3], to: [u8; 3], t: f32| {
+        Rgb::from([
+            (from[0] as f32 + (to[0] as f32 - from[0] as f32) * t) as u8,
+            (from[1] as f32 + (to[1] as f32 - from[1] as f32) * t) as u8,
+            (from[2] as f32 + (to[2] as f32 - from[2] as f32) * t) as u8,
+        ])
+    };
+
+    if value < 0 {
+        lerp([60, 90, 110], [20, 35, 50], value as f32 / i8::MIN as f32)
+    } else {
+        lerp(
+            [130, 110, 80],
+            [230, 215, 180],
+            value as f32 / i8::MAX as f32,
+        )
+    }
+}
+
+/// Saves an image of the world map data of every cell in the [LandmassDiff] with north at the top.
+/// Cells in the `outliers` are left out, so that a far away cell does not enlarge the image.
+pub fn save_world_map(
+    artifacts: &ArtifactSink,
+    merged: &LandmassDiff,
+    outliers: &HashSet<Vec2<i32>>,
+    options: &ImageOptions,
+) {
+    let cells = merged
+        .sorted()
+        .filter(|(coords, _)| !outliers.contains(*coords))
+        .filter_map(|(coords, land)| {
+            land.world_map_data
+                .as_ref()
+                .map(|world_map_data| (*coords, world_map_data.to_terrain()))
+        })
+        .collect_vec();
+
+    let Some(min_x) = cells.iter().map(|(coords, _)| coords.x).min() else {
+        return;
+    };
+
+    let max_x = cells
+        .iter()
+        .map(|(coords, _)| coords.x)
+        .max()
+        .expect("safe");
+    let min_y = cells
+        .iter()
+        .map(|(coords, _)| coords.y)
+        .min()
+        .expect("safe");
+    let max_y = cells
         .iter()
-        .collect();
-        lhs.save_to_image(&file_path);
+        .map(|(coords, _)| coords.y)
+        .max()
+        .expect("safe");
+
+    let width = (max_x - min_x + 1) as u32 * 9;
+    let height = (max_y - min_y + 1) as u32 * 9;
+    let mut img = ImageBuffer::from_pixel(width, height, Rgb::from([0u8, 0, 0]));
+
+    for (coords, world_map_data) in cells.iter() {
+        let cell_x = (coords.x - min_x) as u32 * 9;
+        let cell_y = (max_y - coords.y) as u32 * 9;
+        for local in world_map_data.iter_grid() {
+            let value = world_map_data.get(local) as i8;
+            img.put_pixel(
+                cell_x + local.x as u32,
+                cell_y + (8 - local.y) as u32,
+                world_map_color(value),
+            );
+        }
+    }
+
+    let result = artifacts
+        .can_save(WORLD_MAP_FILE_NAME)
+        .and_then(|can_save| {
+            if !can_save {
+                return Ok(());
+            }
+
+            let img = DynamicImage::from(img).resize_exact(
+                width * options.scale_factor as u32,
+                height * options.scale_factor as u32,
+                options.filter,
+            );
+
+            artifacts.save_image(WORLD_MAP_FILE_NAME, &img)
+        });
+
+    if let Err(e) = result {
+        error!("{}", e.bold().bright_red());
     }
 }
 
-/// Saves images of conflicts between [LandscapeDiff] `reference` and `plugin`.
-fn save_landscape_images(
-    merged_lands_dir: &Path,
-    parsed_plugin: &ParsedPlugin,
-    reference: &LandscapeDiff,
-    plugin: &LandscapeDiff,
+/// The name of the image saved by [save_new_land_map].
+const NEW_LAND_MAP_FILE_NAME: &str = "new_land.png";
+
+/// The number of pixels along each side of a cell in the image saved by [save_new_land_map].
+const NEW_LAND_CELL_PIXELS: u32 = 4;
+
+/// The color of cells with new LAND from a single plugin in the image saved by [save_new_land_map].
+const NEW_LAND_COLOR: Rgb<u8> = Rgb([80, 160, 80]);
+
+/// The color of cells in a [NewLandCollision] in the image saved by [save_new_land_map].
+const NEW_LAND_COLLISION_COLOR: Rgb<u8> = Rgb([220, 40, 40]);
+
+/// Saves a map of every cell in the [LandmassDiff] with north at the top. Cells of the masters are
+/// gray, cells with new LAND from a single plugin are green, and cells in a [NewLandCollision]
+/// are red. Cells in the `outliers` are left out, so that a far away cell does not enlarge the image.
+pub fn save_new_land_map(
+    artifacts: &ArtifactSink,
+    merged: &LandmassDiff,
+    outliers: &HashSet<Vec2<i32>>,
+    new_land: &[NewLandCell],
+    collisions: &[NewLandCollision],
+    options: &ImageOptions,
 ) {
-    save_image(
-        merged_lands_dir,
-        reference.coords,
-        parsed_plugin,
-        "height_map",
-        reference.height_map.as_ref(),
-        plugin.height_map.as_ref(),
-    );
-    save_image(
-        merged_lands_dir,
-        reference.coords,
-        parsed_plugin,
-        "vertex_normals",
-        reference.vertex_normals.as_ref(),
-        plugin.vertex_normals.as_ref(),
-    );
-    save_image(
-        merged_lands_dir,
-        reference.coords,
-        parsed_plugin,
-        "world_map_data",
-        reference.world_map_data.as_ref(),
-        plugin.world_map_data.as_ref(),
-    );
-    save_image(
-        merged_lands_dir,
-        reference.coords,
-        parsed_plugin,
-        "vertex_colors",
-        reference.vertex_colors.as_ref(),
-        plugin.vertex_colors.as_ref(),
-    );
+    let cells = merged
+        .land
+        .keys()
+        .filter(|coords| !outliers.contains(*coords))
+        .collect_vec();
+
+    let Some(min_x) = cells.iter().map(|coords| coords.x).min() else {
+        return;
+    };
+
+    let max_x = cells.iter().map(|coords| coords.x).max().expect("safe");
+    let min_y = cells.iter().map(|coords| coords.y).min().expect("safe");
+    let max_y = cells.iter().map(|coords| coords.y).max().expect("safe");
+
+    let new_land_cells = new_land
+        .iter()
+        .map(|cell| Vec2::from(cell.coords))
+        .collect::<HashSet<_>>();
+    let collision_cells = collisions
+        .iter()
+        .flat_map(|collision| collision.cells.iter())
+        .map(|coords| Vec2::from(*coords))
+        .collect::<HashSet<_>>();
+
+    let width = (max_x - min_x + 1) as u32 * NEW_LAND_CELL_PIXELS;
+    let height = (max_y - min_y + 1) as u32 * NEW_LAND_CELL_PIXELS;
+    let mut img = ImageBuffer::from_pixel(width, height, Rgb::from([0u8, 0, 0]));
+
+    for coords in cells {
+        let color = if collision_cells.contains(coords) {
+            NEW_LAND_COLLISION_COLOR
+        } else if new_land_cells.contains(coords) {
+            NEW_LAND_COLOR
+        } else {
+            MASTERS_COLOR
+        };
+
+        let cell_x = (coords.x - min_x) as u32 * NEW_LAND_CELL_PIXELS;
+        let cell_y = (max_y - coords.y) as u32 * NEW_LAND_CELL_PIXELS;
+        for (x, y) in (0..NEW_LAND_CELL_PIXELS).cartesian_product(0..NEW_LAND_CELL_PIXELS) {
+            img.put_pixel(cell_x + x, cell_y + y, color);
+        }
+    }
+
+    let result = artifacts
+        .can_save(NEW_LAND_MAP_FILE_NAME)
+        .and_then(|can_save| {
+            if !can_save {
+                return Ok(());
+            }
+
+            let img = DynamicImage::from(img).resize_exact(
+                width * options.scale_factor as u32,
+                height * options.scale_factor as u32,
+                options.filter,
+            );
+
+            artifacts.save_image(NEW_LAND_MAP_FILE_NAME, &img)
+        });
+
+    if let Err(e) = result {
+        error!("{}", e.bold().bright_red());
+    }
+}
+
+/// The color of the texture quads in a texture paint image that were not painted by any plugin.
+const MASTERS_COLOR: Rgb<u8> = Rgb([96, 96, 96]);
+
+/// The color of the hatching over conflicting texture quads in a texture paint image.
+const HATCH_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// The color of the sidebar of a texture paint image.
+const SIDEBAR_COLOR: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// The color of the text in the sidebar of a texture paint image.
+const TEXT_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// Returns the color of the plugin at `idx` in a texture paint image.
+/// Successive hues are a golden angle apart so that the colors of nearby plugins differ.
+fn plugin_color(idx: usize) -> Rgb<u8> {
+    let hue = (idx as f32 * 137.508) % 360.;
+    let value = 0.95;
+    let chroma = value * 0.65;
+    let x = chroma * (1. - ((hue / 60.) % 2. - 1.).abs());
+    let m = value - chroma;
+
+    let (r, g, b) = match (hue / 60.) as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+
+    Rgb([
+        ((r + m) * 255.) as u8,
+        ((g + m) * 255.) as u8,
+        ((b + m) * 255.) as u8,
+    ])
+}
+
+/// Returns `true` if the pixel at `x` and `y` is part of the hatching drawn with lines `spacing` apart.
+fn is_hatched(x: u32, y: u32, spacing: u32) -> bool {
+    (x + y) % spacing < (spacing / 4).max(1)
 }
 
-/// Saves images of conflicts between [LandmassDiff] `reference` and `plugin`.
-pub fn save_landmass_images(
-    merged_lands_dir: &Path,
+/// Saves an image of each cell where the texture quads painted by the `plugins` conflict.
+/// Each quad is colored by the plugin whose texture is in the [LandmassDiff] `reference`, and
+/// quads painted differently by more than one plugin are hatched. A sidebar maps each color
+/// to the name of a plugin.
+pub fn render_texture_paint_images(
+    artifacts: &ArtifactSink,
     reference: &LandmassDiff,
-    plugin: &LandmassDiff,
+    plugins: &[LandmassDiff],
+    options: &ImageOptions,
+) {
+    let cells = plugins
+        .iter()
+        .flat_map(|plugin| plugin.land.keys().copied())
+        .unique()
+        .sorted_by_key(|coords| (coords.x, coords.y));
+
+    for coords in cells {
+        let Some(merged) = reference
+            .land
+            .get(&coords)
+            .and_then(|land| land.texture_indices.as_ref())
+        else {
+            continue;
+        };
+
+        let painters = plugins
+            .iter()
+            .filter_map(|plugin| {
+                plugin
+                    .land
+                    .get(&coords)
+                    .and_then(|land| land.texture_indices.as_ref())
+                    .map(|texture_indices| (plugin, texture_indices))
+            })
+            .collect_vec();
+
+        if painters.len() < 2 {
+            continue;
+        }
+
+        // The index in `painters` of the plugin whose texture won each quad, if any.
+        let mut winners: TerrainMap<Option<usize>, 16> = [[None; 16]; 16];
+        let mut conflicts: TerrainMap<bool, 16> = [[false; 16]; 16];
+        for quad in merged.iter_grid() {
+            let painted = painters
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, texture_indices))| texture_indices.has_difference(quad))
+                .map(|(idx, (_, texture_indices))| (idx, texture_indices.get_value(quad)))
+                .collect_vec();
+
+            let value = merged.get_value(quad);
+            *winners.get_mut(quad) = painted
+                .iter()
+                .rev()
+                .find(|(_, painted_value)| *painted_value == value)
+                .map(|(idx, _)| *idx);
+            *conflicts.get_mut(quad) = painted.iter().map(|(_, value)| value).unique().count() > 1;
+        }
+
+        let num_conflicts = conflicts
+            .iter()
+            .flatten()
+            .filter(|conflict| **conflict)
+            .count();
+        if num_conflicts == 0 {
+            continue;
+        }
+
+        let file_name = format!("texture_indices_{}_{}_PAINT.png", coords.x, coords.y);
+        trace!(
+            "({:>4}, {:>4}) | {:<50} | {} conflicting texture quads",
+            coords.x,
+            coords.y,
+            file_name,
+            num_conflicts
+        );
+
+        let mut legend = Vec::new();
+        if winners.iter().flatten().any(|winner| winner.is_none()) {
+            legend.push(("Masters".to_string(), MASTERS_COLOR));
+        }
+
+        for (idx, (plugin, _)) in painters.iter().enumerate() {
+            legend.push((plugin.plugin.name.clone(), plugin_color(idx)));
+        }
+
+        let img = render_texture_paint(&winners, &conflicts, &legend, options);
+        let result = artifacts.can_save(&file_name).and_then(|can_save| {
+            if !can_save {
+                return Ok(());
+            }
+
+            artifacts.save_image(&file_name, &DynamicImage::from(img))
+        });
+
+        if let Err(e) = result {
+            error!("{}", e.bold().bright_red());
+        }
+    }
+}
+
+/// Renders the quads colored by the `winners` and hatched by the `conflicts` next to a sidebar
+/// with the name and color of each entry of the `legend`.
+fn render_texture_paint(
+    winners: &TerrainMap<Option<usize>, 16>,
+    conflicts: &TerrainMap<bool, 16>,
+    legend: &[(String, Rgb<u8>)],
+    options: &ImageOptions,
+) -> RgbImage {
+    let quad_size = 4 * options.scale_factor as u32;
+    let cell_size = 16 * quad_size;
+    let hatch_spacing = (quad_size / 2).max(2);
+
+    let text_scale = (options.scale_factor as u32 / 2).max(1);
+    let padding = 2 * text_scale;
+    let row_height = GLYPH_HEIGHT * text_scale + 2 * padding;
+    let swatch_size = GLYPH_HEIGHT * text_scale;
+
+    // The last row of the legend explains the hatching.
+    let conflict_label = "Conflict";
+    let num_rows = legend.len() as u32 + 1;
+    let text_width = legend
+        .iter()
+        .map(|(name, _)| text_width(name, text_scale))
+        .chain([text_width(conflict_label, text_scale)])
+        .max()
+        .expect("safe");
+
+    let sidebar_width = 3 * padding + swatch_size + text_width;
+    let height = cell_size.max(num_rows * row_height + padding);
+    let mut img = ImageBuffer::from_pixel(cell_size + sidebar_width, height, SIDEBAR_COLOR);
+
+    for quad in winners.iter_grid() {
+        let color = winners.get(quad).map_or(MASTERS_COLOR, plugin_color);
+
+        let is_conflict = conflicts.get(quad);
+        for dy in 0..quad_size {
+            for dx in 0..quad_size {
+                let x = quad.x as u32 * quad_size + dx;
+                let y = quad.y as u32 * quad_size + dy;
+                let color = if is_conflict && is_hatched(x, y, hatch_spacing) {
+                    HATCH_COLOR
+                } else {
+                    color
+                };
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    for (row, (name, color)) in legend
+        .iter()
+        .map(|(name, color)| (name.as_str(), Some(*color)))
+        .chain([(conflict_label, None)])
+        .enumerate()
+    {
+        let swatch_x = cell_size + padding;
+        let swatch_y = padding + row as u32 * row_height;
+        for dy in 0..swatch_size {
+            for dx in 0..swatch_size {
+                let (x, y) = (swatch_x + dx, swatch_y + dy);
+                let color = match color {
+                    Some(color) => color,
+                    None if is_hatched(x, y, hatch_spacing) => HATCH_COLOR,
+                    None => TEXT_COLOR,
+                };
+                img.put_pixel(x, y, color);
+            }
+        }
+
+        draw_text(
+            &mut img,
+            swatch_x + swatch_size + padding,
+            swatch_y,
+            name,
+            text_scale,
+            TEXT_COLOR,
+        );
+    }
+
+    img
+}
+
+/// Returns the name of the image showing which vertices of the `value` modified by the `plugin`
+/// survived in the cell at `coords`.
+pub fn survival_image_name(value: &str, coords: Vec2<i32>, plugin: &str) -> String {
+    format!(
+        "{}_{}_{}_SURVIVED_{}.png",
+        value, coords.x, coords.y, plugin
+    )
+}
+
+/// Saves an image of the vertices modified by the `plugin` [RelativeTerrainMap] that have the
+/// same value in the `merged` [RelativeTerrainMap]. Returns the number of vertices modified by
+/// the `plugin` and the number of those that survived.
+fn render_survival<U: RelativeTo, const T: usize>(
+    artifacts: &ArtifactSink,
+    file_name: &str,
+    merged: Option<&RelativeTerrainMap<U, T>>,
+    plugin: Option<&RelativeTerrainMap<U, T>>,
+    options: &ImageOptions,
+) -> (usize, usize) {
+    let (Some(merged), Some(plugin)) = (merged, plugin) else {
+        return (0, 0);
+    };
+
+    let mut num_modified = 0;
+    let mut num_survived = 0;
+    let mut img = ImageBuffer::new(T as u32, T as u32);
+    for coords in plugin.iter_grid() {
+        if !plugin.has_difference(coords) {
+            *img.get_mut(coords) = Rgb::from([0, 0, 0]);
+            continue;
+        }
+
+        num_modified += 1;
+        *img.get_mut(coords) = if merged.get_value(coords) == plugin.get_value(coords) {
+            num_survived += 1;
+            Rgb::from([0, 255u8, 0])
+        } else {
+            Rgb::from([255u8, 0, 0])
+        };
+    }
+
+    if num_modified > 0 {
+        save_resized_image::<T, _>(img, artifacts, file_name, options)
+            .map_err(|e| error!("{}", e.bold().bright_red()))
+            .ok();
+    }
+
+    (num_modified, num_survived)
+}
+
+/// Saves an image for each value of each cell modified by each of the `plugins`, showing which
+/// of the modified vertices have the same value in the final [LandmassDiff] `reference` and which
+/// were altered by later plugins or repairs. The number of surviving vertices of each plugin
+/// is logged.
+pub fn render_survival_images(
+    artifacts: &ArtifactSink,
+    reference: &LandmassDiff,
+    plugins: &[LandmassDiff],
+    options: &ImageOptions,
 ) {
-    for (coords, land) in plugin.sorted() {
-        let merged_land = reference.land.get(coords).expect("safe");
-        save_landscape_images(merged_lands_dir, &plugin.plugin, merged_land, land);
+    for plugin in plugins.iter() {
+        let mut num_modified = 0;
+        let mut num_survived = 0;
+
+        for (coords, land) in plugin.sorted() {
+            let Some(merged_land) = reference.land.get(coords) else {
+                continue;
+            };
+
+            let name = &plugin.plugin.name;
+            for (modified, survived) in [
+                render_survival(
+                    artifacts,
+                    &survival_image_name("height_map", *coords, name),
+                    merged_land.height_map.as_ref(),
+                    land.height_map.as_ref(),
+                    options,
+                ),
+                render_survival(
+                    artifacts,
+                    &survival_image_name("vertex_normals", *coords, name),
+                    merged_land.vertex_normals.as_ref(),
+                    land.vertex_normals.as_ref(),
+                    options,
+                ),
+                render_survival(
+                    artifacts,
+                    &survival_image_name("world_map_data", *coords, name),
+                    merged_land.world_map_data.as_ref(),
+                    land.world_map_data.as_ref(),
+                    options,
+                ),
+                render_survival(
+                    artifacts,
+                    &survival_image_name("vertex_colors", *coords, name),
+                    merged_land.vertex_colors.as_ref(),
+                    land.vertex_colors.as_ref(),
+                    options,
+                ),
+                render_survival(
+                    artifacts,
+                    &survival_image_name("texture_indices", *coords, name),
+                    merged_land.texture_indices.as_ref(),
+                    land.texture_indices.as_ref(),
+                    options,
+                ),
+            ] {
+                num_modified += modified;
+                num_survived += survived;
+            }
+        }
+
+        if num_modified > 0 {
+            info!(
+                "{:<50} | {} of {} modified vertices survived ({:.1}%)",
+                plugin.plugin.name,
+                num_survived,
+                num_modified,
+                100. * num_survived as f32 / num_modified as f32
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_values_are_normalized_without_nan() {
+        assert_eq!(normalize(5., 5., 5.), 0.);
+        assert_eq!(normalize(5., 0., 10.), 0.5);
     }
 }
@@ -0,0 +1,166 @@
+use crate::land::grid_access::SquareGridIterator;
+use crate::land::terrain_map::{TerrainMap, Vec3};
+use anyhow::{bail, Result};
+use std::fmt::Write as _;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// The file format of a numeric dump.
+pub enum NumericFormat {
+    /// A plain text netpbm image. Single component values are saved as `.pgm` and
+    /// values with multiple components are saved as `.ppm`.
+    Netpbm,
+    /// A `numpy` array saved as `.npy`.
+    Npy,
+}
+
+/// Types implementing [NumericValue] can be saved by [numeric_dump].
+pub trait NumericValue: Copy {
+    /// The `numpy` description of a single component.
+    const DESCR: &'static str;
+
+    /// The number of components in the value.
+    const COMPONENTS: usize;
+
+    /// Calls `f` with each component of the value.
+    fn for_each_component(&self, f: impl FnMut(i64));
+
+    /// Writes each component of the value as little-endian bytes.
+    fn write_le(&self, bytes: &mut Vec<u8>);
+}
+
+impl NumericValue for i32 {
+    const DESCR: &'static str = "<i4";
+    const COMPONENTS: usize = 1;
+
+    fn for_each_component(&self, mut f: impl FnMut(i64)) {
+        f(*self as i64);
+    }
+
+    fn write_le(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NumericValue for u8 {
+    const DESCR: &'static str = "|u1";
+    const COMPONENTS: usize = 1;
+
+    fn for_each_component(&self, mut f: impl FnMut(i64)) {
+        f(*self as i64);
+    }
+
+    fn write_le(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self);
+    }
+}
+
+impl NumericValue for Vec3<u8> {
+    const DESCR: &'static str = "|u1";
+    const COMPONENTS: usize = 3;
+
+    fn for_each_component(&self, mut f: impl FnMut(i64)) {
+        f(self.x as i64);
+        f(self.y as i64);
+        f(self.z as i64);
+    }
+
+    fn write_le(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.x, self.y, self.z]);
+    }
+}
+
+/// The largest value supported by a plain text netpbm image.
+const NETPBM_MAX_VALUE: i64 = u16::MAX as i64;
+
+/// Creates a plain text netpbm image of the `terrain`.
+/// Values are offset by the minimum value, which is recorded in a comment.
+fn to_netpbm<U: NumericValue, const T: usize>(terrain: &TerrainMap<U, T>) -> Result<String> {
+    let mut min_value = i64::MAX;
+    let mut max_value = i64::MIN;
+
+    for coords in terrain.iter_grid() {
+        terrain[coords.y][coords.x].for_each_component(|value| {
+            min_value = min_value.min(value);
+            max_value = max_value.max(value);
+        });
+    }
+
+    let range = (max_value - min_value).max(1);
+    if range > NETPBM_MAX_VALUE {
+        bail!(
+            "Values from {} to {} cannot be saved as a netpbm image",
+            min_value,
+            max_value
+        );
+    }
+
+    let magic = if U::COMPONENTS == 1 { "P2" } else { "P3" };
+
+    let mut text = String::new();
+    writeln!(text, "{}", magic).expect("safe");
+    writeln!(text, "# offset {}", min_value).expect("safe");
+    writeln!(text, "{} {}", T, T).expect("safe");
+    writeln!(text, "{}", range).expect("safe");
+
+    for row in terrain.iter() {
+        let mut values = Vec::with_capacity(T * U::COMPONENTS);
+        for value in row.iter() {
+            value.for_each_component(|value| values.push((value - min_value).to_string()));
+        }
+
+        writeln!(text, "{}", values.join(" ")).expect("safe");
+    }
+
+    Ok(text)
+}
+
+/// Creates a version 1.0 `.npy` file of the `terrain` with shape `(T, T)`, or
+/// `(T, T, COMPONENTS)` for values with multiple components.
+fn to_npy<U: NumericValue, const T: usize>(terrain: &TerrainMap<U, T>) -> Vec<u8> {
+    let shape = if U::COMPONENTS == 1 {
+        format!("({}, {})", T, T)
+    } else {
+        format!("({}, {}, {})", T, T, U::COMPONENTS)
+    };
+
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        U::DESCR,
+        shape
+    );
+
+    // The magic string, version, and header length take 10 bytes. The total must be
+    // a multiple of 64 bytes, including the newline at the end of the header.
+    let padding = 64 - (10 + header.len() + 1) % 64;
+    header.extend(std::iter::repeat(' ').take(padding % 64));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(10 + header.len() + T * T * U::COMPONENTS * 4);
+    bytes.extend_from_slice(b"\x93NUMPY");
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+
+    for row in terrain.iter() {
+        for value in row.iter() {
+            value.write_le(&mut bytes);
+        }
+    }
+
+    bytes
+}
+
+/// Creates a numeric dump of the `terrain` in the [NumericFormat].
+/// Returns the file extension to use and the contents of the file.
+pub fn numeric_dump<U: NumericValue, const T: usize>(
+    terrain: &TerrainMap<U, T>,
+    format: NumericFormat,
+) -> Result<(&'static str, Vec<u8>)> {
+    Ok(match format {
+        NumericFormat::Netpbm => {
+            let extension = if U::COMPONENTS == 1 { "pgm" } else { "ppm" };
+            (extension, to_netpbm(terrain)?.into_bytes())
+        }
+        NumericFormat::Npy => ("npy", to_npy(terrain)),
+    })
+}
@@ -1,7 +1,9 @@
 use crate::cli::SortOrder;
+use crate::io::file_provider::FileProvider;
 use crate::io::meta_schema::{MetaType, PluginMeta, VersionedPluginMeta};
-use crate::io::parsed_plugins::{meta_name, sort_plugins, ParsedPlugin, ParsedPlugins};
-use crate::land::conversions::convert_terrain_map;
+use crate::io::parsed_plugins::{is_esm, meta_name, sort_plugins, ParsedPlugin, ParsedPlugins};
+use crate::io::signature::PluginSignature;
+use crate::land::conversions::{convert_terrain_map, coordinates};
 use crate::land::height_map::calculate_vertex_heights_tes3;
 use crate::land::landscape_diff::LandscapeDiff;
 use crate::land::terrain_map::Vec3;
@@ -10,15 +12,12 @@ use crate::merge::cells::ModifiedCell;
 use crate::merge::relative_terrain_map::{recompute_vertex_normals, DefaultRelativeTerrainMap};
 use crate::{Landmass, LandmassDiff, Vec2};
 use anyhow::{anyhow, Context, Result};
-use filesize::file_real_size;
 use filetime::FileTime;
 use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
-use log::{debug, trace, warn};
+use log::{debug, info, trace, warn};
 use owo_colors::OwoColorize;
-use std::default::default;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::Arc;
 use tes3::esp::{
     FixedString, Header, Landscape, LandscapeFlags, Plugin, TES3Object, TextureIndices,
@@ -28,11 +27,13 @@ use time::format_description;
 
 /// Converts a [LandscapeDiff] to a [Landscape].
 /// The [RemappedTextures] is used to update any texture indices.
+/// The vertex normals are blended over `normal_blend_radius` vertices of each modified vertex.
 fn convert_landscape_diff_to_landscape(
     landscape: &LandscapeDiff,
     remapped_textures: &RemappedTextures,
+    normal_blend_radius: usize,
 ) -> Landscape {
-    let mut new_landscape: Landscape = default();
+    let mut new_landscape: Landscape = Default::default();
 
     assert!(!landscape.plugins.is_empty());
     for (plugin, modified_data) in landscape.plugins.iter() {
@@ -69,7 +70,7 @@ fn convert_landscape_diff_to_landscape(
 
     new_landscape.vertex_normals = Some(VertexNormals {
         data: Box::new(convert_terrain_map(
-            &recompute_vertex_normals(height_map, Some(vertex_normals)),
+            &recompute_vertex_normals(height_map, Some(vertex_normals), normal_blend_radius),
             Vec3::into,
         )),
     });
@@ -83,7 +84,7 @@ fn convert_landscape_diff_to_landscape(
     if let Some(texture_indices) = landscape.texture_indices.as_ref() {
         let mut texture_indices = texture_indices.to_terrain();
 
-        for idx in texture_indices.flatten_mut() {
+        for idx in texture_indices.iter_mut().flatten() {
             *idx = remapped_textures.remapped_index(*idx);
         }
 
@@ -103,14 +104,17 @@ fn convert_landscape_diff_to_landscape(
 
 /// Converts a [LandmassDiff] to a [Landmass].
 /// The [RemappedTextures] is used to update any texture indices.
+/// The vertex normals are blended over `normal_blend_radius` vertices of each modified vertex.
 pub fn convert_landmass_diff_to_landmass(
     landmass: &LandmassDiff,
     remapped_textures: &RemappedTextures,
+    normal_blend_radius: usize,
 ) -> Landmass {
     let mut new_landmass = Landmass::new(landmass.plugin.clone());
 
     for (coords, land) in landmass.sorted() {
-        let landscape = convert_landscape_diff_to_landscape(land, remapped_textures);
+        let landscape =
+            convert_landscape_diff_to_landscape(land, remapped_textures, normal_blend_radius);
         let last_plugin = land.plugins.last().expect("safe").clone().0;
         new_landmass.insert_land(*coords, &last_plugin, &landscape);
     }
@@ -120,14 +124,125 @@ pub fn convert_landmass_diff_to_landmass(
 
 /// Creates a master record for plugin `name` by appending the size
 /// of the file in bytes to the tuple `(name, file_size)`.
-fn to_master_record(data_files: &Path, name: String) -> (String, u64) {
-    let merged_filepath: PathBuf = [data_files, Path::new(&name)].iter().collect();
-    let file_size = file_real_size(merged_filepath).unwrap_or(0);
+fn to_master_record(files: &dyn FileProvider, data_files: &Path, name: String) -> (String, u64) {
+    let file_size = files.file_size(&data_files.join(&name)).unwrap_or(0);
     (name, file_size)
 }
 
+/// Checks that an existing plugin `output_name` in `output_file_dir` can be overwritten.
+/// On Windows, the plugin cannot be written while it is open in the game or the CS.
+pub fn check_output_writable(
+    files: &dyn FileProvider,
+    output_file_dir: &Path,
+    output_name: &str,
+) -> Result<()> {
+    let file_path = output_file_dir.join(output_name);
+    files.check_writable(&file_path).with_context(|| {
+        anyhow!(
+            "Unable to write plugin {}. Close Morrowind, the Construction Set, \
+             or any other program using it and try again",
+            file_path.to_string_lossy()
+        )
+    })?;
+
+    trace!("Plugin {} is writable", output_name);
+    Ok(())
+}
+
+/// Checks that the plugin `output_name` sorts after every plugin in `parsed_plugins` when it is
+/// saved to the `data_files` directory, so that it is merged last by the next run and loaded
+/// last by the game. If `adjust_mtime` is `true`, the last modified time of the plugin is moved
+/// after the last plugin instead of logging a warning.
+pub fn check_output_load_order(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    output_file_dir: &Path,
+    output_name: &str,
+    parsed_plugins: &ParsedPlugins,
+    adjust_mtime: bool,
+) -> Result<()> {
+    let is_same_dir = match (
+        files.canonicalize(data_files),
+        files.canonicalize(output_file_dir),
+    ) {
+        (Ok(data_files), Ok(output_file_dir)) => data_files == output_file_dir,
+        _ => false,
+    };
+
+    if !is_same_dir {
+        return Ok(());
+    }
+
+    let last_modified_time = |plugin_name: &str| {
+        files
+            .modified_time(&data_files.join(plugin_name))
+            .with_context(|| anyhow!("Unable to find plugin {}", plugin_name))
+    };
+
+    // ESMs always sort before ESPs, so an ESP only needs to sort after the other ESPs.
+    let candidates = if is_esm(output_name) {
+        &parsed_plugins.masters
+    } else {
+        &parsed_plugins.plugins
+    };
+
+    let mut last_plugin = None;
+    for plugin in candidates.iter().filter(|plugin| {
+        plugin.meta.meta_type != MetaType::MergedLands
+            && !plugin.name.eq_ignore_ascii_case(output_name)
+    }) {
+        let modified_time = last_modified_time(&plugin.name)?;
+        if last_plugin.map_or(true, |(_, last_time)| modified_time >= last_time) {
+            last_plugin = Some((plugin, modified_time));
+        }
+    }
+
+    let Some((last_plugin, last_time)) = last_plugin else {
+        return Ok(());
+    };
+
+    let output_time = last_modified_time(output_name)?;
+    if output_time > last_time {
+        trace!("{} loads after {}", output_name, last_plugin.name);
+        return Ok(());
+    }
+
+    if adjust_mtime {
+        let adjusted_time = FileTime::from_unix_time(last_time.unix_seconds() + 60, 0);
+        files
+            .set_modified_time(&data_files.join(output_name), adjusted_time)
+            .with_context(|| {
+                anyhow!("Unable to set last modified date on plugin {}", output_name)
+            })?;
+
+        info!(
+            "Moved last modified date of {} after {}",
+            output_name, last_plugin.name
+        );
+    } else {
+        warn!(
+            "{} {}",
+            format!(
+                "Plugin {} loads before {}",
+                output_name.bold(),
+                last_plugin.name.bold()
+            )
+            .yellow(),
+            "and should be moved to the end of the load order. Set `adjust_output_mtime = true` in the config file to do this automatically.".yellow()
+        );
+    }
+
+    Ok(())
+}
+
 /// Saves the [Landmass] with [KnownTextures].
+/// The [PluginSignature] of the inputs is saved in the description of the [Header].
+/// If `deterministic` is `true`, the time the plugin was generated is left out of the description.
+/// The bytes of the plugin are passed to `validate` before any file is written, so that an
+/// invalid plugin is never saved.
+#[allow(clippy::too_many_arguments)]
 pub fn save_plugin(
+    files: &dyn FileProvider,
     data_files: &Path,
     output_file_dir: &Path,
     output_name: &str,
@@ -135,8 +250,11 @@ pub fn save_plugin(
     landmass: &Landmass,
     known_textures: &KnownTextures,
     cells: Option<&HashMap<Vec2<i32>, ModifiedCell>>,
+    signature: &PluginSignature,
+    deterministic: bool,
+    validate: &dyn Fn(&[u8]) -> Result<()>,
 ) -> Result<()> {
-    ParsedPlugins::check_dir_exists(output_file_dir)
+    ParsedPlugins::check_dir_exists(files, output_file_dir)
         .with_context(|| anyhow!("Unable to save file {}", output_name))?;
 
     let mut plugin = Plugin::new();
@@ -146,11 +264,18 @@ pub fn save_plugin(
     let masters = {
         let mut dependencies = HashSet::new();
 
-        let mut add_dependency =
-            |dependency: &Arc<ParsedPlugin>| dependencies.insert(dependency.name.clone());
-
-        // Add plugins that contribute textures.
-        for texture in known_textures.sorted() {
+        let mut add_dependency = |dependency: &Arc<ParsedPlugin>| {
+            // Patches are not plugins, so they cannot be masters.
+            dependency.meta.meta_type != MetaType::LandDiff
+                && dependencies.insert(dependency.name.clone())
+        };
+
+        // Add plugins that contribute textures. Plugins that only change the file name of a
+        // texture are not needed, since the file name is copied to the output.
+        for texture in known_textures
+            .sorted()
+            .filter(|texture| !texture.plugin.is_ltex_only())
+        {
             add_dependency(&texture.plugin);
         }
 
@@ -169,6 +294,11 @@ pub fn save_plugin(
                     )
                 })?;
 
+                // Add plugins that define the region of cells.
+                if let Some(region_source) = cell.region_source.as_ref() {
+                    add_dependency(region_source);
+                }
+
                 let plugin = cell.plugins.last().expect("safe");
                 if add_dependency(plugin) {
                     trace!(
@@ -186,15 +316,16 @@ pub fn save_plugin(
             }
         }
 
-        let mut masters = dependencies.drain().collect_vec();
+        // Sort by name first, so that plugins with the same order are always saved the same way.
+        let mut masters = dependencies.drain().sorted().collect_vec();
 
-        sort_plugins(data_files, &mut masters, sort_order)
+        sort_plugins(files, data_files, &mut masters, sort_order)
             .with_context(|| anyhow!("Unknown load order for {} dependencies", output_name))?;
 
         Some(
             masters
                 .into_iter()
-                .map(|plugin| to_master_record(data_files, plugin))
+                .map(|plugin| to_master_record(files, data_files, plugin))
                 .collect_vec(),
         )
     };
@@ -203,24 +334,31 @@ pub fn save_plugin(
         trace!("Master  | {:>4} | {:<50} | {:>10}", idx, master.0, master.1);
     }
 
-    let time_format =
-        format_description::parse("[year]-[month]-[day] [hour]:[minute]").expect("safe");
-
-    let generated_time = time::OffsetDateTime::now_local()
-        .unwrap_or_else(|e| {
-            warn!(
-                "{}",
-                format!("Unknown local date time offset: {}", e.bold()).yellow()
-            );
-            time::OffsetDateTime::now_utc()
-        })
-        .format(&time_format)
-        .unwrap_or_else(|_| "unknown".into());
-
-    let description = format!(
-        "Merges landscape changes inside of cells. Place at end of load order. Generated at {}.",
-        generated_time
-    );
+    let description = if deterministic {
+        format!(
+            "Merges landscape changes inside of cells. Place at end of load order. {}",
+            signature
+        )
+    } else {
+        let time_format =
+            format_description::parse("[year]-[month]-[day] [hour]:[minute]").expect("safe");
+
+        let generated_time = time::OffsetDateTime::now_local()
+            .unwrap_or_else(|e| {
+                warn!(
+                    "{}",
+                    format!("Unknown local date time offset: {}", e.bold()).yellow()
+                );
+                time::OffsetDateTime::now_utc()
+            })
+            .format(&time_format)
+            .unwrap_or_else(|_| "unknown".into());
+
+        format!(
+            "Merges landscape changes inside of cells. Place at end of load order. Generated at {}. {}",
+            generated_time, signature
+        )
+    };
 
     let author = "Merged Lands by DVD".to_string();
 
@@ -228,7 +366,7 @@ pub fn save_plugin(
         author: FixedString(author),
         description: FixedString(description.clone()),
         masters,
-        ..default()
+        ..Default::default()
     };
 
     debug!("Saving 1 TES3 record");
@@ -262,37 +400,110 @@ pub fn save_plugin(
         plugin.objects.push(TES3Object::Landscape(land.clone()));
     }
 
+    let bytes = plugin
+        .save_bytes()
+        .with_context(|| anyhow!("Unable to save plugin {}", output_name))?;
+    validate(&bytes)?;
+
     let meta_name = meta_name(output_name);
-    let merged_meta: PathBuf = [output_file_dir, Path::new(&meta_name)].iter().collect();
 
-    let meta = VersionedPluginMeta::V0(PluginMeta {
+    let meta = VersionedPluginMeta::V0(Box::new(PluginMeta {
         meta_type: MetaType::MergedLands,
+        profile: None,
+        load_after: None,
+        height_scale: None,
+        height_offset: None,
         height_map: Default::default(),
         vertex_colors: Default::default(),
         texture_indices: Default::default(),
         world_map_data: Default::default(),
-    });
+        texture_authority: Vec::new(),
+    }));
 
     trace!("Saving meta file {}", meta_name);
-    fs::write(merged_meta, toml::to_string(&meta).expect("safe"))
+    files
+        .write(
+            &output_file_dir.join(&meta_name),
+            toml::to_string(&meta).expect("safe").as_bytes(),
+        )
         .with_context(|| anyhow!("Unable to save plugin meta {}", meta_name))?;
 
-    let merged_filepath: PathBuf = [output_file_dir, Path::new(output_name)].iter().collect();
-    let last_modified_time = merged_filepath
-        .metadata()
-        .map(|metadata| FileTime::from_last_modification_time(&metadata))
+    let merged_filepath = output_file_dir.join(output_name);
+    match PluginSignature::read(files, &merged_filepath) {
+        Some(previous) if previous == *signature => {
+            info!("{} was previously produced by the same inputs", output_name)
+        }
+        Some(previous) => debug!(
+            "{} was previously produced by different inputs: {}",
+            output_name, previous
+        ),
+        None => trace!("{} has no previous signature", output_name),
+    }
+
+    let last_modified_time = files
+        .modified_time(&merged_filepath)
         .unwrap_or_else(|_| FileTime::now());
 
     trace!("Saving file {}", output_name);
-    plugin
-        .save_path(&merged_filepath)
+    files
+        .write(&merged_filepath, &bytes)
         .with_context(|| anyhow!("Unable to save plugin {}", output_name))?;
 
-    trace!(" - Description: {}", description);
-
     trace!("Updating last modified time on {}", output_name);
-    filetime::set_file_mtime(merged_filepath, last_modified_time)
+    files
+        .set_modified_time(&merged_filepath, last_modified_time)
         .with_context(|| anyhow!("Unable to set last modified date on plugin {}", output_name))?;
 
+    trace!(" - Description: {}", description);
+
     Ok(())
 }
+
+/// Saves a copy of the plugin `plugin_name` from `data_files` as `output_name` after removing
+/// any LAND records in `unmodified_land` and any exterior CELL records in `unmodified_cells`.
+pub fn save_cleaned_plugin(
+    files: &dyn FileProvider,
+    data_files: &Path,
+    output_file_dir: &Path,
+    plugin_name: &str,
+    output_name: &str,
+    unmodified_land: &HashSet<Vec2<i32>>,
+    unmodified_cells: &HashSet<Vec2<i32>>,
+) -> Result<()> {
+    ParsedPlugins::check_dir_exists(files, output_file_dir)
+        .with_context(|| anyhow!("Unable to save file {}", output_name))?;
+
+    let bytes = files
+        .read(&data_files.join(plugin_name))
+        .with_context(|| anyhow!("Unable to read plugin {}", plugin_name))?;
+
+    let mut plugin = Plugin::new();
+    plugin
+        .load_bytes(&bytes)
+        .with_context(|| anyhow!("Failed to load records from plugin {}", plugin_name))?;
+
+    let num_objects = plugin.objects.len();
+
+    plugin.objects.retain(|object| match object {
+        TES3Object::Landscape(land) => !unmodified_land.contains(&coordinates(land)),
+        TES3Object::Cell(cell) => {
+            let coords = Vec2::new(cell.data.grid.0, cell.data.grid.1);
+            !(cell.is_exterior() && unmodified_cells.contains(&coords))
+        }
+        _ => true,
+    });
+
+    debug!(
+        "Removing {} records from {}",
+        num_objects - plugin.objects.len(),
+        plugin_name
+    );
+
+    trace!("Saving file {}", output_name);
+    let bytes = plugin
+        .save_bytes()
+        .with_context(|| anyhow!("Unable to save plugin {}", output_name))?;
+    files
+        .write(&output_file_dir.join(output_name), &bytes)
+        .with_context(|| anyhow!("Unable to save plugin {}", output_name))
+}
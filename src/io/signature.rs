@@ -0,0 +1,93 @@
+use crate::io::file_provider::FileProvider;
+use crate::io::parsed_plugins::ParsedPlugins;
+use log::trace;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use tes3::esp::{Header, Plugin, TES3Object};
+
+/// The text at the start of a [PluginSignature].
+const SIGNATURE_PREFIX: &str = "[merged_lands v";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A compact signature of the tool version and input plugins that produced a plugin.
+/// The signature is saved at the end of the description in the [Header] of the plugin.
+pub struct PluginSignature {
+    /// The version of the tool.
+    pub version: String,
+    /// The number of input plugins, including masters.
+    pub num_inputs: usize,
+    /// The CRC32 of the name and CRC32 of each input plugin, in load order.
+    pub crc32: u32,
+}
+
+impl PluginSignature {
+    /// Creates a [PluginSignature] for the `parsed_plugins`, using the CRC32 of each plugin
+    /// calculated when it was parsed.
+    pub fn from_plugins(parsed_plugins: &ParsedPlugins) -> Self {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut num_inputs = 0;
+
+        for plugin in parsed_plugins
+            .masters
+            .iter()
+            .chain(parsed_plugins.plugins.iter())
+        {
+            let crc32 = plugin.crc32.unwrap_or_default();
+            trace!("Input   | {:<50} | {:08x}", plugin.name, crc32);
+
+            hasher.update(plugin.name.to_lowercase().as_bytes());
+            hasher.update(&crc32.to_le_bytes());
+            num_inputs += 1;
+        }
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            num_inputs,
+            crc32: hasher.finalize(),
+        }
+    }
+
+    /// Parses the [PluginSignature] at the end of the `description` of a [Header], if any.
+    pub fn parse(description: &str) -> Option<Self> {
+        let start = description.rfind(SIGNATURE_PREFIX)?;
+        let signature = description[start + SIGNATURE_PREFIX.len()..]
+            .trim_end_matches('\0')
+            .strip_suffix(']')?;
+
+        let mut fields = signature.split(' ');
+        let version = fields.next()?.to_string();
+        let num_inputs = fields.next()?.strip_prefix("inputs=")?.parse().ok()?;
+        let crc32 = u32::from_str_radix(fields.next()?.strip_prefix("crc32=")?, 16).ok()?;
+
+        Some(Self {
+            version,
+            num_inputs,
+            crc32,
+        })
+    }
+
+    /// Reads the [PluginSignature] from the [Header] of the plugin at `file_path`, if any.
+    pub fn read(files: &dyn FileProvider, file_path: &Path) -> Option<Self> {
+        let bytes = files.read(file_path).ok()?;
+
+        let mut plugin = Plugin::new();
+        plugin
+            .load_bytes_filtered(&bytes, |tag| matches!(&tag, Header::TAG))
+            .ok()?;
+
+        plugin.objects.iter().find_map(|object| match object {
+            TES3Object::Header(header) => Self::parse(&header.description.0),
+            _ => None,
+        })
+    }
+}
+
+impl Display for PluginSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} inputs={} crc32={:08x}]",
+            SIGNATURE_PREFIX, self.version, self.num_inputs, self.crc32
+        )
+    }
+}
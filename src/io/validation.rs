@@ -0,0 +1,136 @@
+use crate::io::report::{ClampedHeights, HeightEncoding};
+use crate::land::grid_access::{GridAccessor2D, SquareGridIterator};
+use crate::land::height_map::{
+    encoded_height_map, format_height, height_map_encoding_loss, try_calculate_height_map,
+    world_units_to_meters,
+};
+use crate::land::terrain_map::{TerrainMap, Vec2};
+use crate::merge::relative_terrain_map::DefaultRelativeTerrainMap;
+use crate::LandmassDiff;
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use log::{info, trace, warn};
+use owo_colors::OwoColorize;
+use tes3::esp::{Landscape, Plugin};
+
+/// The largest difference in world units between a saved height and the expected height,
+/// since heights are saved in steps of 8 world units.
+pub const HEIGHT_TOLERANCE: i32 = 8;
+
+/// A cell at `coords` of a saved plugin with a height `max_difference` world units from the
+/// height expected from saving the merged height, e.g. because the LAND record was not saved
+/// from the merged land.
+pub struct HeightMismatch {
+    pub coords: Vec2<i32>,
+    pub max_difference: i32,
+}
+
+/// Returns the largest difference between a height of the `actual` [TerrainMap] and the same
+/// height of the `expected` [TerrainMap].
+pub fn max_height_difference(actual: &TerrainMap<i32, 65>, expected: &TerrainMap<i32, 65>) -> i32 {
+    actual
+        .iter_grid()
+        .map(|coords| (actual.get(coords) - expected.get(coords)).abs())
+        .max()
+        .unwrap_or_default()
+}
+
+/// Loads the `bytes` of a saved plugin the same way as the game and calculates the heights of
+/// each LAND record, then compares them with the heights of the `merged` [LandmassDiff] after
+/// they are encoded as [tes3::esp::VertexHeights]. Gradients too steep to save are expected to
+/// be clamped, since these are listed in the [HeightEncoding] of the report instead.
+/// Returns a [HeightMismatch] for each cell with a height further than [HEIGHT_TOLERANCE] from
+/// the expected height, ordered by `x` and `y` coordinates.
+pub fn validate_saved_heights(bytes: &[u8], merged: &LandmassDiff) -> Result<Vec<HeightMismatch>> {
+    let mut plugin = Plugin::new();
+    plugin
+        .load_bytes(bytes)
+        .with_context(|| anyhow!("Unable to load saved plugin for validation"))?;
+
+    let mut mismatches = Vec::new();
+    for land in plugin
+        .objects_of_type::<Landscape>()
+        .sorted_by_key(|land| land.grid)
+    {
+        let coords = Vec2::new(land.grid.0, land.grid.1);
+        let Some(merged_land) = merged.land.get(&coords) else {
+            trace!(
+                "({:>4}, {:>4}) | not in the merged land, skipping validation",
+                coords.x,
+                coords.y
+            );
+            continue;
+        };
+
+        let expected = encoded_height_map(
+            &merged_land
+                .height_map
+                .as_ref()
+                .unwrap_or(&DefaultRelativeTerrainMap::HEIGHT_MAP)
+                .to_terrain(),
+        );
+
+        let max_difference = match try_calculate_height_map(land) {
+            Some(actual) => max_height_difference(&actual, &expected),
+            None => i32::MAX,
+        };
+
+        if max_difference > HEIGHT_TOLERANCE {
+            mismatches.push(HeightMismatch {
+                coords,
+                max_difference,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Returns the [HeightEncoding] of each modified cell of the `merged` [LandmassDiff], i.e. each
+/// cell that is saved to the output, and logs a summary.
+pub fn summarize_height_encoding(merged: &LandmassDiff) -> HeightEncoding {
+    let mut encoding = HeightEncoding::default();
+    for (coords, land) in merged.sorted().filter(|(_, land)| land.is_modified()) {
+        let height_map = land
+            .height_map
+            .as_ref()
+            .unwrap_or(&DefaultRelativeTerrainMap::HEIGHT_MAP)
+            .to_terrain();
+
+        let Some(loss) = height_map_encoding_loss(&height_map) else {
+            encoding.num_lossless += 1;
+            continue;
+        };
+
+        warn!(
+            "{}",
+            format!(
+                "({:>4}, {:>4}) | {} height gradients are too steep to save, moving {} vertices by up to {}",
+                coords.x,
+                coords.y,
+                loss.num_clamped_gradients,
+                loss.num_affected_vertices,
+                format_height(loss.max_difference)
+            )
+            .yellow()
+        );
+
+        encoding.num_affected_vertices += loss.num_affected_vertices;
+        encoding.clamped.push(ClampedHeights {
+            coords: (*coords).into(),
+            num_clamped_gradients: loss.num_clamped_gradients,
+            num_affected_vertices: loss.num_affected_vertices,
+            max_difference: loss.max_difference,
+            max_difference_meters: world_units_to_meters(loss.max_difference),
+        });
+    }
+
+    info!(
+        "Heights of {} cells are saved without loss and {} cells are clamped, affecting {} vertices",
+        encoding.num_lossless,
+        encoding.clamped.len(),
+        encoding.num_affected_vertices
+    );
+
+    encoding
+}
@@ -1,7 +1,6 @@
 use crate::land::grid_access::{GridAccessor2D, SquareGridIterator};
-use crate::land::terrain_map::{TerrainMap, Vec2, Vec3};
+use crate::land::terrain_map::{LandData, TerrainMap, Vec2, Vec3};
 use crate::land::textures::IndexVTEX;
-use std::default::default;
 use tes3::esp::{Landscape, LandscapeFlags};
 
 /// Converts between [TerrainMap] using the provided `conversion` function.
@@ -9,7 +8,7 @@ pub fn convert_terrain_map<I: Copy, U: Copy + Default, const T: usize>(
     original: &TerrainMap<I, T>,
     conversion: fn(I) -> U,
 ) -> TerrainMap<U, T> {
-    let mut new = [[default(); T]; T];
+    let mut new = [[Default::default(); T]; T];
 
     for coords in original.iter_grid() {
         *new.get_mut(coords) = conversion(original.get(coords));
@@ -51,6 +50,18 @@ pub fn landscape_flags(land: &Landscape) -> LandscapeFlags {
     land.landscape_flags
 }
 
+/// Returns the [LandData] included with a [Landscape], as converted from its [LandscapeFlags].
+/// World map data is also included whenever it is present, so that it is not lost from
+/// LAND records with unusual [LandscapeFlags].
+pub fn included_data(land: &Landscape) -> LandData {
+    let mut included_data: LandData = landscape_flags(land).into();
+    if land.world_map_data.is_some() {
+        included_data |= LandData::WORLD_MAP;
+    }
+
+    included_data
+}
+
 /// Access the `grid` location of a [Landscape] as a [Vec2].
 pub fn coordinates(land: &Landscape) -> Vec2<i32> {
     let coords = land.grid;
@@ -1,49 +1,73 @@
-use crate::land::conversions::landscape_flags;
+use crate::io::meta_schema::PluginMeta;
+use crate::land::conversions::included_data;
 use crate::land::grid_access::{GridAccessor2D, Index2D, SquareGridIterator};
-use crate::land::terrain_map::{TerrainMap, Vec3};
+use crate::land::terrain_map::{LandData, TerrainMap, Vec3};
+use itertools::Itertools;
 use log::warn;
 use owo_colors::OwoColorize;
-use std::default::default;
-use tes3::esp::{Landscape, LandscapeFlags, VertexHeights};
+use tes3::esp::{Landscape, VertexHeights};
 
 const CELL_SIZE: usize = 65;
 const HEIGHT_MAP_SCALE_FACTOR: i32 = 8;
 const HEIGHT_MAP_SCALE_FACTOR_F32: f32 = HEIGHT_MAP_SCALE_FACTOR as f32;
 
-/// Limits `gradient` to the range of a [i8].
-fn truncate_gradient(gradient: &mut i32) {
+/// The length in meters of a world unit, since 64 world units are about 0.9 meters.
+pub const METERS_PER_WORLD_UNIT: f32 = 0.9 / 64.;
+
+/// Returns the height in world units of `steps` steps of [VertexHeights].
+pub fn vertex_height_steps_to_world_units(steps: i32) -> i32 {
+    steps * HEIGHT_MAP_SCALE_FACTOR
+}
+
+/// Returns the length in meters of `units` world units.
+pub fn world_units_to_meters(units: i32) -> f32 {
+    units as f32 * METERS_PER_WORLD_UNIT
+}
+
+/// Formats a height of `units` world units with its length in meters, e.g. `296 units (~4.2 m)`.
+pub fn format_height(units: i32) -> String {
+    format!("{} units (~{:.1} m)", units, world_units_to_meters(units))
+}
+
+/// Limits `gradient` to the range of a [i8]. Returns `true` if the `gradient` was out of range.
+fn truncate_gradient(gradient: &mut i32) -> bool {
+    let original = *gradient;
     if *gradient > i8::MAX as i32 {
         *gradient = i8::MAX as i32;
     } else if *gradient < i8::MIN as i32 {
         *gradient = i8::MIN as i32;
     }
+
+    *gradient != original
 }
 
 /// Calculates the vertex heights for the [TerrainMap] as a [TerrainMap] representing
 /// a set of x-y differences and an [f32] for an offset. This is the tuple form of
-/// the [VertexHeights] for any array size `T`.
+/// the [VertexHeights] for any array size `T`. Also returns the number of differences
+/// that were truncated to fit in a [i8].
 fn calculate_vertex_heights<const T: usize>(
     height_map: &TerrainMap<i32, T>,
-) -> (f32, TerrainMap<i8, T>) {
+) -> (f32, TerrainMap<i8, T>, usize) {
     let mut terrain32 = [[0i32; T]; T];
-    let mut terrain = [[default(); T]; T];
+    let mut terrain = [[Default::default(); T]; T];
+    let mut num_truncated = 0;
 
-    let get_pixel = |y: usize, x: usize| (height_map[y][x] / HEIGHT_MAP_SCALE_FACTOR) as i32;
+    let get_pixel = |y: usize, x: usize| height_map[y][x] / HEIGHT_MAP_SCALE_FACTOR;
     let offset = get_pixel(0, 0) as f32;
 
     let get_pixel_with_offset = |y, x| get_pixel(y, x) - offset as i32;
 
     // Compute the first column.
-    for y in 1..T {
-        terrain32[y][0] = get_pixel_with_offset(y, 0) - get_pixel_with_offset(y - 1, 0);
-        truncate_gradient(&mut terrain32[y][0]);
+    for (y, row) in terrain32.iter_mut().enumerate().skip(1) {
+        row[0] = get_pixel_with_offset(y, 0) - get_pixel_with_offset(y - 1, 0);
+        num_truncated += truncate_gradient(&mut row[0]) as usize;
     }
 
     // Compute each row.
-    for y in 0..T {
-        for x in 1..T {
-            terrain32[y][x] = get_pixel_with_offset(y, x) - get_pixel_with_offset(y, x - 1);
-            truncate_gradient(&mut terrain32[y][x]);
+    for (y, row) in terrain32.iter_mut().enumerate() {
+        for (x, gradient) in row.iter_mut().enumerate().skip(1) {
+            *gradient = get_pixel_with_offset(y, x) - get_pixel_with_offset(y, x - 1);
+            num_truncated += truncate_gradient(gradient) as usize;
         }
     }
 
@@ -51,18 +75,62 @@ fn calculate_vertex_heights<const T: usize>(
         *terrain.get_mut(coords) = terrain32.get(coords) as i8;
     }
 
-    (offset, terrain)
+    (offset, terrain, num_truncated)
 }
 
 /// Creates [VertexHeights] from the `height_map` [TerrainMap].
 pub fn calculate_vertex_heights_tes3(height_map: &TerrainMap<i32, CELL_SIZE>) -> VertexHeights {
-    let (offset, terrain) = calculate_vertex_heights(height_map);
+    let (offset, terrain, _) = calculate_vertex_heights(height_map);
     VertexHeights {
         offset,
         data: Box::new(terrain),
     }
 }
 
+/// Saves the `height_map` [TerrainMap] as [VertexHeights] and returns the heights that the game
+/// loads from them. Gradients too steep to save are clamped, like in the saved plugin.
+pub fn encoded_height_map(height_map: &TerrainMap<i32, CELL_SIZE>) -> TerrainMap<i32, CELL_SIZE> {
+    calculate_height_map(&calculate_vertex_heights_tes3(height_map))
+}
+
+/// The heights lost when a height map is saved as [VertexHeights].
+pub struct EncodingLoss {
+    /// The number of differences between neighboring vertices too steep to fit in a [i8].
+    pub num_clamped_gradients: usize,
+    /// The number of vertices saved at a different height, since each difference is
+    /// added to every later vertex of the row or column.
+    pub num_affected_vertices: usize,
+    /// The largest difference in world units between a saved height and the `height_map`.
+    pub max_difference: i32,
+}
+
+/// Saves the `height_map` [TerrainMap] as [VertexHeights] and loads it again the same way as
+/// the game. Returns the [EncodingLoss], or [None] if the heights are saved without loss.
+/// Heights are saved in steps of 8 world units, so smaller differences are not a loss.
+pub fn height_map_encoding_loss(height_map: &TerrainMap<i32, CELL_SIZE>) -> Option<EncodingLoss> {
+    let (offset, terrain, num_clamped_gradients) = calculate_vertex_heights(height_map);
+    if num_clamped_gradients == 0 {
+        return None;
+    }
+
+    let saved = calculate_height_map::<CELL_SIZE>(&VertexHeights {
+        offset,
+        data: Box::new(terrain),
+    });
+
+    let differences = height_map
+        .iter_grid()
+        .map(|coords| (saved.get(coords) - height_map.get(coords)).abs())
+        .filter(|difference| *difference >= HEIGHT_MAP_SCALE_FACTOR)
+        .collect_vec();
+
+    Some(EncodingLoss {
+        num_clamped_gradients,
+        num_affected_vertices: differences.len(),
+        max_difference: differences.into_iter().max().unwrap_or_default(),
+    })
+}
+
 /// Creates [TerrainMap] from the `vertex_heights` [VertexHeights].
 fn calculate_height_map<const T: usize>(vertex_heights: &VertexHeights) -> TerrainMap<i32, T> {
     let mut grid_height = [[0; T]; T];
@@ -79,12 +147,50 @@ fn calculate_height_map<const T: usize>(vertex_heights: &VertexHeights) -> Terra
     }
 
     for coords in grid_height.iter_grid() {
-        *grid_height.get_mut(coords) *= HEIGHT_MAP_SCALE_FACTOR;
+        *grid_height.get_mut(coords) = vertex_height_steps_to_world_units(grid_height.get(coords));
     }
 
     grid_height
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// A correction of the heights of a plugin applied before it is compared to its masters,
+/// e.g. for terrain imported from a tool with a different scale or datum.
+pub struct HeightTransform {
+    /// The factor each height is multiplied by.
+    pub scale: f32,
+    /// The offset in world units added to each height after it is scaled.
+    pub offset: i32,
+}
+
+impl HeightTransform {
+    /// Returns the [HeightTransform] of the [PluginMeta], if it sets `height_scale` or `height_offset`.
+    pub fn from_meta(meta: &PluginMeta) -> Option<Self> {
+        if meta.height_scale.is_none() && meta.height_offset.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            scale: meta.height_scale.unwrap_or(1.),
+            offset: meta.height_offset.unwrap_or(0),
+        })
+    }
+
+    /// Applies the [HeightTransform] to each height of the `height_map` [TerrainMap], rounding to
+    /// the precision of [VertexHeights] so that the result is saved without loss.
+    pub fn apply(&self, height_map: &TerrainMap<i32, CELL_SIZE>) -> TerrainMap<i32, CELL_SIZE> {
+        let mut transformed = *height_map;
+
+        for coords in transformed.iter_grid() {
+            let height = height_map.get(coords) as f32 * self.scale + self.offset as f32;
+            *transformed.get_mut(coords) =
+                (height / HEIGHT_MAP_SCALE_FACTOR_F32).round() as i32 * HEIGHT_MAP_SCALE_FACTOR;
+        }
+
+        transformed
+    }
+}
+
 /// Calculates the vertex normals for the [TerrainMap].
 pub fn calculate_vertex_normals_map<const T: usize>(
     height_map: &TerrainMap<i32, T>,
@@ -106,7 +212,7 @@ pub fn calculate_vertex_normals_map<const T: usize>(
         Index2D::new(x, y)
     }
 
-    let mut terrain = [[default(); T]; T];
+    let mut terrain = [[Default::default(); T]; T];
 
     for coords in height_map.iter_grid() {
         let fixed_coords = fix_coords::<T>(coords);
@@ -118,7 +224,7 @@ pub fn calculate_vertex_normals_map<const T: usize>(
         let v1 = Vec3 {
             x: 128f32 / HEIGHT_MAP_SCALE_FACTOR_F32,
             y: 0f32,
-            z: (x1 - h) as f32,
+            z: x1 - h,
         };
 
         let coords_y1 = Index2D::new(fixed_coords.x, fixed_coords.y + 1);
@@ -126,7 +232,7 @@ pub fn calculate_vertex_normals_map<const T: usize>(
         let v2 = Vec3 {
             x: 0f32,
             y: 128f32 / HEIGHT_MAP_SCALE_FACTOR_F32,
-            z: (y1 - h) as f32,
+            z: y1 - h,
         };
 
         let mut normal = Vec3 {
@@ -151,18 +257,19 @@ pub fn calculate_vertex_normals_map<const T: usize>(
 /// Calculate a [TerrainMap] of the [Landscape]'s height map by converting the
 /// [VertexHeights] if present.
 pub fn try_calculate_height_map(land: &Landscape) -> Option<TerrainMap<i32, 65>> {
-    let included_data = landscape_flags(land);
-    if !included_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS) {
+    if !included_data(land).contains(LandData::VERTEX_HEIGHTS) {
         return None;
     }
 
     let Some(grid_height) = land.vertex_heights.as_ref().map(calculate_height_map) else {
-        warn!("{}", format!(
-            "({:>4}, {:>4}) {:<15} | missing vertex_heights",
-            land.grid.0,
-            land.grid.1,
-            "height_map"
-        ).yellow());
+        warn!(
+            "{}",
+            format!(
+                "({:>4}, {:>4}) {:<15} | missing vertex_heights",
+                land.grid.0, land.grid.1, "height_map"
+            )
+            .yellow()
+        );
         return None;
     };
 
@@ -1,16 +1,15 @@
 use crate::land::conversions::{
-    coordinates, landscape_flags, texture_indices, vertex_colors, vertex_normals, world_map_data,
+    coordinates, included_data, texture_indices, vertex_colors, vertex_normals, world_map_data,
 };
-use crate::land::grid_access::{GridAccessor2D, SquareGridIterator};
-use crate::land::height_map::try_calculate_height_map;
+use crate::land::height_map::{try_calculate_height_map, HeightTransform};
 use crate::land::terrain_map::{LandData, TerrainMap, Vec2, Vec3};
 use crate::land::textures::IndexVTEX;
+use crate::merge::difference_mask::DifferenceMask;
 use crate::merge::relative_terrain_map::{IsModified, OptionalTerrainMap, RelativeTerrainMap};
 use crate::merge::relative_to::RelativeTo;
 use crate::ParsedPlugin;
-use std::default::default;
 use std::sync::Arc;
-use tes3::esp::{Landscape, LandscapeFlags, ObjectFlags};
+use tes3::esp::{Landscape, ObjectFlags};
 
 #[derive(Clone)]
 /// A [LandscapeDiff] is all of the [OptionalTerrainMap] to describe the changes
@@ -63,40 +62,67 @@ impl LandscapeDiff {
         modified
     }
 
+    /// Returns [LandData] representing which portions of the [Landscape] are present.
+    pub fn present_data(&self) -> LandData {
+        let mut present = LandData::default();
+
+        if self.height_map.is_some() {
+            present |= LandData::VERTEX_HEIGHTS;
+        }
+
+        if self.vertex_normals.is_some() {
+            present |= LandData::VERTEX_NORMALS;
+        }
+
+        if self.world_map_data.is_some() {
+            present |= LandData::WORLD_MAP;
+        }
+
+        if self.vertex_colors.is_some() {
+            present |= LandData::VERTEX_COLORS;
+        }
+
+        if self.texture_indices.is_some() {
+            present |= LandData::TEXTURES;
+        }
+
+        present
+    }
+
     /// Creates a new [LandscapeDiff] from the provided [Landscape] and allowed [LandData].
     pub fn from_reference(
         plugin: Arc<ParsedPlugin>,
         land: &Landscape,
         allowed_data: LandData,
     ) -> Self {
-        let included_data = landscape_flags(land);
+        let included_data = included_data(land);
 
         let height_map = Self::calculate_reference(
-            included_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS)
+            included_data.contains(LandData::VERTEX_HEIGHTS)
                 && allowed_data.contains(LandData::VERTEX_HEIGHTS),
             try_calculate_height_map(land).as_ref(),
         );
 
         let vertex_normals = Self::calculate_reference(
-            included_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS)
+            included_data.contains(LandData::VERTEX_NORMALS)
                 && allowed_data.contains(LandData::VERTEX_NORMALS),
             vertex_normals(land).as_ref(),
         );
 
         let world_map_data = Self::calculate_reference(
-            included_data.uses_world_map_data() && allowed_data.contains(LandData::WORLD_MAP),
+            included_data.contains(LandData::WORLD_MAP)
+                && allowed_data.contains(LandData::WORLD_MAP),
             world_map_data(land).as_ref(),
         );
 
         let vertex_colors = Self::calculate_reference(
-            included_data.contains(LandscapeFlags::USES_VERTEX_COLORS)
+            included_data.contains(LandData::VERTEX_COLORS)
                 && allowed_data.contains(LandData::VERTEX_COLORS),
             vertex_colors(land).as_ref(),
         );
 
         let texture_indices = Self::calculate_reference(
-            included_data.contains(LandscapeFlags::USES_TEXTURES)
-                && allowed_data.contains(LandData::TEXTURES),
+            included_data.contains(LandData::TEXTURES) && allowed_data.contains(LandData::TEXTURES),
             texture_indices(land).as_ref(),
         );
 
@@ -114,24 +140,31 @@ impl LandscapeDiff {
 
     /// Creates a new [LandscapeDiff] from the provided `land` [Landscape] and allowed [LandData].
     /// The differences are computed by comparing `land` to the `reference` [Landscape].
+    /// If `height_transform` is [Some], it is applied to the heights of the `land` first.
     pub fn from_difference(
         land: &Landscape,
         reference: Option<&Landscape>,
         allowed_data: LandData,
+        height_transform: Option<HeightTransform>,
     ) -> Self {
-        let included_data = landscape_flags(land);
+        let included_data = included_data(land);
 
         let height_map = Self::calculate_differences(
             "height_map",
-            included_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS)
+            included_data.contains(LandData::VERTEX_HEIGHTS)
                 && allowed_data.contains(LandData::VERTEX_HEIGHTS),
             reference.and_then(try_calculate_height_map).as_ref(),
-            try_calculate_height_map(land).as_ref(),
+            try_calculate_height_map(land)
+                .map(|height_map| match height_transform {
+                    Some(height_transform) => height_transform.apply(&height_map),
+                    None => height_map,
+                })
+                .as_ref(),
         );
 
         let vertex_normals = Self::calculate_differences_with_mask(
             "vertex_normals",
-            included_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS)
+            included_data.contains(LandData::VERTEX_NORMALS)
                 && allowed_data.contains(LandData::VERTEX_NORMALS),
             reference.and_then(vertex_normals).as_ref(),
             vertex_normals(land).as_ref(),
@@ -141,14 +174,15 @@ impl LandscapeDiff {
 
         let world_map_data = Self::calculate_differences(
             "world_map_data",
-            included_data.uses_world_map_data() && allowed_data.contains(LandData::WORLD_MAP),
+            included_data.contains(LandData::WORLD_MAP)
+                && allowed_data.contains(LandData::WORLD_MAP),
             reference.and_then(world_map_data).as_ref(),
             world_map_data(land).as_ref(),
         );
 
         let vertex_colors = Self::calculate_differences(
             "vertex_colors",
-            included_data.contains(LandscapeFlags::USES_VERTEX_COLORS)
+            included_data.contains(LandData::VERTEX_COLORS)
                 && allowed_data.contains(LandData::VERTEX_COLORS),
             reference.and_then(vertex_colors).as_ref(),
             vertex_colors(land).as_ref(),
@@ -156,8 +190,7 @@ impl LandscapeDiff {
 
         let texture_indices = Self::calculate_differences(
             "texture_indices",
-            included_data.contains(LandscapeFlags::USES_TEXTURES)
-                && allowed_data.contains(LandData::TEXTURES),
+            included_data.contains(LandData::TEXTURES) && allowed_data.contains(LandData::TEXTURES),
             reference.and_then(texture_indices).as_ref(),
             texture_indices(land).as_ref(),
         );
@@ -174,16 +207,68 @@ impl LandscapeDiff {
         }
     }
 
-    /// Create a new [RelativeTerrainMap] by applying the `allow` [TerrainMap]
-    /// mask to the `old` [RelativeTerrainMap].
+    /// Returns a [LandscapeDiff] with the values of this [LandscapeDiff] relative to the values of
+    /// the `predecessor` [LandscapeDiff], e.g. the merged land before this plugin is merged.
+    /// Values missing from the `predecessor` keep their existing reference.
+    pub fn relative_to(&self, predecessor: &LandscapeDiff) -> Self {
+        Self {
+            coords: self.coords,
+            flags: self.flags,
+            height_map: Self::calculate_relative_to(
+                self.height_map.as_ref(),
+                predecessor.height_map.as_ref(),
+            ),
+            vertex_normals: Self::calculate_relative_to(
+                self.vertex_normals.as_ref(),
+                predecessor.vertex_normals.as_ref(),
+            ),
+            world_map_data: Self::calculate_relative_to(
+                self.world_map_data.as_ref(),
+                predecessor.world_map_data.as_ref(),
+            ),
+            vertex_colors: Self::calculate_relative_to(
+                self.vertex_colors.as_ref(),
+                predecessor.vertex_colors.as_ref(),
+            ),
+            texture_indices: Self::calculate_relative_to(
+                self.texture_indices.as_ref(),
+                predecessor.texture_indices.as_ref(),
+            ),
+            plugins: self.plugins.clone(),
+        }
+    }
+
+    /// Returns an [OptionalTerrainMap] of the differences between the values of `predecessor`
+    /// and the values of `plugin`. If `predecessor` is [None], `plugin` is returned unchanged.
+    fn calculate_relative_to<U: RelativeTo, const T: usize>(
+        plugin: Option<&RelativeTerrainMap<U, T>>,
+        predecessor: Option<&RelativeTerrainMap<U, T>>,
+    ) -> OptionalTerrainMap<U, T> {
+        let plugin = plugin?;
+
+        let Some(predecessor) = predecessor else {
+            return Some(plugin.clone());
+        };
+
+        let relative =
+            RelativeTerrainMap::from_difference(&predecessor.to_terrain(), &plugin.to_terrain());
+        relative.is_modified().then_some(relative)
+    }
+
+    /// Create a new [RelativeTerrainMap] by applying the `allow` [DifferenceMask]
+    /// to the `old` [RelativeTerrainMap].
     pub fn apply_mask<U: RelativeTo, const T: usize>(
         old: &RelativeTerrainMap<U, T>,
-        allow: Option<&TerrainMap<bool, T>>,
+        allow: Option<&DifferenceMask<T>>,
     ) -> RelativeTerrainMap<U, T> {
         let mut new = old.clone();
 
         if let Some(allowed) = allow {
-            new.clean_some(old.iter_grid().filter(|coords| !allowed.get(*coords)));
+            new.clean_some(
+                old.differences()
+                    .iter()
+                    .filter(|coords| !allowed.get(*coords)),
+            );
         } else {
             new.clean_all();
         }
@@ -192,27 +277,25 @@ impl LandscapeDiff {
     }
 
     /// Returns an [OptionalTerrainMap] of the differences between `reference` and `plugin`, after
-    /// applying any provided `allow` [TerrainMap] mask with [Self::apply_mask].
+    /// applying any provided `allow` [DifferenceMask] with [Self::apply_mask].
     fn calculate_differences_with_mask<U: RelativeTo, const T: usize>(
         _value: &str,
         should_include: bool,
         reference: Option<&TerrainMap<U, T>>,
         plugin: Option<&TerrainMap<U, T>>,
         use_mask: bool,
-        allow: Option<&TerrainMap<bool, T>>,
+        allow: Option<&DifferenceMask<T>>,
     ) -> OptionalTerrainMap<U, T> {
         if !should_include {
             return None;
         }
 
-        let Some(plugin) = plugin else {
-            return None;
-        };
+        let plugin = plugin?;
 
         let relative = if let Some(reference) = reference {
             RelativeTerrainMap::from_difference(reference, plugin)
         } else {
-            let default = [[default(); T]; T];
+            let default = [[Default::default(); T]; T];
             RelativeTerrainMap::from_difference(&default, plugin)
         };
 
@@ -1,12 +1,12 @@
 use crate::land::grid_access::{GridAccessor2D, GridIterator2D, Index2D, SquareGridIterator};
 use bitflags::bitflags;
 use const_default::ConstDefault;
-use std::default::default;
 use tes3::esp::LandscapeFlags;
 
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[repr(C)]
-/// A [Vec2] is an `x` and `y` value. Can be converted to and from `[T; 2]`.
+/// A [Vec2] is an `x` and `y` value, ordered by `x` and then `y`.
+/// Can be converted to and from `[T; 2]`.
 pub struct Vec2<T> {
     pub x: T,
     pub y: T,
@@ -85,7 +85,7 @@ impl<U: Copy, const T: usize> GridAccessor2D<U> for TerrainMap<U, T> {
 
 impl<U, const T: usize> SquareGridIterator<T> for TerrainMap<U, T> {
     fn iter_grid(&self) -> GridIterator2D<T, T> {
-        default()
+        Default::default()
     }
 }
 
@@ -101,27 +101,196 @@ bitflags! {
     }
 }
 
+/// The name of each portion of the [LandData], e.g. as used for the `value` of a conflict.
+pub const LAND_DATA_NAMES: [(LandData, &str); 5] = [
+    (LandData::VERTEX_HEIGHTS, "height_map"),
+    (LandData::VERTEX_NORMALS, "vertex_normals"),
+    (LandData::WORLD_MAP, "world_map_data"),
+    (LandData::VERTEX_COLORS, "vertex_colors"),
+    (LandData::TEXTURES, "texture_indices"),
+];
+
+/// The [LandData] named `value` in [LAND_DATA_NAMES], if any.
+pub fn land_data_named(value: &str) -> Option<LandData> {
+    LAND_DATA_NAMES
+        .iter()
+        .find(|(_, name)| *name == value)
+        .map(|(data, _)| *data)
+}
+
+/// The [LandData] included by each of the [LandscapeFlags]. World map data is included
+/// if any flag is set, including the unknown flag.
+const LANDSCAPE_FLAGS_DATA: [(LandscapeFlags, LandData); 4] = [
+    (
+        LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS,
+        LandData::VERTEX_HEIGHTS
+            .union(LandData::VERTEX_NORMALS)
+            .union(LandData::WORLD_MAP),
+    ),
+    (
+        LandscapeFlags::USES_VERTEX_COLORS,
+        LandData::VERTEX_COLORS.union(LandData::WORLD_MAP),
+    ),
+    (
+        LandscapeFlags::USES_TEXTURES,
+        LandData::TEXTURES.union(LandData::WORLD_MAP),
+    ),
+    (LandscapeFlags::UNKNOWN, LandData::WORLD_MAP),
+];
+
 impl From<LandscapeFlags> for LandData {
     fn from(old: LandscapeFlags) -> Self {
-        let mut new = LandData::default();
+        LANDSCAPE_FLAGS_DATA
+            .iter()
+            .filter(|(flag, _)| old.contains(*flag))
+            .fold(LandData::default(), |new, (_, data)| new | *data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MergeError;
+    use crate::land::conversions::included_data;
+    use crate::merge_tes3_landscape;
+    use crate::test_support::{landscape, master_heights};
+    use tes3::esp::{Landscape, ObjectFlags, VertexColors};
+
+    const HEIGHTS: LandscapeFlags = LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS;
+    const COLORS: LandscapeFlags = LandscapeFlags::USES_VERTEX_COLORS;
+    const TEXTURES: LandscapeFlags = LandscapeFlags::USES_TEXTURES;
+    const UNKNOWN: LandscapeFlags = LandscapeFlags::UNKNOWN;
+
+    const HEIGHT_DATA: LandData = LandData::VERTEX_HEIGHTS.union(LandData::VERTEX_NORMALS);
+    const WORLD_MAP: LandData = LandData::WORLD_MAP;
+
+    /// Every combination of the [LandscapeFlags] and the [LandData] it includes.
+    fn flag_permutations() -> [(LandscapeFlags, LandData); 16] {
+        [
+            (LandscapeFlags::empty(), LandData::empty()),
+            (HEIGHTS, HEIGHT_DATA | WORLD_MAP),
+            (COLORS, LandData::VERTEX_COLORS | WORLD_MAP),
+            (TEXTURES, LandData::TEXTURES | WORLD_MAP),
+            (UNKNOWN, WORLD_MAP),
+            (
+                HEIGHTS | COLORS,
+                HEIGHT_DATA | LandData::VERTEX_COLORS | WORLD_MAP,
+            ),
+            (
+                HEIGHTS | TEXTURES,
+                HEIGHT_DATA | LandData::TEXTURES | WORLD_MAP,
+            ),
+            (HEIGHTS | UNKNOWN, HEIGHT_DATA | WORLD_MAP),
+            (
+                COLORS | TEXTURES,
+                LandData::VERTEX_COLORS | LandData::TEXTURES | WORLD_MAP,
+            ),
+            (COLORS | UNKNOWN, LandData::VERTEX_COLORS | WORLD_MAP),
+            (TEXTURES | UNKNOWN, LandData::TEXTURES | WORLD_MAP),
+            (HEIGHTS | COLORS | TEXTURES, LandData::all()),
+            (
+                HEIGHTS | COLORS | UNKNOWN,
+                HEIGHT_DATA | LandData::VERTEX_COLORS | WORLD_MAP,
+            ),
+            (
+                HEIGHTS | TEXTURES | UNKNOWN,
+                HEIGHT_DATA | LandData::TEXTURES | WORLD_MAP,
+            ),
+            (
+                COLORS | TEXTURES | UNKNOWN,
+                LandData::VERTEX_COLORS | LandData::TEXTURES | WORLD_MAP,
+            ),
+            (HEIGHTS | COLORS | TEXTURES | UNKNOWN, LandData::all()),
+        ]
+    }
 
-        if old.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS) {
-            new |= LandData::VERTEX_HEIGHTS;
-            new |= LandData::VERTEX_NORMALS;
+    /// Returns a [Landscape] with every kind of data and the `flags`.
+    fn landscape_with_flags(flags: LandscapeFlags) -> Landscape {
+        let coords = Vec2::new(0, 0);
+        let mut land = landscape(coords, &master_heights(coords), 1);
+        land.vertex_colors = Some(VertexColors {
+            data: Box::new([[[255; 3]; 65]; 65]),
+        });
+        land.landscape_flags = flags;
+        land
+    }
+
+    #[test]
+    fn permutations_cover_every_flag_combination() {
+        let permutations = flag_permutations();
+        for (idx, (flags, _)) in permutations.iter().enumerate() {
+            assert!(
+                permutations[..idx].iter().all(|(other, _)| other != flags),
+                "{:?} is listed twice",
+                flags
+            );
         }
+    }
 
-        if old.contains(LandscapeFlags::USES_VERTEX_COLORS) {
-            new |= LandData::VERTEX_COLORS;
+    #[test]
+    fn flags_convert_to_land_data() {
+        for (flags, expected) in flag_permutations() {
+            assert_eq!(LandData::from(flags), expected, "flags {:?}", flags);
         }
+    }
+
+    #[test]
+    fn world_map_data_is_kept_with_any_flags() {
+        for (flags, expected) in flag_permutations() {
+            let land = landscape_with_flags(flags);
+            assert_eq!(
+                included_data(&land),
+                expected | WORLD_MAP,
+                "flags {:?}",
+                flags
+            );
 
-        if old.contains(LandscapeFlags::USES_TEXTURES) {
-            new |= LandData::TEXTURES;
+            let mut without_world_map = land.clone();
+            without_world_map.world_map_data = None;
+            assert_eq!(
+                included_data(&without_world_map),
+                expected,
+                "flags {:?}",
+                flags
+            );
         }
+    }
+
+    #[test]
+    fn merged_land_round_trips_included_data() {
+        for (flags, _) in flag_permutations() {
+            let land = landscape_with_flags(flags);
+            let mut empty = landscape_with_flags(LandscapeFlags::empty());
+            empty.world_map_data = None;
 
-        if old.uses_world_map_data() {
-            new |= LandData::WORLD_MAP;
+            let merged = merge_tes3_landscape(&empty, &land).unwrap();
+            assert_eq!(
+                included_data(&merged),
+                included_data(&land),
+                "flags {:?}",
+                flags
+            );
+            assert_eq!(
+                LandData::from(merged.landscape_flags) | WORLD_MAP,
+                included_data(&land) | WORLD_MAP,
+                "flags {:?}",
+                flags
+            );
         }
+    }
 
-        new
+    #[test]
+    fn merging_deleted_land_is_an_invariant_violation() {
+        let land = landscape_with_flags(HEIGHTS);
+        let mut deleted = land.clone();
+        deleted.flags |= ObjectFlags::DELETED;
+
+        for (lhs, rhs) in [(&land, &deleted), (&deleted, &deleted)] {
+            let error = merge_tes3_landscape(lhs, rhs).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<MergeError>(),
+                Some(MergeError::InvariantViolation { .. })
+            ));
+        }
     }
 }
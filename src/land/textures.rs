@@ -1,11 +1,11 @@
-use crate::io::parsed_plugins::ParsedPlugin;
+use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::io::report::{TextureChange, TextureHistory};
 use crate::merge::relative_to::RelativeTo;
 use anyhow::{bail, Error};
 use const_default::ConstDefault;
 use hashbrown::HashMap;
 use itertools::Itertools;
-use log::trace;
-use std::default::default;
+use log::{debug, trace};
 use std::sync::Arc;
 use tes3::esp::{LandscapeTexture, ObjectFlags};
 
@@ -136,6 +136,7 @@ impl RemappedTextures {
     }
 }
 
+#[derive(Clone)]
 /// A [LandscapeTexture] and the [ParsedPlugin] that last added or modified it.
 pub struct KnownTexture {
     inner: LandscapeTexture,
@@ -156,16 +157,73 @@ impl KnownTexture {
         texture_index(&self.inner)
     }
 
+    /// The file name of the texture of the [LandscapeTexture], if any.
+    pub fn file_name(&self) -> Option<&str> {
+        self.inner.file_name.as_deref()
+    }
+
     /// Clones the [LandscapeTexture].
     pub fn clone_landscape_texture(&self) -> LandscapeTexture {
         self.inner.clone()
     }
 }
 
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+/// When a [TextureRemap] was performed.
+pub enum RemapStage {
+    /// The LTEX record of a plugin was registered in the [KnownTextures].
+    Registration,
+    /// The [KnownTextures] were compacted to the textures in use by the merged land.
+    Compaction,
+    /// The LTEX record of a master was registered for a plugin with LAND records that use it.
+    Dependency,
+}
+
+impl RemapStage {
+    /// The name of the [RemapStage], e.g. for a CSV file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Registration => "registration",
+            Self::Compaction => "compaction",
+            Self::Dependency => "dependency",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A change of the index of the LTEX record `id` from `original_index` to `new_index`.
+/// The `new_index` is [None] if the texture was removed because it is unused.
+pub struct TextureRemap {
+    pub stage: RemapStage,
+    pub plugin: String,
+    pub original_index: u16,
+    pub new_index: Option<u16>,
+    pub id: String,
+    pub file_name: Option<String>,
+}
+
 /// [KnownTextures] stores a map of [KnownTexture] accessible by the [KnownTexture::id].
 /// Supports up to [u16::MAX] textures.
+#[derive(Clone, Default)]
 pub struct KnownTextures {
     inner: HashMap<String, KnownTexture>,
+    remaps: Vec<TextureRemap>,
+}
+
+/// Returns `true` if the `plugin` is the authority for the texture `id` in its
+/// [crate::io::meta_schema::PluginMeta].
+fn is_texture_authority(plugin: &ParsedPlugin, id: &str) -> bool {
+    plugin
+        .meta
+        .texture_authority
+        .iter()
+        .any(|authority| authority.eq_ignore_ascii_case(id))
+}
+
+/// Returns `true` if the file name of the texture `id` from the `current` plugin is kept instead
+/// of the file name from the `next` plugin, because the `current` plugin is the authority for it.
+fn keeps_file_name(current: &Arc<ParsedPlugin>, next: &Arc<ParsedPlugin>, id: &str) -> bool {
+    !Arc::ptr_eq(current, next) && is_texture_authority(current, id)
 }
 
 /// Returns [u16] `index` of the [LandscapeTexture].
@@ -182,7 +240,16 @@ fn texture_index(texture: &LandscapeTexture) -> IndexLTEX {
 
 impl KnownTextures {
     pub fn new() -> KnownTextures {
-        Self { inner: default() }
+        Self {
+            inner: Default::default(),
+            remaps: Vec::new(),
+        }
+    }
+
+    /// Returns each [TextureRemap] performed by [Self::add_remapped_texture] and
+    /// [Self::remove_unused], in the order they were performed.
+    pub fn remaps(&self) -> &[TextureRemap] {
+        &self.remaps
     }
 
     /// Returns an [Iterator] over the [KnownTexture] sorted by [KnownTexture::index].
@@ -193,19 +260,127 @@ impl KnownTextures {
     }
 
     /// Update the [KnownTexture] matching `texture` with changes from [ParsedPlugin] `plugin`.
+    /// The file name is not changed if the plugin of the [KnownTexture] is the authority for it.
     pub fn update_texture(&mut self, plugin: &Arc<ParsedPlugin>, texture: &LandscapeTexture) {
         let known_texture = self.inner.get_mut(&texture.id).expect("unknown texture ID");
-        if let Some(file_name) = &texture.file_name {
-            if known_texture
-                .inner
-                .file_name
-                .as_ref()
-                .map(|old_file_name| old_file_name != file_name)
-                .unwrap_or(true)
-            {
-                known_texture.inner.file_name = Some(file_name.into());
+        let Some(file_name) = &texture.file_name else {
+            return;
+        };
+
+        if known_texture.file_name() == Some(file_name.as_str()) {
+            // The authority for a texture keeps it even if an earlier plugin has the same file name.
+            if is_texture_authority(plugin, &texture.id) {
                 known_texture.plugin = plugin.clone();
             }
+        } else if keeps_file_name(&known_texture.plugin, plugin, &texture.id) {
+            trace!(
+                "Keeping texture {} file name {} from authority {} instead of {} from {}",
+                texture.id,
+                known_texture.file_name().unwrap_or_default(),
+                known_texture.plugin.name,
+                file_name,
+                plugin.name
+            );
+        } else {
+            trace!(
+                "Changing texture {} file name from {} ({}) to {} ({})",
+                texture.id,
+                known_texture.file_name().unwrap_or_default(),
+                known_texture.plugin.name,
+                file_name,
+                plugin.name
+            );
+            known_texture.inner.file_name = Some(file_name.into());
+            known_texture.plugin = plugin.clone();
+        }
+    }
+
+    /// Returns the [TextureHistory] of each [KnownTexture] with a file name changed by a later
+    /// plugin of the `parsed_plugins`, ordered by ID. The changes are found in load order like
+    /// [Self::update_texture], so the result matches the LTEX records of the output.
+    pub fn histories(&self, parsed_plugins: &ParsedPlugins) -> Vec<TextureHistory> {
+        let mut changes: HashMap<&str, Vec<(&Arc<ParsedPlugin>, &str)>> = HashMap::new();
+        for plugin in parsed_plugins
+            .masters
+            .iter()
+            .chain(parsed_plugins.plugins.iter())
+        {
+            for texture in plugin.records.objects_of_type::<LandscapeTexture>() {
+                let (Some(known_texture), Some(file_name)) =
+                    (self.inner.get(&texture.id), texture.file_name.as_deref())
+                else {
+                    continue;
+                };
+
+                let texture_changes = changes.entry(known_texture.id()).or_default();
+                if is_texture_authority(plugin, &texture.id)
+                    || texture_changes
+                        .last()
+                        .map(|(_, last_file_name)| *last_file_name != file_name)
+                        .unwrap_or(true)
+                {
+                    texture_changes.push((plugin, file_name));
+                }
+            }
+        }
+
+        changes
+            .into_iter()
+            .filter(|(_, texture_changes)| texture_changes.len() > 1)
+            .sorted_by_key(|(id, _)| *id)
+            .map(|(id, texture_changes)| {
+                let (mut plugin, mut file_name) = texture_changes[0];
+                for (next_plugin, next_file_name) in texture_changes.iter().skip(1) {
+                    if !keeps_file_name(plugin, next_plugin, id) {
+                        plugin = next_plugin;
+                        file_name = next_file_name;
+                    }
+                }
+
+                let history = TextureHistory {
+                    id: id.to_string(),
+                    file_name: file_name.to_string(),
+                    plugin: plugin.name.clone(),
+                    authority: is_texture_authority(plugin, id).then(|| plugin.name.clone()),
+                    changes: texture_changes
+                        .iter()
+                        .map(|(plugin, file_name)| TextureChange {
+                            plugin: plugin.name.clone(),
+                            file_name: file_name.to_string(),
+                        })
+                        .collect(),
+                };
+
+                debug!(
+                    "Texture {} uses {} from {}: {}",
+                    history.id,
+                    history.file_name,
+                    history.plugin,
+                    history
+                        .changes
+                        .iter()
+                        .map(|change| format!("{} ({})", change.file_name, change.plugin))
+                        .join(" -> ")
+                );
+
+                history
+            })
+            .collect()
+    }
+
+    /// Update each [KnownTexture] with changes from the LTEX records of [ParsedPlugin] `plugin`.
+    /// LTEX records with an ID that is not known are skipped, since no LAND uses them.
+    pub fn update_textures(&mut self, plugin: &Arc<ParsedPlugin>) {
+        for texture in plugin.records.objects_of_type::<LandscapeTexture>() {
+            if self.inner.contains_key(&texture.id) {
+                self.update_texture(plugin, texture);
+            } else {
+                trace!(
+                    "Skipping unused texture {} from {}",
+                    texture.id,
+                    plugin.name
+                );
+            }
         }
     }
 
@@ -248,6 +423,66 @@ impl KnownTextures {
                 old_id.as_u16(),
                 new_id.as_u16()
             );
+            self.remaps.push(TextureRemap {
+                stage: RemapStage::Registration,
+                plugin: plugin.name.clone(),
+                original_index: old_id.as_u16(),
+                new_index: Some(new_id.as_u16()),
+                id: texture.id.clone(),
+                file_name: texture.file_name.clone(),
+            });
+        }
+    }
+
+    /// Add a [KnownTexture] matching `texture` from the [ParsedPlugin] `master` for the texture
+    /// indices of the [ParsedPlugin] `plugin`, unless the `plugin` defines the index itself.
+    /// Unlike [Self::add_remapped_texture], a texture that is already known is not changed, since
+    /// the `master` may be loaded before or after the `plugin`. The [RemappedTextures] is updated.
+    pub fn add_dependency_texture(
+        &mut self,
+        plugin: &Arc<ParsedPlugin>,
+        master: &Arc<ParsedPlugin>,
+        texture: &LandscapeTexture,
+        remapped_textures: &mut RemappedTextures,
+    ) {
+        if texture.flags.contains(ObjectFlags::DELETED) {
+            return;
+        }
+
+        let old_id = texture_index(texture);
+        if remapped_textures.inner.contains_key(&old_id.into()) {
+            return;
+        }
+
+        let new_id = match self.inner.get(&texture.id) {
+            Some(known_texture) => known_texture.index(),
+            None => self.add_next_texture(master, texture),
+        };
+
+        trace!(
+            "Remapped {} from {} of {} to {}",
+            texture.id,
+            old_id.as_u16(),
+            master.name,
+            new_id.as_u16()
+        );
+        remapped_textures.inner.insert(old_id.into(), new_id.into());
+        self.remaps.push(TextureRemap {
+            stage: RemapStage::Dependency,
+            plugin: plugin.name.clone(),
+            original_index: old_id.as_u16(),
+            new_index: Some(new_id.as_u16()),
+            id: texture.id.clone(),
+            file_name: texture.file_name.clone(),
+        });
+    }
+
+    /// Add a new [KnownTexture] matching `texture` from [ParsedPlugin] `plugin`, unless a texture
+    /// with the same ID is already known. Used for textures of a [crate::io::bundle] whose
+    /// plugin is not loaded. The `index` of the `texture` is ignored.
+    pub fn add_bundled_texture(&mut self, plugin: &Arc<ParsedPlugin>, texture: &LandscapeTexture) {
+        if !self.inner.contains_key(&texture.id) {
+            self.add_next_texture(plugin, texture);
         }
     }
 
@@ -255,24 +490,42 @@ impl KnownTextures {
     /// [RemappedTextures].
     pub fn remove_unused(&mut self, remapped_textures: &RemappedTextures) -> usize {
         let mut unused_ids = Vec::new();
+        let mut remaps = Vec::new();
 
         for (id, texture) in self.inner.iter_mut() {
-            if let Some(new_idx) = remapped_textures
-                .try_remapped_index(texture.index().into())
-                .map(|idx| IndexLTEX::try_from(idx).expect("safe"))
-            {
+            let original_index = texture.index();
+            let new_index = remapped_textures
+                .try_remapped_index(original_index.into())
+                .map(|idx| IndexLTEX::try_from(idx).expect("safe"));
+
+            if let Some(new_idx) = new_index {
                 trace!(
                     "Remapped {} from {} to {}",
                     id,
-                    texture.index().as_u16(),
+                    original_index.as_u16(),
                     new_idx.as_u16()
                 );
                 texture.inner.index = Some(new_idx.as_u16().into());
             } else {
                 unused_ids.push(id.clone());
             }
+
+            remaps.push(TextureRemap {
+                stage: RemapStage::Compaction,
+                plugin: texture.plugin.name.clone(),
+                original_index: original_index.as_u16(),
+                new_index: new_index.map(|idx| idx.as_u16()),
+                id: id.clone(),
+                file_name: texture.file_name().map(str::to_string),
+            });
         }
 
+        self.remaps.extend(
+            remaps
+                .into_iter()
+                .sorted_by_key(|remap| remap.original_index),
+        );
+
         let num_removed_ids = unused_ids.len();
 
         for id in unused_ids {
@@ -283,6 +536,11 @@ impl KnownTextures {
         num_removed_ids
     }
 
+    /// Returns the [KnownTexture] with the `id`, if any.
+    pub fn get(&self, id: &str) -> Option<&KnownTexture> {
+        self.inner.get(id)
+    }
+
     /// The number of [KnownTexture].
     pub fn len(&self) -> usize {
         let len = self.inner.len();
@@ -290,6 +548,11 @@ impl KnownTextures {
         len
     }
 
+    /// Returns `true` if there are no [KnownTexture].
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
     /// The next [KnownTexture::index].
     fn next_texture_index(&self) -> IndexLTEX {
         IndexLTEX::new(self.len().try_into().expect("safe"))
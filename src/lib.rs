@@ -0,0 +1,2005 @@
+use crate::error::{Error, MergeError};
+use crate::events::{MergeEvent, RunContext};
+use crate::io::conflict_clusters::cluster_conflicts;
+use crate::io::meta_schema::{ConflictStrategy, DuplicateRecordPolicy, GlobalConfig, MetaType};
+use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::io::preset::PresetSettings;
+use crate::io::report::{DuplicateLand, Report, WorldMapOnlyEdits};
+use crate::io::resolutions::Resolutions;
+use crate::io::save_to_image::analyze_landmass_conflicts;
+use crate::land::conversions::{coordinates, included_data, landscape_flags};
+use crate::land::height_map::HeightTransform;
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::{LandData, Vec2};
+use crate::land::textures::{IndexVTEX, KnownTextures, RemappedTextures};
+use crate::log_budget::{BudgetedLogger, MAX_TRACE_MESSAGES};
+use crate::merge::cells::merge_cells;
+use crate::merge::duplicate_land::collapse_duplicate_land;
+use crate::merge::merge_strategy::apply_merge_strategy;
+use crate::merge::relative_terrain_map::{IsModified, RelativeTerrainMap};
+use crate::merge::world_map_edits::drop_world_map_only_edits;
+use crate::pipeline::{
+    clean_plugin, export, export_reference, inspect, preview, report_conflicts, MergePipeline,
+};
+use crate::repair::height_scale::warn_height_scale_mismatch;
+use crate::repair::seam_detection::repair_landmass_seams_until_fixed;
+use crate::self_test::self_test;
+use crate::timings::TimedPhase;
+use anyhow::{anyhow, bail, Context, Result};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::{debug, error, trace};
+use owo_colors::OwoColorize;
+use simplelog::{
+    ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, LevelPadding, TermLogger,
+    TerminalMode, WriteLogger,
+};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tes3::esp::{Landscape, LandscapeFlags, LandscapeTexture, ObjectFlags};
+
+pub mod error;
+pub mod events;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod io;
+pub mod land;
+pub mod log_budget;
+pub mod merge;
+pub mod pipeline;
+pub mod repair;
+pub mod self_test;
+#[cfg(test)]
+mod test_support;
+pub mod timings;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+
+/// A [Landmass] represents a collection of [Landscape] and the associated [ParsedPlugin].
+pub struct Landmass {
+    plugin: Arc<ParsedPlugin>,
+    land: HashMap<Vec2<i32>, Landscape>,
+    plugins: HashMap<Vec2<i32>, Arc<ParsedPlugin>>,
+}
+
+impl Landmass {
+    fn new(plugin: Arc<ParsedPlugin>) -> Self {
+        Self {
+            plugin,
+            land: HashMap::new(),
+            plugins: HashMap::new(),
+        }
+    }
+
+    fn insert_land(&mut self, coords: Vec2<i32>, plugin: &Arc<ParsedPlugin>, land: &Landscape) {
+        self.plugins.insert(coords, plugin.clone());
+        self.land.insert(coords, land.clone());
+    }
+
+    /// Returns an [Iterator] over the [Landscape] ordered by `x` and `y` coordinates.
+    fn sorted(&self) -> impl Iterator<Item = (&Vec2<i32>, &Landscape)> {
+        self.land.iter().sorted_by_key(|f| (f.0.x, f.0.y))
+    }
+}
+
+/// The [Landmass] of a plugin and the `reference` [Landmass] it is compared to.
+/// Used to create the [LandscapeDiff] of a [LandmassDiff] only when they are needed.
+/// The `landmass` keeps every LAND record of the plugin in memory, since these are much
+/// smaller than the [LandscapeDiff] created from them.
+struct DeferredLandmass {
+    landmass: Landmass,
+    reference: Arc<Landmass>,
+}
+
+impl DeferredLandmass {
+    /// Creates the [LandscapeDiff] of the cell at `coords`, if the `landmass` contains it.
+    fn landscape_diff(&self, coords: &Vec2<i32>) -> Option<LandscapeDiff> {
+        let land = self.landmass.land.get(coords)?;
+        Some(find_landscape_diff(
+            &self.landmass.plugin,
+            land,
+            self.reference.land.get(coords),
+        ))
+    }
+}
+
+#[derive(Clone)]
+/// A [LandmassDiff] represents a collection of [LandscapeDiff] and the associated [ParsedPlugin].
+pub struct LandmassDiff {
+    plugin: Arc<ParsedPlugin>,
+    land: HashMap<Vec2<i32>, LandscapeDiff>,
+    /// If [Some], the `land` is empty and each [LandscapeDiff] is created when it is needed.
+    /// See [LandmassDiff::restricted_to].
+    deferred: Option<Arc<DeferredLandmass>>,
+}
+
+impl LandmassDiff {
+    /// Creates an empty [LandmassDiff] for the `plugin`.
+    pub fn new(plugin: Arc<ParsedPlugin>) -> Self {
+        Self {
+            plugin,
+            land: HashMap::new(),
+            deferred: None,
+        }
+    }
+
+    /// Creates a [LandmassDiff] between the `landmass` and `reference` [Landmass] without
+    /// creating any [LandscapeDiff] until they are needed.
+    fn deferred(landmass: Landmass, reference: Arc<Landmass>) -> Self {
+        Self {
+            plugin: landmass.plugin.clone(),
+            land: HashMap::new(),
+            deferred: Some(Arc::new(DeferredLandmass {
+                landmass,
+                reference,
+            })),
+        }
+    }
+
+    /// Returns the coordinates of each cell with a [LandscapeDiff], including deferred cells.
+    fn cells(&self) -> Vec<Vec2<i32>> {
+        match self.deferred.as_ref() {
+            Some(deferred) => deferred.landmass.land.keys().copied().collect(),
+            None => self.land.keys().copied().collect(),
+        }
+    }
+
+    /// Returns the [LandscapeDiff] of the cell at `coords`, creating it if it is deferred.
+    fn land_at(&self, coords: &Vec2<i32>) -> Option<Cow<'_, LandscapeDiff>> {
+        match self.deferred.as_ref() {
+            Some(deferred) => deferred.landscape_diff(coords).map(Cow::Owned),
+            None => self.land.get(coords).map(Cow::Borrowed),
+        }
+    }
+
+    /// Returns a [LandmassDiff] with only the [LandscapeDiff] of the `cells`.
+    /// Any deferred [LandscapeDiff] of the `cells` are created, so the result is never deferred.
+    fn restricted_to(&self, cells: &HashSet<Vec2<i32>>) -> LandmassDiff {
+        let mut restricted = LandmassDiff::new(self.plugin.clone());
+        for coords in cells.iter() {
+            if let Some(land) = self.land_at(coords) {
+                restricted.land.insert(*coords, land.into_owned());
+            }
+        }
+
+        restricted
+    }
+
+    /// Returns the [LandmassDiff] with every deferred [LandscapeDiff] created.
+    fn undeferred(&self) -> Cow<'_, LandmassDiff> {
+        if self.deferred.is_some() {
+            Cow::Owned(self.restricted_to(&self.cells().into_iter().collect()))
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
+    /// Returns a [LandmassDiff] without the [LandscapeDiff] of the `cells`.
+    /// Any deferred [LandscapeDiff] of other cells stay deferred.
+    fn without(&self, cells: &HashSet<Vec2<i32>>) -> LandmassDiff {
+        let Some(deferred) = self.deferred.as_ref() else {
+            let mut landmass = self.clone();
+            landmass.land.retain(|coords, _| !cells.contains(coords));
+            return landmass;
+        };
+
+        let mut landmass = Landmass::new(self.plugin.clone());
+        for (coords, land) in deferred.landmass.land.iter() {
+            if !cells.contains(coords) {
+                let plugin = deferred
+                    .landmass
+                    .plugins
+                    .get(coords)
+                    .unwrap_or(&self.plugin);
+                landmass.insert_land(*coords, plugin, land);
+            }
+        }
+
+        LandmassDiff::deferred(landmass, deferred.reference.clone())
+    }
+
+    /// Removes the [LandscapeDiff] of the `cells` and returns them in a new [LandmassDiff].
+    fn split_off(&mut self, cells: &HashSet<Vec2<i32>>) -> LandmassDiff {
+        let mut split = LandmassDiff::new(self.plugin.clone());
+        for coords in cells.iter() {
+            if let Some(land) = self.land.remove(coords) {
+                split.land.insert(*coords, land);
+            }
+        }
+
+        split
+    }
+
+    /// Moves each [LandscapeDiff] of `other` into this [LandmassDiff].
+    fn append(&mut self, other: LandmassDiff) {
+        self.land.extend(other.land);
+    }
+
+    /// Inserts the [LandscapeDiff] at its coordinates, replacing any existing diff.
+    pub fn insert_land(&mut self, land: LandscapeDiff) {
+        self.land.insert(land.coords, land);
+    }
+
+    /// Returns a [LandmassDiff] with each [LandscapeDiff] relative to the same cell of the
+    /// `predecessor` [LandmassDiff]. See [LandscapeDiff::relative_to].
+    fn relative_to(&self, predecessor: &LandmassDiff) -> LandmassDiff {
+        let mut relative = LandmassDiff::new(self.plugin.clone());
+        for coords in self.cells() {
+            let land = self.land_at(&coords).expect("safe");
+            let land = match predecessor.land.get(&coords) {
+                Some(predecessor) => land.relative_to(predecessor),
+                None => land.into_owned(),
+            };
+
+            if land.is_modified() {
+                relative.insert_land(land);
+            }
+        }
+
+        relative
+    }
+
+    /// Returns an [Iterator] over the [LandscapeDiff] ordered by `x` and `y` coordinates.
+    fn sorted(&self) -> impl Iterator<Item = (&Vec2<i32>, &LandscapeDiff)> {
+        self.land.iter().sorted_by_key(|f| (f.0.x, f.0.y))
+    }
+}
+
+pub mod cli {
+    use crate::io::artifacts::ArtifactSink;
+    use crate::io::excluded_cells::load_excluded_cells;
+    use crate::io::export::{ExportFormat, LodOptions};
+    use crate::io::file_provider::{FileProvider, StdFileProvider};
+    use crate::io::global_config::parse_global_config;
+    use crate::io::locate::{dropped_data_files_dir, locate_data_files_dir};
+    use crate::io::meta_schema::{ConflictStrategy, GlobalConfig, MetaDatabase};
+    use crate::io::paths::{create_dir_if_missing, extended_length_path};
+    use crate::io::preset::{Preset, PresetSettings};
+    use crate::io::report::DiffBaseline;
+    use crate::io::save_to_image::ImageOptions;
+    use crate::io::save_to_numeric::NumericFormat;
+    use crate::land::terrain_map::Vec2;
+    use crate::ParsedPlugins;
+    use anyhow::{anyhow, Context, Result};
+    use clap::{AppSettings, ArgEnum, ArgGroup, Args, Parser, Subcommand};
+    use hashbrown::HashSet;
+    use image::imageops::FilterType;
+    use log::{info, LevelFilter};
+    use shadow_rs::shadow;
+    use std::path::{Path, PathBuf};
+
+    shadow!(build);
+
+    /// The default `data_files_dir`, relative to the working directory.
+    const DEFAULT_DATA_FILES_DIR: &str = "Data Files";
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliLevelFilter {
+        Off,
+        Error,
+        Warn,
+        Info,
+        Debug,
+        Trace,
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum SortOrder {
+        Default,
+        None,
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliConflictStrategy {
+        Auto,
+        Resolve,
+        Overwrite,
+        Ignore,
+    }
+
+    impl From<CliConflictStrategy> for ConflictStrategy {
+        fn from(v: CliConflictStrategy) -> Self {
+            match v {
+                CliConflictStrategy::Auto => ConflictStrategy::Auto,
+                CliConflictStrategy::Resolve => ConflictStrategy::Resolve,
+                CliConflictStrategy::Overwrite => ConflictStrategy::Overwrite,
+                CliConflictStrategy::Ignore => ConflictStrategy::Ignore,
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliPreset {
+        /// Use the conflict strategies of each plugin and flag more conflicts.
+        Conservative,
+        /// Resolve every conflict by merging both sides and flag fewer conflicts.
+        Aggressive,
+        /// Keep the change from the last plugin in load order, like the game would.
+        VanillaFriendly,
+    }
+
+    impl From<CliPreset> for Preset {
+        fn from(v: CliPreset) -> Self {
+            match v {
+                CliPreset::Conservative => Preset::Conservative,
+                CliPreset::Aggressive => Preset::Aggressive,
+                CliPreset::VanillaFriendly => Preset::VanillaFriendly,
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliLandValue {
+        HeightMap,
+        VertexNormals,
+        WorldMapData,
+        VertexColors,
+        TextureIndices,
+    }
+
+    impl CliLandValue {
+        /// Returns the name of the value, e.g. as used for the `value` of a conflict.
+        pub fn name(&self) -> &'static str {
+            match self {
+                CliLandValue::HeightMap => "height_map",
+                CliLandValue::VertexNormals => "vertex_normals",
+                CliLandValue::WorldMapData => "world_map_data",
+                CliLandValue::VertexColors => "vertex_colors",
+                CliLandValue::TextureIndices => "texture_indices",
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliDiffBaseline {
+        /// Compare each plugin with the land of the masters.
+        Reference,
+        /// Compare each plugin with the merged land at its position in the load order.
+        Predecessor,
+    }
+
+    impl From<CliDiffBaseline> for DiffBaseline {
+        fn from(v: CliDiffBaseline) -> Self {
+            match v {
+                CliDiffBaseline::Reference => DiffBaseline::Reference,
+                CliDiffBaseline::Predecessor => DiffBaseline::Predecessor,
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliFilterType {
+        Nearest,
+        Triangle,
+        CatmullRom,
+        Gaussian,
+        Lanczos3,
+    }
+
+    impl From<CliFilterType> for FilterType {
+        fn from(v: CliFilterType) -> Self {
+            match v {
+                CliFilterType::Nearest => FilterType::Nearest,
+                CliFilterType::Triangle => FilterType::Triangle,
+                CliFilterType::CatmullRom => FilterType::CatmullRom,
+                CliFilterType::Gaussian => FilterType::Gaussian,
+                CliFilterType::Lanczos3 => FilterType::Lanczos3,
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliNumericFormat {
+        Pgm,
+        Npy,
+    }
+
+    impl From<CliNumericFormat> for NumericFormat {
+        fn from(v: CliNumericFormat) -> Self {
+            match v {
+                CliNumericFormat::Pgm => NumericFormat::Netpbm,
+                CliNumericFormat::Npy => NumericFormat::Npy,
+            }
+        }
+    }
+
+    #[derive(Copy, PartialEq, Eq, Debug, Hash, Clone, ArgEnum)]
+    pub enum CliExportFormat {
+        LodObj,
+        TesannwynRaw,
+        LandDiff,
+        Bundle,
+    }
+
+    impl From<CliExportFormat> for ExportFormat {
+        fn from(v: CliExportFormat) -> Self {
+            match v {
+                CliExportFormat::LodObj => ExportFormat::LodObj,
+                CliExportFormat::TesannwynRaw => ExportFormat::TesannwynRaw,
+                CliExportFormat::LandDiff => ExportFormat::LandDiff,
+                CliExportFormat::Bundle => ExportFormat::Bundle,
+            }
+        }
+    }
+
+    /// Parses the number of bits of a raw height map, which must be `16` or `32`.
+    fn parse_raw_bits(value: &str) -> Result<u8, String> {
+        match value.trim() {
+            "16" => Ok(16),
+            "32" => Ok(32),
+            _ => Err(format!("expected 16 or 32 but found `{}`", value)),
+        }
+    }
+
+    /// Parses the direction of a light in degrees, which must be from 0 up to 360.
+    fn parse_light_azimuth(value: &str) -> Result<f32, String> {
+        let degrees: f32 = value.trim().parse().map_err(|e| format!("{}", e))?;
+        if !(0. ..360.).contains(&degrees) {
+            return Err(format!("expected 0 up to 360 but found `{}`", value));
+        }
+
+        Ok(degrees)
+    }
+
+    /// Parses the angle of a light above the horizon in degrees, which must be above 0 up to 90.
+    fn parse_light_altitude(value: &str) -> Result<f32, String> {
+        let degrees: f32 = value.trim().parse().map_err(|e| format!("{}", e))?;
+        if !(degrees > 0. && degrees <= 90.) {
+            return Err(format!("expected above 0 up to 90 but found `{}`", value));
+        }
+
+        Ok(degrees)
+    }
+
+    /// Parses cell coordinates formatted as `x,y`.
+    fn parse_cell(value: &str) -> Result<Vec2<i32>, String> {
+        let (x, y) = value
+            .split_once(',')
+            .ok_or_else(|| format!("expected coordinates as `x,y` but found `{}`", value))?;
+        let x = x.trim().parse().map_err(|e| format!("invalid x: {}", e))?;
+        let y = y.trim().parse().map_err(|e| format!("invalid y: {}", e))?;
+        Ok(Vec2::new(x, y))
+    }
+
+    /// Returns the arguments to use when the tool is launched from Windows Explorer, or [None]
+    /// if it was launched some other way. Double-clicking the executable passes no arguments,
+    /// so the `Data Files` directory is located like any other run without `--data-files-dir`.
+    /// Dropping an `.ini` file or a folder onto the executable passes only its path. If the tool
+    /// is the only process using its console window, the window closes as soon as the tool exits,
+    /// so `--wait-for-exit` is added. See [owns_console].
+    fn explorer_args(args: &[String]) -> Option<Vec<String>> {
+        if !cfg!(windows) {
+            return None;
+        }
+
+        let data_files_dir = match args {
+            [_] => None,
+            [_, path] => Some(dropped_data_files_dir(&StdFileProvider, Path::new(path))?),
+            _ => return None,
+        };
+
+        let mut explorer_args = vec![args[0].clone()];
+        if owns_console() {
+            explorer_args.push("--wait-for-exit".to_string());
+        }
+
+        if let Some(data_files_dir) = data_files_dir {
+            explorer_args.push("--data-files-dir".to_string());
+            explorer_args.push(data_files_dir.to_string_lossy().to_string());
+        }
+
+        Some(explorer_args)
+    }
+
+    /// Returns `true` if the tool is the only process attached to its console, i.e. Windows
+    /// created the console for the tool, as when it is started from Explorer. Started from a
+    /// terminal, the terminal is attached to the same console and keeps it open.
+    #[cfg(windows)]
+    fn owns_console() -> bool {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetConsoleProcessList(process_list: *mut u32, process_count: u32) -> u32;
+        }
+
+        let mut process_list = [0u32; 2];
+        // SAFETY: `process_list` has room for the `process_count` process IDs that are written.
+        let num_processes =
+            unsafe { GetConsoleProcessList(process_list.as_mut_ptr(), process_list.len() as u32) };
+        num_processes == 1
+    }
+
+    /// Returns `false`. Only Windows closes the console window when the tool exits.
+    #[cfg(not(windows))]
+    fn owns_console() -> bool {
+        false
+    }
+
+    impl From<CliLevelFilter> for LevelFilter {
+        fn from(v: CliLevelFilter) -> Self {
+            match v {
+                CliLevelFilter::Off => LevelFilter::Off,
+                CliLevelFilter::Error => LevelFilter::Error,
+                CliLevelFilter::Warn => LevelFilter::Warn,
+                CliLevelFilter::Info => LevelFilter::Info,
+                CliLevelFilter::Debug => LevelFilter::Debug,
+                CliLevelFilter::Trace => LevelFilter::Trace,
+            }
+        }
+    }
+
+    #[derive(Parser, Debug)]
+    #[clap(author = "DVD")]
+    #[clap(about = "Merges lands.")]
+    #[clap(version = build::CLAP_LONG_VERSION)]
+    #[clap(long_about = None)] // Read from `Cargo.toml`
+    #[clap(global_setting(AppSettings::DeriveDisplayOrder))]
+    #[clap(group(ArgGroup::new("debug").multiple(true)))]
+    pub struct Cli {
+        #[clap(long, global = true, value_parser, default_value_t = String::from("."))]
+        /// The directory containing the `Conflicts` folder.
+        /// This is also where the `log_file` will be stored.
+        merged_lands_dir: String,
+
+        #[clap(long, global = true, value_parser)]
+        /// The absolute or relative path to the `Data Files` folder containing plugins.
+        /// If not provided, this is the `Data Files` folder in the working directory if it exists,
+        /// and otherwise the `Data Files` folder of a Morrowind installation found in the
+        /// registry, a Steam library folder, or a GOG folder.
+        data_files_dir: Option<String>,
+
+        #[clap(long, value_parser, default_value_t = String::from("Merged Lands.esp"))]
+        /// The name of the output file. This will be written to `output_file_dir`.
+        pub output_file: String,
+
+        #[clap(long, global = true, value_parser)]
+        /// The directory for the `output_file`.
+        /// If not provided, this is the same as `data_files_dir`.
+        output_file_dir: Option<String>,
+
+        #[clap(value_parser, required = false)]
+        /// An ordered list of plugins.
+        /// If this is not provided, the tool will look for an `.ini` file
+        /// in the directory above the `Data Files` and parse that for plugins.
+        input_file_names: Vec<String>,
+
+        #[clap(long, arg_enum, value_parser, default_value_t = SortOrder::Default)]
+        /// The method of sorting plugins.
+        /// `none` is only valid if `input_file_names` are provided.
+        pub sort_order: SortOrder,
+
+        #[clap(long, global = true, value_parser, default_value_t = String::from("merged_lands.log"))]
+        /// The name of the log file. This will be written to `merged_lands_dir`.
+        pub log_file: String,
+
+        #[clap(long, global = true, value_parser, default_value_t = String::from("merged_lands.toml"))]
+        /// The name of the config file. This will be read from `merged_lands_dir`.
+        /// If the file does not exist, the default config is used.
+        config_file: String,
+
+        #[clap(long, global = true, value_parser)]
+        /// A directory searched for meta files that are not in the `data_files_dir`, e.g. the
+        /// `mods` directory of Mod Organizer 2. Each mod folder inside it is searched too.
+        /// This may be provided more than once. Searched before the `meta_search_paths` of the config.
+        meta_search_path: Vec<String>,
+
+        #[clap(long, global = true, value_parser, default_value_t = String::from("resolutions.toml"))]
+        /// The name of the resolutions file. This will be read from `merged_lands_dir`.
+        /// Each run saves a template of this file with every major conflict to the `Conflicts`
+        /// directory. If the file does not exist, every conflict is merged automatically.
+        resolutions_file: String,
+
+        #[clap(long, global = true, value_parser)]
+        /// A directory of meta database `.toml` files to use instead of the bundled meta database.
+        /// The meta database provides settings for known plugins without a `.mergedlands.toml` file.
+        meta_db: Option<String>,
+
+        #[clap(long, global = true, value_parser)]
+        /// The application will not use any meta database when this flag is provided.
+        no_meta_db: bool,
+
+        #[clap(long, global = true, value_parser)]
+        /// The application will not create missing output or report directories when this
+        /// flag is provided. Report files are skipped if the `Conflicts` folder does not exist.
+        pub no_create_dirs: bool,
+
+        #[clap(long, global = true, value_parser)]
+        /// A directory of `.landdiff` patch files to merge like plugins.
+        /// If not provided, this is the `Patches` folder of `merged_lands_dir`, if it exists.
+        patches_dir: Option<String>,
+
+        #[clap(long, global = true, value_parser)]
+        /// The name of a zip archive for saving conflict images and other report files.
+        /// This will be written to `merged_lands_dir`.
+        /// If not provided, the files are saved to the `Conflicts` folder instead.
+        report_archive: Option<String>,
+
+        #[clap(long, global = true, arg_enum, value_parser, default_value_t = CliLevelFilter::Debug)]
+        /// The level of logging.
+        /// If set to Off, no log will will be written.
+        pub log_level: CliLevelFilter,
+
+        #[clap(long, global = true, value_parser)]
+        /// The application will write every trace message to the log file when this flag is
+        /// provided. Otherwise, only the first 1000 trace messages from each line of code are
+        /// written, followed by a count of the rest, so that the log of a large load order stays
+        /// small. Only used if `log_level` is Trace.
+        pub full_trace: bool,
+
+        #[clap(long, global = true, value_parser, default_value_t = 8)]
+        /// The size of the application's stack in MB.
+        stack_size_mb: u8,
+
+        #[clap(long, global = true, value_parser = clap::value_parser!(u16).range(1..))]
+        /// If provided, the land is merged in square chunks of this many cells along each side,
+        /// e.g. 32. Only the differences of each plugin in one chunk are kept in memory at once,
+        /// which reduces the peak memory used by the differences. The LAND records of every
+        /// plugin and the merged land of the whole world are still kept in memory, so the memory
+        /// used still grows with the load order. Seams inside of each chunk are repaired with the
+        /// chunk, and seams between chunks are repaired afterwards.
+        chunk_size: Option<u16>,
+
+        #[clap(long, value_parser = clap::value_parser!(u16).range(1..), requires = "chunk-size")]
+        /// If provided, the chunks are merged on this many threads. The merged land is the same
+        /// for any number of threads, since the chunks are combined in the same order.
+        threads: Option<u16>,
+
+        #[clap(long, value_parser)]
+        /// The application will remove all CELL records when this flag is provided.
+        pub remove_cell_records: bool,
+
+        #[clap(long, value_parser)]
+        /// The application will save only the merged LTEX records to the `output_file` when this
+        /// flag is provided, without any LAND or CELL records. This is useful to find which plugin
+        /// causes a texture problem, since the tiny plugin can be compared between load orders.
+        pub ltex_only: bool,
+
+        #[clap(long, global = true, value_parser)]
+        /// The application will only use the `base_masters` from the config file for the reference
+        /// land when this flag is provided. Any other masters, e.g. `TR_Mainland.esm`, are merged
+        /// like plugins instead. This can also be enabled with `diff_masters` in the config file.
+        pub diff_masters: bool,
+
+        #[clap(long, value_parser, group = "debug")]
+        /// The application will color the LAND vertex colors to show conflicts.
+        /// The colors are saved to a separate ` (Debug)` copy of the `output_file`.
+        pub add_debug_vertex_colors: bool,
+
+        #[clap(long, value_parser, group = "debug")]
+        /// The application will exaggerate the LAND vertex normals of cells with height spikes
+        /// after merging, so that the spikes stand out under in-game lighting. The normals are
+        /// saved to a separate ` (Debug)` copy of the `output_file`.
+        pub add_debug_normals: bool,
+
+        #[clap(long, value_parser)]
+        /// The application will apply every conflict strategy to each conflicted cell without
+        /// changing the merge, and list the cells where the strategies disagree in the report.
+        pub shadow_strategies: bool,
+
+        #[clap(long, value_parser)]
+        /// The application will save an image of each value of each cell modified by each plugin,
+        /// showing which of the modified vertices survived unchanged in the output and which were
+        /// altered by later plugins or repairs.
+        pub survival_images: bool,
+
+        #[clap(long, value_parser)]
+        /// The application will record which plugin and conflict strategy produced each vertex of
+        /// the merged land, and summarize it for each cell in the report. This uses a lot of memory.
+        /// Use the `inspect` command to query a single vertex.
+        pub record_provenance: bool,
+
+        #[clap(long, value_parser)]
+        /// The application will leave every cell with a major conflict out of the `output_file`
+        /// instead of averaging it, so that the game uses the LAND record of the last plugin in
+        /// the load order. The skipped cells are listed in the report.
+        pub skip_major_conflict_cells: bool,
+
+        #[clap(long, value_parser)]
+        /// A text file with the coordinates of a cell on each line, formatted as `x,y`. Each of
+        /// these cells is left out of the `output_file`, so that the game uses the LAND record of
+        /// the last plugin in the load order. The excluded cells are listed in the report.
+        exclude_cells: Option<String>,
+
+        #[clap(long, arg_enum, value_parser, default_value_t = CliDiffBaseline::Reference, conflicts_with = "chunk-size")]
+        /// The land each plugin is compared with to find conflicts. `predecessor` compares each
+        /// plugin with the merged land at its position in the load order, i.e. the land that the
+        /// plugin replaces in-game, instead of the land of the masters. The merge is unchanged.
+        diff_baseline: CliDiffBaseline,
+
+        #[clap(long, arg_enum, value_parser)]
+        /// A bundle of settings for running the tool non-interactively, e.g. from a mod list
+        /// installer. Every preset skips saving conflict images and omits the time from the
+        /// description of the `output_file`, so identical inputs produce an identical output.
+        /// The preset and its settings are saved in the report.
+        preset: Option<CliPreset>,
+
+        #[clap(long, value_parser)]
+        /// The application will regenerate the world map data of cells with modified heights
+        /// from the merged heights instead of merging it, and save an image of the world map.
+        pub regenerate_world_map: bool,
+
+        #[clap(long, value_parser)]
+        /// A land diff file to merge after the plugins. This may be provided more than once.
+        /// Land diff files are created with `export --format land-diff`.
+        land_diff: Vec<String>,
+
+        #[clap(long, value_parser)]
+        /// A `bundle.toml` file created with `export --format bundle` on another machine. The land
+        /// diff of each plugin in the bundle is merged after the plugins, if the CRC32 of each
+        /// master of the bundle matches the local masters.
+        bundle: Option<String>,
+
+        #[clap(long, value_parser, requires = "debug")]
+        /// The application will save the debug vertex colors and normals to the `output_file`
+        /// instead of a separate ` (Debug)` copy.
+        pub debug_in_main: bool,
+
+        #[clap(long, global = true, value_parser = parse_light_azimuth, default_value_t = ImageOptions::default().light_azimuth)]
+        /// The direction of the light used to shade height map images, in degrees clockwise from north.
+        light_azimuth: f32,
+
+        #[clap(long, global = true, value_parser = parse_light_altitude, default_value_t = ImageOptions::default().light_altitude)]
+        /// The angle of the light used to shade height map images, in degrees above the horizon.
+        light_altitude: f32,
+
+        #[clap(long, global = true, value_parser = clap::value_parser!(u8).range(1..), default_value_t = 4)]
+        /// The factor to resize images by. Use 1 to save images at their original size.
+        image_scale: u8,
+
+        #[clap(long, global = true, arg_enum, value_parser, default_value_t = CliFilterType::Nearest)]
+        /// The filter used when resizing images.
+        image_filter: CliFilterType,
+
+        #[clap(long, global = true, arg_enum, value_parser)]
+        /// If provided, the un-scaled data of each `MERGED` image is also saved as a PNG
+        /// and as a numeric dump in this format. Colors are saved as `.ppm` instead of `.pgm`.
+        numeric_dump: Option<CliNumericFormat>,
+
+        #[clap(long, global = true, value_parser)]
+        /// The application will wait for the user to hit the ENTER key before closing.
+        pub wait_for_exit: bool,
+
+        #[clap(subcommand)]
+        pub command: Option<Command>,
+    }
+
+    #[derive(Subcommand, Debug)]
+    pub enum Command {
+        /// Writes a copy of a plugin with any LAND and CELL records identical to its masters removed.
+        CleanPlugin(CleanPluginArgs),
+        /// Saves images of the LAND each conflict strategy would produce for a cell without merging.
+        Preview(PreviewArgs),
+        /// Saves the merged LAND in a format for other tools without saving a plugin.
+        Export(ExportArgs),
+        /// Saves the LAND merged from the masters before any plugin, to verify what the
+        /// plugins are compared to.
+        ExportReference(ExportReferenceArgs),
+        /// Merges the LAND and explains which plugin and conflict strategy produced a vertex.
+        Inspect(InspectArgs),
+        /// Saves the report of the conflicts between the plugins without merging them, to check
+        /// whether a load order needs a merged plugin at all.
+        Report,
+        /// Merges small synthetic plugins in memory and verifies the result, to check that the
+        /// tool works before investigating a load order. No files are read or saved.
+        SelfTest,
+    }
+
+    #[derive(Args, Debug)]
+    pub struct CleanPluginArgs {
+        #[clap(value_parser)]
+        /// The name of the plugin to clean.
+        /// The plugin and all of its masters must exist in `data_files_dir`.
+        pub plugin: String,
+
+        #[clap(long, value_parser)]
+        /// The name of the cleaned copy. This will be written to `output_file_dir`.
+        /// If not provided, this is the name of the plugin with ` (Clean)` appended.
+        pub output_file: Option<String>,
+    }
+
+    #[derive(Args, Debug)]
+    pub struct PreviewArgs {
+        #[clap(long, value_parser = parse_cell, allow_hyphen_values = true)]
+        /// The coordinates of the cell to preview, formatted as `x,y`.
+        pub cell: Vec2<i32>,
+
+        #[clap(long, arg_enum, value_parser)]
+        /// The conflict strategies to preview. This may be provided more than once.
+        /// If not provided, the `resolve`, `overwrite`, and `ignore` strategies are shown.
+        pub strategy: Vec<CliConflictStrategy>,
+    }
+
+    #[derive(Args, Debug)]
+    pub struct InspectArgs {
+        #[clap(long, value_parser = parse_cell, allow_hyphen_values = true)]
+        /// The coordinates of the cell to inspect, formatted as `x,y`.
+        pub cell: Vec2<i32>,
+
+        #[clap(long, value_parser = parse_cell)]
+        /// The coordinates of the vertex to inspect within the cell, formatted as `x,y`.
+        pub vertex: Vec2<i32>,
+
+        #[clap(long, arg_enum, value_parser, default_value_t = CliLandValue::HeightMap)]
+        /// The value of the LAND record to inspect.
+        value: CliLandValue,
+    }
+
+    impl InspectArgs {
+        /// Returns the value to inspect.
+        pub fn value(&self) -> CliLandValue {
+            self.value
+        }
+    }
+
+    #[derive(Args, Debug)]
+    pub struct ExportArgs {
+        #[clap(long, arg_enum, value_parser)]
+        /// The format to export.
+        pub format: CliExportFormat,
+
+        #[clap(long, value_parser)]
+        /// The directory to export to. This will be created if it does not exist.
+        /// If not provided, this is the `Export` folder of `merged_lands_dir`.
+        export_dir: Option<String>,
+
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..), default_value_t = 4)]
+        /// The number of cells along each side of a block saved as a single LOD mesh.
+        lod_block_size: u8,
+
+        #[clap(long, value_parser, default_value_t = 64)]
+        /// The largest difference in world units between a LOD mesh and the merged heights.
+        lod_tolerance: u16,
+
+        #[clap(long, value_parser = parse_raw_bits, default_value_t = 32)]
+        /// The number of bits of each value of a raw height map, either 16 or 32.
+        pub raw_bits: u8,
+    }
+
+    impl ExportArgs {
+        pub fn export_dir(&self, cli: &Cli) -> Result<PathBuf> {
+            match self.export_dir.as_ref() {
+                None => Ok(cli.merged_lands_dir()?.join("Export")),
+                Some(dir) => Ok(extended_length_path(Path::new(dir))),
+            }
+        }
+
+        pub fn lod_options(&self) -> LodOptions {
+            LodOptions {
+                block_size: self.lod_block_size as usize,
+                tolerance: self.lod_tolerance as i32,
+            }
+        }
+    }
+
+    #[derive(Args, Debug)]
+    pub struct ExportReferenceArgs {
+        #[clap(value_parser)]
+        /// The directory to export to. This will be created if it does not exist.
+        /// If not provided, this is the `Reference` folder of `merged_lands_dir`.
+        export_dir: Option<String>,
+    }
+
+    impl ExportReferenceArgs {
+        pub fn export_dir(&self, cli: &Cli) -> Result<PathBuf> {
+            match self.export_dir.as_ref() {
+                None => Ok(cli.merged_lands_dir()?.join("Reference")),
+                Some(dir) => Ok(extended_length_path(Path::new(dir))),
+            }
+        }
+    }
+
+    impl PreviewArgs {
+        pub fn strategies(&self) -> Vec<ConflictStrategy> {
+            if self.strategy.is_empty() {
+                vec![
+                    ConflictStrategy::Resolve,
+                    ConflictStrategy::Overwrite,
+                    ConflictStrategy::Ignore,
+                ]
+            } else {
+                self.strategy
+                    .iter()
+                    .map(|strategy| (*strategy).into())
+                    .collect()
+            }
+        }
+    }
+
+    impl Cli {
+        pub fn read_args() -> Cli {
+            let args = wild::args().collect::<Vec<_>>();
+            Cli::parse_from(explorer_args(&args).unwrap_or(args))
+        }
+
+        pub fn plugins(&self) -> Option<&[String]> {
+            (!self.input_file_names.is_empty()).then_some(&self.input_file_names)
+        }
+
+        pub fn should_write_log_file(&self) -> bool {
+            self.log_level != CliLevelFilter::Off
+        }
+
+        /// Returns the [FileProvider] used to read the plugins and save the output.
+        pub fn file_provider(&self) -> &'static dyn FileProvider {
+            &StdFileProvider
+        }
+
+        /// Returns `true` if debug vertex colors or debug normals are added to the merged land.
+        pub fn adds_debug(&self) -> bool {
+            self.add_debug_vertex_colors || self.add_debug_normals
+        }
+
+        pub fn merged_lands_dir(&self) -> Result<PathBuf> {
+            let dir = &self.merged_lands_dir;
+            Ok(extended_length_path(Path::new(dir)))
+        }
+
+        pub fn land_diffs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+            self.land_diff
+                .iter()
+                .map(|file_path| extended_length_path(Path::new(file_path)))
+        }
+
+        /// Returns the coordinates of each cell in the `exclude_cells` file, if provided.
+        pub fn excluded_cells(&self) -> Result<HashSet<Vec2<i32>>> {
+            match self.exclude_cells.as_ref() {
+                None => Ok(HashSet::new()),
+                Some(file_path) => load_excluded_cells(
+                    self.file_provider(),
+                    &extended_length_path(Path::new(file_path)),
+                ),
+            }
+        }
+
+        pub fn bundle(&self) -> Option<PathBuf> {
+            self.bundle
+                .as_ref()
+                .map(|file_path| extended_length_path(Path::new(file_path)))
+        }
+
+        /// Returns the `data_files_dir`, or the default `Data Files` directory if it was not
+        /// provided. See [locate_data_files_dir]. The located directory is logged if `log` is `true`.
+        fn find_data_files_dir(&self, log: bool) -> PathBuf {
+            if let Some(dir) = self.data_files_dir.as_ref() {
+                return PathBuf::from(dir);
+            }
+
+            let local_dir = PathBuf::from(DEFAULT_DATA_FILES_DIR);
+            if self.file_provider().is_dir(&local_dir).unwrap_or(false) {
+                return local_dir;
+            }
+
+            match locate_data_files_dir(self.file_provider()) {
+                Some((source, dir)) => {
+                    if log {
+                        info!(
+                            "Using {:?} installation at {}",
+                            source,
+                            dir.to_string_lossy()
+                        );
+                    }
+
+                    dir
+                }
+                None => local_dir,
+            }
+        }
+
+        pub fn data_files_dir(&self) -> Result<PathBuf> {
+            let dir = self.find_data_files_dir(true);
+            ParsedPlugins::check_dir_exists(self.file_provider(), &dir)
+                .with_context(|| anyhow!("Invalid `Data Files` directory"))?;
+            Ok(extended_length_path(&dir))
+        }
+
+        pub fn output_file_dir(&self) -> Result<PathBuf> {
+            if let Some(dir) = self
+                .output_file_dir
+                .as_ref()
+                .filter(|_| !self.no_create_dirs)
+            {
+                create_dir_if_missing(self.file_provider(), &extended_length_path(Path::new(dir)))?;
+            }
+
+            let dir = self
+                .output_file_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| self.find_data_files_dir(false));
+            ParsedPlugins::check_dir_exists(self.file_provider(), &dir)
+                .with_context(|| anyhow!("Invalid output file directory"))?;
+            Ok(extended_length_path(&dir))
+        }
+
+        pub fn config_file(&self) -> Result<PathBuf> {
+            Ok(self.merged_lands_dir()?.join(&self.config_file))
+        }
+
+        /// Returns the [GlobalConfig] parsed from the `config_file`, with any `meta_search_path`
+        /// searched before the `meta_search_paths` of the config file.
+        pub fn global_config(&self) -> Result<GlobalConfig> {
+            let mut config = parse_global_config(self.file_provider(), &self.config_file()?)?;
+            config
+                .meta_search_paths
+                .splice(0..0, self.meta_search_path.iter().cloned());
+            Ok(config)
+        }
+
+        pub fn resolutions_file(&self) -> Result<PathBuf> {
+            Ok(self.merged_lands_dir()?.join(&self.resolutions_file))
+        }
+
+        pub fn patches_dir(&self) -> Result<PathBuf> {
+            match self.patches_dir.as_ref() {
+                None => Ok(self.merged_lands_dir()?.join("Patches")),
+                Some(dir) => {
+                    ParsedPlugins::check_dir_exists(self.file_provider(), dir)
+                        .with_context(|| anyhow!("Invalid patches directory"))?;
+                    Ok(extended_length_path(Path::new(dir)))
+                }
+            }
+        }
+
+        pub fn meta_database(&self) -> Result<MetaDatabase> {
+            if self.no_meta_db {
+                return Ok(MetaDatabase::default());
+            }
+
+            match self.meta_db.as_ref() {
+                None => MetaDatabase::bundled(),
+                Some(dir) => {
+                    ParsedPlugins::check_dir_exists(self.file_provider(), dir)
+                        .with_context(|| anyhow!("Invalid meta database directory"))?;
+                    MetaDatabase::from_dir(
+                        self.file_provider(),
+                        &extended_length_path(Path::new(dir)),
+                    )
+                }
+            }
+        }
+
+        pub fn artifacts(&self) -> Result<ArtifactSink> {
+            let merged_lands_dir = self.merged_lands_dir()?;
+            if !self.no_create_dirs {
+                create_dir_if_missing(self.file_provider(), &merged_lands_dir)?;
+            }
+
+            match self.report_archive.as_ref() {
+                None => {
+                    let artifacts =
+                        ArtifactSink::conflicts_dir(self.file_provider(), &merged_lands_dir);
+                    if let ArtifactSink::Directory { directory: dir, .. } = &artifacts {
+                        if !self.no_create_dirs {
+                            create_dir_if_missing(self.file_provider(), dir)?;
+                        }
+                    }
+
+                    Ok(artifacts)
+                }
+                Some(report_archive) => ArtifactSink::archive(
+                    self.file_provider(),
+                    &merged_lands_dir.join(report_archive),
+                ),
+            }
+        }
+
+        pub fn image_options(&self) -> ImageOptions {
+            ImageOptions {
+                light_azimuth: self.light_azimuth,
+                light_altitude: self.light_altitude,
+                scale_factor: self.image_scale as usize,
+                filter: self.image_filter.into(),
+                numeric_format: self.numeric_dump.map(|format| format.into()),
+            }
+        }
+
+        pub fn diff_baseline(&self) -> DiffBaseline {
+            self.diff_baseline.into()
+        }
+
+        pub fn preset(&self) -> Option<Preset> {
+            self.preset.map(Preset::from)
+        }
+
+        /// Returns the [PresetSettings] of the `preset`, or the default [PresetSettings] if no
+        /// `preset` was provided.
+        pub fn preset_settings(&self) -> PresetSettings {
+            self.preset().map(Preset::settings).unwrap_or_default()
+        }
+
+        pub fn chunk_size(&self) -> Option<usize> {
+            self.chunk_size.map(|chunk_size| chunk_size as usize)
+        }
+
+        pub fn threads(&self) -> usize {
+            self.threads.map_or(1, |threads| threads as usize)
+        }
+
+        pub fn stack_size(&self) -> usize {
+            (self.stack_size_mb as usize) * 1024 * 1024
+        }
+    }
+}
+
+use cli::{Cli, Command};
+
+/// Runs the [Command] requested on the command line, or [merge_all] if there was none.
+/// Each [MergeEvent] is sent to the subscribers of the [RunContext], and long running commands
+/// stop early if its [CancellationToken] is cancelled.
+pub fn run(cli: &Cli, context: &RunContext) -> Result<(), Error> {
+    let result = match cli.command.as_ref() {
+        None => merge_all(cli, context).map(drop).map_err(Into::into),
+        Some(Command::CleanPlugin(args)) => clean_plugin(cli, args, context),
+        Some(Command::Preview(args)) => preview(cli, args, context),
+        Some(Command::Export(args)) => export(cli, args, context),
+        Some(Command::ExportReference(args)) => export_reference(cli, args, context),
+        Some(Command::Inspect(args)) => inspect(cli, args, context),
+        Some(Command::Report) => report_conflicts(cli, context),
+        Some(Command::SelfTest) => self_test(context),
+    };
+
+    // Summarize any trace messages skipped by the [BudgetedLogger].
+    log::logger().flush();
+    result.map_err(Error::from)
+}
+
+/// Returns `true` if the [CancellationToken] of the [RunContext] was cancelled, recording the
+/// `phase` in the `report`.
+fn check_cancelled(context: &RunContext, report: &mut Report, phase: &'static str) -> bool {
+    if !context.is_cancelled() {
+        return false;
+    }
+
+    if report.cancelled.is_none() {
+        report.cancelled = Some(phase.to_string());
+        context.emit(MergeEvent::Cancelled { phase });
+    }
+
+    true
+}
+
+/// The main function. Returns the [Report] of the run. If the [RunContext] is cancelled,
+/// the run stops at the next phase or plugin boundary and only the partial [Report] is saved.
+/// See [MergePipeline] to run only some of the stages.
+pub fn merge_all(cli: &Cli, context: &RunContext) -> Result<Report, Error> {
+    MergePipeline::new(cli)
+        .with_context(context)
+        .run(context.cancellation_token())
+        .map(|state| state.report)
+}
+
+/// Merges the `parsed_plugins` and returns a [Report] of the conflicts between each plugin and
+/// the merged land, using the [PresetSettings]. Unlike [merge_all], no files are read or saved,
+/// so this can run without a `Data Files` directory, e.g. in a web page.
+pub fn analyze_conflicts(
+    parsed_plugins: &ParsedPlugins,
+    settings: PresetSettings,
+    context: &RunContext,
+) -> Result<Report, Error> {
+    let config = GlobalConfig::default();
+    let mut known_textures = KnownTextures::new();
+
+    let mut report = Report {
+        settings,
+        ..Report::default()
+    };
+    report.warnings.extend(parsed_plugins.check_master_order());
+    report.warnings.extend(parsed_plugins.check_land_flags());
+    report.warnings.extend(parsed_plugins.check_land_records());
+    report
+        .warnings
+        .extend(parsed_plugins.check_texture_indices());
+    report.load_order = parsed_plugins.load_order.clone();
+
+    let (reference_landmass, modded_landmasses, duplicate_land, world_map_only_edits) =
+        create_landmasses(
+            &config,
+            parsed_plugins,
+            &mut known_textures,
+            config.diff_masters,
+            false,
+            context,
+        )?;
+    report.textures = known_textures.histories(parsed_plugins);
+    report.duplicate_land = duplicate_land;
+    report.world_map_only_edits = world_map_only_edits;
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Merging Lands",
+    });
+
+    let mut merged_lands = create_merged_lands_from_reference(reference_landmass);
+    for modded_landmass in modded_landmasses.iter() {
+        merge_landmass_into(
+            &mut merged_lands,
+            modded_landmass,
+            settings.conflict_strategy,
+            &Resolutions::default(),
+            context,
+        )?;
+    }
+
+    repair_landmass_seams_until_fixed(&mut merged_lands, &config.seams)?.add_to(&mut report);
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Summarizing Conflicts",
+    });
+
+    for modded_landmass in modded_landmasses.iter() {
+        report.conflicts.extend(analyze_landmass_conflicts(
+            &merged_lands,
+            modded_landmass,
+            &settings,
+        ));
+    }
+
+    report.clusters = cluster_conflicts(&report.conflicts, &merge_cells(parsed_plugins));
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Finished" });
+    Ok(report)
+}
+
+/// Initializes a [TermLogger] and [WriteLogger]. If the [WriteLogger] cannot be initialized,
+/// then the program will continue with only the [TermLogger].
+pub fn init_log(cli: &Cli) -> bool {
+    let config = ConfigBuilder::default()
+        .set_time_level(LevelFilter::Off)
+        .set_thread_level(LevelFilter::Off)
+        .set_location_level(LevelFilter::Off)
+        .set_target_level(LevelFilter::Off)
+        .set_level_padding(LevelPadding::Right)
+        .build();
+
+    let get_log_file_path = || {
+        let merged_lands_dir = cli.merged_lands_dir();
+        let log_file_name = &cli.log_file;
+        let log_file_path: Result<PathBuf> = match merged_lands_dir {
+            Ok(path) => Ok([path, PathBuf::from(log_file_name)].iter().collect()),
+            Err(e) => Err(e),
+        };
+        log_file_path
+    };
+
+    let files = cli.file_provider();
+    let write_logger = cli.should_write_log_file().then(|| {
+        let log_file_path = get_log_file_path()?;
+        if let Some(dir) = log_file_path.parent().filter(|_| !cli.no_create_dirs) {
+            // The logger is not initialized yet, so the directory is created silently.
+            files.create_dir_all(dir).with_context(|| {
+                anyhow!("Unable to create `{}` directory", dir.to_string_lossy())
+            })?;
+        }
+
+        files
+            .create(&log_file_path)
+            .map(|file| {
+                BudgetedLogger::new(
+                    WriteLogger::new(cli.log_level.into(), config.clone(), file),
+                    (!cli.full_trace).then_some(MAX_TRACE_MESSAGES),
+                )
+            })
+            .with_context(|| {
+                anyhow!(
+                    "Unable to create log file at {}",
+                    log_file_path.to_string_lossy()
+                )
+            })
+    });
+
+    let term_logger = TermLogger::new(
+        LevelFilter::Debug,
+        config,
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    );
+
+    match write_logger {
+        Some(Ok(write_logger)) => {
+            CombinedLogger::init(vec![term_logger, write_logger]).expect("safe");
+            trace!(
+                "Log file will be saved to {}",
+                get_log_file_path().expect("safe").to_string_lossy()
+            );
+
+            true
+        }
+        Some(Err(e)) => {
+            CombinedLogger::init(vec![term_logger]).expect("safe");
+            error!(
+                "{} {}",
+                format!(
+                    "Failed to create log file at {}",
+                    get_log_file_path()
+                        .unwrap_or_else(|_| PathBuf::from(&cli.log_file))
+                        .to_string_lossy()
+                        .bold()
+                )
+                .bright_red(),
+                format!("due to: {:?}", e.bold()).bright_red()
+            );
+
+            false
+        }
+        None => {
+            trace!("No log file will be created.");
+            CombinedLogger::init(vec![term_logger]).expect("safe");
+            false
+        }
+    }
+}
+
+/// Copy [Landscape] records from `plugin` and remap the texture indices with [RemappedTextures].
+/// [Landscape] records flagged as [ObjectFlags::IGNORED] are skipped, since the game does not
+/// load them. [ObjectFlags::BLOCKED] has no effect on LAND, so the flag is cleared instead.
+/// Several [Landscape] records for the same cell are combined with the [DuplicateRecordPolicy].
+fn try_copy_landscape_and_remap_textures(
+    plugin: &Arc<ParsedPlugin>,
+    remapped_textures: &RemappedTextures,
+    duplicate_records: DuplicateRecordPolicy,
+) -> Option<Landmass> {
+    let mut landmass = Landmass::new(plugin.clone());
+
+    if plugin.records.objects_of_type::<Landscape>().any(|_| true) {
+        debug!("Creating landmass from {}", plugin.name);
+    }
+
+    for land in plugin.records.objects_of_type::<Landscape>() {
+        if land.flags.contains(ObjectFlags::IGNORED) {
+            let coords = coordinates(land);
+            trace!(
+                "Skipping ignored LAND record at ({}, {}) in {}",
+                coords.x,
+                coords.y,
+                plugin.name
+            );
+            continue;
+        }
+
+        let mut updated_land = land.clone();
+        updated_land.flags.remove(ObjectFlags::BLOCKED);
+
+        if let Some(texture_indices) = updated_land.texture_indices.as_mut() {
+            for idx in texture_indices.data.iter_mut().flatten() {
+                // Indices without an LTEX record are reported by `check_texture_indices`.
+                *idx = remapped_textures
+                    .try_remapped_index(IndexVTEX::new(*idx))
+                    .unwrap_or_default()
+                    .as_u16();
+            }
+        }
+
+        let coords = coordinates(land);
+        if let Some(previous) = landmass.land.get(&coords) {
+            match duplicate_records {
+                DuplicateRecordPolicy::FirstWins => continue,
+                DuplicateRecordPolicy::LastWins => {}
+                DuplicateRecordPolicy::Merge => {
+                    if previous.flags == updated_land.flags
+                        && !updated_land.flags.contains(ObjectFlags::DELETED)
+                    {
+                        updated_land = merge_tes3_landscape(previous, &updated_land).expect("safe");
+                    }
+                }
+            }
+        }
+
+        landmass.insert_land(coords, plugin, &updated_land);
+    }
+
+    if !landmass.land.is_empty() {
+        Some(landmass)
+    } else {
+        None
+    }
+}
+
+/// Creates a [Landmass] from the `plugin` and updates [KnownTextures].
+/// See [try_copy_landscape_and_remap_textures].
+fn try_create_landmass(
+    parsed_plugins: &ParsedPlugins,
+    plugin: &Arc<ParsedPlugin>,
+    known_textures: &mut KnownTextures,
+    duplicate_records: DuplicateRecordPolicy,
+) -> Option<Landmass> {
+    if plugin
+        .records
+        .objects_of_type::<LandscapeTexture>()
+        .any(|_| true)
+    {
+        debug!("Remapping textures from {}", plugin.name);
+    }
+
+    let remapped_textures = add_known_textures(parsed_plugins, plugin, known_textures);
+    try_copy_landscape_and_remap_textures(plugin, &remapped_textures, duplicate_records)
+}
+
+/// Adds the LTEX records of the `plugin` to the [KnownTextures].
+/// Texture indices without an LTEX record in the `plugin` are remapped to the LTEX record of the
+/// first of its [ParsedPlugins::texture_dependencies] in the `parsed_plugins` that defines them.
+/// Returns the [RemappedTextures] for the texture indices of the `plugin`.
+fn add_known_textures(
+    parsed_plugins: &ParsedPlugins,
+    plugin: &Arc<ParsedPlugin>,
+    known_textures: &mut KnownTextures,
+) -> RemappedTextures {
+    let mut remapped_textures = RemappedTextures::new(known_textures);
+    for texture in plugin.records.objects_of_type::<LandscapeTexture>() {
+        known_textures.add_remapped_texture(plugin, texture, &mut remapped_textures);
+    }
+
+    if plugin.records.objects_of_type::<Landscape>().any(|_| true) {
+        for master in parsed_plugins.texture_dependencies(plugin) {
+            for texture in master.records.objects_of_type::<LandscapeTexture>() {
+                known_textures.add_dependency_texture(
+                    plugin,
+                    &master,
+                    texture,
+                    &mut remapped_textures,
+                );
+            }
+        }
+    }
+
+    remapped_textures
+}
+
+/// Returns a "merged" [Landscape] combining `rhs` and `lhs` by stomping over
+/// any changes in `lhs` with the records from `rhs`.
+/// Returns an `Err` if the flags of `lhs` and `rhs` differ, or if `rhs` is deleted.
+fn merge_tes3_landscape(lhs: &Landscape, rhs: &Landscape) -> Result<Landscape> {
+    let coords = coordinates(lhs);
+    if lhs.flags != rhs.flags {
+        bail!(MergeError::InvariantViolation {
+            coords,
+            invariant: "expected identical LAND flags",
+        });
+    }
+
+    if rhs.flags.contains(ObjectFlags::DELETED) {
+        bail!(MergeError::InvariantViolation {
+            coords,
+            invariant: "tried to add deleted LAND",
+        });
+    }
+
+    let mut land = lhs.clone();
+
+    let mut old_data = landscape_flags(lhs);
+    let new_data = landscape_flags(rhs);
+
+    if new_data.contains(LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS) {
+        if let Some(vertex_heights) = rhs.vertex_heights.as_ref() {
+            old_data |= LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS;
+            land.vertex_heights = Some(vertex_heights.clone());
+        }
+        if let Some(vertex_normals) = rhs.vertex_normals.as_ref() {
+            old_data |= LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS;
+            land.vertex_normals = Some(vertex_normals.clone());
+        }
+    }
+
+    if new_data.contains(LandscapeFlags::USES_VERTEX_COLORS) {
+        if let Some(vertex_colors) = rhs.vertex_colors.as_ref() {
+            old_data |= LandscapeFlags::USES_VERTEX_COLORS;
+            land.vertex_colors = Some(vertex_colors.clone());
+        }
+    }
+
+    if new_data.contains(LandscapeFlags::USES_TEXTURES) {
+        if let Some(texture_indices) = rhs.texture_indices.as_ref() {
+            old_data |= LandscapeFlags::USES_TEXTURES;
+            land.texture_indices = Some(texture_indices.clone());
+        }
+    }
+
+    if included_data(rhs).contains(LandData::WORLD_MAP) {
+        if let Some(world_map_data) = rhs.world_map_data.as_ref() {
+            land.world_map_data = Some(world_map_data.clone());
+        }
+    }
+
+    land.landscape_flags = old_data;
+
+    Ok(land)
+}
+
+/// Creates a single [Landmass] by calling [merge_tes3_landscape] on all `landmasses`.
+fn merge_tes3_landmasses(
+    plugin: &Arc<ParsedPlugin>,
+    landmasses: impl Iterator<Item = Landmass>,
+) -> Result<Landmass> {
+    let mut merged_landmass = Landmass::new(plugin.clone());
+
+    for landmass in landmasses {
+        for (coords, land) in landmass.land.iter() {
+            let merged_land = if merged_landmass.land.contains_key(coords) {
+                merge_tes3_landscape(merged_landmass.land.get(coords).expect("safe"), land)?
+            } else {
+                land.clone()
+            };
+
+            merged_landmass.insert_land(*coords, &landmass.plugin, &merged_land);
+        }
+    }
+
+    Ok(merged_landmass)
+}
+
+/// Given a [ParsedPlugin] and a specific [Landscape], returns [LandData] representing
+/// what should be used when creating or merging a [LandscapeDiff].
+fn find_allowed_data(plugin: &ParsedPlugin, land: &Landscape) -> LandData {
+    let mut allowed_data = included_data(land);
+    let coords = Vec2::new(land.grid.0, land.grid.1);
+
+    if !plugin.meta.height_map.includes(coords) {
+        allowed_data.remove(LandData::VERTEX_HEIGHTS | LandData::VERTEX_NORMALS);
+    }
+
+    if !plugin.meta.vertex_colors.includes(coords) {
+        allowed_data.remove(LandData::VERTEX_COLORS);
+    }
+
+    if !plugin.meta.texture_indices.includes(coords) {
+        allowed_data.remove(LandData::TEXTURES);
+    }
+
+    if !plugin.meta.world_map_data.includes(coords) {
+        allowed_data.remove(LandData::WORLD_MAP);
+    }
+
+    allowed_data
+}
+
+/// Creates the [LandscapeDiff] between the `land` of the `plugin` and the `reference` [Landscape].
+fn find_landscape_diff(
+    plugin: &ParsedPlugin,
+    land: &Landscape,
+    reference: Option<&Landscape>,
+) -> LandscapeDiff {
+    let allowed_data = find_allowed_data(plugin, land);
+    LandscapeDiff::from_difference(
+        land,
+        reference,
+        allowed_data,
+        HeightTransform::from_meta(&plugin.meta),
+    )
+}
+
+/// Creates a [LandmassDiff] representing the set of [LandscapeDiff] between the
+/// `landmass` and `reference` [Landmass].
+fn find_landmass_diff(landmass: &Landmass, reference: Arc<Landmass>) -> LandmassDiff {
+    let mut landmass_diff = LandmassDiff::new(landmass.plugin.clone());
+
+    for (coords, land) in landmass.land.iter() {
+        let landscape_diff =
+            find_landscape_diff(&landmass.plugin, land, reference.land.get(coords));
+        landmass_diff.land.insert(*coords, landscape_diff);
+    }
+
+    landmass_diff
+}
+
+/// Creates the reference [Landmass] from the masters in `parsed_plugins` and a [LandmassDiff]
+/// for each plugin. If `diff_masters` is `true`, only the `base_masters` in the [GlobalConfig] are
+/// used for the reference and a [LandmassDiff] is created for each other master as well.
+/// If `deferred` is `true`, the [LandscapeDiff] of each [LandmassDiff] are not created yet.
+/// Also returns each [DuplicateLand] that was only kept for one plugin, and each
+/// [WorldMapOnlyEdits] that was ignored because of `ignore_world_map_only_edits`.
+#[allow(clippy::type_complexity)]
+fn create_landmasses(
+    config: &GlobalConfig,
+    parsed_plugins: &ParsedPlugins,
+    known_textures: &mut KnownTextures,
+    diff_masters: bool,
+    deferred: bool,
+    context: &RunContext,
+) -> Result<(
+    Arc<Landmass>,
+    Vec<LandmassDiff>,
+    Vec<DuplicateLand>,
+    Vec<WorldMapOnlyEdits>,
+)> {
+    let (reference_masters, diffed_masters) = if diff_masters {
+        parsed_plugins.split_base_masters(&config.base_masters)
+    } else {
+        (parsed_plugins.masters.clone(), Vec::new())
+    };
+
+    if diff_masters {
+        debug!(
+            "Using {} base masters and diffing {} masters",
+            reference_masters.len(),
+            diffed_masters.len()
+        );
+    }
+
+    let reference_landmass = Arc::new(create_tes3_landmass(
+        "ReferenceLandmass.esp",
+        parsed_plugins,
+        reference_masters.iter(),
+        known_textures,
+        config.duplicate_records,
+    )?);
+
+    let (modded_landmasses, duplicate_land, world_map_only_edits) = create_modded_landmasses(
+        parsed_plugins,
+        &diffed_masters
+            .iter()
+            .chain(parsed_plugins.plugins.iter())
+            .collect_vec(),
+        &reference_landmass,
+        known_textures,
+        config.duplicate_records,
+        config.ignore_world_map_only_edits,
+        deferred,
+        context,
+    );
+
+    Ok((
+        reference_landmass,
+        modded_landmasses,
+        duplicate_land,
+        world_map_only_edits,
+    ))
+}
+
+/// Creates a [LandmassDiff] for each plugin in `parsed_plugins` that contains LAND records.
+/// Texture indices are remapped against the LTEX records of the masters in `all_plugins`.
+/// The [LandmassDiff] are calculated with respect to the `reference` [Landmass].
+/// Identical LAND records of several plugins are only kept for the last of them, and a
+/// [DuplicateLand] is returned for each set of plugins with identical LAND records.
+/// Several LAND records for the same cell in one plugin are combined with the [DuplicateRecordPolicy].
+/// If `ignore_world_map_only_edits` is `true`, LAND records that only change the world map data
+/// are removed, and a [WorldMapOnlyEdits] is returned for each plugin with such records.
+/// If `deferred` is `true`, each [LandscapeDiff] is only calculated when it is needed.
+#[allow(clippy::too_many_arguments)]
+fn create_modded_landmasses(
+    all_plugins: &ParsedPlugins,
+    parsed_plugins: &[&Arc<ParsedPlugin>],
+    reference: &Arc<Landmass>,
+    known_textures: &mut KnownTextures,
+    duplicate_records: DuplicateRecordPolicy,
+    ignore_world_map_only_edits: bool,
+    deferred: bool,
+    context: &RunContext,
+) -> (
+    Vec<LandmassDiff>,
+    Vec<DuplicateLand>,
+    Vec<WorldMapOnlyEdits>,
+) {
+    let mut landmasses = parsed_plugins
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, plugin)| {
+            if plugin.meta.meta_type == MetaType::MergedLands {
+                trace!("Skipping {}", plugin.name);
+                return None;
+            }
+
+            // Texture replacers only change LTEX records, so they do not contribute any land.
+            let landmass = if plugin.is_ltex_only() {
+                debug!("Updating textures from {}", plugin.name);
+                known_textures.update_textures(plugin);
+                None
+            } else {
+                context.time_plugin(TimedPhase::Diffing, &plugin.name, || {
+                    try_create_landmass(all_plugins, plugin, known_textures, duplicate_records)
+                })
+            };
+
+            context.emit(MergeEvent::PluginProcessed {
+                plugin: plugin.name.clone(),
+                index: idx + 1,
+                total: parsed_plugins.len(),
+            });
+
+            landmass
+        })
+        .collect_vec();
+
+    let duplicate_land = collapse_duplicate_land(&mut landmasses);
+    let world_map_only_edits = if ignore_world_map_only_edits {
+        drop_world_map_only_edits(&mut landmasses, reference)
+    } else {
+        Vec::new()
+    };
+
+    let landmass_diffs = landmasses
+        .into_iter()
+        .map(|landmass| {
+            warn_height_scale_mismatch(&landmass, reference);
+            if deferred {
+                LandmassDiff::deferred(landmass, reference.clone())
+            } else {
+                context.time_plugin(TimedPhase::Diffing, &landmass.plugin.name, || {
+                    find_landmass_diff(&landmass, reference.clone())
+                })
+            }
+        })
+        .collect_vec();
+
+    (landmass_diffs, duplicate_land, world_map_only_edits)
+}
+
+/// Merges `old` and `new` [LandscapeDiff].
+/// If `forced_strategy` is [Some], that [ConflictStrategy] is used instead of the
+/// [ConflictStrategy] in the [crate::io::meta_schema::PluginMeta] of the `plugin`.
+/// A [ConflictStrategy] from the `resolutions` is used instead of either.
+/// Any conflicts are emitted to the [RunContext].
+fn merge_landscape_diff(
+    plugin: &Arc<ParsedPlugin>,
+    old: &LandscapeDiff,
+    new: &LandscapeDiff,
+    forced_strategy: Option<ConflictStrategy>,
+    resolutions: &Resolutions,
+    context: &RunContext,
+) -> Result<LandscapeDiff> {
+    let mut merged = old.clone();
+    merged.plugins.push((plugin.clone(), new.modified_data()));
+
+    let coords = merged.coords;
+    let strategy = |value: &str, meta_strategy: ConflictStrategy| {
+        resolutions
+            .conflict_strategy(&plugin.name, coords, value)
+            .or(forced_strategy)
+            .unwrap_or(meta_strategy)
+    };
+
+    merged.height_map = apply_merge_strategy(
+        coords,
+        plugin,
+        "height_map",
+        old.height_map.as_ref(),
+        new.height_map.as_ref(),
+        strategy("height_map", plugin.meta.height_map.conflict_strategy),
+        context,
+    );
+
+    merged.vertex_normals = apply_merge_strategy(
+        coords,
+        plugin,
+        "vertex_normals",
+        old.vertex_normals.as_ref(),
+        new.vertex_normals.as_ref(),
+        strategy("vertex_normals", plugin.meta.height_map.conflict_strategy),
+        context,
+    );
+
+    if let Some(vertex_normals) = merged.vertex_normals.as_ref() {
+        merged.vertex_normals = Some(LandscapeDiff::apply_mask(
+            vertex_normals,
+            merged
+                .height_map
+                .as_ref()
+                .map(RelativeTerrainMap::differences),
+        ));
+    }
+
+    if merged.vertex_normals.is_modified() && !merged.height_map.is_modified() {
+        bail!(MergeError::InvariantViolation {
+            coords,
+            invariant: "modified vertex normals without modified heights",
+        });
+    }
+
+    merged.world_map_data = apply_merge_strategy(
+        coords,
+        plugin,
+        "world_map_data",
+        old.world_map_data.as_ref(),
+        new.world_map_data.as_ref(),
+        strategy(
+            "world_map_data",
+            plugin.meta.world_map_data.conflict_strategy,
+        ),
+        context,
+    );
+
+    merged.vertex_colors = apply_merge_strategy(
+        coords,
+        plugin,
+        "vertex_colors",
+        old.vertex_colors.as_ref(),
+        new.vertex_colors.as_ref(),
+        strategy("vertex_colors", plugin.meta.vertex_colors.conflict_strategy),
+        context,
+    );
+
+    merged.texture_indices = apply_merge_strategy(
+        coords,
+        plugin,
+        "texture_indices",
+        old.texture_indices.as_ref(),
+        new.texture_indices.as_ref(),
+        strategy(
+            "texture_indices",
+            plugin.meta.texture_indices.conflict_strategy,
+        ),
+        context,
+    );
+
+    Ok(merged)
+}
+
+/// Merges `plugin` [LandmassDiff] into `merged` [LandmassDiff].
+/// See [merge_landscape_diff] for the meaning of `forced_strategy`, `resolutions`, and `context`.
+pub fn merge_landmass_into(
+    merged: &mut LandmassDiff,
+    plugin: &LandmassDiff,
+    forced_strategy: Option<ConflictStrategy>,
+    resolutions: &Resolutions,
+    context: &RunContext,
+) -> Result<(), Error> {
+    debug!(
+        "Merging {} LAND records from {} into {}",
+        plugin.land.len(),
+        plugin.plugin.name,
+        merged.plugin.name
+    );
+
+    for (coords, land) in plugin.sorted() {
+        let merged_land = merge_optional_landscape_diff(
+            &plugin.plugin,
+            merged.land.get(coords),
+            land,
+            forced_strategy,
+            resolutions,
+            context,
+        )?;
+        merged.land.insert(*coords, merged_land);
+    }
+
+    Ok(())
+}
+
+/// Merges `new` [LandscapeDiff] into the `old` [LandscapeDiff], if it exists.
+/// See [merge_landscape_diff] for the meaning of `forced_strategy`, `resolutions`, and `context`.
+fn merge_optional_landscape_diff(
+    plugin: &Arc<ParsedPlugin>,
+    old: Option<&LandscapeDiff>,
+    new: &LandscapeDiff,
+    forced_strategy: Option<ConflictStrategy>,
+    resolutions: &Resolutions,
+    context: &RunContext,
+) -> Result<LandscapeDiff> {
+    if let Some(old) = old {
+        merge_landscape_diff(plugin, old, new, forced_strategy, resolutions, context)
+    } else {
+        let mut merged = new.clone();
+        merged.plugins.push((plugin.clone(), new.modified_data()));
+        Ok(merged)
+    }
+}
+
+/// Creates a [Landmass] from the `masters` of the `parsed_plugins` and updates [KnownTextures].
+fn create_tes3_landmass<'a>(
+    plugin_name: &str,
+    parsed_plugins: &ParsedPlugins,
+    masters: impl Iterator<Item = &'a Arc<ParsedPlugin>>,
+    known_textures: &mut KnownTextures,
+    duplicate_records: DuplicateRecordPolicy,
+) -> Result<Landmass> {
+    let plugin = Arc::new(ParsedPlugin::empty(plugin_name));
+    let master_landmasses = masters.flat_map(|esm| {
+        try_create_landmass(parsed_plugins, esm, known_textures, duplicate_records)
+    });
+    merge_tes3_landmasses(&plugin, master_landmasses)
+}
+
+/// Creates a [LandmassDiff] representing a set of empty [LandscapeDiff] for the `reference` [Landmass].
+/// Prior to returning, the [LandmassDiff] will be updated by [repair_landmass_seams_until_fixed].
+fn create_merged_lands_from_reference(reference: Arc<Landmass>) -> LandmassDiff {
+    let mut landmass_diff = LandmassDiff::new(reference.plugin.clone());
+
+    for (coords, land) in reference.land.iter() {
+        let allowed_data = included_data(land);
+        let plugin = reference.plugins.get(coords).expect("safe");
+        let landscape_diff = LandscapeDiff::from_reference(plugin.clone(), land, allowed_data);
+        assert!(!landscape_diff.is_modified());
+        landmass_diff.land.insert(*coords, landscape_diff);
+    }
+
+    for (_, land) in landmass_diff.land.iter_mut() {
+        assert_eq!(land.plugins.len(), 1);
+        let modified_data = land.modified_data();
+        let plugin_data = land.plugins.get_mut(0).expect("safe");
+        plugin_data.1 = modified_data;
+    }
+
+    landmass_diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::save_to_plugin::convert_landmass_diff_to_landmass;
+    use crate::test_support::{hill_heights, landscape, plugin, PluginBuilder, CELL};
+    use tes3::esp::TES3Object;
+
+    /// Each combination of [ObjectFlags::IGNORED] and [ObjectFlags::BLOCKED] of a LAND record,
+    /// and the flags of the LAND record once it is read, or [None] if it is skipped.
+    fn flag_combinations() -> [(ObjectFlags, Option<ObjectFlags>); 4] {
+        [
+            (ObjectFlags::empty(), Some(ObjectFlags::empty())),
+            (ObjectFlags::IGNORED, None),
+            (ObjectFlags::BLOCKED, Some(ObjectFlags::empty())),
+            (ObjectFlags::IGNORED | ObjectFlags::BLOCKED, None),
+        ]
+    }
+
+    /// Returns a [ParsedPlugin] with a LAND record at [CELL] with the `flags`.
+    fn plugin_with_land(flags: ObjectFlags) -> Arc<ParsedPlugin> {
+        let mut land = landscape(CELL, &hill_heights(CELL, 4), 1);
+        land.flags = flags | ObjectFlags::MODIFIED;
+
+        PluginBuilder::new("Flagged.esp")
+            .record(TES3Object::Landscape(land))
+            .build()
+    }
+
+    fn identity_textures() -> RemappedTextures {
+        RemappedTextures::from(&[true; 2])
+    }
+
+    #[test]
+    fn flagged_land_is_read_per_flag() {
+        for (flags, expected) in flag_combinations() {
+            let landmass = try_copy_landscape_and_remap_textures(
+                &plugin_with_land(flags),
+                &identity_textures(),
+                DuplicateRecordPolicy::default(),
+            );
+
+            let actual = landmass
+                .as_ref()
+                .and_then(|landmass| landmass.land.get(&CELL))
+                .map(|land| land.flags - ObjectFlags::MODIFIED);
+            assert_eq!(actual, expected, "reading LAND flagged {:?}", flags);
+        }
+    }
+
+    #[test]
+    fn flagged_land_is_written_per_flag() {
+        for (flags, expected) in flag_combinations() {
+            let Some(landmass) = try_copy_landscape_and_remap_textures(
+                &plugin_with_land(flags),
+                &identity_textures(),
+                DuplicateRecordPolicy::default(),
+            ) else {
+                assert_eq!(expected, None, "writing LAND flagged {:?}", flags);
+                continue;
+            };
+
+            let reference = Arc::new(Landmass::new(plugin("Master.esm")));
+            let diff = find_landmass_diff(&landmass, reference.clone());
+            assert_eq!(
+                diff.land
+                    .get(&CELL)
+                    .map(|land| land.flags - ObjectFlags::MODIFIED),
+                expected,
+                "diffing LAND flagged {:?}",
+                flags
+            );
+
+            let mut merged = create_merged_lands_from_reference(reference);
+            merge_landmass_into(
+                &mut merged,
+                &diff,
+                None,
+                &Resolutions::default(),
+                &RunContext::default(),
+            )
+            .unwrap();
+            let written = convert_landmass_diff_to_landmass(&merged, &identity_textures(), 0);
+            assert_eq!(
+                written
+                    .land
+                    .get(&CELL)
+                    .map(|land| land.flags - ObjectFlags::MODIFIED),
+                expected,
+                "writing LAND flagged {:?}",
+                flags
+            );
+        }
+    }
+}
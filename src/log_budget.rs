@@ -0,0 +1,119 @@
+use hashbrown::HashMap;
+use itertools::Itertools;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, SharedLogger};
+use std::sync::Mutex;
+
+/// The number of trace messages logged from each line of code before the rest are counted.
+pub const MAX_TRACE_MESSAGES: usize = 1000;
+
+/// The number of trace messages logged and skipped from a line of code.
+#[derive(Default)]
+struct TraceCount {
+    num_logged: usize,
+    num_skipped: usize,
+}
+
+/// A [BudgetedLogger] logs at most `max_trace_messages` trace messages from each line of code
+/// to the `inner` [SharedLogger], since a merge of a large load order traces every cell and
+/// texture. Skipped messages are counted and summarized as `... and N more` when flushed.
+/// Messages of any other level are always logged.
+pub struct BudgetedLogger {
+    inner: Box<dyn SharedLogger>,
+    max_trace_messages: Option<usize>,
+    counts: Mutex<HashMap<(&'static str, u32), TraceCount>>,
+}
+
+impl BudgetedLogger {
+    /// Creates a [BudgetedLogger] for the `inner` [SharedLogger]. If `max_trace_messages` is
+    /// [None], every trace message is logged.
+    pub fn new(inner: Box<dyn SharedLogger>, max_trace_messages: Option<usize>) -> Box<Self> {
+        Box::new(Self {
+            inner,
+            max_trace_messages,
+            counts: Default::default(),
+        })
+    }
+}
+
+impl Log for BudgetedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let (Some(max_trace_messages), Level::Trace, Some(file), Some(line)) = (
+            self.max_trace_messages,
+            record.level(),
+            record.file_static(),
+            record.line(),
+        ) else {
+            self.inner.log(record);
+            return;
+        };
+
+        let mut counts = self.counts.lock().expect("safe");
+        let count = counts.entry((file, line)).or_default();
+        if count.num_logged == max_trace_messages {
+            count.num_skipped += 1;
+            return;
+        }
+
+        count.num_logged += 1;
+        self.inner.log(record);
+
+        if count.num_logged == max_trace_messages {
+            self.inner.log(
+                &Record::builder()
+                    .level(Level::Trace)
+                    .args(format_args!(
+                        "Skipping further trace messages from {}:{}",
+                        file, line
+                    ))
+                    .build(),
+            );
+        }
+    }
+
+    /// Logs a summary of the trace messages skipped since the last flush, then flushes the
+    /// `inner` [SharedLogger].
+    fn flush(&self) {
+        let mut counts = self.counts.lock().expect("safe");
+        for ((file, line), count) in counts
+            .iter_mut()
+            .filter(|(_, count)| count.num_skipped > 0)
+            .sorted_by_key(|(site, _)| **site)
+        {
+            self.inner.log(
+                &Record::builder()
+                    .level(Level::Trace)
+                    .args(format_args!(
+                        "... and {} more trace messages from {}:{}",
+                        count.num_skipped, file, line
+                    ))
+                    .build(),
+            );
+            count.num_skipped = 0;
+        }
+
+        self.inner.flush();
+    }
+}
+
+impl SharedLogger for BudgetedLogger {
+    fn level(&self) -> LevelFilter {
+        self.inner.level()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.inner.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -1,14 +1,81 @@
 use crate::io::meta_schema::MetaType;
 use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::io::report::{PluginRegion, ReportWarning};
 use crate::land::terrain_map::Vec2;
 use hashbrown::HashMap;
-use std::default::default;
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
 use std::sync::Arc;
-use tes3::esp::Cell;
+use tes3::esp::{Cell, CellFlags, Region};
 
 pub struct ModifiedCell {
     pub inner: Cell,
     pub plugins: Vec<Arc<ParsedPlugin>>,
+    /// The [CellFlags] of the cell after merging the masters.
+    master_flags: CellFlags,
+    /// The [CellFlags] of the last plugin that modified the cell.
+    last_flags: CellFlags,
+    /// The region of the cell after merging the masters.
+    master_region: Option<String>,
+    /// Each plugin that sets the region of the cell and the region it sets, in load order.
+    regions: Vec<(Arc<ParsedPlugin>, String)>,
+    /// The last plugin in the load order with a REGN record for the region of the cell, if any.
+    pub region_source: Option<Arc<ParsedPlugin>>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// How a flag of the [CellFlags] of a plugin is merged into the [ModifiedCell].
+enum FlagMerge {
+    /// The flag is set if any plugin sets it.
+    Any,
+    /// The flag is set only if every plugin sets it.
+    All,
+    /// The flag of the last plugin that changes it from the masters is used.
+    LastChange,
+}
+
+/// The [FlagMerge] and name of each known flag of the [CellFlags].
+/// Any other flags are merged with [FlagMerge::Any].
+const CELL_FLAGS_MERGE: [(CellFlags, FlagMerge, &str); 4] = [
+    // An exterior cell is never turned into an interior cell by a single plugin.
+    (CellFlags::IS_INTERIOR, FlagMerge::All, "is_interior"),
+    // A plugin that removes water must not have it added back by plugins that copy the masters.
+    (CellFlags::HAS_WATER, FlagMerge::LastChange, "has_water"),
+    (
+        CellFlags::ILLEGAL_TO_SLEEP,
+        FlagMerge::Any,
+        "illegal_to_sleep",
+    ),
+    (
+        CellFlags::BEHAVES_LIKE_EXTERIOR,
+        FlagMerge::Any,
+        "behaves_like_exterior",
+    ),
+];
+
+/// Merges the `rhs` [CellFlags] into the `lhs` [CellFlags] with the [FlagMerge] of each flag.
+/// The `master` [CellFlags] are the flags of the cell after merging the masters.
+fn merge_cell_flags(lhs: CellFlags, rhs: CellFlags, master: CellFlags) -> CellFlags {
+    let mut merged = lhs | rhs;
+
+    for (flag, merge, _) in CELL_FLAGS_MERGE {
+        let value = match merge {
+            FlagMerge::Any => lhs.contains(flag) || rhs.contains(flag),
+            FlagMerge::All => lhs.contains(flag) && rhs.contains(flag),
+            FlagMerge::LastChange => {
+                if rhs.contains(flag) != master.contains(flag) {
+                    rhs.contains(flag)
+                } else {
+                    lhs.contains(flag)
+                }
+            }
+        };
+
+        merged.set(flag, value);
+    }
+
+    merged
 }
 
 fn merge_cell_into(lhs: &mut ModifiedCell, rhs: &Cell, plugin: &Arc<ParsedPlugin>) {
@@ -22,18 +89,24 @@ fn merge_cell_into(lhs: &mut ModifiedCell, rhs: &Cell, plugin: &Arc<ParsedPlugin
 
     if new.data != rhs.data {
         assert_eq!(new.data.grid, rhs.data.grid);
-        new.data.flags |= rhs.data.flags;
+        new.data.flags = merge_cell_flags(new.data.flags, rhs.data.flags, lhs.master_flags);
         is_modified = true;
     }
 
+    lhs.last_flags = rhs.data.flags;
+
     if !rhs.id.is_empty() && new.id != rhs.id {
         new.id = rhs.id.clone();
         is_modified = true;
     }
 
-    if let Some(record) = new.region.as_ref() {
-        new.region = Some(record.clone());
-        is_modified = true;
+    if let Some(region) = rhs.region.as_ref() {
+        if new.region.as_ref() != Some(region) {
+            new.region = Some(region.clone());
+            is_modified = true;
+        }
+
+        lhs.regions.push((plugin.clone(), region.clone()));
     }
 
     if let Some(record) = new.map_color.as_ref() {
@@ -79,9 +152,18 @@ fn merge_cells_into(cells: &mut HashMap<Vec2<i32>, ModifiedCell>, plugins: &[Arc
                         map_color: cell.map_color,
                         water_height: cell.water_height,
                         atmosphere_data: cell.atmosphere_data.clone(),
-                        references: default(),
+                        references: Default::default(),
                     },
                     plugins: vec![plugin.clone()],
+                    master_flags: cell.data.flags,
+                    last_flags: cell.data.flags,
+                    master_region: None,
+                    regions: cell
+                        .region
+                        .iter()
+                        .map(|region| (plugin.clone(), region.clone()))
+                        .collect(),
+                    region_source: None,
                 };
 
                 cells.insert(coords, new_cell);
@@ -91,10 +173,273 @@ fn merge_cells_into(cells: &mut HashMap<Vec2<i32>, ModifiedCell>, plugins: &[Arc
 }
 
 pub fn merge_cells(parsed_plugins: &ParsedPlugins) -> HashMap<Vec2<i32>, ModifiedCell> {
-    let mut cells = default();
+    let mut cells = Default::default();
 
     merge_cells_into(&mut cells, &parsed_plugins.masters);
+
+    for cell in cells.values_mut() {
+        cell.master_flags = cell.inner.data.flags;
+        cell.master_region = cell.inner.region.clone();
+    }
+
     merge_cells_into(&mut cells, &parsed_plugins.plugins);
 
+    // Later plugins override the REGN records of earlier plugins.
+    let mut region_sources = HashMap::new();
+    for plugin in parsed_plugins
+        .masters
+        .iter()
+        .chain(parsed_plugins.plugins.iter())
+    {
+        for region in plugin.records.objects_of_type::<Region>() {
+            region_sources.insert(region.id.to_lowercase(), plugin.clone());
+        }
+    }
+
+    for cell in cells.values_mut() {
+        cell.region_source = cell
+            .inner
+            .region
+            .as_ref()
+            .and_then(|region| region_sources.get(&region.to_lowercase()).cloned());
+    }
+
     cells
 }
+
+/// Returns a [ReportWarning] for each [ModifiedCell] with [CellFlags] that differ from the flags
+/// of the last plugin that modified the cell, i.e. the flags the game would use without merging.
+/// The warnings are ordered by `x` and `y` coordinates.
+pub fn check_cell_flags(cells: &HashMap<Vec2<i32>, ModifiedCell>) -> Vec<ReportWarning> {
+    let mut warnings = Vec::new();
+
+    for (coords, cell) in cells
+        .iter()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+    {
+        let merged = cell.inner.data.flags;
+        let flags = CELL_FLAGS_MERGE
+            .iter()
+            .filter(|(flag, _, _)| merged.contains(*flag) != cell.last_flags.contains(*flag))
+            .map(|(_, _, name)| name.to_string())
+            .collect_vec();
+
+        if flags.is_empty() {
+            continue;
+        }
+
+        let plugin = cell
+            .plugins
+            .last()
+            .map(|plugin| plugin.name.clone())
+            .unwrap_or_default();
+
+        warn!(
+            "{} {}",
+            format!(
+                "Merged CELL record at ({}, {}) has flags {}",
+                coords.x,
+                coords.y,
+                flags.join(", ").bold()
+            )
+            .yellow(),
+            format!("that differ from the last plugin {}", plugin.bold()).yellow()
+        );
+
+        warnings.push(ReportWarning::CellFlagConflict {
+            plugin,
+            x: coords.x,
+            y: coords.y,
+            flags,
+        });
+    }
+
+    warnings
+}
+
+/// Returns a [ReportWarning] for each [ModifiedCell] with a region that no plugin in the load
+/// order has a REGN record for, and for each [ModifiedCell] that plugins set to different regions
+/// than the masters and each other. The warnings are ordered by `x` and `y` coordinates.
+pub fn check_cell_regions(cells: &HashMap<Vec2<i32>, ModifiedCell>) -> Vec<ReportWarning> {
+    let mut warnings = Vec::new();
+
+    for (coords, cell) in cells
+        .iter()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+    {
+        let Some(region) = cell.inner.region.as_ref() else {
+            continue;
+        };
+
+        if cell.region_source.is_none() {
+            let plugin = cell
+                .regions
+                .last()
+                .map(|(plugin, _)| plugin.name.clone())
+                .unwrap_or_default();
+
+            warn!(
+                "{} {}",
+                format!(
+                    "Merged CELL record at ({}, {}) has region {}",
+                    coords.x,
+                    coords.y,
+                    region.bold()
+                )
+                .yellow(),
+                format!(
+                    "from {} without a REGN record in the load order",
+                    plugin.bold()
+                )
+                .yellow()
+            );
+
+            warnings.push(ReportWarning::MissingRegion {
+                plugin,
+                x: coords.x,
+                y: coords.y,
+                region: region.clone(),
+            });
+        }
+
+        let master_region = cell
+            .master_region
+            .as_ref()
+            .map(|region| region.to_lowercase());
+        let changed_regions = cell
+            .regions
+            .iter()
+            .filter(|(_, region)| Some(region.to_lowercase()) != master_region)
+            .collect_vec();
+
+        let num_regions = changed_regions
+            .iter()
+            .map(|(_, region)| region.to_lowercase())
+            .unique()
+            .count();
+
+        if num_regions < 2 {
+            continue;
+        }
+
+        warn!(
+            "{} {}",
+            format!(
+                "Plugins set CELL record at ({}, {}) to {} different regions.",
+                coords.x, coords.y, num_regions
+            )
+            .yellow(),
+            format!("The merged region is {}", region.bold()).yellow()
+        );
+
+        warnings.push(ReportWarning::RegionConflict {
+            x: coords.x,
+            y: coords.y,
+            region: region.clone(),
+            plugins: changed_regions
+                .into_iter()
+                .map(|(plugin, region)| PluginRegion {
+                    plugin: plugin.name.clone(),
+                    region: region.clone(),
+                })
+                .collect(),
+        });
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{parsed_plugins, PluginBuilder};
+    use tes3::esp::{CellData, TES3Object};
+
+    const NONE: CellFlags = CellFlags::empty();
+    const INTERIOR: CellFlags = CellFlags::IS_INTERIOR;
+    const WATER: CellFlags = CellFlags::HAS_WATER;
+    const NO_SLEEP: CellFlags = CellFlags::ILLEGAL_TO_SLEEP;
+    const LIKE_EXTERIOR: CellFlags = CellFlags::BEHAVES_LIKE_EXTERIOR;
+
+    #[test]
+    fn flags_merge_per_flag() {
+        // (lhs, rhs, master, merged)
+        let cases = [
+            // Any plugin can set these flags.
+            (NONE, NO_SLEEP, NONE, NO_SLEEP),
+            (NO_SLEEP, NONE, NONE, NO_SLEEP),
+            (NO_SLEEP, NONE, NO_SLEEP, NO_SLEEP),
+            (LIKE_EXTERIOR, NO_SLEEP, NONE, LIKE_EXTERIOR | NO_SLEEP),
+            // Only interior if every plugin agrees.
+            (INTERIOR, INTERIOR, INTERIOR, INTERIOR),
+            (INTERIOR, NONE, INTERIOR, NONE),
+            (NONE, INTERIOR, NONE, NONE),
+            // Water follows the last plugin that changes it from the masters.
+            (WATER, WATER, WATER, WATER),
+            (NONE, WATER, WATER, NONE),
+            (WATER, NONE, WATER, NONE),
+            (NONE, WATER, NONE, WATER),
+            (WATER, NONE, NONE, WATER),
+            (NONE, NONE, NONE, NONE),
+        ];
+
+        for (lhs, rhs, master, merged) in cases {
+            assert_eq!(
+                merge_cell_flags(lhs, rhs, master),
+                merged,
+                "merging {:?} into {:?} with masters {:?}",
+                rhs,
+                lhs,
+                master
+            );
+        }
+    }
+
+    /// Returns a [ParsedPlugin] named `name` with an exterior CELL at `(0, 0)` with the `flags`.
+    fn plugin_with_cell(name: &str, flags: CellFlags) -> Arc<ParsedPlugin> {
+        PluginBuilder::new(name)
+            .record(TES3Object::Cell(Cell {
+                data: CellData {
+                    flags,
+                    grid: (0, 0),
+                },
+                ..Default::default()
+            }))
+            .build()
+    }
+
+    #[test]
+    fn removed_water_is_not_added_back() {
+        let parsed_plugins = parsed_plugins(
+            vec![plugin_with_cell("Master.esm", WATER)],
+            vec![
+                plugin_with_cell("Dry.esp", NONE),
+                plugin_with_cell("Copy.esp", WATER | NO_SLEEP),
+            ],
+        );
+
+        let cells = merge_cells(&parsed_plugins);
+        let cell = &cells[&Vec2::new(0, 0)];
+        assert_eq!(cell.inner.data.flags, NO_SLEEP);
+
+        let warnings = check_cell_flags(&cells);
+        assert_eq!(warnings.len(), 1);
+        let ReportWarning::CellFlagConflict { plugin, flags, .. } = &warnings[0] else {
+            panic!("expected a cell flag conflict");
+        };
+        assert_eq!(plugin, "Copy.esp");
+        assert_eq!(flags, &vec!["has_water".to_string()]);
+    }
+
+    #[test]
+    fn matching_flags_are_not_reported() {
+        let parsed_plugins = parsed_plugins(
+            vec![plugin_with_cell("Master.esm", WATER)],
+            vec![plugin_with_cell("A.esp", WATER | NO_SLEEP)],
+        );
+
+        let cells = merge_cells(&parsed_plugins);
+        assert_eq!(cells[&Vec2::new(0, 0)].inner.data.flags, WATER | NO_SLEEP);
+        assert!(check_cell_flags(&cells).is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+use crate::land::terrain_map::Vec2;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+/// Returns the coordinates of the chunk of `chunk_size` cells containing the cell at `coords`.
+pub fn chunk_coords(coords: Vec2<i32>, chunk_size: usize) -> Vec2<i32> {
+    let chunk_size = chunk_size as i32;
+    Vec2::new(
+        coords.x.div_euclid(chunk_size),
+        coords.y.div_euclid(chunk_size),
+    )
+}
+
+/// Groups the `cells` into square chunks of `chunk_size` cells along each side.
+/// Each chunk is returned as the set of its cells, ordered by the `x` and `y` coordinates
+/// of the chunk.
+pub fn chunk_cells(
+    cells: impl Iterator<Item = Vec2<i32>>,
+    chunk_size: usize,
+) -> Vec<HashSet<Vec2<i32>>> {
+    let mut chunks: HashMap<Vec2<i32>, HashSet<Vec2<i32>>> = HashMap::new();
+    for coords in cells {
+        chunks
+            .entry(chunk_coords(coords, chunk_size))
+            .or_default()
+            .insert(coords);
+    }
+
+    chunks
+        .into_iter()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+        .map(|(_, cells)| cells)
+        .collect()
+}
@@ -45,16 +45,16 @@ fn classify_conflict<U>(lhs: f32, rhs: f32, params: &ConflictParams) -> Conflict
 where
     f32: RoundTo<U>,
 {
-    let lhs_weight = (lhs.abs() as f32) / ((lhs.abs() as f32) + (rhs.abs() as f32));
+    let lhs_weight = lhs.abs() / (lhs.abs() + rhs.abs());
     let rhs_weight = 1. - lhs_weight;
     let lhs_weight_2 = lhs_weight.powf(1.5);
     let rhs_weight_2 = rhs_weight.powf(1.5);
     let lhs_weight = lhs_weight_2 / (lhs_weight_2 + rhs_weight_2);
     let rhs_weight = 1. - lhs_weight;
-    let average = lhs_weight * (lhs as f32) + rhs_weight * (rhs as f32);
-    let minimum = lhs.min(rhs) as f32;
+    let average = lhs_weight * lhs + rhs_weight * rhs;
+    let minimum = lhs.min(rhs);
     let proportional_threshold =
-        (params.minor_threshold_pct * minimum as f32).max(params.minor_threshold_min);
+        (params.minor_threshold_pct * minimum).max(params.minor_threshold_min);
     let difference = f32::abs(minimum - average);
     if difference >= proportional_threshold.min(params.minor_threshold_max) {
         ConflictType::Major(average.round_to())
@@ -0,0 +1,98 @@
+use crate::land::grid_access::Index2D;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// A [DifferenceMask] is a fixed bitset with one bit for each coordinate of a `T` by `T` grid.
+/// Each row is stored as a [u128], so `T` cannot exceed `128`. The number of set bits is
+/// cached so that [DifferenceMask::is_empty] and [DifferenceMask::count] are `O(1)`.
+pub struct DifferenceMask<const T: usize> {
+    rows: [u128; T],
+    count: usize,
+}
+
+impl<const T: usize> DifferenceMask<T> {
+    /// The bits of a row that correspond to a coordinate in the grid.
+    const ROW_BITS: u128 = if T == u128::BITS as usize {
+        u128::MAX
+    } else {
+        (1 << T) - 1
+    };
+
+    /// Creates a [DifferenceMask] without any set bits.
+    pub const fn new() -> Self {
+        assert!(
+            T <= u128::BITS as usize,
+            "grid is too large for a difference mask"
+        );
+        Self {
+            rows: [0; T],
+            count: 0,
+        }
+    }
+
+    /// Returns `true` if the bit at `coords` is set.
+    pub fn get(&self, coords: Index2D) -> bool {
+        assert!(coords.x < T);
+        self.rows[coords.y] & (1 << coords.x) != 0
+    }
+
+    /// Sets the bit at `coords` to `value`.
+    pub fn set(&mut self, coords: Index2D, value: bool) {
+        assert!(coords.x < T);
+        let bit = 1 << coords.x;
+        let row = &mut self.rows[coords.y];
+        if (*row & bit != 0) == value {
+            return;
+        }
+
+        if value {
+            *row |= bit;
+            self.count += 1;
+        } else {
+            *row &= !bit;
+            self.count -= 1;
+        }
+    }
+
+    /// Returns the bits of the row `y`. The bit `1 << x` is set if the bit at `x` is set.
+    pub fn row(&self, y: usize) -> u128 {
+        self.rows[y]
+    }
+
+    /// Replaces the bits of the row `y`. See [DifferenceMask::row].
+    pub fn set_row(&mut self, y: usize, bits: u128) {
+        assert_eq!(bits & !Self::ROW_BITS, 0, "bits outside of the grid");
+        self.count -= self.rows[y].count_ones() as usize;
+        self.count += bits.count_ones() as usize;
+        self.rows[y] = bits;
+    }
+
+    /// Returns `true` if no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of set bits.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Clears every bit.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns an [Iterator] over the coordinates of each set bit, ordered by row.
+    pub fn iter(&self) -> impl Iterator<Item = Index2D> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            (0..T)
+                .filter(move |x| row & (1 << x) != 0)
+                .map(move |x| Index2D::new(x, y))
+        })
+    }
+}
+
+impl<const T: usize> Default for DifferenceMask<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
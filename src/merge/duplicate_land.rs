@@ -0,0 +1,259 @@
+use crate::io::meta_schema::PluginMeta;
+use crate::io::report::DuplicateLand;
+use crate::land::conversions::{
+    included_data, texture_indices, vertex_colors, vertex_normals, world_map_data,
+};
+use crate::land::height_map::try_calculate_height_map;
+use crate::land::terrain_map::{LandData, TerrainMap, Vec2, Vec3};
+use crate::land::textures::IndexVTEX;
+use crate::Landmass;
+use itertools::Itertools;
+use log::{debug, info};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use tes3::esp::Landscape;
+
+#[derive(Hash, Eq, PartialEq)]
+/// The data of a [Landscape] that is merged, after the texture indices are remapped.
+struct LandPayload {
+    included_data: LandData,
+    height_map: Option<TerrainMap<i32, 65>>,
+    vertex_normals: Option<TerrainMap<Vec3<i8>, 65>>,
+    world_map_data: Option<TerrainMap<u8, 9>>,
+    vertex_colors: Option<TerrainMap<Vec3<u8>, 65>>,
+    texture_indices: Option<TerrainMap<IndexVTEX, 16>>,
+}
+
+impl LandPayload {
+    fn from(land: &Landscape) -> Self {
+        Self {
+            included_data: included_data(land),
+            height_map: try_calculate_height_map(land),
+            vertex_normals: vertex_normals(land),
+            world_map_data: world_map_data(land),
+            vertex_colors: vertex_colors(land),
+            texture_indices: texture_indices(land),
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(self, &mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Returns `true` if plugins with the [PluginMeta] `lhs` and `rhs` merge identical LAND records
+/// the same way, i.e. the same [MergeSettings](crate::io::meta_schema::MergeSettings) and height
+/// transform apply to both.
+fn merges_alike(lhs: &PluginMeta, rhs: &PluginMeta) -> bool {
+    lhs.meta_type == rhs.meta_type
+        && lhs.height_scale == rhs.height_scale
+        && lhs.height_offset == rhs.height_offset
+        && lhs.height_map == rhs.height_map
+        && lhs.vertex_colors == rhs.vertex_colors
+        && lhs.texture_indices == rhs.texture_indices
+        && lhs.world_map_data == rhs.world_map_data
+}
+
+/// Finds each cell where plugins of the `landmasses` have identical LAND records, e.g. because a
+/// compilation re-ships the LAND of another mod, and removes the LAND from all but the last of
+/// these plugins so that it is only merged once. The `landmasses` must be in load order.
+/// Only plugins that follow each other among the plugins with LAND in the cell, and that are
+/// merged with the same [PluginMeta] settings, are collapsed, so the merged land is unchanged.
+/// Landmasses without any LAND left are removed.
+/// Returns a [DuplicateLand] for each set of plugins, ordered by descending number of cells.
+pub fn collapse_duplicate_land(landmasses: &mut Vec<Landmass>) -> Vec<DuplicateLand> {
+    // Hashes are compared first, so that only a single payload is kept in memory at once.
+    let mut cells: BTreeMap<(i32, i32), Vec<(usize, u64)>> = BTreeMap::new();
+    for (idx, landmass) in landmasses.iter().enumerate() {
+        for (coords, land) in landmass.land.iter() {
+            let hash = LandPayload::from(land).hash();
+            cells
+                .entry((coords.x, coords.y))
+                .or_default()
+                .push((idx, hash));
+        }
+    }
+
+    let is_duplicate =
+        |coords: &Vec2<i32>, (lhs, lhs_hash): (usize, u64), (rhs, rhs_hash): (usize, u64)| {
+            lhs_hash == rhs_hash
+                && merges_alike(&landmasses[lhs].plugin.meta, &landmasses[rhs].plugin.meta)
+                && LandPayload::from(&landmasses[lhs].land[coords])
+                    == LandPayload::from(&landmasses[rhs].land[coords])
+        };
+
+    let mut duplicates: BTreeMap<Vec<usize>, Vec<Vec2<i32>>> = BTreeMap::new();
+    for ((x, y), mut entries) in cells.into_iter() {
+        let coords = Vec2::new(x, y);
+        entries.sort_by_key(|(idx, _)| *idx);
+
+        let mut run = vec![entries[0].0];
+        for (prev, next) in entries.iter().copied().tuple_windows() {
+            if is_duplicate(&coords, prev, next) {
+                run.push(next.0);
+                continue;
+            }
+
+            if run.len() > 1 {
+                duplicates.entry(run).or_default().push(coords);
+            }
+
+            run = vec![next.0];
+        }
+
+        if run.len() > 1 {
+            duplicates.entry(run).or_default().push(coords);
+        }
+    }
+
+    let duplicate_land = duplicates
+        .into_iter()
+        .map(|(indices, cells)| {
+            let (last, earlier) = indices.split_last().expect("safe");
+            for idx in earlier {
+                let landmass = &mut landmasses[*idx];
+                for coords in cells.iter() {
+                    landmass.land.remove(coords);
+                    landmass.plugins.remove(coords);
+                }
+            }
+
+            let plugins = indices
+                .iter()
+                .map(|idx| landmasses[*idx].plugin.name.clone())
+                .collect_vec();
+
+            DuplicateLand {
+                merged: landmasses[*last].plugin.name.clone(),
+                plugins,
+                cells: cells
+                    .iter()
+                    .sorted_by_key(|coords| (coords.x, coords.y))
+                    .map(|coords| (*coords).into())
+                    .collect(),
+            }
+        })
+        .sorted_by(|lhs, rhs| {
+            rhs.cells
+                .len()
+                .cmp(&lhs.cells.len())
+                .then(lhs.plugins.cmp(&rhs.plugins))
+        })
+        .collect_vec();
+
+    for duplicate in duplicate_land.iter() {
+        info!(
+            "{} have {} identical LAND records, only merging {}",
+            duplicate.plugins.join(" and "),
+            duplicate.cells.len(),
+            duplicate.merged
+        );
+    }
+
+    landmasses.retain(|landmass| {
+        if landmass.land.is_empty() {
+            debug!(
+                "Skipping {}, since all of its LAND is duplicated",
+                landmass.plugin.name
+            );
+        }
+
+        !landmass.land.is_empty()
+    });
+
+    duplicate_land
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::meta_schema::ConflictStrategy;
+    use crate::test_support::{LandmassBuilder, PluginBuilder, CELL};
+
+    /// Returns a [Landmass] of the plugin `name` with the `meta` and a LAND record at each of
+    /// the `cells` with a hill of `scale`.
+    fn landmass(name: &str, meta: PluginMeta, cells: &[Vec2<i32>], scale: i32) -> Landmass {
+        cells
+            .iter()
+            .fold(
+                LandmassBuilder::new(PluginBuilder::new(name).meta(meta).build()),
+                |builder, coords| builder.hill(*coords, scale, 1),
+            )
+            .build()
+    }
+
+    fn names(landmasses: &[Landmass]) -> Vec<&str> {
+        landmasses
+            .iter()
+            .map(|landmass| landmass.plugin.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn identical_copies_are_merged_once() {
+        let mut landmasses = vec![
+            landmass("A.esp", PluginMeta::default(), &[CELL], 4),
+            landmass("B.esp", PluginMeta::default(), &[CELL], 4),
+        ];
+
+        let duplicates = collapse_duplicate_land(&mut landmasses);
+        assert_eq!(names(&landmasses), vec!["B.esp"]);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].plugins, vec!["A.esp", "B.esp"]);
+        assert_eq!(duplicates[0].merged, "B.esp");
+        assert_eq!(duplicates[0].cells, vec![[0, 0]]);
+    }
+
+    #[test]
+    fn copies_with_different_metas_are_kept() {
+        let mut excluded = PluginMeta::default();
+        excluded.height_map.included = false;
+
+        let mut ignored = PluginMeta::default();
+        ignored.height_map.conflict_strategy = ConflictStrategy::Ignore;
+
+        for meta in [excluded, ignored] {
+            let mut landmasses = vec![
+                landmass("A.esp", PluginMeta::default(), &[CELL], 4),
+                landmass("B.esp", meta, &[CELL], 4),
+            ];
+
+            let duplicates = collapse_duplicate_land(&mut landmasses);
+            assert!(duplicates.is_empty());
+            assert_eq!(names(&landmasses), vec!["A.esp", "B.esp"]);
+        }
+    }
+
+    #[test]
+    fn copies_with_a_plugin_in_between_are_kept() {
+        let mut landmasses = vec![
+            landmass("A.esp", PluginMeta::default(), &[CELL], 4),
+            landmass("B.esp", PluginMeta::default(), &[CELL], -4),
+            landmass("C.esp", PluginMeta::default(), &[CELL], 4),
+        ];
+
+        let duplicates = collapse_duplicate_land(&mut landmasses);
+        assert!(duplicates.is_empty());
+        assert_eq!(names(&landmasses), vec!["A.esp", "B.esp", "C.esp"]);
+    }
+
+    #[test]
+    fn plugins_in_between_without_the_cell_are_skipped() {
+        let other = Vec2::new(1, 0);
+        let mut landmasses = vec![
+            landmass("A.esp", PluginMeta::default(), &[CELL, other], 4),
+            landmass("B.esp", PluginMeta::default(), &[other], -4),
+            landmass("C.esp", PluginMeta::default(), &[CELL], 4),
+        ];
+
+        let duplicates = collapse_duplicate_land(&mut landmasses);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].plugins, vec!["A.esp", "C.esp"]);
+        assert_eq!(names(&landmasses), vec!["A.esp", "B.esp", "C.esp"]);
+        assert!(!landmasses[0].land.contains_key(&CELL));
+        assert!(landmasses[0].land.contains_key(&other));
+    }
+}
@@ -1,46 +1,50 @@
-use crate::land::grid_access::SquareGridIterator;
+use crate::events::RunContext;
 use crate::land::terrain_map::Vec2;
 use crate::merge::conflict::ConflictResolver;
 use crate::merge::merge_strategy::MergeStrategy;
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::merge::relative_to::RelativeTo;
+use crate::merge::resolve_conflict_strategy::ResolveConflictStrategy;
 use crate::ParsedPlugin;
-use std::default::default;
 
 #[derive(Default)]
 /// Implements [MergeStrategy] to ignore any conflicts by dropping the newest change.
 pub struct IgnoreStrategy {}
 
-impl MergeStrategy for IgnoreStrategy {
-    fn apply<U: RelativeTo + ConflictResolver, const T: usize>(
-        &self,
-        _coords: Vec2<i32>,
-        _plugin: &ParsedPlugin,
-        _value: &str,
+impl IgnoreStrategy {
+    /// Merges the `lhs` and `rhs` [RelativeTerrainMap] like [MergeStrategy::apply] without
+    /// emitting a [crate::events::MergeEvent].
+    pub fn ignore<U: RelativeTo, const T: usize>(
         lhs: &RelativeTerrainMap<U, T>,
         rhs: &RelativeTerrainMap<U, T>,
     ) -> RelativeTerrainMap<U, T> {
-        let mut new = lhs.clone();
-
-        for coords in new.iter_grid() {
-            let lhs_diff = lhs.has_difference(coords);
-            let rhs_diff = rhs.has_difference(coords);
-
-            let mut diff = default();
-            if lhs_diff && !rhs_diff {
-                diff = lhs.get_difference(coords);
-            } else if !lhs_diff && rhs_diff {
-                diff = rhs.get_difference(coords);
-            } else if !lhs_diff && !rhs_diff {
-                // NOP.
-            } else {
+        lhs.merge_rows(rhs, |lhs, rhs, new| {
+            for ((new, lhs), rhs) in new.iter_mut().zip(lhs).zip(rhs) {
                 // Conflict -- choose lhs.
-                diff = lhs.get_difference(coords);
+                *new = if *lhs != Default::default() {
+                    *lhs
+                } else {
+                    *rhs
+                };
             }
+        })
+    }
+}
 
-            new.set_difference(coords, diff);
-        }
-
-        new
+impl MergeStrategy for IgnoreStrategy {
+    fn apply<U: RelativeTo + ConflictResolver, const T: usize>(
+        &self,
+        coords: Vec2<i32>,
+        plugin: &ParsedPlugin,
+        value: &str,
+        lhs: &RelativeTerrainMap<U, T>,
+        rhs: &RelativeTerrainMap<U, T>,
+        context: &RunContext,
+    ) -> RelativeTerrainMap<U, T>
+    where
+        <U as RelativeTo>::Delta: ConflictResolver,
+    {
+        ResolveConflictStrategy::emit_conflicts(coords, plugin, value, lhs, rhs, context);
+        Self::ignore(lhs, rhs)
     }
 }
@@ -0,0 +1,179 @@
+use crate::io::parsed_plugins::ParsedPlugins;
+use crate::io::report::ReportWarning;
+use crate::land::conversions::{coordinates, included_data};
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::{LandData, Vec2};
+use crate::merge::chunks::chunk_coords;
+use hashbrown::{HashMap, HashSet};
+use log::{debug, warn};
+use owo_colors::OwoColorize;
+use std::mem::size_of;
+use sysinfo::{RefreshKind, System, SystemExt};
+use tes3::esp::Landscape;
+
+/// The number of bytes in a GiB.
+const BYTES_PER_GIB: f64 = (1024 * 1024 * 1024) as f64;
+
+/// The suggested `--chunk-size` if the estimate is larger than the available memory.
+const SUGGESTED_CHUNK_SIZE: usize = 32;
+
+/// The worst-case memory used by a merge, estimated from the LAND records of each plugin.
+pub struct MemoryEstimate {
+    /// The number of unique cells with a LAND record in any plugin.
+    pub num_cells: usize,
+    /// The number of LAND records in plugins that are not masters.
+    pub num_plugin_cells: usize,
+    /// The bytes used by the LAND records of each plugin and the copies made while merging.
+    pub records_bytes: usize,
+    /// The bytes used by the [LandscapeDiff] of each plugin that are in memory at once.
+    pub diff_bytes: usize,
+    /// The bytes used by the merged [LandscapeDiff] of each cell.
+    pub merged_bytes: usize,
+}
+
+impl MemoryEstimate {
+    /// Returns the total estimated bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.records_bytes + self.diff_bytes + self.merged_bytes
+    }
+}
+
+/// Returns the bytes used by the data of a LAND record with the [LandData].
+fn land_bytes(data: LandData) -> usize {
+    let mut bytes = size_of::<Landscape>();
+
+    if data.contains(LandData::VERTEX_HEIGHTS) {
+        bytes += 65 * 65 + size_of::<f32>();
+    }
+
+    if data.contains(LandData::VERTEX_NORMALS) {
+        bytes += 65 * 65 * 3;
+    }
+
+    if data.contains(LandData::VERTEX_COLORS) {
+        bytes += 65 * 65 * 3;
+    }
+
+    if data.contains(LandData::TEXTURES) {
+        bytes += 16 * 16 * size_of::<u16>();
+    }
+
+    if data.contains(LandData::WORLD_MAP) {
+        bytes += 9 * 9;
+    }
+
+    bytes
+}
+
+/// Estimates the worst-case memory used to merge the `parsed_plugins`. If `chunk_size` is
+/// [Some], only the [LandscapeDiff] of the largest chunk are counted. If `debug_copy` is `true`,
+/// the copy of the merged land used for debug vertex colors is counted too.
+pub fn estimate_memory(
+    parsed_plugins: &ParsedPlugins,
+    chunk_size: Option<usize>,
+    debug_copy: bool,
+) -> MemoryEstimate {
+    let mut cells: HashSet<Vec2<i32>> = HashSet::new();
+    let mut chunks: HashMap<Vec2<i32>, usize> = HashMap::new();
+    let mut num_plugin_cells = 0;
+    let mut master_bytes = 0;
+    let mut plugin_bytes = 0;
+
+    for master in parsed_plugins.masters.iter() {
+        for land in master.records.objects_of_type::<Landscape>() {
+            cells.insert(coordinates(land));
+            master_bytes += land_bytes(included_data(land));
+        }
+    }
+
+    for plugin in parsed_plugins.plugins.iter() {
+        for land in plugin.records.objects_of_type::<Landscape>() {
+            let coords = coordinates(land);
+            cells.insert(coords);
+            num_plugin_cells += 1;
+            plugin_bytes += land_bytes(included_data(land));
+
+            if let Some(chunk_size) = chunk_size {
+                *chunks.entry(chunk_coords(coords, chunk_size)).or_default() += 1;
+            }
+        }
+    }
+
+    // The reference land is a copy of the LAND records of the masters. With chunks, the
+    // plugins also keep a copy of their LAND records to create each chunk from.
+    let records_bytes = if chunk_size.is_some() {
+        2 * (master_bytes + plugin_bytes)
+    } else {
+        2 * master_bytes + plugin_bytes
+    };
+
+    let num_diffs = match chunk_size {
+        None => num_plugin_cells,
+        Some(_) => chunks.values().copied().max().unwrap_or_default(),
+    };
+
+    let num_merged_copies = if debug_copy { 2 } else { 1 };
+
+    MemoryEstimate {
+        num_cells: cells.len(),
+        num_plugin_cells,
+        records_bytes,
+        diff_bytes: num_diffs * size_of::<LandscapeDiff>(),
+        merged_bytes: num_merged_copies * cells.len() * size_of::<LandscapeDiff>(),
+    }
+}
+
+/// Formats the `bytes` in GiB.
+fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / BYTES_PER_GIB)
+}
+
+/// Returns a [ReportWarning] if the [MemoryEstimate] is larger than the available memory.
+/// The warning suggests using chunks, or smaller chunks if `chunk_size` is [Some].
+pub fn check_memory(estimate: &MemoryEstimate, chunk_size: Option<usize>) -> Option<ReportWarning> {
+    let system = System::new_with_specifics(RefreshKind::new().with_memory());
+    let available_bytes = system.available_memory();
+    let estimated_bytes = estimate.total_bytes() as u64;
+
+    debug!(
+        "Estimated peak memory of {} for {} cells and {} plugin LAND records, {} available",
+        format_gib(estimated_bytes),
+        estimate.num_cells,
+        estimate.num_plugin_cells,
+        if available_bytes > 0 {
+            format_gib(available_bytes)
+        } else {
+            "unknown".to_string()
+        }
+    );
+
+    // The available memory is 0 if it is unknown on this platform.
+    if available_bytes == 0 || estimated_bytes <= available_bytes {
+        return None;
+    }
+
+    let suggestion = match chunk_size {
+        None => format!("Consider passing --chunk-size {}", SUGGESTED_CHUNK_SIZE),
+        Some(chunk_size) => format!(
+            "Consider passing a smaller --chunk-size than {}",
+            chunk_size
+        ),
+    };
+
+    warn!(
+        "{} {}",
+        format!(
+            "Estimated peak memory of {} is more than the {} available.",
+            format_gib(estimated_bytes).bold(),
+            format_gib(available_bytes).bold()
+        )
+        .yellow(),
+        suggestion.yellow()
+    );
+
+    Some(ReportWarning::LowMemory {
+        estimated_bytes,
+        available_bytes,
+        suggestion,
+    })
+}
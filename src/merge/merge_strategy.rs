@@ -1,3 +1,4 @@
+use crate::events::RunContext;
 use crate::io::meta_schema::ConflictStrategy;
 use crate::land::terrain_map::Vec2;
 use crate::merge::conflict::ConflictResolver;
@@ -8,13 +9,13 @@ use crate::merge::relative_to::RelativeTo;
 use crate::merge::resolve_conflict_strategy::ResolveConflictStrategy;
 use crate::ParsedPlugin;
 use log::trace;
-use std::default::default;
 
 /// Types implementing [MergeStrategy] can create a new [RelativeTerrainMap] by combining
 /// the `lhs` and `rhs` [RelativeTerrainMap]. The method for combining the maps is determined
 /// by the type implementing [MergeStrategy::apply].
 pub trait MergeStrategy {
     /// Combine the `lhs` and `rhs` [RelativeTerrainMap] into a new [RelativeTerrainMap].
+    /// Any conflicts are emitted to the [RunContext].
     fn apply<U: RelativeTo + ConflictResolver, const T: usize>(
         &self,
         coords: Vec2<i32>,
@@ -22,6 +23,7 @@ pub trait MergeStrategy {
         value: &str,
         lhs: &RelativeTerrainMap<U, T>,
         rhs: &RelativeTerrainMap<U, T>,
+        context: &RunContext,
     ) -> RelativeTerrainMap<U, T>
     where
         <U as RelativeTo>::Delta: ConflictResolver;
@@ -38,32 +40,23 @@ fn apply_strategy<U: RelativeTo + ConflictResolver, const T: usize>(
     old: Option<&RelativeTerrainMap<U, T>>,
     new: Option<&RelativeTerrainMap<U, T>>,
     strategy: &impl MergeStrategy,
+    context: &RunContext,
 ) -> OptionalTerrainMap<U, T>
 where
     <U as RelativeTo>::Delta: ConflictResolver,
 {
-    if old.is_some() && new.is_some() {
-        let merged = strategy.apply(
-            coords,
-            plugin,
-            value,
-            old.as_ref().expect("safe"),
-            new.as_ref().expect("safe"),
-        );
-
-        Some(merged)
-    } else if old.is_some() {
-        old.cloned()
-    } else if new.is_some() {
-        new.cloned()
-    } else {
-        None
+    match (old, new) {
+        (Some(old), Some(new)) => Some(strategy.apply(coords, plugin, value, old, new, context)),
+        (Some(old), None) => Some(old.clone()),
+        (None, Some(new)) => Some(new.clone()),
+        (None, None) => None,
     }
 }
 
 /// Given optional `old` and `new` [RelativeTerrainMap], and a desired [ConflictStrategy],
 /// apply the desired [MergeStrategy] as indicated by the `conflict_strategy`.
 /// If `conflict_strategy` is [ConflictStrategy::Auto], use the [MergeStrategy] `auto_strategy`.
+#[allow(clippy::too_many_arguments)]
 pub fn apply_preferred_strategy<U: RelativeTo + ConflictResolver, const T: usize>(
     coords: Vec2<i32>,
     plugin: &ParsedPlugin,
@@ -72,13 +65,14 @@ pub fn apply_preferred_strategy<U: RelativeTo + ConflictResolver, const T: usize
     new: Option<&RelativeTerrainMap<U, T>>,
     conflict_strategy: ConflictStrategy,
     auto_strategy: &impl MergeStrategy,
+    context: &RunContext,
 ) -> OptionalTerrainMap<U, T>
 where
     <U as RelativeTo>::Delta: ConflictResolver,
 {
-    let resolve_strategy: ResolveConflictStrategy = default();
-    let overwrite_strategy: OverwriteStrategy = default();
-    let ignore_strategy: IgnoreStrategy = default();
+    let resolve_strategy: ResolveConflictStrategy = Default::default();
+    let overwrite_strategy: OverwriteStrategy = Default::default();
+    let ignore_strategy: IgnoreStrategy = Default::default();
 
     if conflict_strategy != ConflictStrategy::Auto {
         trace!(
@@ -92,15 +86,23 @@ where
     }
 
     match conflict_strategy {
-        ConflictStrategy::Auto => apply_strategy(coords, plugin, value, old, new, auto_strategy),
-        ConflictStrategy::Resolve => {
-            apply_strategy(coords, plugin, value, old, new, &resolve_strategy)
+        ConflictStrategy::Auto => {
+            apply_strategy(coords, plugin, value, old, new, auto_strategy, context)
         }
-        ConflictStrategy::Overwrite => {
-            apply_strategy(coords, plugin, value, old, new, &overwrite_strategy)
+        ConflictStrategy::Resolve => {
+            apply_strategy(coords, plugin, value, old, new, &resolve_strategy, context)
         }
+        ConflictStrategy::Overwrite => apply_strategy(
+            coords,
+            plugin,
+            value,
+            old,
+            new,
+            &overwrite_strategy,
+            context,
+        ),
         ConflictStrategy::Ignore => {
-            apply_strategy(coords, plugin, value, old, new, &ignore_strategy)
+            apply_strategy(coords, plugin, value, old, new, &ignore_strategy, context)
         }
     }
 }
@@ -114,12 +116,13 @@ pub fn apply_merge_strategy<U: RelativeTo + ConflictResolver, const T: usize>(
     old: Option<&RelativeTerrainMap<U, T>>,
     new: Option<&RelativeTerrainMap<U, T>>,
     conflict_strategy: ConflictStrategy,
+    context: &RunContext,
 ) -> OptionalTerrainMap<U, T>
 where
     <U as RelativeTo>::Delta: ConflictResolver,
 {
-    let resolve_strategy: ResolveConflictStrategy = default();
-    let overwrite_strategy: OverwriteStrategy = default();
+    let resolve_strategy: ResolveConflictStrategy = Default::default();
+    let overwrite_strategy: OverwriteStrategy = Default::default();
 
     match value {
         "height_map" | "world_map_data" | "vertex_colors" | "vertex_normals" => {
@@ -131,6 +134,7 @@ where
                 new,
                 conflict_strategy,
                 &resolve_strategy,
+                context,
             )
         }
         "texture_indices" => apply_preferred_strategy(
@@ -141,6 +145,7 @@ where
             new,
             conflict_strategy,
             &overwrite_strategy,
+            context,
         ),
         _ => {
             // TODO(dvd): #refactor Why aren't these enums?
@@ -1,9 +1,18 @@
 pub mod cells;
+pub mod chunks;
 pub mod conflict;
+pub mod difference_mask;
+pub mod duplicate_land;
 pub mod ignore_strategy;
+pub mod memory_estimate;
 pub mod merge_strategy;
+pub mod multi_way;
 pub mod overwrite_strategy;
+pub mod parallel;
+pub mod provenance;
 pub mod relative_terrain_map;
 pub mod relative_to;
 pub mod resolve_conflict_strategy;
 mod round_to;
+pub mod shadow_evaluation;
+pub mod world_map_edits;
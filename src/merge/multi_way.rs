@@ -0,0 +1,198 @@
+use crate::io::meta_schema::{AveragingKernel, ConflictStrategy};
+use crate::io::report::MultiWayConflict;
+use crate::io::resolutions::Resolutions;
+use crate::land::grid_access::SquareGridIterator;
+use crate::LandmassDiff;
+use itertools::Itertools;
+use log::debug;
+
+/// Returns the result of the [AveragingKernel] for the `deltas` of three or more plugins,
+/// or [None] with [AveragingKernel::Pairwise].
+fn apply_kernel(kernel: AveragingKernel, deltas: &mut [i32]) -> Option<i32> {
+    deltas.sort_unstable();
+    let mid = deltas.len() / 2;
+    match kernel {
+        AveragingKernel::Pairwise => None,
+        AveragingKernel::Median if deltas.len() % 2 == 1 => Some(deltas[mid]),
+        AveragingKernel::Median => Some(mean(&deltas[mid - 1..=mid])),
+        AveragingKernel::TrimmedMean => Some(mean(&deltas[1..deltas.len() - 1])),
+    }
+}
+
+/// Returns the mean of the `deltas`, rounded to the nearest integer.
+fn mean(deltas: &[i32]) -> i32 {
+    let sum: i64 = deltas.iter().map(|delta| *delta as i64).sum();
+    (sum as f64 / deltas.len() as f64).round() as i32
+}
+
+/// Finds each height of the `merged` [LandmassDiff] modified by three or more of the
+/// `modded_landmasses`, and replaces it with the result of the [AveragingKernel] `kernel` over
+/// the change of every plugin. Heights are only replaced if each of these plugins resolves its
+/// height map conflicts, since the result of overwriting or ignoring a change depends on the
+/// order of the plugins. See [crate::merge_landscape_diff] for `forced_strategy` and `resolutions`.
+/// Returns a [MultiWayConflict] for each cell, ordered by `x` and `y` coordinates.
+pub fn resolve_multi_way_conflicts(
+    merged: &mut LandmassDiff,
+    modded_landmasses: &[LandmassDiff],
+    kernel: AveragingKernel,
+    forced_strategy: Option<ConflictStrategy>,
+    resolutions: &Resolutions,
+) -> Vec<MultiWayConflict> {
+    let mut conflicts = Vec::new();
+    let cells = modded_landmasses
+        .iter()
+        .flat_map(|landmass| landmass.land.keys().copied())
+        .unique()
+        .sorted_by_key(|coords| (coords.x, coords.y))
+        .collect_vec();
+
+    for coords in cells {
+        let contributors = modded_landmasses
+            .iter()
+            .filter_map(|landmass| {
+                let height_map = landmass.land.get(&coords)?.height_map.as_ref()?;
+                let strategy = resolutions
+                    .conflict_strategy(&landmass.plugin.name, coords, "height_map")
+                    .or(forced_strategy)
+                    .unwrap_or(landmass.plugin.meta.height_map.conflict_strategy);
+                let resolves =
+                    matches!(strategy, ConflictStrategy::Auto | ConflictStrategy::Resolve);
+                Some((landmass.plugin.name.as_str(), height_map, resolves))
+            })
+            .collect_vec();
+
+        if contributors.len() < 3 {
+            continue;
+        }
+
+        let Some(merged_heights) = merged
+            .land
+            .get_mut(&coords)
+            .and_then(|land| land.height_map.as_mut())
+        else {
+            continue;
+        };
+
+        let mut involved = vec![false; contributors.len()];
+        let mut num_vertices = 0;
+        let mut max_plugins = 0;
+        for vertex in merged_heights.iter_grid() {
+            let modified = contributors
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, height_map, _))| height_map.has_difference(vertex))
+                .collect_vec();
+
+            if modified.len() < 3 {
+                continue;
+            }
+
+            num_vertices += 1;
+            max_plugins = max_plugins.max(modified.len());
+            for (idx, _) in modified.iter() {
+                involved[*idx] = true;
+            }
+
+            if modified.iter().any(|(_, (_, _, resolves))| !resolves) {
+                continue;
+            }
+
+            let mut deltas = modified
+                .iter()
+                .map(|(_, (_, height_map, _))| height_map.get_difference(vertex))
+                .collect_vec();
+            if let Some(delta) = apply_kernel(kernel, &mut deltas) {
+                merged_heights.set_difference(vertex, delta);
+            }
+        }
+
+        if num_vertices == 0 {
+            continue;
+        }
+
+        debug!(
+            "({:>4}, {:>4}) {:<15} | {} vertices modified by up to {} plugins",
+            coords.x, coords.y, "height_map", num_vertices, max_plugins
+        );
+
+        conflicts.push(MultiWayConflict {
+            coords: coords.into(),
+            plugins: contributors
+                .iter()
+                .zip(involved)
+                .filter(|(_, involved)| *involved)
+                .map(|((plugin, _, _), _)| plugin.to_string())
+                .collect(),
+            num_vertices,
+            max_plugins,
+            kernel,
+        });
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairwise_has_no_result() {
+        assert_eq!(
+            apply_kernel(AveragingKernel::Pairwise, &mut [1, 5, 9]),
+            None
+        );
+    }
+
+    #[test]
+    fn median_of_odd_counts_is_the_middle_value() {
+        assert_eq!(
+            apply_kernel(AveragingKernel::Median, &mut [9, 1, 5]),
+            Some(5)
+        );
+        assert_eq!(
+            apply_kernel(AveragingKernel::Median, &mut [100, -3, 2, 7, 0]),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn median_of_even_counts_is_the_mean_of_the_middle_values() {
+        assert_eq!(
+            apply_kernel(AveragingKernel::Median, &mut [100, 1, 5, 3]),
+            Some(4)
+        );
+        assert_eq!(
+            apply_kernel(AveragingKernel::Median, &mut [0, 1, 2, -50]),
+            Some(1)
+        );
+        assert_eq!(
+            apply_kernel(AveragingKernel::Median, &mut [-4, -1, 10, 20]),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn trimmed_mean_drops_the_smallest_and_largest_values() {
+        assert_eq!(
+            apply_kernel(AveragingKernel::TrimmedMean, &mut [100, 1, 5]),
+            Some(5)
+        );
+        assert_eq!(
+            apply_kernel(AveragingKernel::TrimmedMean, &mut [-100, 2, 4, 100]),
+            Some(3)
+        );
+        assert_eq!(
+            apply_kernel(AveragingKernel::TrimmedMean, &mut [0, 1, 2, 10, 1000]),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn means_are_rounded_to_the_nearest_integer() {
+        assert_eq!(mean(&[1, 2]), 2);
+        assert_eq!(mean(&[1, 1, 2]), 1);
+        assert_eq!(mean(&[-1, -2]), -2);
+        assert_eq!(mean(&[i32::MAX, i32::MAX]), i32::MAX);
+    }
+}
@@ -0,0 +1,72 @@
+use itertools::Itertools;
+use std::sync::Mutex;
+
+/// The stack size of each thread, the same as the default `--stack-size-mb` of the main worker
+/// thread. Debug builds of the merge use more stack than a spawned thread has by default.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Calls `f` with each of the `items` on up to `threads` threads. Returns the results in the order
+/// of the `items`, regardless of the order the threads finish in, so that anything reduced from
+/// the results does not depend on the number of threads. With a single thread, or a single item,
+/// `f` is called on the current thread.
+pub fn map_in_order<T: Send, R: Send>(
+    items: Vec<T>,
+    threads: usize,
+    f: impl Fn(T) -> R + Sync,
+) -> Vec<R> {
+    let threads = threads.min(items.len());
+    if threads <= 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            std::thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, || loop {
+                    let Some((idx, item)) = queue.lock().expect("safe").next() else {
+                        break;
+                    };
+
+                    let result = f(item);
+                    results.lock().expect("safe").push((idx, result));
+                })
+                .expect("unable to create worker thread");
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("safe")
+        .into_iter()
+        .sorted_by_key(|(idx, _)| *idx)
+        .map(|(_, result)| result)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_are_in_the_order_of_the_items() {
+        for threads in [0, 1, 2, 8, 64] {
+            // Earlier items take longer, so that the threads finish in reverse order.
+            let results = map_in_order((0..16u64).collect(), threads, |item| {
+                std::thread::sleep(Duration::from_millis(16 - item));
+                item * 2
+            });
+
+            assert_eq!(results, (0..16).map(|item| item * 2).collect_vec());
+        }
+    }
+
+    #[test]
+    fn no_items_have_no_results() {
+        let results = map_in_order(Vec::<usize>::new(), 8, |item| item);
+        assert!(results.is_empty());
+    }
+}
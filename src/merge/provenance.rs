@@ -0,0 +1,357 @@
+use crate::io::meta_schema::{ConflictStrategy, MergeSettings, PluginMeta};
+use crate::io::report::ProvenanceSummary;
+use crate::io::resolutions::Resolutions;
+use crate::land::grid_access::{Index2D, SquareGridIterator};
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::Vec2;
+use crate::merge::relative_terrain_map::RelativeTerrainMap;
+use crate::merge::relative_to::RelativeTo;
+use crate::LandmassDiff;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// The name of each value of a [LandscapeDiff] with a [ProvenanceMap].
+pub const PROVENANCE_VALUES: [&str; 5] = [
+    "height_map",
+    "vertex_normals",
+    "world_map_data",
+    "vertex_colors",
+    "texture_indices",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The reason a vertex of the merged land has its value.
+pub enum VertexDecision {
+    /// The value was changed by merging the plugin at index `plugin` of the [Provenance]
+    /// with the [ConflictStrategy] `strategy`. If `conflict` is `true`, an earlier plugin
+    /// had also modified the vertex.
+    Merged {
+        plugin: usize,
+        strategy: ConflictStrategy,
+        conflict: bool,
+    },
+    /// The value was changed after merging, e.g. while repairing a seam or averaging the
+    /// changes of three or more plugins.
+    Repaired,
+}
+
+#[derive(Clone)]
+/// The latest [VertexDecision] of each vertex of one value of a cell.
+/// Vertices without a [VertexDecision] have the value of the reference land.
+pub struct ProvenanceMap {
+    size: usize,
+    decisions: Vec<Option<VertexDecision>>,
+}
+
+impl ProvenanceMap {
+    /// Creates a [ProvenanceMap] of `size` by `size` vertices without any [VertexDecision].
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            decisions: vec![None; size * size],
+        }
+    }
+
+    /// Returns the [VertexDecision] of the vertex at `coords`, if any.
+    pub fn get(&self, coords: Index2D) -> Option<VertexDecision> {
+        self.decisions[coords.y * self.size + coords.x]
+    }
+
+    /// Sets the [VertexDecision] of the vertex at `coords`.
+    fn set(&mut self, coords: Index2D, decision: VertexDecision) {
+        self.decisions[coords.y * self.size + coords.x] = Some(decision);
+    }
+}
+
+/// Returns the [ConflictStrategy] used to merge the `value` with the [ConflictStrategy] `strategy`.
+/// [ConflictStrategy::Auto] overwrites texture indices and resolves every other value.
+fn effective_strategy(value: &str, strategy: ConflictStrategy) -> ConflictStrategy {
+    match (value, strategy) {
+        ("texture_indices", ConflictStrategy::Auto) => ConflictStrategy::Overwrite,
+        (_, ConflictStrategy::Auto) => ConflictStrategy::Resolve,
+        (_, strategy) => strategy,
+    }
+}
+
+/// Returns the [MergeSettings] of a value in a [PluginMeta].
+type SettingsOf = fn(&PluginMeta) -> &MergeSettings;
+
+/// Calls `f` with the name of each value of the `before` and `after` [LandscapeDiff], the
+/// [MergeSettings] of the value, and the [VertexChanges] between them.
+fn for_each_value(
+    before: Option<&LandscapeDiff>,
+    after: &LandscapeDiff,
+    mut f: impl FnMut(&'static str, SettingsOf, VertexChanges),
+) {
+    f(
+        "height_map",
+        |meta| &meta.height_map,
+        VertexChanges::between(
+            before.and_then(|land| land.height_map.as_ref()),
+            after.height_map.as_ref(),
+        ),
+    );
+    f(
+        "vertex_normals",
+        |meta| &meta.height_map,
+        VertexChanges::between(
+            before.and_then(|land| land.vertex_normals.as_ref()),
+            after.vertex_normals.as_ref(),
+        ),
+    );
+    f(
+        "world_map_data",
+        |meta| &meta.world_map_data,
+        VertexChanges::between(
+            before.and_then(|land| land.world_map_data.as_ref()),
+            after.world_map_data.as_ref(),
+        ),
+    );
+    f(
+        "vertex_colors",
+        |meta| &meta.vertex_colors,
+        VertexChanges::between(
+            before.and_then(|land| land.vertex_colors.as_ref()),
+            after.vertex_colors.as_ref(),
+        ),
+    );
+    f(
+        "texture_indices",
+        |meta| &meta.texture_indices,
+        VertexChanges::between(
+            before.and_then(|land| land.texture_indices.as_ref()),
+            after.texture_indices.as_ref(),
+        ),
+    );
+}
+
+/// The vertices of one value of a cell that changed, and the vertices that were modified before.
+struct VertexChanges {
+    size: usize,
+    changed: Vec<Index2D>,
+    previously_modified: Vec<bool>,
+}
+
+impl VertexChanges {
+    /// Returns the [VertexChanges] from the `before` to the `after` [RelativeTerrainMap].
+    fn between<U: RelativeTo, const T: usize>(
+        before: Option<&RelativeTerrainMap<U, T>>,
+        after: Option<&RelativeTerrainMap<U, T>>,
+    ) -> Self {
+        let mut changes = Self {
+            size: T,
+            changed: Vec::new(),
+            previously_modified: vec![false; T * T],
+        };
+
+        let Some(after) = after else {
+            return changes;
+        };
+
+        for coords in after.iter_grid() {
+            let previous = match before {
+                Some(before) => {
+                    changes.previously_modified[coords.y * T + coords.x] =
+                        before.has_difference(coords);
+                    before.get_value(coords)
+                }
+                None => after.get_value(coords),
+            };
+
+            let is_changed = match before {
+                Some(_) => previous != after.get_value(coords),
+                None => after.has_difference(coords),
+            };
+
+            if is_changed {
+                changes.changed.push(coords);
+            }
+        }
+
+        changes
+    }
+
+    /// Returns `true` if the vertex at `coords` was modified before the change.
+    fn was_modified(&self, coords: Index2D) -> bool {
+        self.previously_modified[coords.y * self.size + coords.x]
+    }
+}
+
+#[derive(Default)]
+/// The [VertexDecision] of each vertex of the merged land that was changed by a plugin
+/// or a repair. Recording a [Provenance] uses a lot of memory, so it is optional.
+pub struct Provenance {
+    /// The name of each plugin referred to by a [VertexDecision].
+    plugins: Vec<String>,
+    /// The [ProvenanceMap] of each value of each cell, ordered so that the [Provenance]
+    /// does not depend on the order the cells were recorded in.
+    cells: BTreeMap<Vec2<i32>, BTreeMap<&'static str, ProvenanceMap>>,
+}
+
+impl Provenance {
+    /// Returns the name of the plugin at index `plugin` of a [VertexDecision::Merged].
+    pub fn plugin_name(&self, plugin: usize) -> &str {
+        &self.plugins[plugin]
+    }
+
+    /// Returns the [ProvenanceMap] of the `value` of the cell at `coords`, if any vertex changed.
+    pub fn get(&self, coords: Vec2<i32>, value: &str) -> Option<&ProvenanceMap> {
+        self.cells.get(&coords)?.get(value)
+    }
+
+    /// Sets the [VertexDecision] of each changed vertex in the [VertexChanges] of the `value`
+    /// of the cell at `coords`.
+    fn record(
+        &mut self,
+        coords: Vec2<i32>,
+        value: &'static str,
+        changes: &VertexChanges,
+        decision: impl Fn(Index2D) -> VertexDecision,
+    ) {
+        if changes.changed.is_empty() {
+            return;
+        }
+
+        let map = self
+            .cells
+            .entry(coords)
+            .or_default()
+            .entry(value)
+            .or_insert_with(|| ProvenanceMap::new(changes.size));
+
+        for vertex in changes.changed.iter() {
+            map.set(*vertex, decision(*vertex));
+        }
+    }
+
+    /// Records the vertices of the `merged` [LandmassDiff] changed by merging the `plugin`
+    /// [LandmassDiff]. The `previous` [LandmassDiff] contains each cell of the `merged`
+    /// [LandmassDiff] modified by the `plugin` before it was merged.
+    pub fn record_merge(
+        &mut self,
+        previous: &LandmassDiff,
+        merged: &LandmassDiff,
+        plugin: &LandmassDiff,
+        forced_strategy: Option<ConflictStrategy>,
+        resolutions: &Resolutions,
+    ) {
+        // With chunks, each plugin is merged once for each chunk.
+        let plugin_idx = match self
+            .plugins
+            .iter()
+            .position(|name| *name == plugin.plugin.name)
+        {
+            Some(plugin_idx) => plugin_idx,
+            None => {
+                self.plugins.push(plugin.plugin.name.clone());
+                self.plugins.len() - 1
+            }
+        };
+
+        for (coords, _) in plugin.sorted() {
+            let Some(after) = merged.land.get(coords) else {
+                continue;
+            };
+
+            for_each_value(
+                previous.land.get(coords),
+                after,
+                |value, settings, changes| {
+                    let strategy = resolutions
+                        .conflict_strategy(&plugin.plugin.name, *coords, value)
+                        .or(forced_strategy)
+                        .unwrap_or(settings(&plugin.plugin.meta).conflict_strategy);
+                    let strategy = effective_strategy(value, strategy);
+                    self.record(*coords, value, &changes, |vertex| VertexDecision::Merged {
+                        plugin: plugin_idx,
+                        strategy,
+                        conflict: changes.was_modified(vertex),
+                    });
+                },
+            );
+        }
+    }
+
+    /// Moves the [ProvenanceMap] of each cell of the `other` [Provenance] into this one, e.g.
+    /// from a chunk merged on another thread. The cells must not have been recorded yet.
+    pub fn append(&mut self, other: Provenance) {
+        let plugin_indices = other
+            .plugins
+            .into_iter()
+            .map(
+                |name| match self.plugins.iter().position(|other| *other == name) {
+                    Some(plugin_idx) => plugin_idx,
+                    None => {
+                        self.plugins.push(name);
+                        self.plugins.len() - 1
+                    }
+                },
+            )
+            .collect_vec();
+
+        for (coords, mut values) in other.cells {
+            for map in values.values_mut() {
+                for decision in map.decisions.iter_mut().flatten() {
+                    if let VertexDecision::Merged { plugin, .. } = decision {
+                        *plugin = plugin_indices[*plugin];
+                    }
+                }
+            }
+
+            let previous = self.cells.insert(coords, values);
+            assert!(previous.is_none(), "cell recorded twice");
+        }
+    }
+
+    /// Records the vertices of each cell of the `merged` [LandmassDiff] changed by a repair.
+    /// The `previous` [LandmassDiff] is a copy of the `merged` [LandmassDiff] before the repair.
+    pub fn record_repair(&mut self, previous: &LandmassDiff, merged: &LandmassDiff) {
+        for (coords, after) in merged.sorted() {
+            let Some(before) = previous.land.get(coords) else {
+                continue;
+            };
+
+            for_each_value(Some(before), after, |value, _, changes| {
+                self.record(*coords, value, &changes, |_| VertexDecision::Repaired);
+            });
+        }
+    }
+
+    /// Returns a [ProvenanceSummary] of each value of each cell with a [ProvenanceMap],
+    /// ordered by the coordinates of the cell.
+    pub fn summarize(&self) -> Vec<ProvenanceSummary> {
+        let mut summaries = Vec::new();
+        for (coords, values) in self.cells.iter() {
+            for value in PROVENANCE_VALUES {
+                let Some(map) = values.get(value) else {
+                    continue;
+                };
+
+                let mut plugins: BTreeMap<String, usize> = BTreeMap::new();
+                let mut num_conflicts = 0;
+                let mut num_repaired = 0;
+                for decision in map.decisions.iter().flatten() {
+                    match decision {
+                        VertexDecision::Merged {
+                            plugin, conflict, ..
+                        } => {
+                            *plugins.entry(self.plugins[*plugin].clone()).or_default() += 1;
+                            num_conflicts += *conflict as usize;
+                        }
+                        VertexDecision::Repaired => num_repaired += 1,
+                    }
+                }
+
+                summaries.push(ProvenanceSummary {
+                    coords: (*coords).into(),
+                    value: value.to_string(),
+                    plugins,
+                    num_conflicts,
+                    num_repaired,
+                });
+            }
+        }
+
+        summaries
+    }
+}
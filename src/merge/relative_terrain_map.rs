@@ -1,18 +1,18 @@
 use crate::land::grid_access::{GridAccessor2D, GridIterator2D, Index2D, SquareGridIterator};
 use crate::land::height_map::calculate_vertex_normals_map;
 use crate::land::terrain_map::{TerrainMap, Vec3};
+use crate::merge::difference_mask::DifferenceMask;
 use crate::merge::relative_to::RelativeTo;
 use const_default::ConstDefault;
-use std::default::default;
 
 #[derive(Clone)]
-/// A [RelativeTerrainMap] is a set of 3 [TerrainMap] representing the original terrain,
-/// any differences from that original terrain as a delta, and a boolean grid of values
-/// where `true` indicates tht the difference from the original terrain is not zero.
+/// A [RelativeTerrainMap] is a set of 2 [TerrainMap] representing the original terrain and
+/// any differences from that original terrain as a delta, and a [DifferenceMask] where a set
+/// bit indicates that the difference from the original terrain is not zero.
 pub struct RelativeTerrainMap<U: RelativeTo, const T: usize> {
     reference: TerrainMap<U, T>,
     relative: TerrainMap<<U as RelativeTo>::Delta, T>,
-    has_difference: TerrainMap<bool, T>,
+    has_difference: DifferenceMask<T>,
 }
 
 /// Type-erased struct for holding default [RelativeTerrainMap] constants.
@@ -28,7 +28,7 @@ impl DefaultRelativeTerrainMap {
 
 impl<U: RelativeTo, const T: usize> SquareGridIterator<T> for RelativeTerrainMap<U, T> {
     fn iter_grid(&self) -> GridIterator2D<T, T> {
-        default()
+        Default::default()
     }
 }
 
@@ -37,7 +37,7 @@ impl<U: RelativeTo, const T: usize> RelativeTerrainMap<U, T> {
     pub const fn default() -> Self {
         let reference = [[<U as ConstDefault>::DEFAULT; T]; T];
         let relative = [[<<U as RelativeTo>::Delta as ConstDefault>::DEFAULT; T]; T];
-        let has_difference = [[false; T]; T];
+        let has_difference = DifferenceMask::new();
         Self {
             reference,
             relative,
@@ -49,7 +49,7 @@ impl<U: RelativeTo, const T: usize> RelativeTerrainMap<U, T> {
     /// differences from the reference.
     pub const fn empty(reference: TerrainMap<U, T>) -> Self {
         let relative = [[<<U as RelativeTo>::Delta as ConstDefault>::DEFAULT; T]; T];
-        let has_difference = [[false; T]; T];
+        let has_difference = DifferenceMask::new();
         Self {
             reference,
             relative,
@@ -65,15 +65,28 @@ impl<U: RelativeTo, const T: usize> RelativeTerrainMap<U, T> {
     ) -> RelativeTerrainMap<U, T> {
         let mut output = RelativeTerrainMap::empty(*reference);
 
-        for coords in reference.iter_grid() {
-            output.set_value(coords, plugin.get(coords));
+        // Iterate each row instead of calling [Self::set_value] for each coordinate
+        // so that the loop can be vectorized.
+        for y in 0..T {
+            let mut bits = 0;
+            for (x, ((relative, reference), plugin)) in output.relative[y]
+                .iter_mut()
+                .zip(reference[y].iter())
+                .zip(plugin[y].iter())
+                .enumerate()
+            {
+                *relative = U::subtract(*plugin, *reference);
+                bits |= ((*relative != Default::default()) as u128) << x;
+            }
+
+            output.has_difference.set_row(y, bits);
         }
 
         output
     }
 
-    /// Read-only access to the `true` or `false` differences grid.
-    pub fn differences(&self) -> &TerrainMap<bool, T> {
+    /// Read-only access to the [DifferenceMask].
+    pub fn differences(&self) -> &DifferenceMask<T> {
         &self.has_difference
     }
 
@@ -82,17 +95,23 @@ impl<U: RelativeTo, const T: usize> RelativeTerrainMap<U, T> {
         <U as RelativeTo>::add(self.reference.get(coords), self.relative.get(coords))
     }
 
+    /// Returns the value of the reference at `coords`, ignoring any difference.
+    pub fn get_reference(&self, coords: Index2D) -> U {
+        self.reference.get(coords)
+    }
+
     /// Set the value at `coords` by calculating a new difference from the reference.
     pub fn set_value(&mut self, coords: Index2D, value: U) {
         let difference = U::subtract(value, self.reference.get(coords));
         *self.relative.get_mut(coords) = difference;
-        *self.has_difference.get_mut(coords) = difference != default();
+        self.has_difference
+            .set(coords, difference != Default::default());
     }
 
     /// Get the difference at `coords`.
     pub fn get_difference(&self, coords: Index2D) -> <U as RelativeTo>::Delta {
         let delta = self.relative.get(coords);
-        if delta == default() {
+        if delta == Default::default() {
             assert!(!self.has_difference.get(coords));
         } else {
             assert!(self.has_difference.get(coords));
@@ -103,45 +122,93 @@ impl<U: RelativeTo, const T: usize> RelativeTerrainMap<U, T> {
     /// Set the difference at `coords`.
     pub fn set_difference(&mut self, coords: Index2D, difference: <U as RelativeTo>::Delta) {
         *self.relative.get_mut(coords) = difference;
-        *self.has_difference.get_mut(coords) = difference != default();
+        self.has_difference
+            .set(coords, difference != Default::default());
     }
 
     /// Returns `true` if there is a difference at `coords` with respect to the reference.
     pub fn has_difference(&self, coords: Index2D) -> bool {
         if self.has_difference.get(coords) {
-            assert_ne!(self.relative.get(coords), default());
+            assert_ne!(self.relative.get(coords), Default::default());
             true
         } else {
-            assert_eq!(self.relative.get(coords), default());
+            assert_eq!(self.relative.get(coords), Default::default());
             false
         }
     }
 
+    /// Returns `true` if there is a difference anywhere in the row `y`.
+    pub fn row_has_difference(&self, y: usize) -> bool {
+        self.has_difference.row(y) != 0
+    }
+
+    /// Creates a new [RelativeTerrainMap] by merging the differences of `rhs` into `self`.
+    /// Rows without differences in `rhs` are taken from `self`, and rows without differences
+    /// in `self` are taken from `rhs`. Every other row is merged by `merge_row`, which receives
+    /// the differences in the row of `self` and `rhs` and writes the merged differences.
+    pub fn merge_rows(
+        &self,
+        rhs: &Self,
+        mut merge_row: impl FnMut(
+            &[<U as RelativeTo>::Delta; T],
+            &[<U as RelativeTo>::Delta; T],
+            &mut [<U as RelativeTo>::Delta; T],
+        ),
+    ) -> Self {
+        let mut new = self.clone();
+
+        for y in 0..T {
+            if !rhs.row_has_difference(y) {
+                continue;
+            }
+
+            if !self.row_has_difference(y) {
+                new.relative[y] = rhs.relative[y];
+                new.has_difference.set_row(y, rhs.has_difference.row(y));
+                continue;
+            }
+
+            merge_row(&self.relative[y], &rhs.relative[y], &mut new.relative[y]);
+
+            let mut bits = 0;
+            for (x, relative) in new.relative[y].iter().enumerate() {
+                bits |= ((*relative != Default::default()) as u128) << x;
+            }
+
+            new.has_difference.set_row(y, bits);
+        }
+
+        new
+    }
+
     /// Remove all differences.
     pub fn clean_all(&mut self) {
-        for v in self.has_difference.flatten_mut() {
-            *v = false;
-        }
+        self.has_difference.clear();
 
-        for v in self.relative.flatten_mut() {
-            *v = default();
+        for v in self.relative.iter_mut().flatten() {
+            *v = Default::default();
         }
     }
 
     /// Remove differences from all coordinates passed via `iter`.
     pub fn clean_some(&mut self, iter: impl Iterator<Item = Index2D>) {
         for coords in iter {
-            *self.has_difference.get_mut(coords) = false;
-            *self.relative.get_mut(coords) = default();
+            self.has_difference.set(coords, false);
+            *self.relative.get_mut(coords) = Default::default();
         }
     }
 
     /// Create a new [TerrainMap] by adding the differences to the reference.
     /// This is the same as calling [RelativeTerrainMap::get_value] in a loop for each coordinate.
     pub fn to_terrain(&self) -> TerrainMap<U, T> {
-        let mut terrain = [[default(); T]; T];
-        for coords in self.iter_grid() {
-            *terrain.get_mut(coords) = self.get_value(coords);
+        let mut terrain = [[Default::default(); T]; T];
+        for ((value, reference), relative) in terrain
+            .iter_mut()
+            .flatten()
+            .zip(self.reference.iter().flatten())
+            .zip(self.relative.iter().flatten())
+        {
+            *value = U::add(*reference, *relative);
         }
         terrain
     }
@@ -159,11 +226,11 @@ pub trait IsModified {
 
 impl<U: RelativeTo, const T: usize> IsModified for RelativeTerrainMap<U, T> {
     fn is_modified(&self) -> bool {
-        self.has_difference.flatten().contains(&true)
+        !self.has_difference.is_empty()
     }
 
     fn num_differences(&self) -> usize {
-        self.has_difference.flatten().iter().filter(|v| **v).count()
+        self.has_difference.count()
     }
 }
 
@@ -182,11 +249,15 @@ impl<U: RelativeTo, const T: usize> IsModified for OptionalTerrainMap<U, T> {
 
 /// Creates a [TerrainMap] representing the vertex normals of the `height_map` argument by
 /// recalculating the vertex normals from the terrain. If the optional `vertex_normals`
-/// is [Some], then the function will reuse those vertex normals on any unmodified coordinate
-/// in the `height_map` instead of calculating new normals.
+/// is [Some], then the function will reuse the reference of those vertex normals on any unmodified
+/// coordinate in the `height_map` instead of calculating new normals. Any difference in the vertex
+/// normals at an unmodified coordinate is ignored, since it cannot match the heights. Unmodified coordinates within
+/// `blend_radius` vertices of a modified coordinate blend the recalculated normals into the
+/// reused normals, so the lighting does not change abruptly at the edge of the modification.
 pub fn recompute_vertex_normals(
     height_map: &RelativeTerrainMap<i32, 65>,
     vertex_normals: Option<&RelativeTerrainMap<Vec3<i8>, 65>>,
+    blend_radius: usize,
 ) -> TerrainMap<Vec3<i8>, 65> {
     let height_map_abs = height_map.to_terrain();
 
@@ -195,11 +266,71 @@ pub fn recompute_vertex_normals(
     if let Some(vertex_normals) = vertex_normals {
         for coords in height_map.iter_grid() {
             if !height_map.has_difference(coords) {
-                assert_eq!(vertex_normals.get_difference(coords), default());
-                *recomputed_vertex_normals.get_mut(coords) = vertex_normals.get_value(coords);
+                let vertex_normal = vertex_normals.get_reference(coords);
+                *recomputed_vertex_normals.get_mut(coords) =
+                    match distance_to_difference(height_map, coords, blend_radius) {
+                        Some(distance) => blend_vertex_normals(
+                            recomputed_vertex_normals.get(coords),
+                            vertex_normal,
+                            distance as f32 / (blend_radius + 1) as f32,
+                        ),
+                        None => vertex_normal,
+                    };
             }
         }
     }
 
     recomputed_vertex_normals
 }
+
+/// Returns the distance to the nearest coordinate of the `height_map` with a difference,
+/// if any is within `radius` vertices of `coords`.
+fn distance_to_difference<const T: usize>(
+    height_map: &RelativeTerrainMap<i32, T>,
+    coords: Index2D,
+    radius: usize,
+) -> Option<usize> {
+    let max = T - 1;
+    let (min_x, max_x) = (
+        coords.x.saturating_sub(radius),
+        (coords.x + radius).min(max),
+    );
+    let (min_y, max_y) = (
+        coords.y.saturating_sub(radius),
+        (coords.y + radius).min(max),
+    );
+
+    (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| Index2D::new(x, y)))
+        .filter(|neighbor| height_map.has_difference(*neighbor))
+        .map(|neighbor| {
+            neighbor
+                .x
+                .abs_diff(coords.x)
+                .max(neighbor.y.abs_diff(coords.y))
+        })
+        .min()
+}
+
+/// Returns the normalized blend of the `lhs` and `rhs` vertex normals, where a `t` of `0`
+/// is `lhs` and a `t` of `1` is `rhs`.
+fn blend_vertex_normals(lhs: Vec3<i8>, rhs: Vec3<i8>, t: f32) -> Vec3<i8> {
+    let blend = |lhs: i8, rhs: i8| lhs as f32 * (1.0 - t) + rhs as f32 * t;
+    let normal = Vec3::new(
+        blend(lhs.x, rhs.x),
+        blend(lhs.y, rhs.y),
+        blend(lhs.z, rhs.z),
+    );
+
+    let length = (normal.x.powi(2) + normal.y.powi(2) + normal.z.powi(2)).sqrt();
+    if length == 0.0 {
+        return rhs;
+    }
+
+    let scale = 127.0 / length;
+    Vec3::new(
+        (normal.x * scale) as i8,
+        (normal.y * scale) as i8,
+        (normal.z * scale) as i8,
+    )
+}
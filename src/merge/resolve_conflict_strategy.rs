@@ -1,63 +1,116 @@
-use crate::land::grid_access::SquareGridIterator;
+use crate::events::{MergeEvent, RunContext};
 use crate::land::terrain_map::Vec2;
 use crate::merge::conflict::{ConflictResolver, ConflictType};
 use crate::merge::merge_strategy::MergeStrategy;
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::merge::relative_to::RelativeTo;
 use crate::ParsedPlugin;
-use std::default::default;
 
 #[derive(Default)]
 /// Implements [MergeStrategy] to resolve any conflicts by merging changes together.
 pub struct ResolveConflictStrategy {}
 
-impl MergeStrategy for ResolveConflictStrategy {
-    fn apply<U: RelativeTo, const T: usize>(
-        &self,
-        _coords: Vec2<i32>,
-        _plugin: &ParsedPlugin,
-        _value: &str,
+impl ResolveConflictStrategy {
+    /// Merges the `lhs` and `rhs` [RelativeTerrainMap] like [MergeStrategy::apply] without
+    /// emitting a [MergeEvent]. Returns the merged map and the number of minor and major conflicts.
+    pub fn resolve<U: RelativeTo, const T: usize>(
         lhs: &RelativeTerrainMap<U, T>,
         rhs: &RelativeTerrainMap<U, T>,
-    ) -> RelativeTerrainMap<U, T>
+    ) -> (RelativeTerrainMap<U, T>, usize, usize)
     where
         <U as RelativeTo>::Delta: ConflictResolver,
     {
-        let mut new = lhs.clone();
-
-        let params = default();
-
-        for coords in new.iter_grid() {
-            let lhs_diff = lhs.has_difference(coords);
-            let rhs_diff = rhs.has_difference(coords);
-
-            let mut diff = default();
-            if lhs_diff && !rhs_diff {
-                diff = lhs.get_difference(coords);
-            } else if !lhs_diff && rhs_diff {
-                diff = rhs.get_difference(coords);
-            } else if !lhs_diff && !rhs_diff {
-                // NOP.
-            } else {
-                let lhs_diff = lhs.get_difference(coords);
-                let rhs_diff = rhs.get_difference(coords);
-
-                match lhs_diff.average(rhs_diff, &params) {
-                    None => {
-                        diff = lhs.get_difference(coords);
-                    }
+        let params = Default::default();
+        let mut num_minor = 0;
+        let mut num_major = 0;
+
+        let new = lhs.merge_rows(rhs, |lhs, rhs, new| {
+            // Take whichever difference is not zero. This loop can be vectorized.
+            for ((new, lhs), rhs) in new.iter_mut().zip(lhs).zip(rhs) {
+                *new = if *lhs != Default::default() {
+                    *lhs
+                } else {
+                    *rhs
+                };
+            }
+
+            // Conflicts are only possible where both differences are not zero.
+            for ((new, lhs), rhs) in new.iter_mut().zip(lhs).zip(rhs) {
+                if *lhs == Default::default() || *rhs == Default::default() {
+                    continue;
+                }
+
+                match lhs.average(*rhs, &params) {
+                    None => {}
                     Some(ConflictType::Minor(value)) => {
-                        diff = value;
+                        *new = value;
+                        num_minor += 1;
                     }
                     Some(ConflictType::Major(value)) => {
-                        diff = value;
+                        *new = value;
+                        num_major += 1;
                     }
                 }
             }
+        });
+
+        (new, num_minor, num_major)
+    }
+
+    /// Emits a [MergeEvent::ConflictFound] to the [RunContext] if the `lhs` and `rhs`
+    /// [RelativeTerrainMap] of the `value` of the cell at `coords` conflict, regardless of the
+    /// [MergeStrategy] used to merge them.
+    pub fn emit_conflicts<U: RelativeTo, const T: usize>(
+        coords: Vec2<i32>,
+        plugin: &ParsedPlugin,
+        value: &str,
+        lhs: &RelativeTerrainMap<U, T>,
+        rhs: &RelativeTerrainMap<U, T>,
+        context: &RunContext,
+    ) where
+        <U as RelativeTo>::Delta: ConflictResolver,
+    {
+        let (_, num_minor, num_major) = Self::resolve(lhs, rhs);
+        emit_conflict_found(coords, plugin, value, num_minor, num_major, context);
+    }
+}
 
-            new.set_difference(coords, diff);
-        }
+/// Emits a [MergeEvent::ConflictFound] to the [RunContext] if there are any minor or major
+/// conflicts.
+fn emit_conflict_found(
+    coords: Vec2<i32>,
+    plugin: &ParsedPlugin,
+    value: &str,
+    num_minor: usize,
+    num_major: usize,
+    context: &RunContext,
+) {
+    if num_minor + num_major > 0 {
+        context.emit(MergeEvent::ConflictFound {
+            plugin: plugin.name.clone(),
+            coords,
+            value: value.to_string(),
+            num_minor,
+            num_major,
+        });
+    }
+}
 
+impl MergeStrategy for ResolveConflictStrategy {
+    fn apply<U: RelativeTo, const T: usize>(
+        &self,
+        coords: Vec2<i32>,
+        plugin: &ParsedPlugin,
+        value: &str,
+        lhs: &RelativeTerrainMap<U, T>,
+        rhs: &RelativeTerrainMap<U, T>,
+        context: &RunContext,
+    ) -> RelativeTerrainMap<U, T>
+    where
+        <U as RelativeTo>::Delta: ConflictResolver,
+    {
+        let (new, num_minor, num_major) = Self::resolve(lhs, rhs);
+        emit_conflict_found(coords, plugin, value, num_minor, num_major, context);
         new
     }
 }
@@ -0,0 +1,233 @@
+use crate::io::meta_schema::ConflictStrategy;
+use crate::io::report::{StrategyDivergence, StrategyPairDivergence};
+use crate::land::grid_access::SquareGridIterator;
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::Vec2;
+use crate::merge::conflict::{ConflictResolver, ConflictType};
+use crate::merge::ignore_strategy::IgnoreStrategy;
+use crate::merge::overwrite_strategy::OverwriteStrategy;
+use crate::merge::relative_terrain_map::RelativeTerrainMap;
+use crate::merge::relative_to::RelativeTo;
+use crate::merge::resolve_conflict_strategy::ResolveConflictStrategy;
+use crate::{LandmassDiff, ParsedPlugin};
+use itertools::Itertools;
+use log::trace;
+use owo_colors::OwoColorize;
+
+/// The [ConflictStrategy] of each merge strategy compared by a shadow evaluation.
+const SHADOW_STRATEGIES: [ConflictStrategy; 3] = [
+    ConflictStrategy::Resolve,
+    ConflictStrategy::Overwrite,
+    ConflictStrategy::Ignore,
+];
+
+/// Two strategies disagree strongly if their results have a major disagreement in at least
+/// this fraction of the vertices of a cell.
+const STRONG_DISAGREEMENT_FRACTION: f32 = 0.01;
+
+/// Combines the `lhs` and `rhs` [RelativeTerrainMap] with the [ConflictStrategy] `strategy`.
+/// Unlike merging, no conflicts are emitted.
+fn shadow_apply<U: RelativeTo + ConflictResolver, const T: usize>(
+    strategy: ConflictStrategy,
+    lhs: &RelativeTerrainMap<U, T>,
+    rhs: &RelativeTerrainMap<U, T>,
+) -> RelativeTerrainMap<U, T>
+where
+    <U as RelativeTo>::Delta: ConflictResolver,
+{
+    match strategy {
+        ConflictStrategy::Resolve => ResolveConflictStrategy::resolve(lhs, rhs).0,
+        ConflictStrategy::Overwrite => OverwriteStrategy::overwrite(lhs, rhs),
+        ConflictStrategy::Ignore => IgnoreStrategy::ignore(lhs, rhs),
+        ConflictStrategy::Auto => {
+            unreachable!("SHADOW_STRATEGIES only contains concrete strategies")
+        }
+    }
+}
+
+/// Counts the minor and major disagreements between the `lhs` and `rhs` [RelativeTerrainMap].
+/// Vertices are classified with [ConflictResolver::average], like conflicts during a merge.
+fn count_disagreements<U: RelativeTo + ConflictResolver, const T: usize>(
+    lhs: &RelativeTerrainMap<U, T>,
+    rhs: &RelativeTerrainMap<U, T>,
+) -> (usize, usize) {
+    let params = Default::default();
+    let mut num_minor = 0;
+    let mut num_major = 0;
+
+    for coords in lhs.iter_grid() {
+        if !lhs.has_difference(coords) && !rhs.has_difference(coords) {
+            continue;
+        }
+
+        match lhs
+            .get_value(coords)
+            .average(rhs.get_value(coords), &params)
+        {
+            None => {}
+            Some(ConflictType::Minor(_)) => num_minor += 1,
+            Some(ConflictType::Major(_)) => num_major += 1,
+        }
+    }
+
+    (num_minor, num_major)
+}
+
+/// Applies every merge strategy to the `old` and `new` [RelativeTerrainMap] of the `value` in
+/// the cell at `coords` and compares the results. Returns a [StrategyDivergence] if the results
+/// of any strategies differ.
+fn evaluate_strategies<U: RelativeTo + ConflictResolver, const T: usize>(
+    coords: Vec2<i32>,
+    plugin: &ParsedPlugin,
+    value: &str,
+    strategy: ConflictStrategy,
+    old: Option<&RelativeTerrainMap<U, T>>,
+    new: Option<&RelativeTerrainMap<U, T>>,
+) -> Option<StrategyDivergence>
+where
+    <U as RelativeTo>::Delta: ConflictResolver,
+{
+    let (Some(old), Some(new)) = (old, new) else {
+        return None;
+    };
+
+    let results = SHADOW_STRATEGIES.map(|strategy| shadow_apply(strategy, old, new));
+
+    let pairs = SHADOW_STRATEGIES
+        .iter()
+        .zip(results.iter())
+        .tuple_combinations()
+        .map(|((lhs_strategy, lhs), (rhs_strategy, rhs))| {
+            let (num_minor, num_major) = count_disagreements(lhs, rhs);
+            StrategyPairDivergence {
+                lhs: *lhs_strategy,
+                rhs: *rhs_strategy,
+                num_minor,
+                num_major,
+            }
+        })
+        .collect_vec();
+
+    if pairs
+        .iter()
+        .all(|pair| pair.num_minor == 0 && pair.num_major == 0)
+    {
+        return None;
+    }
+
+    let strong_disagreement_threshold =
+        (((T * T) as f32 * STRONG_DISAGREEMENT_FRACTION) as usize).max(1);
+    let disagree_strongly = pairs
+        .iter()
+        .any(|pair| pair.num_major >= strong_disagreement_threshold);
+
+    for pair in pairs.iter() {
+        trace!(
+            "({:>4}, {:>4}) {:<15} | {:<50} | {:?} vs {:?} | {:>4} Major | {:>4} Minor{}",
+            coords.x,
+            coords.y,
+            value,
+            plugin.name,
+            pair.lhs,
+            pair.rhs,
+            pair.num_major,
+            pair.num_minor,
+            if pair.num_major >= strong_disagreement_threshold {
+                " *".bold().bright_red().to_string()
+            } else {
+                "".to_string()
+            }
+        );
+    }
+
+    Some(StrategyDivergence {
+        plugin: plugin.name.clone(),
+        coords: coords.into(),
+        value: value.to_string(),
+        strategy,
+        pairs,
+        disagree_strongly,
+    })
+}
+
+/// Compares every merge strategy for each value of the `new` [LandscapeDiff] of the `plugin`
+/// and the `old` [LandscapeDiff] it will be merged into. The `forced_strategy`, if any, is
+/// recorded as the strategy used by the merge instead of the one in the meta file.
+fn evaluate_landscape_strategies(
+    plugin: &ParsedPlugin,
+    old: &LandscapeDiff,
+    new: &LandscapeDiff,
+    forced_strategy: Option<ConflictStrategy>,
+) -> Vec<StrategyDivergence> {
+    let coords = new.coords;
+    let strategy = |meta_strategy| forced_strategy.unwrap_or(meta_strategy);
+    [
+        evaluate_strategies(
+            coords,
+            plugin,
+            "height_map",
+            strategy(plugin.meta.height_map.conflict_strategy),
+            old.height_map.as_ref(),
+            new.height_map.as_ref(),
+        ),
+        evaluate_strategies(
+            coords,
+            plugin,
+            "vertex_normals",
+            strategy(plugin.meta.height_map.conflict_strategy),
+            old.vertex_normals.as_ref(),
+            new.vertex_normals.as_ref(),
+        ),
+        evaluate_strategies(
+            coords,
+            plugin,
+            "world_map_data",
+            strategy(plugin.meta.world_map_data.conflict_strategy),
+            old.world_map_data.as_ref(),
+            new.world_map_data.as_ref(),
+        ),
+        evaluate_strategies(
+            coords,
+            plugin,
+            "vertex_colors",
+            strategy(plugin.meta.vertex_colors.conflict_strategy),
+            old.vertex_colors.as_ref(),
+            new.vertex_colors.as_ref(),
+        ),
+        evaluate_strategies(
+            coords,
+            plugin,
+            "texture_indices",
+            strategy(plugin.meta.texture_indices.conflict_strategy),
+            old.texture_indices.as_ref(),
+            new.texture_indices.as_ref(),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Runs a shadow evaluation of merging the `plugin` [LandmassDiff] into the `merged`
+/// [LandmassDiff]. Every merge strategy is applied to each conflicted cell without changing
+/// the `merged` [LandmassDiff], and each [StrategyDivergence] between the results is returned.
+/// The `forced_strategy` is the [ConflictStrategy] forced by the preset, if any.
+pub fn shadow_evaluate_landmass(
+    merged: &LandmassDiff,
+    plugin: &LandmassDiff,
+    forced_strategy: Option<ConflictStrategy>,
+) -> Vec<StrategyDivergence> {
+    let mut divergences = Vec::new();
+    for (coords, land) in plugin.sorted() {
+        if let Some(merged_land) = merged.land.get(coords) {
+            divergences.extend(evaluate_landscape_strategies(
+                &plugin.plugin,
+                merged_land,
+                land,
+                forced_strategy,
+            ));
+        }
+    }
+
+    divergences
+}
@@ -0,0 +1,63 @@
+use crate::io::report::WorldMapOnlyEdits;
+use crate::land::terrain_map::LandData;
+use crate::{find_landscape_diff, Landmass};
+use itertools::Itertools;
+use log::{debug, info};
+
+/// Finds each cell where a plugin of the `landmasses` only changes the world map data of the
+/// `reference` [Landmass], e.g. a mod that only recolors the world map, and removes the LAND of
+/// that plugin so that it is not merged. Landmasses without any LAND left are removed, so that
+/// these plugins are not masters of the output.
+/// Returns a [WorldMapOnlyEdits] for each plugin, in load order.
+pub fn drop_world_map_only_edits(
+    landmasses: &mut Vec<Landmass>,
+    reference: &Landmass,
+) -> Vec<WorldMapOnlyEdits> {
+    let mut world_map_only_edits = Vec::new();
+    for landmass in landmasses.iter_mut() {
+        let cells = landmass
+            .land
+            .iter()
+            .filter(|(coords, land)| {
+                let landscape_diff =
+                    find_landscape_diff(&landmass.plugin, land, reference.land.get(coords));
+                landscape_diff.modified_data() == LandData::WORLD_MAP
+            })
+            .map(|(coords, _)| *coords)
+            .sorted_by_key(|coords| (coords.x, coords.y))
+            .collect_vec();
+
+        if cells.is_empty() {
+            continue;
+        }
+
+        for coords in cells.iter() {
+            landmass.land.remove(coords);
+            landmass.plugins.remove(coords);
+        }
+
+        info!(
+            "Ignoring {} LAND records of {} that only change the world map",
+            cells.len(),
+            landmass.plugin.name
+        );
+
+        world_map_only_edits.push(WorldMapOnlyEdits {
+            plugin: landmass.plugin.name.clone(),
+            cells: cells.into_iter().map(Into::into).collect(),
+        });
+    }
+
+    landmasses.retain(|landmass| {
+        if landmass.land.is_empty() {
+            debug!(
+                "Skipping {}, since all of its LAND only changes the world map",
+                landmass.plugin.name
+            );
+        }
+
+        !landmass.land.is_empty()
+    });
+
+    world_map_only_edits
+}
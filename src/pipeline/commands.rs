@@ -0,0 +1,550 @@
+use crate::cli::{
+    CleanPluginArgs, Cli, CliLandValue, ExportArgs, ExportReferenceArgs, InspectArgs, PreviewArgs,
+};
+use crate::error::MergeError;
+use crate::events::{MergeEvent, RunContext};
+use crate::io::artifacts::ArtifactSink;
+use crate::io::bundle::save_bundle;
+use crate::io::export::{
+    save_lod_meshes, save_reference_snapshot, save_tesannwyn_raw, ExportFormat,
+};
+use crate::io::land_diff::save_land_diffs;
+use crate::io::parsed_plugins::{cleaned_name, ParsedPlugins};
+use crate::io::resolutions::Resolutions;
+use crate::io::save_to_image::save_strategy_previews;
+use crate::io::save_to_plugin::save_cleaned_plugin;
+use crate::land::grid_access::Index2D;
+use crate::land::terrain_map::Vec2;
+use crate::land::textures::KnownTextures;
+use crate::merge::cells::merge_cells;
+use crate::merge::provenance::{Provenance, VertexDecision};
+use crate::merge::relative_terrain_map::RelativeTerrainMap;
+use crate::merge::relative_to::RelativeTo;
+use crate::pipeline::{MergePipeline, MergeStage};
+use crate::repair::cleaning::{find_unmodified_cells, find_unmodified_land};
+use crate::repair::outlier_cells::{find_outlier_cells, handle_outlier_cells, stitched_exclusions};
+use crate::repair::skipped_cells::exclude_cells;
+use crate::{
+    create_merged_lands_from_reference, create_tes3_landmass, merge_optional_landscape_diff,
+    try_create_landmass,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+use log::{debug, info, warn};
+use owo_colors::OwoColorize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Parses and diffs the plugins, and saves the [Report] of their conflicts without merging them.
+/// See [MergePipeline::report_only].
+///
+/// [Report]: crate::io::report::Report
+pub(crate) fn report_conflicts(cli: &Cli, context: &RunContext) -> Result<()> {
+    MergePipeline::new(cli)
+        .with_context(context)
+        .report_only()
+        .run(context.cancellation_token())?;
+    Ok(())
+}
+
+/// Merges the LAND of all plugins and saves it in the format in [ExportArgs].
+/// Nothing is exported if the [RunContext] is cancelled before the merge finishes.
+pub(crate) fn export(cli: &Cli, args: &ExportArgs, context: &RunContext) -> Result<()> {
+    let start = Instant::now();
+    let cancel = context.cancellation_token();
+    let pipeline = MergePipeline::new(cli)
+        .with_context(context)
+        .with_reporting(false)
+        .stop_after(MergeStage::Merge);
+
+    let mut state = pipeline.run_stages(cancel)?;
+    if state.report.cancelled.is_some() {
+        return Ok(());
+    }
+
+    let mut merged_lands = state
+        .merged_lands
+        .take()
+        .with_context(|| anyhow!("Unable to export LAND that was not merged"))?;
+
+    exclude_cells(&mut merged_lands, &state.excluded_cells);
+
+    let config = &state.config;
+    let outliers = find_outlier_cells(&merged_lands, config.outlier_distance);
+    let stitched_outliers = stitched_exclusions(&outliers, config.outlier_cells);
+    handle_outlier_cells(&mut merged_lands, &outliers, config.outlier_cells);
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Exporting" });
+
+    let export_dir = args.export_dir(cli)?;
+    let files = cli.file_provider();
+    files.create_dir_all(&export_dir).with_context(|| {
+        anyhow!(
+            "Unable to create export directory {}",
+            export_dir.to_string_lossy()
+        )
+    })?;
+
+    let artifacts = ArtifactSink::directory(files, export_dir);
+    match args.format.into() {
+        ExportFormat::LodObj => save_lod_meshes(&artifacts, &merged_lands, &args.lod_options())?,
+        ExportFormat::TesannwynRaw => {
+            save_tesannwyn_raw(&artifacts, &merged_lands, &stitched_outliers, args.raw_bits)?
+        }
+        ExportFormat::LandDiff => {
+            let modded_landmasses = state
+                .modded_landmasses
+                .iter()
+                .map(|modded_landmass| modded_landmass.undeferred().into_owned())
+                .collect_vec();
+            save_land_diffs(&artifacts, &modded_landmasses, &state.known_textures)?
+        }
+        ExportFormat::Bundle => {
+            let modded_landmasses = state
+                .modded_landmasses
+                .iter()
+                .map(|modded_landmass| modded_landmass.undeferred().into_owned())
+                .collect_vec();
+            save_bundle(
+                &artifacts,
+                &state.parsed_plugins,
+                &modded_landmasses,
+                &state.known_textures,
+            )?
+        }
+    }
+
+    pipeline.finish(&state, cancel, start);
+    Ok(())
+}
+
+/// Saves a snapshot of the LAND merged from the masters before any plugin, i.e. the LAND that
+/// each plugin is compared to, to the directory in [ExportReferenceArgs].
+pub(crate) fn export_reference(
+    cli: &Cli,
+    args: &ExportReferenceArgs,
+    context: &RunContext,
+) -> Result<()> {
+    let start = Instant::now();
+    let cancel = context.cancellation_token();
+    let pipeline = MergePipeline::new(cli)
+        .with_context(context)
+        .with_reporting(false)
+        .stop_after(MergeStage::Parse);
+
+    let state = pipeline.run_stages(cancel)?;
+    if state.report.cancelled.is_some() {
+        return Ok(());
+    }
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Merging Masters",
+    });
+
+    // Only the masters are loaded, so that the LTEX records are only those of the masters.
+    let config = &state.config;
+    let parsed_plugins = &state.parsed_plugins;
+    let reference_masters = if cli.diff_masters || config.diff_masters {
+        parsed_plugins.split_base_masters(&config.base_masters).0
+    } else {
+        parsed_plugins.masters.clone()
+    };
+
+    let mut known_textures = KnownTextures::new();
+    let reference_landmass = Arc::new(create_tes3_landmass(
+        "ReferenceLandmass.esp",
+        parsed_plugins,
+        reference_masters.iter(),
+        &mut known_textures,
+        config.duplicate_records,
+    )?);
+
+    let reference = create_merged_lands_from_reference(reference_landmass);
+    let outliers = find_outlier_cells(&reference, config.outlier_distance);
+    let stitched_outliers = stitched_exclusions(&outliers, config.outlier_cells);
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Exporting" });
+
+    let export_dir = args.export_dir(cli)?;
+    let files = cli.file_provider();
+    files.create_dir_all(&export_dir).with_context(|| {
+        anyhow!(
+            "Unable to create export directory {}",
+            export_dir.to_string_lossy()
+        )
+    })?;
+
+    let artifacts = ArtifactSink::directory(files, export_dir);
+    save_reference_snapshot(
+        &artifacts,
+        &reference,
+        &stitched_outliers,
+        &known_textures,
+        &cli.image_options(),
+    )?;
+
+    info!(
+        "Exported {} LAND records and {} LTEX records from {} masters",
+        reference.land.len(),
+        known_textures.len(),
+        reference_masters.len()
+    );
+
+    pipeline.finish(&state, cancel, start);
+    Ok(())
+}
+
+/// Merges the LAND like [crate::merge_all] while recording a [Provenance], and logs how the
+/// vertex in [InspectArgs] got its value. Nothing is saved.
+pub(crate) fn inspect(cli: &Cli, args: &InspectArgs, context: &RunContext) -> Result<()> {
+    let start = Instant::now();
+    let cancel = context.cancellation_token();
+    let pipeline = MergePipeline::new(cli)
+        .with_context(context)
+        .with_reporting(false)
+        .with_provenance()
+        .stop_after(MergeStage::Merge);
+
+    let state = pipeline.run_stages(cancel)?;
+    if state.report.cancelled.is_some() {
+        return Ok(());
+    }
+
+    let (Some(merged_lands), Some(provenance)) =
+        (state.merged_lands.as_ref(), state.provenance.as_ref())
+    else {
+        bail!("Unable to inspect LAND that was not merged");
+    };
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Inspecting",
+    });
+
+    let coords = args.cell;
+    let Some(merged_land) = merged_lands.land.get(&coords) else {
+        bail!(MergeError::NoLandRecord(coords));
+    };
+
+    let plugin_lands = state
+        .modded_landmasses
+        .iter()
+        .filter_map(|modded_landmass| {
+            modded_landmass
+                .land_at(&coords)
+                .map(|land| (modded_landmass.plugin.name.as_str(), land.into_owned()))
+        })
+        .collect_vec();
+
+    let value = args.value();
+    let name = value.name();
+    let vertex = args.vertex;
+    match value {
+        CliLandValue::HeightMap => inspect_vertex(
+            provenance,
+            coords,
+            name,
+            vertex,
+            merged_land.height_map.as_ref(),
+            plugin_lands
+                .iter()
+                .map(|(name, land)| (*name, land.height_map.as_ref())),
+        ),
+        CliLandValue::VertexNormals => inspect_vertex(
+            provenance,
+            coords,
+            name,
+            vertex,
+            merged_land.vertex_normals.as_ref(),
+            plugin_lands
+                .iter()
+                .map(|(name, land)| (*name, land.vertex_normals.as_ref())),
+        ),
+        CliLandValue::WorldMapData => inspect_vertex(
+            provenance,
+            coords,
+            name,
+            vertex,
+            merged_land.world_map_data.as_ref(),
+            plugin_lands
+                .iter()
+                .map(|(name, land)| (*name, land.world_map_data.as_ref())),
+        ),
+        CliLandValue::VertexColors => inspect_vertex(
+            provenance,
+            coords,
+            name,
+            vertex,
+            merged_land.vertex_colors.as_ref(),
+            plugin_lands
+                .iter()
+                .map(|(name, land)| (*name, land.vertex_colors.as_ref())),
+        ),
+        CliLandValue::TextureIndices => inspect_vertex(
+            provenance,
+            coords,
+            name,
+            vertex,
+            merged_land.texture_indices.as_ref(),
+            plugin_lands
+                .iter()
+                .map(|(name, land)| (*name, land.texture_indices.as_ref())),
+        ),
+    }?;
+
+    pipeline.finish(&state, cancel, start);
+    Ok(())
+}
+
+/// Logs the value of the `vertex` of the `value` of the cell at `coords` in the `merged`
+/// [RelativeTerrainMap], its [VertexDecision] in the [Provenance], and the value of each
+/// of the `plugins` that modified it.
+fn inspect_vertex<'a, U: RelativeTo, const T: usize>(
+    provenance: &Provenance,
+    coords: Vec2<i32>,
+    value: &str,
+    vertex: Vec2<i32>,
+    merged: Option<&RelativeTerrainMap<U, T>>,
+    plugins: impl Iterator<Item = (&'a str, Option<&'a RelativeTerrainMap<U, T>>)>,
+) -> Result<()> {
+    if !(0..T as i32).contains(&vertex.x) || !(0..T as i32).contains(&vertex.y) {
+        bail!(MergeError::VertexOutOfBounds {
+            vertex,
+            size: T,
+            value: value.to_string(),
+        });
+    }
+
+    let Some(merged) = merged else {
+        bail!(MergeError::MissingValue {
+            coords,
+            value: value.to_string(),
+        });
+    };
+
+    let index = Index2D::new(vertex.x as usize, vertex.y as usize);
+    info!(
+        "({:>4}, {:>4}) {:<15} | Vertex ({}, {}) = {:?}",
+        coords.x,
+        coords.y,
+        value,
+        vertex.x,
+        vertex.y,
+        merged.get_value(index)
+    );
+
+    let decision = provenance.get(coords, value).and_then(|map| map.get(index));
+    match decision {
+        None => info!("Unchanged from the masters"),
+        Some(VertexDecision::Merged {
+            plugin,
+            strategy,
+            conflict,
+        }) => info!(
+            "Merged from {} with strategy {:?}{}",
+            provenance.plugin_name(plugin).bold(),
+            strategy,
+            if conflict {
+                " over a change by an earlier plugin"
+            } else {
+                ""
+            }
+        ),
+        Some(VertexDecision::Repaired) => info!("Changed by a repair after merging"),
+    }
+
+    for (name, plugin) in plugins {
+        let Some(plugin) = plugin else {
+            continue;
+        };
+
+        if plugin.has_difference(index) {
+            info!("{:<50} | {:?}", name, plugin.get_value(index));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a copy of the plugin in [CleanPluginArgs] without any LAND or CELL records
+/// that are identical to the records provided by the plugin's masters.
+pub(crate) fn clean_plugin(cli: &Cli, args: &CleanPluginArgs, context: &RunContext) -> Result<()> {
+    let start = Instant::now();
+    let cancel = context.cancellation_token();
+    let pipeline = MergePipeline::new(cli)
+        .with_context(context)
+        .with_reporting(false)
+        .with_masters_of(&args.plugin)
+        .stop_after(MergeStage::Parse);
+
+    let state = pipeline.run_stages(cancel)?;
+    if state.report.cancelled.is_some() {
+        return Ok(());
+    }
+
+    let plugin = state
+        .parsed_plugins
+        .plugins
+        .first()
+        .with_context(|| anyhow!("Unable to clean plugin {}", args.plugin))?;
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Creating Reference Land",
+    });
+
+    let master_plugins = ParsedPlugins {
+        masters: state.parsed_plugins.masters.clone(),
+        plugins: Vec::new(),
+        load_order: Vec::new(),
+    };
+
+    let config = &state.config;
+    let mut known_textures = KnownTextures::new();
+    let reference_landmass = create_tes3_landmass(
+        "ReferenceLandmass.esp",
+        &master_plugins,
+        master_plugins.masters.iter(),
+        &mut known_textures,
+        config.duplicate_records,
+    )?;
+
+    let reference_cells = merge_cells(&master_plugins);
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Cleaning Plugin",
+    });
+
+    let unmodified_land = try_create_landmass(
+        &master_plugins,
+        plugin,
+        &mut known_textures,
+        config.duplicate_records,
+    )
+    .map(|landmass| find_unmodified_land(&landmass, &reference_landmass))
+    .unwrap_or_default();
+
+    let unmodified_cells = find_unmodified_cells(plugin, &reference_cells);
+
+    debug!(
+        "Found {} LAND records identical to masters",
+        unmodified_land.len()
+    );
+    debug!(
+        "Found {} CELL records identical to masters",
+        unmodified_cells.len()
+    );
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Saving" });
+
+    let data_files = cli.data_files_dir()?;
+    let output_file_dir = cli.output_file_dir()?;
+    let file_name = args
+        .output_file
+        .clone()
+        .unwrap_or_else(|| cleaned_name(&plugin.name));
+
+    save_cleaned_plugin(
+        cli.file_provider(),
+        &data_files,
+        &output_file_dir,
+        &plugin.name,
+        &file_name,
+        &unmodified_land,
+        &unmodified_cells,
+    )?;
+
+    pipeline.finish(&state, cancel, start);
+    Ok(())
+}
+
+/// Saves images of the LAND that each [ConflictStrategy] in [PreviewArgs] would produce
+/// for a single cell. No plugin is saved.
+///
+/// [ConflictStrategy]: crate::io::meta_schema::ConflictStrategy
+pub(crate) fn preview(cli: &Cli, args: &PreviewArgs, context: &RunContext) -> Result<()> {
+    let start = Instant::now();
+    let cancel = context.cancellation_token();
+    let pipeline = MergePipeline::new(cli)
+        .with_context(context)
+        .with_reporting(false)
+        .stop_after(MergeStage::Diff);
+
+    let mut state = pipeline.run_stages(cancel)?;
+    if state.report.cancelled.is_some() {
+        return Ok(());
+    }
+
+    let reference_landmass = state
+        .reference_landmass
+        .take()
+        .with_context(|| anyhow!("Unable to preview LAND that was not diffed"))?;
+
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Previewing Strategies",
+    });
+
+    let coords = args.cell;
+    let merged_lands = create_merged_lands_from_reference(reference_landmass);
+
+    let plugin_lands = state
+        .modded_landmasses
+        .iter()
+        .filter_map(|modded_landmass| {
+            modded_landmass
+                .land_at(&coords)
+                .map(|land| (&modded_landmass.plugin, land))
+        })
+        .collect_vec();
+
+    if plugin_lands.is_empty() {
+        bail!(MergeError::NoModifiedLandRecord(coords));
+    } else if plugin_lands.len() == 1 {
+        warn!(
+            "{}",
+            format!(
+                "Only 1 plugin modifies the LAND record at ({}, {})",
+                coords.x, coords.y
+            )
+            .yellow()
+        );
+    }
+
+    let mut previews = Vec::new();
+    for strategy in args.strategies() {
+        let mut merged_land = merged_lands.land.get(&coords).cloned();
+
+        for (plugin, land) in plugin_lands.iter() {
+            merged_land = Some(merge_optional_landscape_diff(
+                plugin,
+                merged_land.as_ref(),
+                land,
+                Some(strategy),
+                &Resolutions::default(),
+                context,
+            )?);
+        }
+
+        let Some(height_map) = merged_land.and_then(|land| land.height_map) else {
+            warn!(
+                "{}",
+                format!(
+                    "Skipping {:?} strategy panel since the merged LAND record at ({}, {}) has no height map",
+                    strategy, coords.x, coords.y
+                )
+                .yellow()
+            );
+            continue;
+        };
+
+        debug!("Previewing {:?} strategy", strategy);
+        previews.push((strategy, height_map.to_terrain()));
+    }
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Saving" });
+
+    let artifacts = cli.artifacts()?;
+    save_strategy_previews(&artifacts, coords, &previews, &cli.image_options())?;
+    artifacts.finish()?;
+
+    pipeline.finish(&state, cancel, start);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use crate::cli::Cli;
+use crate::error::MergeError;
+use crate::events::RunContext;
+use crate::io::bundle::load_bundle;
+use crate::io::land_diff::{insert_patches, load_land_diff, load_patches};
+use crate::io::meta_schema::{GlobalConfig, MetaDatabase, NewLandPolicy};
+use crate::io::new_land::find_new_land_collisions;
+use crate::io::parsed_plugins::ParsedPlugins;
+use crate::io::report::{NewLandCell, Report};
+use crate::land::terrain_map::Vec2;
+use crate::land::textures::KnownTextures;
+use crate::{create_landmasses, Landmass, LandmassDiff};
+use anyhow::{bail, Result};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::{debug, warn};
+use owo_colors::OwoColorize;
+use std::sync::Arc;
+
+/// Creates the reference [Landmass] of the masters in `parsed_plugins` and a [LandmassDiff] of
+/// each plugin, bundle, land diff, and patch that is merged, in the order they are merged.
+/// Cells with new LAND are handled by the [NewLandPolicy] and recorded in the `report`.
+pub(crate) fn diff_plugins(
+    cli: &Cli,
+    config: &GlobalConfig,
+    database: &MetaDatabase,
+    parsed_plugins: &ParsedPlugins,
+    known_textures: &mut KnownTextures,
+    report: &mut Report,
+    context: &RunContext,
+) -> Result<(Arc<Landmass>, Vec<LandmassDiff>)> {
+    let (reference_landmass, mut modded_landmasses, duplicate_land, world_map_only_edits) =
+        create_landmasses(
+            config,
+            parsed_plugins,
+            known_textures,
+            cli.diff_masters || config.diff_masters,
+            cli.chunk_size().is_some(),
+            context,
+        )?;
+    report.duplicate_land = duplicate_land;
+    report.world_map_only_edits = world_map_only_edits;
+
+    if let Some(file_path) = cli.bundle() {
+        let num_loaded = load_bundle(
+            cli.file_provider(),
+            &cli.data_files_dir()?,
+            &file_path,
+            parsed_plugins,
+            &reference_landmass,
+            known_textures,
+            &mut modded_landmasses,
+        )?;
+        debug!(
+            "Loaded {} land diffs from bundle {}",
+            num_loaded,
+            file_path.to_string_lossy()
+        );
+    }
+
+    for file_path in cli.land_diffs() {
+        let land_diff = load_land_diff(
+            cli.file_provider(),
+            &file_path,
+            &reference_landmass,
+            known_textures,
+        )?;
+        debug!(
+            "Loaded {} LAND records for {} from land diff {}",
+            land_diff.land.len(),
+            land_diff.plugin.name,
+            file_path.to_string_lossy()
+        );
+        modded_landmasses.push(land_diff);
+    }
+
+    let patches = load_patches(
+        cli.file_provider(),
+        &cli.patches_dir()?,
+        config,
+        database,
+        &reference_landmass,
+        known_textures,
+    )?;
+    debug!("Found {} patches", patches.len());
+    insert_patches(&mut modded_landmasses, patches);
+
+    report.new_land =
+        apply_new_land_policy(config.new_land, &reference_landmass, &mut modded_landmasses)?;
+    report.new_land_collisions = find_new_land_collisions(&report.new_land);
+
+    debug!(
+        "Found {} masters and {} plugins",
+        parsed_plugins.masters.len(),
+        parsed_plugins.plugins.len(),
+    );
+    debug!("Found {} unique LTEX records", known_textures.len());
+    debug!("{} plugins contain LAND records", modded_landmasses.len());
+
+    Ok((reference_landmass, modded_landmasses))
+}
+
+/// Returns a [NewLandCell] for each cell where the `modded_landmasses` add LAND that the
+/// `reference` [Landmass] does not have, ordered by `x` and `y` coordinates. Cells with new LAND
+/// from more than one plugin are handled by the [NewLandPolicy].
+fn apply_new_land_policy(
+    policy: NewLandPolicy,
+    reference: &Landmass,
+    modded_landmasses: &mut [LandmassDiff],
+) -> Result<Vec<NewLandCell>> {
+    let mut new_land: HashMap<Vec2<i32>, Vec<usize>> = HashMap::new();
+    for (idx, modded_landmass) in modded_landmasses.iter().enumerate() {
+        for coords in modded_landmass.cells() {
+            if !reference.land.contains_key(&coords) {
+                new_land.entry(coords).or_default().push(idx);
+            }
+        }
+    }
+
+    let mut dropped_cells: HashMap<usize, HashSet<Vec2<i32>>> = HashMap::new();
+    let mut new_land_cells = Vec::new();
+    for (coords, indices) in new_land
+        .into_iter()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+    {
+        let plugins = indices
+            .iter()
+            .map(|idx| modded_landmasses[*idx].plugin.name.clone())
+            .collect_vec();
+
+        let merged = if plugins.len() > 1 {
+            match policy {
+                NewLandPolicy::Resolve => plugins.clone(),
+                NewLandPolicy::FirstWins => {
+                    warn!(
+                        "{} {}",
+                        format!(
+                            "Plugins {} each add LAND at ({}, {}) where no master has LAND.",
+                            plugins.join(", ").bold(),
+                            coords.x,
+                            coords.y
+                        )
+                        .yellow(),
+                        format!("Only the LAND of {} is merged", plugins[0].bold()).yellow()
+                    );
+
+                    for idx in indices.iter().skip(1) {
+                        dropped_cells.entry(*idx).or_default().insert(coords);
+                    }
+
+                    vec![plugins[0].clone()]
+                }
+                NewLandPolicy::Fail => {
+                    bail!(MergeError::ContestedNewLand {
+                        coords,
+                        plugins: plugins.join(", "),
+                    });
+                }
+            }
+        } else {
+            plugins.clone()
+        };
+
+        new_land_cells.push(NewLandCell {
+            coords: coords.into(),
+            plugins,
+            merged,
+        });
+    }
+
+    for (idx, cells) in dropped_cells.into_iter() {
+        modded_landmasses[idx] = modded_landmasses[idx].without(&cells);
+    }
+
+    debug!(
+        "Found {} cells with new LAND, {} from more than one plugin",
+        new_land_cells.len(),
+        new_land_cells
+            .iter()
+            .filter(|cell| cell.plugins.len() > 1)
+            .count()
+    );
+
+    Ok(new_land_cells)
+}
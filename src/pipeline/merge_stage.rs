@@ -0,0 +1,374 @@
+use crate::cli::Cli;
+use crate::events::{MergeEvent, RunContext};
+use crate::io::meta_schema::{GlobalConfig, SeamSettings};
+use crate::io::report::{DiffBaseline, MultiWayConflict, Report};
+use crate::io::resolutions::Resolutions;
+use crate::merge::chunks::chunk_cells;
+use crate::merge::multi_way::resolve_multi_way_conflicts;
+use crate::merge::parallel::map_in_order;
+use crate::merge::provenance::Provenance;
+use crate::merge::shadow_evaluation::shadow_evaluate_landmass;
+use crate::repair::seam_detection::repair_landmass_seams_until_fixed;
+use crate::repair::world_map::regenerate_world_map_data;
+use crate::timings::TimedPhase;
+use crate::{
+    check_cancelled, create_merged_lands_from_reference, merge_landmass_into, Landmass,
+    LandmassDiff,
+};
+use anyhow::Result;
+use itertools::Itertools;
+use log::debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Merges the `modded_landmasses` into the `reference_landmass` of the masters and repairs any
+/// seams. Returns the merged [LandmassDiff].
+/// With the predecessor [DiffBaseline], the [LandmassDiff] of each plugin relative to the merged
+/// land before it was merged is also returned, and is otherwise empty.
+/// If the [RunContext] is cancelled, the merge stops before the next plugin and the
+/// `report` records where it stopped. If `provenance` is [Some], the [VertexDecision] of each
+/// vertex changed by a plugin or a repair is recorded.
+///
+/// [VertexDecision]: crate::merge::provenance::VertexDecision
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_merged_lands(
+    cli: &Cli,
+    config: &GlobalConfig,
+    reference_landmass: Arc<Landmass>,
+    modded_landmasses: &[LandmassDiff],
+    resolutions: &Resolutions,
+    report: &mut Report,
+    mut provenance: Option<&mut Provenance>,
+    context: &RunContext,
+) -> Result<(LandmassDiff, Vec<LandmassDiff>)> {
+    // STEP 2:
+    // Create the MergedLands.esp:
+    //  - Calculate the "naive" TES3 merge of the ordered ESMs.
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Creating Reference Land",
+    });
+
+    debug!(
+        "Reference contains {} LAND records",
+        reference_landmass.land.len()
+    );
+
+    let mut merged_lands = create_merged_lands_from_reference(reference_landmass);
+
+    // STEP 3:
+    // For each LandmassDiff, [IMPLEMENTATION NOTE] same order as Plugin:
+    //  - Merge into `MergedLands.esp`.
+    //     - If LAND does not exist in MergedLands.esp, insert.
+    //     - Else, apply merge strategies.
+    //        - Each merge is applied to the result of any previous merge.
+    //        - Each merge is tracked so it can be referenced in the future.
+    //        - Merge strategies may use the optional `.mergedlands.toml` for conflict resolution.
+    //  - Iterate through updated landmass and check for seams on any modified cell.
+    //  - With the predecessor [DiffBaseline], diff each LandmassDiff against the merged land
+    //    before it is merged, so that conflicts are found with what the plugin replaces in-game.
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Merging Lands",
+    });
+
+    let mut predecessor_landmasses = Vec::new();
+    match cli.chunk_size() {
+        None => {
+            for (idx, modded_landmass) in modded_landmasses.iter().enumerate() {
+                if check_cancelled(context, report, "Merging Lands") {
+                    return Ok((merged_lands, predecessor_landmasses));
+                }
+
+                if cli.diff_baseline() == DiffBaseline::Predecessor {
+                    predecessor_landmasses.push(modded_landmass.relative_to(&merged_lands));
+                }
+
+                context.time_plugin(TimedPhase::Merging, &modded_landmass.plugin.name, || {
+                    merge_plugin_landmass(
+                        cli,
+                        &mut merged_lands,
+                        modded_landmass,
+                        resolutions,
+                        report,
+                        provenance.as_deref_mut(),
+                        context,
+                    )
+                })?;
+                context.emit(MergeEvent::Progress {
+                    phase: "Merging Lands",
+                    completed: idx + 1,
+                    total: modded_landmasses.len(),
+                });
+            }
+
+            report.multi_way_conflicts =
+                record_repair(&mut merged_lands, provenance.as_deref_mut(), |land| {
+                    resolve_multi_way_conflicts(
+                        land,
+                        modded_landmasses,
+                        config.averaging_kernel,
+                        cli.preset_settings().conflict_strategy,
+                        resolutions,
+                    )
+                });
+        }
+        Some(chunk_size) => {
+            let cancelled = merge_chunks(
+                &mut merged_lands,
+                modded_landmasses,
+                chunk_size,
+                cli.threads(),
+                &config.seams,
+                report,
+                provenance.as_deref_mut(),
+                |merged_chunk, chunk_landmass, chunk_report, chunk_provenance| {
+                    context.time_plugin(TimedPhase::Merging, &chunk_landmass.plugin.name, || {
+                        merge_plugin_landmass(
+                            cli,
+                            merged_chunk,
+                            chunk_landmass,
+                            resolutions,
+                            chunk_report,
+                            chunk_provenance,
+                            context,
+                        )
+                    })
+                },
+                |merged_chunk, chunk_landmasses| {
+                    resolve_multi_way_conflicts(
+                        merged_chunk,
+                        chunk_landmasses,
+                        config.averaging_kernel,
+                        cli.preset_settings().conflict_strategy,
+                        resolutions,
+                    )
+                },
+                context,
+            )?;
+
+            if cancelled && check_cancelled(context, report, "Merging Lands") {
+                return Ok((merged_lands, predecessor_landmasses));
+            }
+
+            report
+                .multi_way_conflicts
+                .sort_by_key(|conflict| conflict.coords);
+        }
+    }
+
+    // We fix seams as a post-processing step because individual mods can introduce
+    // tears into the landscape that would be fixed by subsequent mods. (e.g. patches)
+    // If we try to fix the seams early, sadness results.
+    context
+        .time_phase(TimedPhase::SeamRepair, || {
+            record_repair(&mut merged_lands, provenance.as_deref_mut(), |land| {
+                repair_landmass_seams_until_fixed(land, &config.seams)
+            })
+        })?
+        .add_to(report);
+
+    if cli.regenerate_world_map {
+        let num_regenerated =
+            record_repair(&mut merged_lands, provenance, regenerate_world_map_data);
+        debug!("Regenerated world map data for {} cells", num_regenerated);
+    }
+
+    Ok((merged_lands, predecessor_landmasses))
+}
+
+/// A chunk of cells of the merged land after merging each plugin with [merge_chunks].
+struct MergedChunk {
+    land: LandmassDiff,
+    /// The parts of the [Report] found while merging the chunk.
+    report: Report,
+    provenance: Option<Provenance>,
+    cancelled: bool,
+}
+
+/// Merges the `modded_landmasses` into the `merged_lands` with `merge` in square chunks of
+/// `chunk_size` cells, resolves the heights modified by three or more plugins of each chunk with
+/// `resolve_multi_way`, and repairs the seams inside of each chunk. The chunks are merged on up to
+/// `threads` threads, each with its own [Report] and [Provenance]. These are reduced in the order
+/// of the chunks, regardless of the order the threads finish in, so that the result does not
+/// depend on the number of threads. Returns `true` if the [RunContext] was cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn merge_chunks(
+    merged_lands: &mut LandmassDiff,
+    modded_landmasses: &[LandmassDiff],
+    chunk_size: usize,
+    threads: usize,
+    seams: &SeamSettings,
+    report: &mut Report,
+    mut provenance: Option<&mut Provenance>,
+    merge: impl Fn(&mut LandmassDiff, &LandmassDiff, &mut Report, Option<&mut Provenance>) -> Result<()>
+        + Sync,
+    resolve_multi_way: impl Fn(&mut LandmassDiff, &[LandmassDiff]) -> Vec<MultiWayConflict> + Sync,
+    context: &RunContext,
+) -> Result<bool> {
+    let cells = modded_landmasses.iter().flat_map(LandmassDiff::cells);
+    let chunks = chunk_cells(cells, chunk_size);
+    debug!(
+        "Merging {} chunks of up to {}x{} cells on {} threads",
+        chunks.len(),
+        chunk_size,
+        chunk_size,
+        threads.min(chunks.len()).max(1)
+    );
+
+    let num_chunks = chunks.len();
+    let num_completed = AtomicUsize::new(0);
+    let record_provenance = provenance.is_some();
+    let merged_chunks = chunks
+        .iter()
+        .map(|cells| (cells, merged_lands.split_off(cells)))
+        .collect_vec();
+
+    let merged_chunks = map_in_order(
+        merged_chunks,
+        threads,
+        |(cells, mut merged_chunk)| -> Result<MergedChunk> {
+            let mut chunk_report = Report::default();
+            let mut chunk_provenance = record_provenance.then(Provenance::default);
+            let mut chunk_landmasses = Vec::new();
+            for modded_landmass in modded_landmasses.iter() {
+                if context.is_cancelled() {
+                    return Ok(MergedChunk {
+                        land: merged_chunk,
+                        report: chunk_report,
+                        provenance: chunk_provenance,
+                        cancelled: true,
+                    });
+                }
+
+                let chunk_landmass = modded_landmass.restricted_to(cells);
+                if !chunk_landmass.land.is_empty() {
+                    merge(
+                        &mut merged_chunk,
+                        &chunk_landmass,
+                        &mut chunk_report,
+                        chunk_provenance.as_mut(),
+                    )?;
+                    chunk_landmasses.push(chunk_landmass);
+                }
+            }
+
+            chunk_report.multi_way_conflicts =
+                record_repair(&mut merged_chunk, chunk_provenance.as_mut(), |land| {
+                    resolve_multi_way(land, &chunk_landmasses)
+                });
+
+            // Seams between chunks are repaired after every chunk is merged.
+            context
+                .time_phase(TimedPhase::SeamRepair, || {
+                    record_repair(&mut merged_chunk, chunk_provenance.as_mut(), |land| {
+                        repair_landmass_seams_until_fixed(land, seams)
+                    })
+                })?
+                .add_to(&mut chunk_report);
+
+            context.emit(MergeEvent::Progress {
+                phase: "Merging Lands",
+                completed: num_completed.fetch_add(1, Ordering::Relaxed) + 1,
+                total: num_chunks,
+            });
+
+            Ok(MergedChunk {
+                land: merged_chunk,
+                report: chunk_report,
+                provenance: chunk_provenance,
+                cancelled: false,
+            })
+        },
+    );
+
+    let mut cancelled = false;
+    for merged_chunk in merged_chunks {
+        let merged_chunk = merged_chunk?;
+        report
+            .strategy_divergences
+            .extend(merged_chunk.report.strategy_divergences);
+        report
+            .repaired_seams
+            .extend(merged_chunk.report.repaired_seams);
+        report
+            .multi_way_conflicts
+            .extend(merged_chunk.report.multi_way_conflicts);
+        if let (Some(provenance), Some(chunk_provenance)) =
+            (provenance.as_deref_mut(), merged_chunk.provenance)
+        {
+            provenance.append(chunk_provenance);
+        }
+
+        merged_lands.append(merged_chunk.land);
+        cancelled |= merged_chunk.cancelled;
+    }
+
+    Ok(cancelled)
+}
+
+/// Merges the `modded_landmass` into the `merged_lands`. If `shadow_strategies` is enabled,
+/// the merge is evaluated with every conflict strategy first. If `provenance` is [Some],
+/// the vertices changed by the merge are recorded.
+fn merge_plugin_landmass(
+    cli: &Cli,
+    merged_lands: &mut LandmassDiff,
+    modded_landmass: &LandmassDiff,
+    resolutions: &Resolutions,
+    report: &mut Report,
+    provenance: Option<&mut Provenance>,
+    context: &RunContext,
+) -> Result<()> {
+    let forced_strategy = cli.preset_settings().conflict_strategy;
+    if cli.shadow_strategies {
+        report.strategy_divergences.extend(shadow_evaluate_landmass(
+            merged_lands,
+            modded_landmass,
+            forced_strategy,
+        ));
+    }
+
+    let Some(provenance) = provenance else {
+        merge_landmass_into(
+            merged_lands,
+            modded_landmass,
+            forced_strategy,
+            resolutions,
+            context,
+        )?;
+        return Ok(());
+    };
+
+    let previous = merged_lands.restricted_to(&modded_landmass.cells().into_iter().collect());
+    merge_landmass_into(
+        merged_lands,
+        modded_landmass,
+        forced_strategy,
+        resolutions,
+        context,
+    )?;
+    provenance.record_merge(
+        &previous,
+        merged_lands,
+        modded_landmass,
+        forced_strategy,
+        resolutions,
+    );
+
+    Ok(())
+}
+
+/// Calls `repair` with the `merged_lands`. If `provenance` is [Some], the vertices changed
+/// by the `repair` are recorded.
+fn record_repair<R>(
+    merged_lands: &mut LandmassDiff,
+    provenance: Option<&mut Provenance>,
+    repair: impl FnOnce(&mut LandmassDiff) -> R,
+) -> R {
+    let Some(provenance) = provenance else {
+        return repair(merged_lands);
+    };
+
+    let previous = merged_lands.clone();
+    let result = repair(merged_lands);
+    provenance.record_repair(&previous, merged_lands);
+    result
+}
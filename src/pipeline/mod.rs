@@ -0,0 +1,916 @@
+use crate::cli::Cli;
+use crate::error::Error;
+use crate::events::{CancellationToken, MergeEvent, RunContext};
+use crate::io::artifacts::ArtifactSink;
+use crate::io::compatibility::{compare_plugin_edits, score_plugin_pairs};
+use crate::io::conflict_clusters::cluster_conflicts;
+use crate::io::export::{save_texture_remaps, CellMap, TerrainStatistics};
+use crate::io::meta_database::plugin_crc32;
+use crate::io::meta_schema::{GlobalConfig, MetaDatabase};
+use crate::io::parsed_plugins::{debug_name, ParsedPlugin, ParsedPlugins};
+use crate::io::report::{DiffBaseline, Report};
+use crate::io::resolutions::Resolutions;
+use crate::io::run_history::RunSnapshot;
+use crate::io::save_to_image::{
+    analyze_landmass_conflicts, analyze_overwrite_conflicts, render_conflict_images,
+    render_survival_images, render_texture_paint_images, save_new_land_map, save_world_map,
+};
+use crate::io::save_to_plugin::check_output_writable;
+use crate::io::validation::summarize_height_encoding;
+use crate::land::terrain_map::Vec2;
+use crate::land::textures::KnownTextures;
+use crate::merge::cells::{check_cell_flags, check_cell_regions, merge_cells, ModifiedCell};
+use crate::merge::chunks::chunk_cells;
+use crate::merge::memory_estimate::{check_memory, estimate_memory};
+use crate::merge::provenance::Provenance;
+use crate::repair::debugging::{add_debug_vertex_colors_to_landmass, find_height_spikes};
+use crate::repair::outlier_cells::{find_outlier_cells, handle_outlier_cells, stitched_exclusions};
+use crate::repair::skipped_cells::{exclude_cells, skip_major_conflict_cells};
+use crate::repair::stray_normals::clear_stray_vertex_normals;
+use crate::timings::{TimedPhase, Timings};
+use crate::{check_cancelled, Landmass, LandmassDiff};
+use anyhow::{anyhow, Context, Result};
+use diff_stage::diff_plugins;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::{info, warn};
+use merge_stage::create_merged_lands;
+use parse_stage::{check_forbidden_masters, check_missing_masters, check_missing_textures};
+use save_stage::{save_merged_lands, save_merged_textures};
+use std::sync::Arc;
+use std::time::Instant;
+
+mod commands;
+mod diff_stage;
+mod merge_stage;
+mod parse_stage;
+mod save_stage;
+
+pub(crate) use commands::{
+    clean_plugin, export, export_reference, inspect, preview, report_conflicts,
+};
+pub(crate) use merge_stage::merge_chunks;
+
+/// The name of the file saved by [MergePipeline::save] with a one line summary of the merge.
+const MERGE_SUMMARY_FILE_NAME: &str = "merge_summary.txt";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A stage of a [MergePipeline], in the order the stages run.
+pub enum MergeStage {
+    /// The plugins are parsed and checked for problems.
+    Parse,
+    /// The LAND of each plugin is compared to the LAND of the masters.
+    Diff,
+    /// The plugins are merged in load order and seams are repaired.
+    Merge,
+    /// The conflicts between each plugin and the merged land are found and saved as artifacts.
+    Summarize,
+    /// The merged land is saved to the output plugin.
+    Save,
+}
+
+/// The state of a [MergePipeline] between each [MergeStage]. A host running the stages one at a
+/// time can inspect or replace any of it before running the next [MergeStage].
+pub struct MergeState {
+    pub report: Report,
+    pub config: GlobalConfig,
+    pub database: MetaDatabase,
+    pub resolutions: Resolutions,
+    /// The cells left out of the output, from the `exclude_cells` file of the [Cli].
+    pub excluded_cells: HashSet<Vec2<i32>>,
+    pub parsed_plugins: ParsedPlugins,
+    /// The merged CELL record of each cell of the `parsed_plugins`.
+    pub cells: HashMap<Vec2<i32>, ModifiedCell>,
+    pub known_textures: KnownTextures,
+    /// The land of the masters. Taken by [MergeStage::Merge].
+    pub reference_landmass: Option<Arc<Landmass>>,
+    /// The [LandmassDiff] of each plugin in the order they are merged.
+    pub modded_landmasses: Vec<LandmassDiff>,
+    /// The [LandmassDiff] of each plugin relative to the merged land before it was merged.
+    /// Only created with the predecessor [DiffBaseline].
+    pub predecessor_landmasses: Vec<LandmassDiff>,
+    /// The merged land, once [MergeStage::Merge] has run. Taken by [MergeStage::Save].
+    pub merged_lands: Option<LandmassDiff>,
+    /// The [Provenance] of the merged land, if it is recorded.
+    pub provenance: Option<Provenance>,
+    /// The [ArtifactSink] of the run, once the first artifact is saved.
+    /// Completed by [MergePipeline::finish_artifacts].
+    pub artifacts: Option<ArtifactSink>,
+    /// The [Timings] of the run. Each phase run by [MergePipeline::run] is timed.
+    pub timings: Arc<Timings>,
+}
+
+/// The steps of [crate::merge_all] as a pipeline of [MergeStage]. Each stage can be run on its
+/// own, e.g. by a GUI host that stops after [MergeStage::Diff], and the plugins, conflict
+/// strategies, and reporting of the [Cli] can be overridden.
+///
+/// ```ignore
+/// let state = MergePipeline::new(&cli)
+///     .with_plugins(vec!["A.esp".to_string(), "B.esp".to_string()])
+///     .with_reporting(false)
+///     .stop_after(MergeStage::Merge)
+///     .run(&CancellationToken::default())?;
+/// ```
+pub struct MergePipeline<'a> {
+    cli: &'a Cli,
+    plugins: Option<Vec<String>>,
+    /// The plugin parsed with the masters in its header instead of the plugins, if any.
+    masters_of: Option<String>,
+    resolutions: Option<Resolutions>,
+    reporting: bool,
+    record_provenance: bool,
+    last_stage: MergeStage,
+    report_only: bool,
+    /// The subscribers receiving each [MergeEvent] of a run.
+    context: RunContext,
+}
+
+impl<'a> MergePipeline<'a> {
+    /// Creates a [MergePipeline] that runs every [MergeStage] with the options of the [Cli].
+    pub fn new(cli: &'a Cli) -> Self {
+        Self {
+            cli,
+            plugins: None,
+            masters_of: None,
+            resolutions: None,
+            reporting: true,
+            record_provenance: false,
+            last_stage: MergeStage::Save,
+            report_only: false,
+            context: RunContext::default(),
+        }
+    }
+
+    /// Calls `subscriber` with each [MergeEvent] of each run of the [MergePipeline]. Other
+    /// pipelines running at the same time do not send their events to the `subscriber`.
+    pub fn with_events(mut self, subscriber: impl Fn(&MergeEvent) + Send + Sync + 'static) -> Self {
+        self.context = self.context.with_events(subscriber);
+        self
+    }
+
+    /// Sends each [MergeEvent] of a run to the subscribers of the `context`.
+    pub(crate) fn with_context(mut self, context: &RunContext) -> Self {
+        self.context = context.clone();
+        self
+    }
+
+    /// Merges the `plugins` instead of the plugins of the [Cli] or `Morrowind.ini`.
+    pub fn with_plugins(mut self, plugins: Vec<String>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Parses the `plugin` and the masters in its header, in the order of the header, instead of
+    /// the plugins of the [Cli] or `Morrowind.ini`. Every master is parsed as a master.
+    pub fn with_masters_of(mut self, plugin: &str) -> Self {
+        self.masters_of = Some(plugin.to_string());
+        self
+    }
+
+    /// Uses the conflict strategies of the [Resolutions] instead of the `resolutions_file`.
+    pub fn with_strategy_overrides(mut self, resolutions: Resolutions) -> Self {
+        self.resolutions = Some(resolutions);
+        self
+    }
+
+    /// If `reporting` is `false`, no report, images, or other artifacts are saved.
+    /// The [Report] is still returned in the [MergeState].
+    pub fn with_reporting(mut self, reporting: bool) -> Self {
+        self.reporting = reporting;
+        self
+    }
+
+    /// Records the [Provenance] of the merged land, as with `--record-provenance`.
+    pub fn with_provenance(mut self) -> Self {
+        self.record_provenance = true;
+        self
+    }
+
+    /// Stops after the `stage` instead of saving the output.
+    pub fn stop_after(mut self, stage: MergeStage) -> Self {
+        self.last_stage = stage;
+        self
+    }
+
+    /// Stops after [MergeStage::Diff] and runs [Self::analyze] instead of merging the plugins.
+    pub fn report_only(mut self) -> Self {
+        self.last_stage = MergeStage::Diff;
+        self.report_only = true;
+        self
+    }
+
+    /// Runs each [MergeStage] up to the last stage. If the `cancel` token is cancelled, the run
+    /// stops at the next phase or plugin boundary and the partial [Report] is saved.
+    pub fn run(&self, cancel: &CancellationToken) -> Result<MergeState, Error> {
+        let start = Instant::now();
+        let state = self.run_stages(cancel)?;
+        self.finish(&state, cancel, start);
+        Ok(state)
+    }
+
+    /// Runs each [MergeStage] up to the last stage like [Self::run], but without finishing the run,
+    /// so that a subcommand can continue with the [MergeState]. See [Self::finish].
+    pub(crate) fn run_stages(&self, cancel: &CancellationToken) -> Result<MergeState> {
+        // Fail before doing any work if the output cannot be saved.
+        if self.last_stage == MergeStage::Save {
+            let output_file_dir = self.cli.output_file_dir()?;
+            let files = self.cli.file_provider();
+            check_output_writable(files, &output_file_dir, &self.cli.output_file)?;
+            if self.cli.adds_debug() && !self.cli.debug_in_main {
+                check_output_writable(files, &output_file_dir, &debug_name(&self.cli.output_file))?;
+            }
+        }
+
+        let timings = Arc::new(Timings::default());
+        let mut state = timings.time_phase(TimedPhase::Parsing, || {
+            self.parse_with_timings(cancel, timings.clone())
+        })?;
+        if self.cli.ltex_only {
+            if state.report.cancelled.is_none() && self.last_stage == MergeStage::Save {
+                timings.time_phase(TimedPhase::Saving, || self.save_textures(&mut state))?;
+            }
+        } else {
+            for stage in [
+                MergeStage::Diff,
+                MergeStage::Merge,
+                MergeStage::Summarize,
+                MergeStage::Save,
+            ] {
+                if stage > self.last_stage || state.report.cancelled.is_some() {
+                    break;
+                }
+
+                match stage {
+                    MergeStage::Parse => unreachable!(),
+                    MergeStage::Diff => {
+                        timings.time_phase(TimedPhase::Diffing, || self.diff(&mut state))?
+                    }
+                    MergeStage::Merge => timings
+                        .time_phase(TimedPhase::Merging, || self.merge(&mut state, cancel))?,
+                    MergeStage::Summarize => timings.time_phase(TimedPhase::Summarizing, || {
+                        self.summarize(&mut state, cancel)
+                    })?,
+                    MergeStage::Save => {
+                        timings.time_phase(TimedPhase::Saving, || self.save(&mut state, cancel))?
+                    }
+                }
+            }
+        }
+
+        if self.report_only && state.report.cancelled.is_none() {
+            timings.time_phase(TimedPhase::Summarizing, || self.analyze(&mut state, cancel))?;
+        }
+
+        self.finish_artifacts(&mut state)?;
+        Ok(state)
+    }
+
+    /// Finishes a run that started at `start`, logging the time elapsed unless the [MergeState]
+    /// was cancelled.
+    pub(crate) fn finish(&self, state: &MergeState, cancel: &CancellationToken, start: Instant) {
+        if state.report.cancelled.is_none() {
+            self.context(state, cancel)
+                .emit(MergeEvent::PhaseStarted { phase: "Finished" });
+            let elapsed = Instant::now().duration_since(start);
+            info!("Time Elapsed: {:?}", elapsed);
+            state.timings.log(elapsed);
+        }
+    }
+
+    /// Returns the [RunContext] of a run stopped by the `cancel` token, recording to the [Timings]
+    /// of the [MergeState].
+    fn context(&self, state: &MergeState, cancel: &CancellationToken) -> RunContext {
+        self.context
+            .with_cancellation_token(cancel)
+            .with_timings(&state.timings)
+    }
+
+    /// Returns the [ArtifactSink] of the run, creating the [ArtifactSink] of the [Cli] if this is
+    /// the first artifact, or [ArtifactSink::Discard] without reporting.
+    fn artifacts<'s>(&self, artifacts: &'s mut Option<ArtifactSink>) -> Result<&'s ArtifactSink> {
+        if artifacts.is_none() {
+            *artifacts = Some(if self.reporting {
+                self.cli.artifacts()?
+            } else {
+                ArtifactSink::Discard
+            });
+        }
+
+        Ok(artifacts.as_ref().expect("safe"))
+    }
+
+    /// Saves the [Report] and the table of each texture remap of the [MergeState], and completes
+    /// its [ArtifactSink]. This runs after the last [MergeStage], so that the seams repaired and
+    /// the textures compacted while saving the output are included.
+    pub fn finish_artifacts(&self, state: &mut MergeState) -> Result<(), Error> {
+        let artifacts = self.artifacts(&mut state.artifacts)?;
+        state.report.save(artifacts)?;
+        save_texture_remaps(artifacts, &state.known_textures)?;
+
+        if let Some(artifacts) = state.artifacts.take() {
+            artifacts.finish()?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs [MergeStage::Parse] and returns the initial [MergeState].
+    pub fn parse(&self, cancel: &CancellationToken) -> Result<MergeState, Error> {
+        self.parse_with_timings(cancel, Arc::new(Timings::default()))
+    }
+
+    /// Runs [MergeStage::Parse] like [Self::parse], recording to the `timings` of the run.
+    fn parse_with_timings(
+        &self,
+        cancel: &CancellationToken,
+        timings: Arc<Timings>,
+    ) -> Result<MergeState, Error> {
+        let cli = self.cli;
+        let context = &self
+            .context
+            .with_cancellation_token(cancel)
+            .with_timings(&timings);
+
+        // STEP 1:
+        // For each Plugin, ordered by last modified:
+        //  - Get or create reference landmass.
+        //      - References are created by a list of ESMs / ESPs.
+        //      - By default, the references are pulled from the TES3 header.
+        //      - If the plugin has an associated `.mergedlands.meta`, read additional references from that.
+        //      - Order the list by ESMs then ESPs, then within each category, order by last modified date.
+        //      - [WARN] The current plugin loads before one or more of the references.
+        //      - Calculate the "naive" TES3 merge of the ordered ESMs / ESPs.
+        //  - Calculate diff from reference landmass.
+        //  => return LandmassDiff
+
+        // [IMPLEMENTATION NOTE] Whenever an ESM or ESP is loaded, all LTEX records are registered with
+        // the KnownTextures and all texture indices in LAND records are updated accordingly.
+
+        // [IMPLEMENTATION NOTE] Each loaded Plugin is stored in an Arc<...> with any data from the
+        // optional `.mergedlands.toml` if it existed. The Arc<...> is copied into each LandscapeDiff.
+        context.emit(MergeEvent::PhaseStarted {
+            phase: "Parsing Plugins",
+        });
+
+        let config = cli.global_config()?;
+        let resolutions = match self.resolutions.as_ref() {
+            Some(resolutions) => resolutions.clone(),
+            None => Resolutions::load(cli.file_provider(), &cli.resolutions_file()?)?,
+        };
+        let excluded_cells = cli.excluded_cells()?;
+        let database = cli.meta_database()?;
+        let data_files = cli.data_files_dir()?;
+        let mut parsed_plugins = match self.masters_of.as_deref() {
+            Some(plugin) => ParsedPlugins::with_masters_of(
+                cli.file_provider(),
+                &data_files,
+                plugin,
+                &config,
+                &database,
+            )?,
+            None => ParsedPlugins::new(
+                cli.file_provider(),
+                &data_files,
+                self.plugins.as_deref().or_else(|| cli.plugins()),
+                cli.sort_order,
+                &config,
+                &database,
+                context,
+            )?,
+        };
+
+        let settings = cli.preset_settings();
+        if let Some(preset) = cli.preset() {
+            info!("Using preset {:?}: {:?}", preset, settings);
+        }
+
+        let mut report = Report {
+            preset: cli.preset(),
+            settings,
+            diff_baseline: cli.diff_baseline(),
+            ..Report::default()
+        };
+        report
+            .warnings
+            .extend(check_forbidden_masters(&config, &mut parsed_plugins)?);
+        report
+            .warnings
+            .extend(check_missing_masters(&config, &mut parsed_plugins)?);
+        report.warnings.extend(parsed_plugins.check_land_flags());
+        report.warnings.extend(parsed_plugins.check_land_records());
+        report
+            .warnings
+            .extend(parsed_plugins.check_duplicate_land_records(config.duplicate_records));
+        report
+            .warnings
+            .extend(check_missing_textures(&config, &parsed_plugins)?);
+        let cells = merge_cells(&parsed_plugins);
+        if !cli.remove_cell_records {
+            report.warnings.extend(check_cell_flags(&cells));
+            report.warnings.extend(check_cell_regions(&cells));
+        }
+
+        let memory_estimate = estimate_memory(&parsed_plugins, cli.chunk_size(), cli.adds_debug());
+        report
+            .warnings
+            .extend(check_memory(&memory_estimate, cli.chunk_size()));
+        report.load_order = parsed_plugins.load_order.clone();
+
+        check_cancelled(context, &mut report, "Parsing Plugins");
+
+        Ok(MergeState {
+            report,
+            config,
+            database,
+            resolutions,
+            excluded_cells,
+            parsed_plugins,
+            cells,
+            known_textures: KnownTextures::new(),
+            reference_landmass: None,
+            modded_landmasses: Vec::new(),
+            predecessor_landmasses: Vec::new(),
+            merged_lands: None,
+            provenance: None,
+            artifacts: None,
+            timings,
+        })
+    }
+
+    /// Saves only the merged LTEX records of the plugins to the output, as with `--ltex-only`.
+    fn save_textures(&self, state: &mut MergeState) -> Result<()> {
+        state.known_textures = save_merged_textures(
+            self.cli,
+            &state.config,
+            &state.parsed_plugins,
+            &self.context.with_timings(&state.timings),
+        )?;
+        state.report.textures = state.known_textures.histories(&state.parsed_plugins);
+        Ok(())
+    }
+
+    /// Runs [MergeStage::Diff], creating the `reference_landmass` and `modded_landmasses`.
+    pub fn diff(&self, state: &mut MergeState) -> Result<(), Error> {
+        // TODO(dvd): #feature Support "ignored" maps for hiding differences that we don't care about.
+        let (reference_landmass, modded_landmasses) = diff_plugins(
+            self.cli,
+            &state.config,
+            &state.database,
+            &state.parsed_plugins,
+            &mut state.known_textures,
+            &mut state.report,
+            &self.context.with_timings(&state.timings),
+        )?;
+
+        state.report.textures = state.known_textures.histories(&state.parsed_plugins);
+        state.reference_landmass = Some(reference_landmass);
+        state.modded_landmasses = modded_landmasses;
+        Ok(())
+    }
+
+    /// Runs [MergeStage::Merge], creating the `merged_lands` from the `reference_landmass`.
+    pub fn merge(&self, state: &mut MergeState, cancel: &CancellationToken) -> Result<(), Error> {
+        let cli = self.cli;
+        let context = &self.context(state, cancel);
+        let reference_landmass = state
+            .reference_landmass
+            .take()
+            .with_context(|| anyhow!("The plugins must be diffed before they are merged"))?;
+
+        let mut provenance =
+            (self.record_provenance || cli.record_provenance).then(Provenance::default);
+        let (mut merged_lands, predecessor_landmasses) = create_merged_lands(
+            cli,
+            &state.config,
+            reference_landmass,
+            &state.modded_landmasses,
+            &state.resolutions,
+            &mut state.report,
+            provenance.as_mut(),
+            context,
+        )?;
+
+        state.report.stray_vertex_normals =
+            clear_stray_vertex_normals(&mut merged_lands, provenance.as_ref());
+
+        if let Some(provenance) = provenance.as_ref() {
+            state.report.provenance = provenance.summarize();
+        }
+
+        state.merged_lands = Some(merged_lands);
+        state.predecessor_landmasses = predecessor_landmasses;
+        state.provenance = provenance;
+        Ok(())
+    }
+
+    /// Runs [MergeStage::Summarize], finding the conflicts of each plugin with the `merged_lands`
+    /// and saving the images and other artifacts to the [ArtifactSink] of the run.
+    pub fn summarize(
+        &self,
+        state: &mut MergeState,
+        cancel: &CancellationToken,
+    ) -> Result<(), Error> {
+        let context = &self.context(state, cancel);
+
+        // STEP 4:
+        //  - Produce images of the final merge results.
+        context.emit(MergeEvent::PhaseStarted {
+            phase: "Summarizing Conflicts",
+        });
+
+        let cli = self.cli;
+        let report = &mut state.report;
+        let merged_lands = state
+            .merged_lands
+            .as_mut()
+            .with_context(|| anyhow!("The plugins must be merged before they are summarized"))?;
+
+        let outliers = find_outlier_cells(merged_lands, state.config.outlier_distance);
+        let stitched_outliers = stitched_exclusions(&outliers, state.config.outlier_cells);
+
+        let conflict_landmasses = match cli.diff_baseline() {
+            DiffBaseline::Reference => &state.modded_landmasses,
+            DiffBaseline::Predecessor => &state.predecessor_landmasses,
+        };
+
+        let settings = report.settings;
+        let artifacts = self.artifacts(&mut state.artifacts)?;
+        let image_options = cli.image_options();
+        let mut overlaps = HashMap::new();
+        for_each_chunk(conflict_landmasses, cli.chunk_size(), |modded_landmasses| {
+            if cli.diff_baseline() == DiffBaseline::Reference {
+                compare_plugin_edits(modded_landmasses, &mut overlaps);
+            }
+
+            let num_conflicts = report.conflicts.len();
+            for modded_landmass in modded_landmasses.iter() {
+                if check_cancelled(context, report, "Summarizing Conflicts") {
+                    break;
+                }
+
+                let conflicts = context.time_plugin(
+                    TimedPhase::Summarizing,
+                    &modded_landmass.plugin.name,
+                    || analyze_landmass_conflicts(merged_lands, modded_landmass, &settings),
+                );
+                report.conflicts.extend(conflicts);
+            }
+
+            context.time_phase(TimedPhase::Imaging, || {
+                if settings.save_images && self.reporting {
+                    render_conflict_images(
+                        artifacts,
+                        merged_lands,
+                        modded_landmasses,
+                        &report.conflicts[num_conflicts..],
+                        &image_options,
+                    );
+
+                    render_texture_paint_images(
+                        artifacts,
+                        merged_lands,
+                        modded_landmasses,
+                        &image_options,
+                    );
+                }
+
+                if cli.survival_images && self.reporting {
+                    render_survival_images(
+                        artifacts,
+                        merged_lands,
+                        modded_landmasses,
+                        &image_options,
+                    );
+                }
+            });
+        });
+
+        report.clusters = cluster_conflicts(&report.conflicts, &state.cells);
+
+        // The direction of the changes of each plugin is always relative to the masters. Chunks
+        // cannot be used with the predecessor baseline, so these are never deferred.
+        if cli.diff_baseline() == DiffBaseline::Predecessor {
+            compare_plugin_edits(&state.modded_landmasses, &mut overlaps);
+        }
+
+        report.plugin_pairs = score_plugin_pairs(merged_lands, &report.conflicts, &overlaps);
+        log_conflict_summary(report);
+
+        if !report.new_land_collisions.is_empty() {
+            context.time_phase(TimedPhase::Imaging, || {
+                save_new_land_map(
+                    artifacts,
+                    merged_lands,
+                    &stitched_outliers,
+                    &report.new_land,
+                    &report.new_land_collisions,
+                    &image_options,
+                )
+            });
+        }
+
+        // Cells removed from the output below still exist in-game with the LAND of the last
+        // plugin, which is the owner of the cell in the cell map.
+        let cell_map = CellMap::from_landmass(merged_lands);
+
+        if report.cancelled.is_none() {
+            state
+                .resolutions
+                .save_template(artifacts, &report.conflicts)?;
+
+            let skipped_cells = state.resolutions.skipped_cells();
+            report.skipped_cells =
+                skip_major_conflict_cells(merged_lands, &report.conflicts, |coords| {
+                    cli.skip_major_conflict_cells || skipped_cells.contains(&coords)
+                });
+            report.excluded_cells = exclude_cells(merged_lands, &state.excluded_cells);
+            report.outlier_cells =
+                handle_outlier_cells(merged_lands, &outliers, state.config.outlier_cells);
+            report.height_encoding = summarize_height_encoding(merged_lands);
+        }
+
+        if cli.regenerate_world_map && report.cancelled.is_none() {
+            context.time_phase(TimedPhase::Imaging, || {
+                save_world_map(artifacts, merged_lands, &stitched_outliers, &image_options)
+            });
+        }
+
+        TerrainStatistics::from_landmass(merged_lands).save(artifacts)?;
+        cell_map.save(artifacts)?;
+        if report.cancelled.is_none() && self.reporting {
+            self.compare_with_previous_run(report, merged_lands)?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds the [crate::io::report::RunChanges] of the [Report] since the previous run, if it was
+    /// saved, and saves the [RunSnapshot] of this run in its place. The snapshot is saved next to
+    /// the log, since the other artifacts may be saved to an archive.
+    fn compare_with_previous_run(&self, report: &mut Report, merged: &LandmassDiff) -> Result<()> {
+        let merged_lands_dir = self.cli.merged_lands_dir()?;
+        let snapshot = RunSnapshot::new(&report.conflicts, merged);
+        if let Some(previous) = RunSnapshot::load(self.cli.file_provider(), &merged_lands_dir) {
+            report.changes = Some(snapshot.changes_since(&previous));
+        }
+
+        snapshot.save(&ArtifactSink::directory(
+            self.cli.file_provider(),
+            merged_lands_dir,
+        ))
+    }
+
+    /// Runs the conflict analysis of [MergeStage::Summarize] without [MergeStage::Merge]. Each
+    /// plugin is compared to the land of the earlier plugins that it overwrites instead of the
+    /// merged land. The images, terrain statistics, and cell map show
+    /// the overwritten land, i.e. the land of the last plugin to modify each cell.
+    pub fn analyze(&self, state: &mut MergeState, cancel: &CancellationToken) -> Result<(), Error> {
+        let context = &self.context(state, cancel);
+        context.emit(MergeEvent::PhaseStarted {
+            phase: "Analyzing Conflicts",
+        });
+
+        let report = &mut state.report;
+        let settings = report.settings;
+        let artifacts = self.artifacts(&mut state.artifacts)?;
+        let image_options = self.cli.image_options();
+        let mut overwritten =
+            LandmassDiff::new(Arc::new(ParsedPlugin::empty("OverwrittenLandmass.esp")));
+        let mut overlaps = HashMap::new();
+        for_each_chunk(
+            &state.modded_landmasses,
+            self.cli.chunk_size(),
+            |modded_landmasses| {
+                if check_cancelled(context, report, "Analyzing Conflicts") {
+                    return;
+                }
+
+                let num_conflicts = report.conflicts.len();
+                compare_plugin_edits(modded_landmasses, &mut overlaps);
+                report.conflicts.extend(analyze_overwrite_conflicts(
+                    modded_landmasses,
+                    &mut overwritten,
+                    &settings,
+                ));
+
+                if settings.save_images && self.reporting {
+                    context.time_phase(TimedPhase::Imaging, || {
+                        render_conflict_images(
+                            artifacts,
+                            &overwritten,
+                            modded_landmasses,
+                            &report.conflicts[num_conflicts..],
+                            &image_options,
+                        );
+
+                        render_texture_paint_images(
+                            artifacts,
+                            &overwritten,
+                            modded_landmasses,
+                            &image_options,
+                        );
+                    });
+                }
+            },
+        );
+
+        report.clusters = cluster_conflicts(&report.conflicts, &state.cells);
+        report.plugin_pairs = score_plugin_pairs(&overwritten, &report.conflicts, &overlaps);
+        log_conflict_summary(report);
+
+        TerrainStatistics::from_landmass(&overwritten).save(artifacts)?;
+        CellMap::from_landmass(&overwritten).save(artifacts)?;
+        if report.cancelled.is_none() {
+            state
+                .resolutions
+                .save_template(artifacts, &report.conflicts)?;
+            if self.reporting {
+                self.compare_with_previous_run(report, &overwritten)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs [MergeStage::Save], saving the `merged_lands` to the output, and to the debug output
+    /// if debug colors are added.
+    pub fn save(&self, state: &mut MergeState, cancel: &CancellationToken) -> Result<(), Error> {
+        let cli = self.cli;
+        let context = &self.context(state, cancel);
+        if check_cancelled(context, &mut state.report, "Saving") {
+            return Ok(());
+        }
+
+        let mut merged_lands = state
+            .merged_lands
+            .take()
+            .with_context(|| anyhow!("The plugins must be merged before they are saved"))?;
+
+        let mut debug_normals = HashSet::new();
+        if cli.adds_debug() {
+            context.emit(MergeEvent::PhaseStarted {
+                phase: "Adding Debug Colors",
+            });
+            let mut debug_lands = merged_lands.clone();
+            if cli.add_debug_vertex_colors {
+                for_each_chunk(
+                    &state.modded_landmasses,
+                    cli.chunk_size(),
+                    |modded_landmasses| {
+                        for modded_landmass in modded_landmasses.iter() {
+                            add_debug_vertex_colors_to_landmass(&mut debug_lands, modded_landmass);
+                        }
+                    },
+                );
+            }
+
+            let height_spikes = if cli.add_debug_normals {
+                let height_spikes = find_height_spikes(&debug_lands);
+                if !height_spikes.is_empty() {
+                    info!(
+                        "Exaggerating the normals of {} cells with height spikes",
+                        height_spikes.len()
+                    );
+                }
+                height_spikes
+            } else {
+                HashSet::new()
+            };
+
+            if cli.debug_in_main {
+                warn!("Debug colors will be saved to {}", cli.output_file);
+                merged_lands = debug_lands;
+                debug_normals = height_spikes;
+            } else {
+                save_merged_lands(
+                    cli,
+                    &state.config,
+                    &state.parsed_plugins,
+                    &state.cells,
+                    &state.modded_landmasses,
+                    debug_lands,
+                    state.known_textures.clone(),
+                    &debug_name(&cli.output_file),
+                    &height_spikes,
+                    None,
+                    context,
+                )?;
+            }
+        }
+
+        let num_cells = merged_lands
+            .land
+            .values()
+            .filter(|land| land.is_modified())
+            .count();
+        state.known_textures = save_merged_lands(
+            cli,
+            &state.config,
+            &state.parsed_plugins,
+            &state.cells,
+            &state.modded_landmasses,
+            merged_lands,
+            std::mem::take(&mut state.known_textures),
+            &cli.output_file,
+            &debug_normals,
+            Some(&mut state.report),
+            context,
+        )?;
+
+        self.save_merge_summary(state, num_cells)?;
+        Ok(())
+    }
+
+    /// Logs a one line summary of the merge of `num_cells` LAND records, e.g. for the release notes
+    /// of a modlist, and saves it to the `merged_lands_dir` next to the log and to the [Report].
+    /// Only the conflicts resolved by the merge are counted. See [Report::resolved_conflicts].
+    fn save_merge_summary(&self, state: &mut MergeState, num_cells: usize) -> Result<()> {
+        let output_file_dir = self.cli.output_file_dir()?;
+        let crc32 = plugin_crc32(
+            self.cli.file_provider(),
+            &output_file_dir,
+            &self.cli.output_file,
+        );
+
+        let report = &state.report;
+        let (num_minor, num_major) =
+            report
+                .resolved_conflicts()
+                .fold((0, 0), |(num_minor, num_major), conflict| {
+                    (
+                        num_minor + conflict.num_minor,
+                        num_major + conflict.num_major,
+                    )
+                });
+        let summary = format!(
+            "merged_lands v{} | {} masters, {} plugins | {} LAND cells merged | {} minor / {} major conflicts resolved | {} seams repaired | {} crc32={}",
+            env!("CARGO_PKG_VERSION"),
+            state.parsed_plugins.masters.len(),
+            state.parsed_plugins.plugins.len(),
+            num_cells,
+            num_minor,
+            num_major,
+            report.repaired_seams.len(),
+            self.cli.output_file,
+            crc32.map_or("unknown".to_string(), |crc32| format!("{:08x}", crc32))
+        );
+
+        info!("{}", summary);
+        state.report.summary = Some(summary.clone());
+        if !self.reporting {
+            return Ok(());
+        }
+
+        let artifacts =
+            ArtifactSink::directory(self.cli.file_provider(), self.cli.merged_lands_dir()?);
+        if !artifacts.can_save(MERGE_SUMMARY_FILE_NAME)? {
+            return Ok(());
+        }
+
+        artifacts.save_bytes(MERGE_SUMMARY_FILE_NAME, format!("{}\n", summary).as_bytes())
+    }
+}
+
+/// Calls `f` with the `modded_landmasses`. If `chunk_size` is [Some], `f` is instead called
+/// for each chunk of cells with only the [LandscapeDiff] in that chunk, so that the deferred
+/// [LandscapeDiff] of every plugin are never created at once.
+///
+/// [LandscapeDiff]: crate::land::landscape_diff::LandscapeDiff
+fn for_each_chunk(
+    modded_landmasses: &[LandmassDiff],
+    chunk_size: Option<usize>,
+    mut f: impl FnMut(&[LandmassDiff]),
+) {
+    let Some(chunk_size) = chunk_size else {
+        f(modded_landmasses);
+        return;
+    };
+
+    let cells = modded_landmasses.iter().flat_map(LandmassDiff::cells);
+    for cells in chunk_cells(cells, chunk_size) {
+        let chunk_landmasses = modded_landmasses
+            .iter()
+            .map(|modded_landmass| modded_landmass.restricted_to(&cells))
+            .collect_vec();
+        f(&chunk_landmasses);
+    }
+}
+
+/// Logs the summary of each conflict cluster, and the suggestion for each pair of plugins with
+/// major conflicts, of the [Report].
+fn log_conflict_summary(report: &Report) {
+    for cluster in report.clusters.iter() {
+        info!("{}", cluster.summary);
+    }
+
+    for plugin_pair in report
+        .plugin_pairs
+        .iter()
+        .filter(|plugin_pair| plugin_pair.num_major > 0)
+    {
+        info!("{}", plugin_pair.suggestion);
+        if let Some(direction_suggestion) = plugin_pair.direction_suggestion.as_ref() {
+            info!("{}", direction_suggestion);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::error::MergeError;
+use crate::io::meta_schema::{
+    ForbiddenMasterPolicy, GlobalConfig, MissingMasterPolicy, MissingTexturePolicy,
+};
+use crate::io::parsed_plugins::ParsedPlugins;
+use crate::io::report::ReportWarning;
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
+
+/// Removes each plugin in the `forbidden_masters` of the `config` that could be a master of the
+/// output from the `parsed_plugins`, and returns a [ReportWarning] for each. Fails instead if the
+/// [ForbiddenMasterPolicy] of the `config` is [ForbiddenMasterPolicy::Fail].
+pub(crate) fn check_forbidden_masters(
+    config: &GlobalConfig,
+    parsed_plugins: &mut ParsedPlugins,
+) -> Result<Vec<ReportWarning>> {
+    let forbidden_masters = parsed_plugins.find_forbidden_masters(&config.forbidden_masters);
+    if config.on_forbidden_master == ForbiddenMasterPolicy::Fail {
+        if let Some((plugin, _)) = forbidden_masters.first() {
+            bail!(MergeError::ForbiddenMaster(plugin.name.clone()));
+        }
+    }
+
+    let mut warnings = Vec::new();
+    for (plugin, num_records) in forbidden_masters {
+        warn!(
+            "{} {}",
+            format!("Dropping plugin {}", plugin.name.bold()).yellow(),
+            format!(
+                "and its {} records, since it must not be a master of the output",
+                num_records
+            )
+            .yellow()
+        );
+
+        parsed_plugins.remove(&plugin.name);
+        warnings.push(ReportWarning::DroppedForbiddenMaster {
+            plugin: plugin.name.clone(),
+            num_records,
+        });
+    }
+
+    Ok(warnings)
+}
+
+/// Returns a [ReportWarning] for each plugin that loads before one of its masters, or whose
+/// master is not in the load order. With the [MissingMasterPolicy] of the `config`, plugins with
+/// missing masters are removed from the `parsed_plugins`, or the first of them fails the merge.
+pub(crate) fn check_missing_masters(
+    config: &GlobalConfig,
+    parsed_plugins: &mut ParsedPlugins,
+) -> Result<Vec<ReportWarning>> {
+    let mut warnings = parsed_plugins.check_master_order();
+    match config.missing_masters {
+        MissingMasterPolicy::Warn => {}
+        MissingMasterPolicy::Skip => {
+            warnings.extend(parsed_plugins.skip_plugins_with_missing_masters());
+        }
+        MissingMasterPolicy::Fail => {
+            // The warnings are in load order, so the first missing master names the plugin.
+            let missing = warnings
+                .iter()
+                .filter_map(|warning| match warning {
+                    ReportWarning::MissingMaster { plugin, master } => Some((plugin, master)),
+                    _ => None,
+                })
+                .collect_vec();
+            if let Some((first, _)) = missing.first() {
+                bail!(MergeError::MissingMasters {
+                    plugin: first.to_string(),
+                    masters: missing
+                        .iter()
+                        .filter(|(plugin, _)| plugin == first)
+                        .map(|(_, master)| master)
+                        .join(", "),
+                });
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Returns a [ReportWarning] for each LAND record with texture indices that do not match any
+/// LTEX record. Fails instead if the `missing_textures` policy of the `config` is
+/// [MissingTexturePolicy::Fail], naming the missing masters of the first plugin with any.
+pub(crate) fn check_missing_textures(
+    config: &GlobalConfig,
+    parsed_plugins: &ParsedPlugins,
+) -> Result<Vec<ReportWarning>> {
+    let warnings = parsed_plugins.check_texture_indices();
+    if config.missing_textures == MissingTexturePolicy::Fail && !warnings.is_empty() {
+        for warning in warnings.iter() {
+            if let ReportWarning::MissingTextureMaster {
+                plugin, masters, ..
+            } = warning
+            {
+                bail!(MergeError::MissingTextureMasters {
+                    plugin: plugin.clone(),
+                    masters: masters.join(", "),
+                });
+            }
+        }
+
+        bail!(MergeError::MissingTextures(warnings.len()));
+    }
+
+    Ok(warnings)
+}
@@ -0,0 +1,222 @@
+use crate::cli::{Cli, SortOrder};
+use crate::error::MergeError;
+use crate::events::{MergeEvent, RunContext};
+use crate::io::meta_schema::GlobalConfig;
+use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::io::report::Report;
+use crate::io::save_to_plugin::{
+    check_output_load_order, convert_landmass_diff_to_landmass, save_plugin,
+};
+use crate::io::signature::PluginSignature;
+use crate::io::validation::validate_saved_heights;
+use crate::land::height_map::format_height;
+use crate::land::terrain_map::Vec2;
+use crate::land::textures::KnownTextures;
+use crate::merge::cells::ModifiedCell;
+use crate::repair::cleaning::{clean_known_textures, clean_landmass_diff};
+use crate::repair::debugging::exaggerate_vertex_normals;
+use crate::timings::TimedPhase;
+use crate::{add_known_textures, Landmass, LandmassDiff};
+use anyhow::{bail, Result};
+use hashbrown::{HashMap, HashSet};
+use log::{debug, error};
+use owo_colors::OwoColorize;
+use std::sync::Arc;
+
+/// Cleans the `merged_lands` and saves them to the plugin `output_name`, along with the merged
+/// `cells` unless CELL records are removed.
+/// The vertex normals of the cells in `debug_normals` are exaggerated.
+/// Returns the [KnownTextures] that were saved.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn save_merged_lands(
+    cli: &Cli,
+    config: &GlobalConfig,
+    parsed_plugins: &ParsedPlugins,
+    cells: &HashMap<Vec2<i32>, ModifiedCell>,
+    modded_landmasses: &[LandmassDiff],
+    mut merged_lands: LandmassDiff,
+    mut known_textures: KnownTextures,
+    output_name: &str,
+    debug_normals: &HashSet<Vec2<i32>>,
+    report: Option<&mut Report>,
+    context: &RunContext,
+) -> Result<KnownTextures> {
+    // STEP 5:
+    // - Iterate through cells in MergedLands.esp and drop anything that is unchanged from the
+    //   reference landmass created for MergedLands.esp.
+    // - Update all LandData flags to match TES3 expectations.
+    // - Repeat seam repair until no seams are found.
+    // [IMPLEMENTATION NOTE] This is an optimization to make MergedLands.esp friendlier.
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Cleaning Land",
+    });
+
+    let seam_repairs = context.time_phase(TimedPhase::Cleaning, || {
+        clean_landmass_diff(&mut merged_lands, modded_landmasses, &config.seams)
+    })?;
+    if let Some(report) = report {
+        seam_repairs.add_to(report);
+    }
+
+    // ---------------------------------------------------------------------------------------------
+    // [IMPLEMENTATION NOTE] Below this line, the merged landmass cannot be diff'd against plugins.
+    // ---------------------------------------------------------------------------------------------
+
+    // STEP 6:
+    // Update LTEX records to only include textures in use in modified cells.
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Updating LTEX Records",
+    });
+
+    let remapped_textures = context.time_phase(TimedPhase::Cleaning, || {
+        clean_known_textures(parsed_plugins, &merged_lands, &mut known_textures)
+    });
+
+    // STEP 7:
+    // Convert "height map" representation of LAND records to "xy delta + offset" representation.
+    // Remap texture indices.
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Converting to LAND Records",
+    });
+
+    let mut landmass = convert_landmass_diff_to_landmass(
+        &merged_lands,
+        &remapped_textures,
+        config.normal_blend_radius,
+    );
+    for coords in debug_normals.iter() {
+        if let Some(land) = landmass.land.get_mut(coords) {
+            exaggerate_vertex_normals(land);
+        }
+    }
+
+    // STEP 7:
+    // Save to an ESP.
+    //  - [IMPLEMENTATION NOTE] Reuse last modified date if the ESP already exists.
+    context.emit(MergeEvent::PhaseStarted { phase: "Saving" });
+
+    let data_files = cli.data_files_dir()?;
+    let output_file_dir = cli.output_file_dir()?;
+    let include_cell_records = !cli.remove_cell_records;
+    let signature = PluginSignature::from_plugins(parsed_plugins);
+
+    // STEP 8:
+    // Before the ESP is written, load it like the game would and compare it with the merged land.
+    let validate = |bytes: &[u8]| {
+        context.emit(MergeEvent::PhaseStarted {
+            phase: "Validating",
+        });
+
+        let mismatches = validate_saved_heights(bytes, &merged_lands)?;
+        for mismatch in mismatches.iter() {
+            error!(
+                "{}",
+                format!(
+                    "({:>4}, {:>4}) | saved heights differ from the expected encoding by up to {}",
+                    mismatch.coords.x,
+                    mismatch.coords.y,
+                    format_height(mismatch.max_difference)
+                )
+                .bright_red()
+            );
+        }
+
+        if !mismatches.is_empty() {
+            bail!(MergeError::InvalidOutput {
+                plugin: output_name.to_string(),
+                num_cells: mismatches.len(),
+            });
+        }
+
+        Ok(())
+    };
+
+    save_plugin(
+        cli.file_provider(),
+        &data_files,
+        &output_file_dir,
+        output_name,
+        cli.sort_order,
+        &landmass,
+        &known_textures,
+        include_cell_records.then_some(cells),
+        &signature,
+        cli.preset_settings().deterministic,
+        &validate,
+    )?;
+
+    if cli.sort_order == SortOrder::Default {
+        check_output_load_order(
+            cli.file_provider(),
+            &data_files,
+            &output_file_dir,
+            output_name,
+            parsed_plugins,
+            config.adjust_output_mtime,
+        )?;
+    }
+
+    Ok(known_textures)
+}
+
+/// Saves a plugin with only the LTEX records of the [KnownTextures] of the `parsed_plugins`.
+/// Unlike [save_merged_lands], every known texture is kept, since there is no LAND to check
+/// which textures are in use. Returns the [KnownTextures] that were saved.
+pub(crate) fn save_merged_textures(
+    cli: &Cli,
+    config: &GlobalConfig,
+    parsed_plugins: &ParsedPlugins,
+    context: &RunContext,
+) -> Result<KnownTextures> {
+    context.emit(MergeEvent::PhaseStarted {
+        phase: "Updating LTEX Records",
+    });
+
+    let mut known_textures = KnownTextures::new();
+    for plugin in parsed_plugins
+        .masters
+        .iter()
+        .chain(parsed_plugins.plugins.iter())
+    {
+        if !plugin.is_ltex_only() {
+            add_known_textures(parsed_plugins, plugin, &mut known_textures);
+        }
+
+        known_textures.update_textures(plugin);
+    }
+
+    debug!("Found {} unique LTEX records", known_textures.len());
+
+    context.emit(MergeEvent::PhaseStarted { phase: "Saving" });
+
+    let data_files = cli.data_files_dir()?;
+    let output_file_dir = cli.output_file_dir()?;
+    let landmass = Landmass::new(Arc::new(ParsedPlugin::empty(&cli.output_file)));
+    let signature = PluginSignature::from_plugins(parsed_plugins);
+    save_plugin(
+        cli.file_provider(),
+        &data_files,
+        &output_file_dir,
+        &cli.output_file,
+        cli.sort_order,
+        &landmass,
+        &known_textures,
+        None,
+        &signature,
+        cli.preset_settings().deterministic,
+        &|_| Ok(()),
+    )?;
+
+    if cli.sort_order == SortOrder::Default {
+        check_output_load_order(
+            cli.file_provider(),
+            &data_files,
+            &output_file_dir,
+            &cli.output_file,
+            parsed_plugins,
+            config.adjust_output_mtime,
+        )?;
+    }
+
+    Ok(known_textures)
+}
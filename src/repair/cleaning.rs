@@ -1,17 +1,20 @@
+use crate::error::SeamError;
+use crate::io::meta_schema::SeamSettings;
 use crate::io::parsed_plugins::{is_esp, ParsedPlugin, ParsedPlugins};
+use crate::land::conversions::included_data;
 use crate::land::grid_access::SquareGridIterator;
 use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::Vec2;
 use crate::land::textures::{KnownTextures, RemappedTextures};
+use crate::merge::cells::ModifiedCell;
 use crate::merge::conflict::{ConflictResolver, ConflictType};
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::merge::relative_to::RelativeTo;
-use crate::repair::seam_detection::repair_landmass_seams;
-use crate::LandmassDiff;
+use crate::repair::seam_detection::{repair_landmass_seams_until_fixed, SeamRepairs};
+use crate::{Landmass, LandmassDiff};
+use hashbrown::{HashMap, HashSet};
 use log::debug;
-use std::collections::HashMap;
-use std::default::default;
-use std::sync::Arc;
-use tes3::esp::LandscapeTexture;
+use tes3::esp::{Cell, ObjectFlags};
 
 pub fn has_difference<U: RelativeTo + ConflictResolver, const T: usize>(
     lhs: Option<&RelativeTerrainMap<U, T>>,
@@ -25,7 +28,7 @@ pub fn has_difference<U: RelativeTo + ConflictResolver, const T: usize>(
         return false;
     };
 
-    let params = default();
+    let params = Default::default();
 
     for coords in lhs.iter_grid() {
         let actual = lhs.get_value(coords);
@@ -64,9 +67,20 @@ fn has_any_difference(reference: &LandscapeDiff, plugin: &LandscapeDiff) -> bool
         )
 }
 
-/// Remove any unmodified [crate::LandscapeDiff] from the [LandmassDiff].
-pub fn clean_landmass_diff(landmass: &mut LandmassDiff, modded_landmasses: &[LandmassDiff]) {
-    assert_eq!(repair_landmass_seams(landmass), 0);
+/// Remove any unmodified [crate::LandscapeDiff] from the [LandmassDiff]. Returns the
+/// [SeamRepairs] of the seams left by the previous repair.
+pub fn clean_landmass_diff(
+    landmass: &mut LandmassDiff,
+    modded_landmasses: &[LandmassDiff],
+    seam_settings: &SeamSettings,
+) -> Result<SeamRepairs, SeamError> {
+    let seam_repairs = repair_landmass_seams_until_fixed(landmass, seam_settings)?;
+    if seam_repairs.num_repaired > 0 {
+        debug!(
+            "Repaired {} seams left by the previous repair",
+            seam_repairs.num_repaired
+        );
+    }
 
     let mut modded_landmasses_map = HashMap::with_capacity(modded_landmasses.len());
     for modded_landmass in modded_landmasses.iter() {
@@ -98,8 +112,8 @@ pub fn clean_landmass_diff(landmass: &mut LandmassDiff, modded_landmasses: &[Lan
         assert!(is_esp(&plugin.name));
 
         let modded_landmass = modded_landmasses_map.get(&plugin.name).expect("safe");
-        let modded_landmass_land = modded_landmass.land.get(coords).expect("safe");
-        if !has_any_difference(land, modded_landmass_land) {
+        let modded_landmass_land = modded_landmass.land_at(coords).expect("safe");
+        if !has_any_difference(land, &modded_landmass_land) {
             unmodified.push(*coords);
             num_unmodified_from_plugin += 1;
         }
@@ -118,6 +132,8 @@ pub fn clean_landmass_diff(landmass: &mut LandmassDiff, modded_landmasses: &[Lan
     for coords in unmodified.drain(..) {
         landmass.land.remove(&coords);
     }
+
+    Ok(seam_repairs)
 }
 
 /// Remove any unused [crate::land::textures::KnownTexture] from the [KnownTextures].
@@ -132,20 +148,14 @@ pub fn clean_known_textures(
         "exceeded maximum number of textures"
     );
 
-    fn update_known_textures(plugin: &Arc<ParsedPlugin>, known_textures: &mut KnownTextures) {
-        for texture in plugin.records.objects_of_type::<LandscapeTexture>() {
-            known_textures.update_texture(plugin, texture);
-        }
-    }
-
     // Make sure all LTEX records have the correct filenames.
 
     for master in parsed_plugins.masters.iter() {
-        update_known_textures(master, known_textures);
+        known_textures.update_textures(master);
     }
 
-    for plugin in parsed_plugins.masters.iter() {
-        update_known_textures(plugin, known_textures);
+    for plugin in parsed_plugins.plugins.iter() {
+        known_textures.update_textures(plugin);
     }
 
     // Determine all LTEX records in use in the final MergedLands.esp.
@@ -174,3 +184,70 @@ pub fn clean_known_textures(
 
     remapped_textures
 }
+
+/// Returns the coordinates of every [tes3::esp::Landscape] in `landmass` that is identical to
+/// the same [tes3::esp::Landscape] in the `reference` [Landmass].
+pub fn find_unmodified_land(landmass: &Landmass, reference: &Landmass) -> HashSet<Vec2<i32>> {
+    let mut unmodified = HashSet::new();
+
+    for (coords, land) in landmass.sorted() {
+        if land.flags.contains(ObjectFlags::DELETED) {
+            continue;
+        }
+
+        let Some(reference_land) = reference.land.get(coords) else {
+            continue;
+        };
+
+        let reference_data = included_data(reference_land);
+        let included_data = included_data(land);
+        if included_data != reference_data {
+            continue;
+        }
+
+        let landscape_diff =
+            LandscapeDiff::from_difference(land, Some(reference_land), included_data, None);
+        if !landscape_diff.is_modified() {
+            unmodified.insert(*coords);
+        }
+    }
+
+    unmodified
+}
+
+/// Returns `true` if the `lhs` and `rhs` [Cell] have the same fields, ignoring any references.
+fn is_same_cell(lhs: &Cell, rhs: &Cell) -> bool {
+    lhs.flags == rhs.flags
+        && lhs.id == rhs.id
+        && lhs.data == rhs.data
+        && lhs.region == rhs.region
+        && lhs.map_color == rhs.map_color
+        && lhs.water_height == rhs.water_height
+        && lhs.atmosphere_data == rhs.atmosphere_data
+}
+
+/// Returns the coordinates of every exterior [Cell] in `plugin` that does not contain any
+/// references and is identical to the [ModifiedCell] in the `reference` cells.
+pub fn find_unmodified_cells(
+    plugin: &ParsedPlugin,
+    reference: &HashMap<Vec2<i32>, ModifiedCell>,
+) -> HashSet<Vec2<i32>> {
+    let mut unmodified = HashSet::new();
+
+    for cell in plugin.records.objects_of_type::<Cell>() {
+        if !cell.references.is_empty() {
+            continue;
+        }
+
+        let coords = Vec2::new(cell.data.grid.0, cell.data.grid.1);
+        let Some(reference_cell) = reference.get(&coords) else {
+            continue;
+        };
+
+        if is_same_cell(cell, &reference_cell.inner) {
+            unmodified.insert(coords);
+        }
+    }
+
+    unmodified
+}
@@ -1,11 +1,22 @@
-use crate::land::grid_access::SquareGridIterator;
+use crate::land::conversions::convert_terrain_map;
+use crate::land::grid_access::{GridAccessor2D, Index2D, SquareGridIterator};
+use crate::land::height_map::{calculate_vertex_normals_map, try_calculate_height_map};
 use crate::land::landscape_diff::LandscapeDiff;
-use crate::land::terrain_map::Vec3;
+use crate::land::terrain_map::{Vec2, Vec3};
 use crate::merge::conflict::{ConflictResolver, ConflictType};
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::merge::relative_to::RelativeTo;
 use crate::LandmassDiff;
-use std::default::default;
+use hashbrown::HashSet;
+use log::{debug, trace};
+use tes3::esp::{Landscape, VertexNormals};
+
+/// The smallest difference in world units between a modified vertex and each of its neighbors
+/// for the vertex to be a height spike.
+const SPIKE_HEIGHT: i32 = 512;
+
+/// The factor the heights of a cell are multiplied by before calculating exaggerated normals.
+const NORMAL_EXAGGERATION: i32 = 8;
 
 /// Adds any conflicts between the `lhs` [RelativeTerrainMap] and
 /// the `rhs` [RelativeTerrainMap] to the `vertex_colors`.
@@ -26,7 +37,7 @@ pub fn add_vertex_colors<U: RelativeTo + ConflictResolver, const T: usize>(
         return;
     };
 
-    let params = default();
+    let params = Default::default();
 
     const MAJOR_COLOR: Vec3<u8> = Vec3::new(255u8, 0, 0);
     const MINOR_COLOR: Vec3<u8> = Vec3::new(255u8, 255u8, 0);
@@ -77,3 +88,71 @@ pub fn add_debug_vertex_colors_to_landmass(reference: &mut LandmassDiff, plugin:
         add_debug_vertex_colors_to_landscape(merged_land, land);
     }
 }
+
+/// Returns the coordinates of each cell of the `merged` [LandmassDiff] with a height spike, i.e.
+/// a vertex modified by the merge that is higher or lower than each of its neighbors by more
+/// than [SPIKE_HEIGHT] world units.
+pub fn find_height_spikes(merged: &LandmassDiff) -> HashSet<Vec2<i32>> {
+    let mut cells = HashSet::new();
+
+    for (coords, land) in merged.sorted() {
+        let Some(height_map) = land.height_map.as_ref() else {
+            continue;
+        };
+
+        let heights = height_map.to_terrain();
+        let is_spike = |vertex: Index2D| {
+            let height = heights.get(vertex);
+            let differences = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .into_iter()
+                .map(|(dx, dy)| (vertex.x as i32 + dx, vertex.y as i32 + dy))
+                .filter(|(x, y)| (0..65).contains(x) && (0..65).contains(y))
+                .map(|(x, y)| height - heights.get(Index2D::new(x as usize, y as usize)))
+                .collect::<Vec<_>>();
+
+            differences
+                .iter()
+                .all(|difference| *difference > SPIKE_HEIGHT)
+                || differences
+                    .iter()
+                    .all(|difference| *difference < -SPIKE_HEIGHT)
+        };
+
+        if let Some(vertex) = height_map
+            .iter_grid()
+            .filter(|vertex| height_map.has_difference(*vertex))
+            .find(|vertex| is_spike(*vertex))
+        {
+            trace!(
+                "({:>4}, {:>4}) | Height spike at vertex ({}, {})",
+                coords.x,
+                coords.y,
+                vertex.x,
+                vertex.y
+            );
+            cells.insert(*coords);
+        }
+    }
+
+    debug!("Found {} cells with height spikes", cells.len());
+    cells
+}
+
+/// Replaces the vertex normals of the `land` with the normals of its heights multiplied by
+/// [NORMAL_EXAGGERATION], so that every slope of the cell stands out under in-game lighting.
+pub fn exaggerate_vertex_normals(land: &mut Landscape) {
+    let Some(mut height_map) = try_calculate_height_map(land) else {
+        return;
+    };
+
+    for height in height_map.iter_mut().flatten() {
+        *height *= NORMAL_EXAGGERATION;
+    }
+
+    land.vertex_normals = Some(VertexNormals {
+        data: Box::new(convert_terrain_map(
+            &calculate_vertex_normals_map(&height_map),
+            Vec3::into,
+        )),
+    });
+}
@@ -0,0 +1,109 @@
+use crate::land::grid_access::{GridAccessor2D, SquareGridIterator};
+use crate::land::height_map::{try_calculate_height_map, HeightTransform};
+use crate::Landmass;
+use itertools::Itertools;
+use log::{trace, warn};
+use owo_colors::OwoColorize;
+
+/// Heights of the reference closer to 0 than this are ignored, since their ratios are unreliable.
+const MIN_REFERENCE_HEIGHT: i32 = 256;
+
+/// The smallest number of changed vertices needed to detect a height scale, i.e. about 1 cell.
+const MIN_SAMPLES: usize = 4096;
+
+/// Ratios closer to 1 than this are assumed to be intentional edits of the land.
+const MIN_RATIO: f32 = 2.;
+
+/// The largest relative difference from the median ratio for a ratio to count as uniform.
+const UNIFORM_TOLERANCE: f32 = 0.05;
+
+/// The smallest fraction of ratios that must be uniform to detect a height scale.
+const MIN_UNIFORM_FRACTION: f32 = 0.9;
+
+/// Detects a `landmass` whose heights are a uniform multiple of the heights of the `reference`
+/// [Landmass], e.g. a plugin converted by an external tool with heights 8 times too large.
+/// Returns the `height_scale` that would correct the heights, if any.
+pub fn detect_height_scale(landmass: &Landmass, reference: &Landmass) -> Option<f32> {
+    let mut ratios = Vec::new();
+
+    for (coords, land) in landmass.land.iter() {
+        let Some(reference_land) = reference.land.get(coords) else {
+            continue;
+        };
+
+        // Missing vertex heights are already reported when the land is compared to its masters.
+        if land.vertex_heights.is_none() || reference_land.vertex_heights.is_none() {
+            continue;
+        }
+
+        let (Some(height_map), Some(reference_height_map)) = (
+            try_calculate_height_map(land),
+            try_calculate_height_map(reference_land),
+        ) else {
+            continue;
+        };
+
+        for vertex in height_map.iter_grid() {
+            let height = height_map.get(vertex);
+            let reference_height = reference_height_map.get(vertex);
+            if height != reference_height && reference_height.abs() >= MIN_REFERENCE_HEIGHT {
+                ratios.push(height as f32 / reference_height as f32);
+            }
+        }
+    }
+
+    if ratios.len() < MIN_SAMPLES {
+        return None;
+    }
+
+    let ratios = ratios
+        .into_iter()
+        .sorted_by(|a, b| a.total_cmp(b))
+        .collect_vec();
+    let median = ratios[ratios.len() / 2];
+    if median <= 0. || (median < MIN_RATIO && median > 1. / MIN_RATIO) {
+        return None;
+    }
+
+    let num_uniform = ratios
+        .iter()
+        .filter(|ratio| ((*ratio - median) / median).abs() <= UNIFORM_TOLERANCE)
+        .count();
+    let uniform_fraction = num_uniform as f32 / ratios.len() as f32;
+
+    trace!(
+        "{} heights are {:.3}x the reference for {:.1}% of {} vertices",
+        landmass.plugin.name,
+        median,
+        uniform_fraction * 100.,
+        ratios.len()
+    );
+
+    (uniform_fraction >= MIN_UNIFORM_FRACTION).then_some(1. / median)
+}
+
+/// Logs a warning if the heights of the `landmass` look scaled incorrectly compared to the
+/// `reference` [Landmass]. Plugins with a [HeightTransform] in their meta file are not checked.
+pub fn warn_height_scale_mismatch(landmass: &Landmass, reference: &Landmass) {
+    if HeightTransform::from_meta(&landmass.plugin.meta).is_some() {
+        return;
+    }
+
+    let Some(height_scale) = detect_height_scale(landmass, reference) else {
+        return;
+    };
+
+    warn!(
+        "{} {}",
+        format!(
+            "Plugin {} has heights that are a uniform multiple of its masters.",
+            landmass.plugin.name.bold()
+        )
+        .yellow(),
+        format!(
+            "If they are scaled incorrectly, set `height_scale = {:.3}` in its meta file",
+            height_scale
+        )
+        .yellow()
+    );
+}
@@ -1,3 +1,8 @@
 pub mod cleaning;
 pub mod debugging;
+pub mod height_scale;
+pub mod outlier_cells;
 pub mod seam_detection;
+pub mod skipped_cells;
+pub mod stray_normals;
+pub mod world_map;
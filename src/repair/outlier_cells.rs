@@ -0,0 +1,145 @@
+use crate::io::meta_schema::OutlierCellPolicy;
+use crate::io::report::OutlierCells;
+use crate::land::terrain_map::Vec2;
+use crate::LandmassDiff;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
+
+/// Returns each group of cells of the `merged` [LandmassDiff] other than the largest one.
+/// Cells within `distance` cells of each other along both axes are in the same group, so each
+/// returned group is farther than `distance` cells from every other cell.
+/// Each group is ordered by `x` and `y` coordinates, and the groups by their first cell.
+pub fn find_outlier_cells(merged: &LandmassDiff, distance: usize) -> Vec<Vec<Vec2<i32>>> {
+    let distance = distance.clamp(1, i32::MAX as usize) as i32;
+    let bucket =
+        |coords: Vec2<i32>| Vec2::new(coords.x.div_euclid(distance), coords.y.div_euclid(distance));
+
+    // Only the cells of neighboring buckets can be within `distance` of a cell.
+    let mut buckets: HashMap<Vec2<i32>, Vec<Vec2<i32>>> = HashMap::new();
+    for coords in merged.land.keys() {
+        buckets.entry(bucket(*coords)).or_default().push(*coords);
+    }
+
+    let mut grouped = HashSet::new();
+    let mut groups = Vec::new();
+    for start in merged
+        .land
+        .keys()
+        .sorted_by_key(|coords| (coords.x, coords.y))
+    {
+        if grouped.contains(start) {
+            continue;
+        }
+
+        let mut group = Vec::new();
+        let mut pending = vec![*start];
+        grouped.insert(*start);
+        while let Some(coords) = pending.pop() {
+            group.push(coords);
+            let center = bucket(coords);
+            for (x, y) in (-1..=1).cartesian_product(-1..=1) {
+                let Some(cells) = buckets.get_mut(&Vec2::new(center.x + x, center.y + y)) else {
+                    continue;
+                };
+
+                cells.retain(|other| {
+                    if grouped.contains(other) {
+                        return false;
+                    }
+
+                    let is_near = (other.x - coords.x).abs() <= distance
+                        && (other.y - coords.y).abs() <= distance;
+                    if is_near {
+                        grouped.insert(*other);
+                        pending.push(*other);
+                    }
+
+                    !is_near
+                });
+            }
+        }
+
+        group.sort_by_key(|coords| (coords.x, coords.y));
+        groups.push(group);
+    }
+
+    // The first of the largest groups is the main landmass.
+    let main = groups
+        .iter()
+        .enumerate()
+        .max_by_key(|(idx, group)| (group.len(), std::cmp::Reverse(*idx)))
+        .map(|(idx, _)| idx);
+    if let Some(main) = main {
+        groups.remove(main);
+    }
+
+    groups
+}
+
+/// Returns the cells of the `outliers` that are left out of images and exports stitched from
+/// every cell, e.g. the world map, with the [OutlierCellPolicy] `policy`.
+pub fn stitched_exclusions(
+    outliers: &[Vec<Vec2<i32>>],
+    policy: OutlierCellPolicy,
+) -> HashSet<Vec2<i32>> {
+    match policy {
+        OutlierCellPolicy::Include => HashSet::new(),
+        OutlierCellPolicy::Warn | OutlierCellPolicy::Exclude => {
+            outliers.iter().flatten().copied().collect()
+        }
+    }
+}
+
+/// Returns an [OutlierCells] for each group of `outliers` of the `merged` [LandmassDiff].
+/// With [OutlierCellPolicy::Exclude], the cells are also removed from the `merged` [LandmassDiff],
+/// so that the game uses the LAND record of the last plugin in the load order instead.
+pub fn handle_outlier_cells(
+    merged: &mut LandmassDiff,
+    outliers: &[Vec<Vec2<i32>>],
+    policy: OutlierCellPolicy,
+) -> Vec<OutlierCells> {
+    outliers
+        .iter()
+        .map(|cells| {
+            let plugins = cells
+                .iter()
+                .filter_map(|coords| merged.land.get(coords))
+                .flat_map(|land| land.plugins.iter())
+                .map(|(plugin, _)| plugin.name.clone())
+                .unique()
+                .collect_vec();
+
+            let first = cells[0];
+            let action = match policy {
+                OutlierCellPolicy::Include => "are included everywhere",
+                OutlierCellPolicy::Warn => "are left out of the world map and stitched exports",
+                OutlierCellPolicy::Exclude => "are left out of the output",
+            };
+            warn!(
+                "{} {}",
+                format!(
+                    "Found {} cells starting at ({}, {}) far away from the rest of the land.",
+                    cells.len(),
+                    first.x,
+                    first.y
+                )
+                .yellow(),
+                format!("These cells from {} {}", plugins.join(", ").bold(), action).yellow()
+            );
+
+            if policy == OutlierCellPolicy::Exclude {
+                for coords in cells.iter() {
+                    merged.land.remove(coords);
+                }
+            }
+
+            OutlierCells {
+                cells: cells.iter().map(|coords| (*coords).into()).collect(),
+                plugins,
+                policy,
+            }
+        })
+        .collect()
+}
@@ -1,10 +1,15 @@
+use crate::error::SeamError;
+use crate::io::meta_schema::{BlendProfile, SeamSettings};
+use crate::io::report::{RepairedSeam, Report, ReportWarning};
 use crate::land::grid_access::Index2D;
-use crate::land::terrain_map::Vec2;
+use crate::land::height_map::{format_height, world_units_to_meters};
+use crate::land::terrain_map::{LandData, Vec2};
 use crate::merge::relative_terrain_map::RelativeTerrainMap;
 use crate::LandmassDiff;
 use hashbrown::HashSet;
 use itertools::Itertools;
-use log::{debug, trace};
+use log::{debug, trace, warn};
+use owo_colors::OwoColorize;
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 
@@ -43,6 +48,77 @@ fn push_back_neighbors(
     }
 }
 
+/// The largest `blend_band` of the [SeamSettings]. Larger bands would overlap the opposite seam.
+const MAX_BLEND_BAND: usize = 32;
+
+/// Returns the fraction of a seam correction applied `distance` rows away from the seam
+/// for a band of `band` rows. The seam itself receives the full correction.
+fn blend_weight(profile: BlendProfile, distance: usize, band: usize) -> f32 {
+    let t = 1. - distance as f32 / band as f32;
+    match profile {
+        BlendProfile::Linear => t,
+        BlendProfile::Smoothstep => t * t * (3. - 2. * t),
+    }
+}
+
+/// Returns the coordinates in the `lhs` and `rhs` cells that are `distance` rows away from
+/// the seam at `index` along the seam.
+fn seam_coords(is_top_seam: bool, index: usize, distance: usize) -> (Index2D, Index2D) {
+    if is_top_seam {
+        (
+            Index2D::new(index, 64 - distance),
+            Index2D::new(index, distance),
+        )
+    } else {
+        (
+            Index2D::new(64 - distance, index),
+            Index2D::new(distance, index),
+        )
+    }
+}
+
+/// Returns whether the blend band of the side shared by the `seam` cells overlaps the band of
+/// another side at the start and at the end of the side, i.e. whether either cell has a
+/// neighbor with a height map across that end.
+fn band_overlaps(merged: &LandmassDiff, seam: (Vec2<i32>, Vec2<i32>)) -> [bool; 2] {
+    // Cells with the same `x` coordinate share a side running along `x`.
+    let along = if seam.0.x == seam.1.x { [1, 0] } else { [0, 1] };
+    [-1, 1].map(|sign| {
+        [seam.0, seam.1].iter().any(|coords| {
+            let neighbor = coords_with_offset(*coords, [sign * along[0], sign * along[1]]);
+            merged
+                .land
+                .get(&neighbor)
+                .map_or(false, |land| land.height_map.is_some())
+        })
+    })
+}
+
+/// Creates a [RepairedSeam] for the `cells` in the `merged` [LandmassDiff].
+fn repaired_seam(
+    merged: &LandmassDiff,
+    cells: &[Vec2<i32>],
+    vertices_moved: usize,
+    max_delta: usize,
+) -> RepairedSeam {
+    let plugins = cells
+        .iter()
+        .flat_map(|coords| merged.land.get(coords))
+        .flat_map(|land| land.plugins.iter())
+        .filter(|(_, modified_data)| modified_data.contains(LandData::VERTEX_HEIGHTS))
+        .map(|(plugin, _)| plugin.name.clone())
+        .unique()
+        .collect_vec();
+
+    RepairedSeam {
+        cells: cells.iter().map(|coords| [coords.x, coords.y]).collect(),
+        vertices_moved,
+        max_delta,
+        max_delta_meters: world_units_to_meters(max_delta as i32),
+        plugins,
+    }
+}
+
 /// A corner of a landscape.
 struct Corner {
     coords: Index2D,
@@ -56,11 +132,14 @@ struct CornerCase {
     corners: [Corner; 4],
 }
 
-/// Repairs corner seams by averaging their values together.
+/// Repairs corner seams by averaging their values together. Corners shared by fewer than
+/// `min_corner_neighbors` cells in the [SeamSettings] are skipped.
 fn repair_corner_seams(
     merged: &mut LandmassDiff,
     coords: Vec2<i32>,
+    settings: &SeamSettings,
     num_seams_repaired: &mut usize,
+    repairs: &mut Vec<RepairedSeam>,
 ) {
     let cases = [
         CornerCase {
@@ -162,7 +241,15 @@ fn repair_corner_seams(
                 num_values += 1;
             }
 
-            if num_values > 0 {
+            if (num_values as usize) < settings.min_corner_neighbors {
+                trace!(
+                    "Skipping corner of ({}, {}) with {} neighbors",
+                    coords.x,
+                    coords.y,
+                    num_values
+                );
+                None
+            } else if num_values > 0 {
                 average /= num_values;
                 Some(average)
             } else {
@@ -174,10 +261,12 @@ fn repair_corner_seams(
             continue;
         };
 
+        let mut cells = Vec::new();
+        let mut vertices_moved = 0;
+        let mut max_delta = 0;
         for corner in case.corners.iter() {
-            let Some(land) = merged
-                .land
-                .get_mut(&coords_with_offset(coords, corner.cell_offset)) else {
+            let corner_coords = coords_with_offset(coords, corner.cell_offset);
+            let Some(land) = merged.land.get_mut(&corner_coords) else {
                 continue;
             };
 
@@ -185,11 +274,20 @@ fn repair_corner_seams(
                 continue;
             };
 
-            if height_map.get_value(corner.coords) != average {
+            cells.push(corner_coords);
+
+            let value = height_map.get_value(corner.coords);
+            if value != average {
                 height_map.set_value(corner.coords, average);
-                *num_seams_repaired += 1;
+                vertices_moved += 1;
+                max_delta = max_delta.max((average - value).unsigned_abs() as usize);
             }
         }
+
+        if vertices_moved > 0 {
+            *num_seams_repaired += vertices_moved;
+            repairs.push(repaired_seam(merged, &cells, vertices_moved, max_delta));
+        }
     }
 }
 
@@ -197,8 +295,14 @@ fn repair_corner_seams(
 /// corner seams by averaging the values of all vertices shared by 4 cells. Then, the
 /// algorithm will repair seams on the sides between cells by picking the average value
 /// of both sides. For performance, only seams adjacent to coordinates in the `possible_seams`
-/// field of the [LandmassDiff] will be visited.
-pub fn repair_landmass_seams(merged: &mut LandmassDiff) -> usize {
+/// field of the [LandmassDiff] will be visited. Corners skipped due to the [SeamSettings]
+/// are not averaged, but the two cells of each side still agree at the ends of the side.
+/// Each seam that is repaired is added to `repairs`.
+pub fn repair_landmass_seams(
+    merged: &mut LandmassDiff,
+    settings: &SeamSettings,
+    repairs: &mut Vec<RepairedSeam>,
+) -> Result<usize, SeamError> {
     let mut possible_seams = VecDeque::new();
     let mut visited = HashSet::new();
     let mut repaired = HashSet::new();
@@ -206,33 +310,66 @@ pub fn repair_landmass_seams(merged: &mut LandmassDiff) -> usize {
     let mut num_seams_repaired = 0;
 
     for coords in merged.sorted().map(|pair| *pair.0).collect_vec() {
-        repair_corner_seams(merged, coords, &mut num_seams_repaired);
+        repair_corner_seams(merged, coords, settings, &mut num_seams_repaired, repairs);
         push_back_neighbors(&mut possible_seams, &mut visited, coords);
     }
 
-    /// Repairs a seam shared by two cells along a side.
+    /// Repairs a seam shared by two cells along a side. The correction is spread over the
+    /// `blend_band` of the [SeamSettings], so no vertex moves further than the seam itself.
+    /// If the band `overlaps` the band of the side meeting this side at its start or end, each
+    /// vertex near that corner is only blended by the nearer side, so it is not blended twice.
     fn try_repair_seam<const T: usize>(
-        lhs_coord: Index2D,
-        rhs_coord: Index2D,
+        is_top_seam: bool,
         lhs_map: &mut RelativeTerrainMap<i32, T>,
         rhs_map: &mut RelativeTerrainMap<i32, T>,
         index: usize,
+        overlaps: [bool; 2],
+        settings: &SeamSettings,
     ) -> usize {
+        // Corners shared by enough cells were fixed first, so both sides already agree.
+        // Corners that were skipped are only made to agree for the two cells of this side,
+        // without spreading the correction along the other side of the corner.
+        let is_corner = index == 0 || index == 64;
+
+        let (lhs_coord, rhs_coord) = seam_coords(is_top_seam, index, 0);
         let lhs_value = lhs_map.get_value(lhs_coord);
         let rhs_value = rhs_map.get_value(rhs_coord);
         if lhs_value != rhs_value {
-            assert!(
-                index != 0 && index != 64,
-                "corners should have been fixed first"
-            );
-
             // TODO(dvd): #feature Should this use the ConflictResolver instead?
             let average = (lhs_value + rhs_value) / 2;
-            let lhs_diff = (average - lhs_value).abs();
-            let rhs_diff = (average - rhs_value).abs();
+            let lhs_diff = average - lhs_value;
+            let rhs_diff = average - rhs_value;
             lhs_map.set_value(lhs_coord, average);
             rhs_map.set_value(rhs_coord, average);
-            lhs_diff.max(rhs_diff) as usize
+
+            let mut band = if is_corner {
+                0
+            } else {
+                settings.blend_band.min(MAX_BLEND_BAND)
+            };
+
+            // Vertices as near to both sides are blended by the top side.
+            for (overlaps, corner_distance) in overlaps.into_iter().zip([index, 64 - index]) {
+                if overlaps {
+                    band = band.min(corner_distance + is_top_seam as usize);
+                }
+            }
+            for distance in 1..band {
+                let weight = blend_weight(settings.blend_profile, distance, band);
+                let (lhs_coord, rhs_coord) = seam_coords(is_top_seam, index, distance);
+                let lhs_value = lhs_map.get_value(lhs_coord);
+                let rhs_value = rhs_map.get_value(rhs_coord);
+                lhs_map.set_value(
+                    lhs_coord,
+                    lhs_value + (lhs_diff as f32 * weight).round() as i32,
+                );
+                rhs_map.set_value(
+                    rhs_coord,
+                    rhs_value + (rhs_diff as f32 * weight).round() as i32,
+                );
+            }
+
+            lhs_diff.abs().max(rhs_diff.abs()) as usize
         } else {
             0
         }
@@ -241,6 +378,7 @@ pub fn repair_landmass_seams(merged: &mut LandmassDiff) -> usize {
     while !possible_seams.is_empty() {
         let next = possible_seams.pop_front().expect("safe");
 
+        let overlaps = band_overlaps(merged, next);
         let Some(mut lands) = merged.land.get_many_mut([&next.0, &next.1]) else {
             continue;
         };
@@ -257,51 +395,47 @@ pub fn repair_landmass_seams(merged: &mut LandmassDiff) -> usize {
             continue;
         };
 
-        let is_top_seam = if lhs.coords.x == rhs.coords.x {
-            assert!(lhs.coords.y < rhs.coords.y);
+        let is_top_seam = if lhs.coords.x == rhs.coords.x && lhs.coords.y < rhs.coords.y {
             true
-        } else {
-            assert!(lhs.coords.x < rhs.coords.x);
+        } else if lhs.coords.y == rhs.coords.y && lhs.coords.x < rhs.coords.x {
             false
+        } else {
+            return Err(SeamError::NotAdjacent {
+                lhs: lhs.coords,
+                rhs: rhs.coords,
+            });
         };
 
         let mut seam_size = 0;
         let mut sum = 0;
         let mut max_delta = usize::MIN;
         let mut min_delta = usize::MAX;
-        if is_top_seam {
-            for x in 0..65 {
-                let lhs_coord = Index2D::new(x, 64);
-                let rhs_coord = Index2D::new(x, 0);
-                let delta =
-                    try_repair_seam(lhs_coord, rhs_coord, lhs_height_map, rhs_height_map, x);
-                if delta > 0 {
-                    num_seams_repaired += 1;
-                    seam_size += 1;
-                    sum += delta;
-                    max_delta = max_delta.max(delta);
-                    min_delta = min_delta.min(delta);
-                }
-            }
-        } else {
-            for y in 0..65 {
-                let lhs_coord = Index2D::new(64, y);
-                let rhs_coord = Index2D::new(0, y);
-                let delta =
-                    try_repair_seam(lhs_coord, rhs_coord, lhs_height_map, rhs_height_map, y);
-                if delta > 0 {
-                    num_seams_repaired += 1;
-                    seam_size += 1;
-                    sum += delta;
-                    max_delta = max_delta.max(delta);
-                    min_delta = min_delta.min(delta);
-                }
+        for index in 0..65 {
+            let delta = try_repair_seam(
+                is_top_seam,
+                lhs_height_map,
+                rhs_height_map,
+                index,
+                overlaps,
+                settings,
+            );
+            if delta > 0 {
+                num_seams_repaired += 1;
+                seam_size += 1;
+                sum += delta;
+                max_delta = max_delta.max(delta);
+                min_delta = min_delta.min(delta);
             }
         }
 
-        if seam_size > 0 {
-            let average = sum / seam_size;
+        if let Some(average) = sum.checked_div(seam_size) {
             repaired.insert((next, seam_size, max_delta, min_delta, average));
+            repairs.push(repaired_seam(
+                merged,
+                &[next.0, next.1],
+                seam_size,
+                max_delta,
+            ));
         }
     }
 
@@ -309,18 +443,213 @@ pub fn repair_landmass_seams(merged: &mut LandmassDiff) -> usize {
         debug!("Repaired {} seams", num_seams_repaired);
         for seam in repaired.iter().sorted_by_key(|a| std::cmp::Reverse(a.1)) {
             trace!(
-                " - ({:>4}, {:>4}) | ({:>4}, {:>4}) | # of Seams = {:<3} | Max = {:<3} | Min = {:<3} | Avg = {}",
+                " - ({:>4}, {:>4}) | ({:>4}, {:>4}) | # of Seams = {:<3} | Max = {} | Min = {} | Avg = {}",
                 seam.0 .0.x,
                 seam.0 .0.y,
                 seam.0 .1.x,
                 seam.0 .1.y,
                 seam.1,
-                seam.2,
-                seam.3,
-                seam.4
+                format_height(seam.2 as i32),
+                format_height(seam.3 as i32),
+                format_height(seam.4 as i32)
             );
         }
     }
 
-    num_seams_repaired
+    Ok(num_seams_repaired)
+}
+
+/// The seams repaired by [repair_landmass_seams_until_fixed].
+pub struct SeamRepairs {
+    /// The number of vertices repaired over every pass.
+    pub num_repaired: usize,
+    /// Each [RepairedSeam] of every pass.
+    pub repaired: Vec<RepairedSeam>,
+    /// Each seam that remains after the last pass, if seam repair did not converge.
+    pub remaining: Vec<RepairedSeam>,
+}
+
+impl SeamRepairs {
+    /// Adds the `repaired` seams to the [Report], and a [ReportWarning::UnrepairedSeam] for
+    /// each of the `remaining` seams.
+    pub fn add_to(self, report: &mut Report) {
+        report.repaired_seams.extend(self.repaired);
+        report
+            .warnings
+            .extend(
+                self.remaining
+                    .into_iter()
+                    .map(|seam| ReportWarning::UnrepairedSeam {
+                        cells: seam.cells,
+                        num_vertices: seam.vertices_moved,
+                        max_delta: seam.max_delta,
+                    }),
+            );
+    }
+}
+
+/// Repeats [repair_landmass_seams] until no seams are repaired, up to the `max_repair_passes`
+/// in the [SeamSettings]. If seams are still repaired by the last pass, the seams that remain
+/// are found without changing the [LandmassDiff], logged as a warning, and returned.
+pub fn repair_landmass_seams_until_fixed(
+    merged: &mut LandmassDiff,
+    settings: &SeamSettings,
+) -> Result<SeamRepairs, SeamError> {
+    let mut repaired = Vec::new();
+    let mut num_repaired = 0;
+
+    for pass in 0..settings.max_repair_passes {
+        let num_seams_repaired = repair_landmass_seams(merged, settings, &mut repaired)?;
+        if num_seams_repaired == 0 {
+            trace!("Seams converged after {} passes", pass);
+            return Ok(SeamRepairs {
+                num_repaired,
+                repaired,
+                remaining: Vec::new(),
+            });
+        }
+
+        num_repaired += num_seams_repaired;
+    }
+
+    let mut remaining = Vec::new();
+    let num_remaining = repair_landmass_seams(&mut merged.clone(), settings, &mut remaining)?;
+    if num_remaining > 0 {
+        warn!(
+            "{} {}",
+            format!(
+                "{} seams remain after {} passes",
+                remaining.len(),
+                settings.max_repair_passes
+            )
+            .yellow(),
+            "because seam repair did not converge".yellow()
+        );
+    }
+
+    Ok(SeamRepairs {
+        num_repaired,
+        repaired,
+        remaining,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::land::grid_access::SquareGridIterator;
+    use crate::test_support::{plugin, LandmassBuilder};
+
+    /// The cells sharing the corner at the origin, in the order they are added, with the vertex
+    /// of each cell at the corner.
+    const CORNER: [(Vec2<i32>, Index2D); 4] = [
+        (Vec2 { x: 0, y: 0 }, Index2D { x: 0, y: 0 }),
+        (Vec2 { x: -1, y: 0 }, Index2D { x: 64, y: 0 }),
+        (Vec2 { x: 0, y: -1 }, Index2D { x: 0, y: 64 }),
+        (Vec2 { x: -1, y: -1 }, Index2D { x: 64, y: 64 }),
+    ];
+
+    /// Returns a [LandmassDiff] with the first `num_neighbors` cells of the [CORNER],
+    /// each flat at a different height.
+    fn corner_landmass(num_neighbors: usize) -> LandmassDiff {
+        CORNER
+            .iter()
+            .take(num_neighbors)
+            .enumerate()
+            .fold(
+                LandmassBuilder::new(plugin("Merged.esp")),
+                |builder, (idx, (coords, _))| {
+                    let height = (idx as i32 + 1) * 800;
+                    builder.heights(*coords, &[[height; 65]; 65], 1)
+                },
+            )
+            .build_diff(LandData::all())
+    }
+
+    /// Returns the height of each cell of the `merged` [LandmassDiff] at the [CORNER].
+    fn corner_heights(merged: &LandmassDiff) -> Vec<i32> {
+        CORNER
+            .iter()
+            .flat_map(|(coords, vertex)| {
+                let height_map = merged.land.get(coords)?.height_map.as_ref()?;
+                Some(height_map.get_value(*vertex))
+            })
+            .collect()
+    }
+
+    fn repair(num_neighbors: usize, min_corner_neighbors: usize) -> Vec<i32> {
+        let settings = SeamSettings {
+            min_corner_neighbors,
+            ..Default::default()
+        };
+
+        let mut merged = corner_landmass(num_neighbors);
+        repair_landmass_seams_until_fixed(&mut merged, &settings).unwrap();
+        corner_heights(&merged)
+    }
+
+    #[test]
+    fn corners_are_averaged_with_any_number_of_neighbors() {
+        assert_eq!(repair(1, 1), vec![800]);
+        assert_eq!(repair(2, 1), vec![1200; 2]);
+        assert_eq!(repair(3, 1), vec![1600; 3]);
+        assert_eq!(repair(4, 1), vec![2000; 4]);
+    }
+
+    #[test]
+    fn corners_with_too_few_neighbors_still_close_their_sides() {
+        // A single cell has no seam.
+        assert_eq!(repair(1, 4), vec![800]);
+        // The corner is skipped, but the side shared by both cells is repaired to its end.
+        assert_eq!(repair(2, 4), vec![1200; 2]);
+        // The corner is skipped, but each side is repaired until the cells agree.
+        let heights = repair(3, 4);
+        assert!(heights.iter().all_equal(), "{:?}", heights);
+        // Enough neighbors to average the corner.
+        assert_eq!(repair(4, 4), vec![2000; 4]);
+    }
+
+    #[test]
+    fn sides_are_repaired_without_moving_the_skipped_corner_along_the_other_side() {
+        let settings = SeamSettings {
+            min_corner_neighbors: 4,
+            blend_band: 8,
+            ..Default::default()
+        };
+
+        let mut merged = corner_landmass(2);
+        repair_landmass_seams_until_fixed(&mut merged, &settings).unwrap();
+
+        let height_map = merged.land[&Vec2::new(0, 0)].height_map.as_ref().unwrap();
+        // Only the vertex at the end of the side is moved, not the vertices next to it along
+        // the side of the cell without a neighbor.
+        assert_eq!(height_map.get_value(Index2D::new(0, 0)), 1200);
+        assert_eq!(height_map.get_value(Index2D::new(0, 1)), 1200);
+        assert_eq!(height_map.get_value(Index2D::new(1, 0)), 800);
+        assert_eq!(height_map.get_value(Index2D::new(1, 1)), 800 + 350);
+    }
+
+    #[test]
+    fn vertices_near_a_corner_are_only_blended_by_one_side() {
+        let settings = SeamSettings {
+            min_corner_neighbors: 1,
+            blend_band: 8,
+            ..Default::default()
+        };
+
+        let mut merged = corner_landmass(3);
+        repair_landmass_seams(&mut merged, &settings, &mut Vec::new()).unwrap();
+
+        // The cell at 800 is raised to 1200 along its side and 1600 along its top, so no
+        // vertex inside of it may be raised further than 1600.
+        let height_map = merged.land[&Vec2::new(0, 0)].height_map.as_ref().unwrap();
+        for vertex in height_map.iter_grid() {
+            let height = height_map.get_value(vertex);
+            assert!((800..=1600).contains(&height), "{:?} = {}", vertex, height);
+        }
+
+        // Each vertex is blended by the nearer side, with a band ending at the corner.
+        assert_eq!(height_map.get_value(Index2D::new(1, 2)), 800 + 400 / 2);
+        assert_eq!(height_map.get_value(Index2D::new(2, 1)), 800 + 800 * 2 / 3);
+    }
 }
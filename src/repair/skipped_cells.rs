@@ -0,0 +1,112 @@
+use crate::io::report::{CellConflict, ExcludedCell, SkippedCell};
+use crate::land::terrain_map::Vec2;
+use crate::LandmassDiff;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
+
+/// Removes each cell of the `merged` [LandmassDiff] with a [CellConflict] that has any major
+/// conflicts and that `skip` returns `true` for, so that the game uses the LAND record of the
+/// last plugin in the load order instead.
+/// Returns a [SkippedCell] for each removed cell, ordered by `x` and `y` coordinates.
+pub fn skip_major_conflict_cells(
+    merged: &mut LandmassDiff,
+    conflicts: &[CellConflict],
+    skip: impl Fn(Vec2<i32>) -> bool,
+) -> Vec<SkippedCell> {
+    let mut major_conflicts: HashMap<Vec2<i32>, Vec<&CellConflict>> = HashMap::new();
+    for conflict in conflicts
+        .iter()
+        .filter(|conflict| conflict.num_major > 0 && skip(Vec2::from(conflict.coords)))
+    {
+        major_conflicts
+            .entry(Vec2::from(conflict.coords))
+            .or_default()
+            .push(conflict);
+    }
+
+    let mut skipped_cells = Vec::new();
+    for (coords, conflicts) in major_conflicts
+        .into_iter()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+    {
+        let Some(land) = merged.land.remove(&coords) else {
+            continue;
+        };
+
+        let winner = land
+            .plugins
+            .last()
+            .map(|(plugin, _)| plugin.name.clone())
+            .unwrap_or_default();
+
+        warn!(
+            "{} {}",
+            format!(
+                "Skipped cell ({}, {}) with major conflicts.",
+                coords.x, coords.y
+            )
+            .yellow(),
+            format!("The game will use the LAND record of {}", winner.bold()).yellow()
+        );
+
+        skipped_cells.push(SkippedCell {
+            coords: coords.into(),
+            plugins: conflicts
+                .iter()
+                .map(|conflict| conflict.plugin.clone())
+                .unique()
+                .sorted()
+                .collect(),
+            values: conflicts
+                .iter()
+                .map(|conflict| conflict.value.clone())
+                .unique()
+                .sorted()
+                .collect(),
+            num_major: conflicts.iter().map(|conflict| conflict.num_major).sum(),
+            winner,
+        });
+    }
+
+    skipped_cells
+}
+
+/// Removes each cell of the `merged` [LandmassDiff] in the `excluded_cells`, so that the game uses
+/// the LAND record of the last plugin in the load order instead.
+/// Returns an [ExcludedCell] for each removed cell, ordered by `x` and `y` coordinates.
+pub fn exclude_cells(
+    merged: &mut LandmassDiff,
+    excluded_cells: &HashSet<Vec2<i32>>,
+) -> Vec<ExcludedCell> {
+    let mut removed_cells = Vec::new();
+    for coords in excluded_cells
+        .iter()
+        .sorted_by_key(|coords| (coords.x, coords.y))
+    {
+        // Cells unchanged from the masters are removed while cleaning the merged land anyway.
+        let Some(land) = merged.land.remove(coords).filter(|land| land.is_modified()) else {
+            continue;
+        };
+
+        let winner = land
+            .plugins
+            .last()
+            .map(|(plugin, _)| plugin.name.clone())
+            .unwrap_or_default();
+
+        warn!(
+            "{} {}",
+            format!("Excluded cell ({}, {}).", coords.x, coords.y).yellow(),
+            format!("The game will use the LAND record of {}", winner.bold()).yellow()
+        );
+
+        removed_cells.push(ExcludedCell {
+            coords: (*coords).into(),
+            winner,
+        });
+    }
+
+    removed_cells
+}
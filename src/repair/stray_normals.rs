@@ -0,0 +1,96 @@
+use crate::io::report::StrayVertexNormals;
+use crate::land::grid_access::SquareGridIterator;
+use crate::land::terrain_map::LandData;
+use crate::merge::provenance::{Provenance, VertexDecision};
+use crate::LandmassDiff;
+use itertools::Itertools;
+use log::warn;
+use owo_colors::OwoColorize;
+
+/// Removes each difference in the vertex normals of the `merged` [LandmassDiff] at a vertex
+/// without a difference in the height map. Vertex normals are only recalculated where the
+/// height changed, so these differences cannot match the height map of the output.
+/// The plugins of each vertex are read from the [Provenance], if it was recorded.
+/// Returns a [StrayVertexNormals] for each changed cell, ordered by `x` and `y` coordinates.
+pub fn clear_stray_vertex_normals(
+    merged: &mut LandmassDiff,
+    provenance: Option<&Provenance>,
+) -> Vec<StrayVertexNormals> {
+    let mut stray_vertex_normals = Vec::new();
+    for (coords, land) in merged
+        .land
+        .iter_mut()
+        .sorted_by_key(|(coords, _)| (coords.x, coords.y))
+    {
+        let Some(vertex_normals) = land.vertex_normals.as_mut() else {
+            continue;
+        };
+
+        let vertices = vertex_normals
+            .iter_grid()
+            .filter(|vertex| {
+                vertex_normals.has_difference(*vertex)
+                    && !land
+                        .height_map
+                        .as_ref()
+                        .map(|height_map| height_map.has_difference(*vertex))
+                        .unwrap_or(false)
+            })
+            .collect_vec();
+
+        if vertices.is_empty() {
+            continue;
+        }
+
+        vertex_normals.clean_some(vertices.iter().copied());
+
+        let mut plugins = provenance
+            .and_then(|provenance| {
+                let map = provenance.get(*coords, "vertex_normals")?;
+                Some(
+                    vertices
+                        .iter()
+                        .filter_map(|vertex| match map.get(*vertex) {
+                            Some(VertexDecision::Merged { plugin, .. }) => {
+                                Some(provenance.plugin_name(plugin).to_string())
+                            }
+                            _ => None,
+                        })
+                        .unique()
+                        .sorted()
+                        .collect_vec(),
+                )
+            })
+            .unwrap_or_default();
+
+        if plugins.is_empty() {
+            plugins = land
+                .plugins
+                .iter()
+                .filter(|(_, modified_data)| modified_data.contains(LandData::VERTEX_NORMALS))
+                .map(|(plugin, _)| plugin.name.clone())
+                .unique()
+                .collect();
+        }
+
+        warn!(
+            "{} {}",
+            format!(
+                "Cleared {} vertex normals without a height change in cell ({}, {}).",
+                vertices.len(),
+                coords.x,
+                coords.y
+            )
+            .yellow(),
+            format!("Modified by: {}", plugins.join(", ").bold()).yellow()
+        );
+
+        stray_vertex_normals.push(StrayVertexNormals {
+            coords: (*coords).into(),
+            vertices: vertices.iter().map(|vertex| [vertex.x, vertex.y]).collect(),
+            plugins,
+        });
+    }
+
+    stray_vertex_normals
+}
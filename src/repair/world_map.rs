@@ -0,0 +1,57 @@
+use crate::land::grid_access::{GridAccessor2D, Index2D, SquareGridIterator};
+use crate::land::terrain_map::TerrainMap;
+use crate::merge::relative_terrain_map::{IsModified, RelativeTerrainMap};
+use crate::LandmassDiff;
+
+/// The number of height map vertices between each sample of the world map data.
+const WORLD_MAP_SAMPLE_SPACING: usize = 8;
+
+/// The height in world units represented by a single step of the world map data.
+const WORLD_MAP_HEIGHT_SCALE: i32 = 128;
+
+/// Calculates the world map data of a cell from its `height_map` the same way as the
+/// Construction Set. Every 8th vertex is sampled and divided by 128, then saved as an [i8].
+pub fn calculate_world_map_data(height_map: &RelativeTerrainMap<i32, 65>) -> TerrainMap<u8, 9> {
+    let mut world_map_data: TerrainMap<u8, 9> = [[Default::default(); 9]; 9];
+
+    for coords in world_map_data.iter_grid() {
+        let height = height_map.get_value(Index2D::new(
+            coords.x * WORLD_MAP_SAMPLE_SPACING,
+            coords.y * WORLD_MAP_SAMPLE_SPACING,
+        ));
+
+        let value = (height / WORLD_MAP_HEIGHT_SCALE).clamp(i8::MIN as i32, i8::MAX as i32);
+        *world_map_data.get_mut(coords) = value as i8 as u8;
+    }
+
+    world_map_data
+}
+
+/// Replaces the world map data of each cell in the [LandmassDiff] with a modified height map
+/// by [calculate_world_map_data]. Returns the number of cells that were updated.
+pub fn regenerate_world_map_data(merged: &mut LandmassDiff) -> usize {
+    let mut num_regenerated = 0;
+
+    for land in merged.land.values_mut() {
+        let Some(height_map) = land.height_map.as_ref() else {
+            continue;
+        };
+
+        if !height_map.is_modified() {
+            continue;
+        }
+
+        let Some(world_map_data) = land.world_map_data.as_mut() else {
+            continue;
+        };
+
+        let new_data = calculate_world_map_data(height_map);
+        for coords in new_data.iter_grid() {
+            world_map_data.set_value(coords, new_data.get(coords));
+        }
+
+        num_regenerated += 1;
+    }
+
+    num_regenerated
+}
@@ -0,0 +1,543 @@
+use crate::cli::SortOrder;
+use crate::error::MergeError;
+use crate::events::RunContext;
+use crate::io::file_provider::{FileProvider, MemoryFileProvider};
+use crate::io::meta_schema::{GlobalConfig, MetaDatabase};
+use crate::io::parsed_plugins::ParsedPlugins;
+use crate::io::preset::PresetSettings;
+use crate::io::report::Report;
+use crate::io::resolutions::Resolutions;
+use crate::io::save_to_plugin::{convert_landmass_diff_to_landmass, save_plugin};
+use crate::io::signature::PluginSignature;
+use crate::io::validation::{max_height_difference, HEIGHT_TOLERANCE};
+use crate::land::conversions::convert_terrain_map;
+use crate::land::height_map::{
+    calculate_vertex_heights_tes3, calculate_vertex_normals_map, format_height,
+    height_map_encoding_loss, try_calculate_height_map,
+};
+use crate::land::terrain_map::{TerrainMap, Vec2, Vec3};
+use crate::land::textures::{IndexVTEX, KnownTextures, RemappedTextures};
+use crate::pipeline::merge_chunks;
+use crate::repair::cleaning::{clean_known_textures, clean_landmass_diff};
+use crate::repair::outlier_cells::find_outlier_cells;
+use crate::repair::seam_detection::{repair_landmass_seams, repair_landmass_seams_until_fixed};
+use crate::{
+    analyze_conflicts, create_landmasses, create_merged_lands_from_reference, merge_landmass_into,
+    LandmassDiff,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use filetime::FileTime;
+use log::{error, info};
+use owo_colors::OwoColorize;
+use std::path::Path;
+use std::time::Instant;
+use tes3::esp::{
+    FixedString, Header, Landscape, LandscapeFlags, LandscapeTexture, Plugin, TES3Object,
+    TextureIndices, VertexNormals, WorldMapData,
+};
+
+/// The directory the synthetic plugins are stored in.
+const DATA_FILES: &str = "Data Files";
+
+/// The master of the synthetic plugins. It has [GRID_SIZE]x[GRID_SIZE] cells of sloped land.
+const MASTER: &str = "Self Test.esm";
+
+/// A plugin adding a hill to cells `(0, 1)` and `(1, 1)`.
+const PLUGIN_A: &str = "Self Test A.esp";
+
+/// A plugin adding a pit to cells `(1, 1)` and `(2, 1)`, and a new texture to cell `(2, 1)`.
+const PLUGIN_B: &str = "Self Test B.esp";
+
+/// The output file saved by merging the synthetic plugins.
+const OUTPUT: &str = "Self Test Merged.esp";
+
+/// The number of cells along each side of the land of the [MASTER].
+const GRID_SIZE: i32 = 3;
+
+/// The radius in vertices of the hills and pits added by the plugins. Hills and pits are
+/// centered in a cell, so that the edges of the cell are not changed.
+const RADIUS: i32 = 16;
+
+/// Returns the height map of the land of the [MASTER] at `coords`.
+pub(crate) fn master_heights(coords: Vec2<i32>) -> TerrainMap<i32, 65> {
+    let mut terrain = [[0; 65]; 65];
+    for (y, row) in terrain.iter_mut().enumerate() {
+        for (x, height) in row.iter_mut().enumerate() {
+            *height = 16 * (coords.x * 64 + x as i32) + 8 * (coords.y * 64 + y as i32);
+        }
+    }
+
+    terrain
+}
+
+/// Returns the height map of the [MASTER] at `coords` with a hill of `scale` in the center of
+/// the cell, or a pit if `scale` is negative.
+pub(crate) fn hill_heights(coords: Vec2<i32>, scale: i32) -> TerrainMap<i32, 65> {
+    let mut terrain = master_heights(coords);
+    for (y, row) in terrain.iter_mut().enumerate() {
+        for (x, height) in row.iter_mut().enumerate() {
+            let dx = x as i32 - 32;
+            let dy = y as i32 - 32;
+            let distance = dx * dx + dy * dy;
+            if distance < RADIUS * RADIUS {
+                *height += 8 * (scale * (RADIUS * RADIUS - distance) / (RADIUS * RADIUS));
+            }
+        }
+    }
+
+    terrain
+}
+
+/// Creates a [Landscape] at `coords` with the `height_map` and a single `texture`.
+pub(crate) fn landscape(
+    coords: Vec2<i32>,
+    height_map: &TerrainMap<i32, 65>,
+    texture: u16,
+) -> Landscape {
+    Landscape {
+        grid: (coords.x, coords.y),
+        landscape_flags: LandscapeFlags::USES_VERTEX_HEIGHTS_AND_NORMALS
+            | LandscapeFlags::USES_TEXTURES,
+        vertex_heights: Some(calculate_vertex_heights_tes3(height_map)),
+        vertex_normals: Some(VertexNormals {
+            data: Box::new(convert_terrain_map(
+                &calculate_vertex_normals_map(height_map),
+                Vec3::into,
+            )),
+        }),
+        world_map_data: Some(WorldMapData {
+            data: Box::new([[0; 9]; 9]),
+        }),
+        texture_indices: Some(TextureIndices {
+            data: Box::new([[texture; 16]; 16]),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates an `LTEX` record with the `id` and `index`.
+pub(crate) fn landscape_texture(id: &str, index: u32) -> LandscapeTexture {
+    LandscapeTexture {
+        id: id.to_string(),
+        index: Some(index),
+        file_name: Some(format!("{}.dds", id)),
+        ..Default::default()
+    }
+}
+
+/// Saves a plugin with the `masters` and `objects` to the bytes of a plugin file.
+fn plugin_bytes(masters: &[&str], objects: Vec<TES3Object>) -> Result<Vec<u8>> {
+    let mut plugin = Plugin::new();
+    plugin.objects.push(TES3Object::Header(Header {
+        author: FixedString("merged_lands".to_string()),
+        description: FixedString("Self-test fixture".to_string()),
+        masters: (!masters.is_empty())
+            .then(|| masters.iter().map(|name| (name.to_string(), 0)).collect()),
+        ..Default::default()
+    }));
+    plugin.objects.extend(objects);
+
+    plugin
+        .save_bytes()
+        .with_context(|| anyhow!("Unable to save self-test fixture"))
+}
+
+/// Adds the [MASTER], [PLUGIN_A], and [PLUGIN_B] to a [MemoryFileProvider].
+fn create_fixtures() -> Result<MemoryFileProvider> {
+    let files = MemoryFileProvider::new();
+    let data_files = Path::new(DATA_FILES);
+
+    let mut master = vec![TES3Object::LandscapeTexture(landscape_texture(
+        "SelfTest_Grass",
+        0,
+    ))];
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            let coords = Vec2::new(x, y);
+            master.push(TES3Object::Landscape(landscape(
+                coords,
+                &master_heights(coords),
+                1,
+            )));
+        }
+    }
+
+    let plugin_a = [Vec2::new(0, 1), Vec2::new(1, 1)]
+        .into_iter()
+        .map(|coords| TES3Object::Landscape(landscape(coords, &hill_heights(coords, 32), 1)))
+        .collect();
+
+    let plugin_b = vec![
+        TES3Object::LandscapeTexture(landscape_texture("SelfTest_Rock", 0)),
+        TES3Object::Landscape(landscape(
+            Vec2::new(1, 1),
+            &hill_heights(Vec2::new(1, 1), -32),
+            0,
+        )),
+        TES3Object::Landscape(landscape(
+            Vec2::new(2, 1),
+            &hill_heights(Vec2::new(2, 1), -32),
+            1,
+        )),
+    ];
+
+    files.insert(
+        data_files.join(MASTER),
+        plugin_bytes(&[], master)?,
+        FileTime::zero(),
+    );
+    files.insert(
+        data_files.join(PLUGIN_A),
+        plugin_bytes(&[MASTER], plugin_a)?,
+        FileTime::zero(),
+    );
+    files.insert(
+        data_files.join(PLUGIN_B),
+        plugin_bytes(&[MASTER], plugin_b)?,
+        FileTime::zero(),
+    );
+
+    Ok(files)
+}
+
+/// Returns the merged heights of the `merged` [LandmassDiff] at `coords`, if any.
+fn merged_heights(merged: &LandmassDiff, coords: Vec2<i32>) -> Option<TerrainMap<i32, 65>> {
+    merged
+        .land
+        .get(&coords)
+        .and_then(|land| land.height_map.as_ref())
+        .map(|height_map| height_map.to_terrain())
+}
+
+/// Logs the result of the self-test check `name`. Returns `passed`.
+fn check(name: &str, passed: bool) -> bool {
+    if passed {
+        info!("Self-test | {:<40} | {}", name, "passed".green());
+    } else {
+        error!("Self-test | {:<40} | {}", name, "failed".bright_red());
+    }
+
+    passed
+}
+
+/// Parses, merges, and saves the synthetic plugins in `files` like a merge of a load order,
+/// and returns the bytes of the [OUTPUT] file. If `threads` is [Some], each cell is merged as
+/// its own chunk on that many threads.
+fn save_merged_fixtures(
+    files: &MemoryFileProvider,
+    config: &GlobalConfig,
+    threads: Option<usize>,
+    context: &RunContext,
+) -> Result<Vec<u8>> {
+    let data_files = Path::new(DATA_FILES);
+    let plugin_names = [MASTER, PLUGIN_A, PLUGIN_B].map(str::to_string);
+    let parsed_plugins = ParsedPlugins::new(
+        files,
+        data_files,
+        Some(&plugin_names),
+        SortOrder::None,
+        config,
+        &MetaDatabase::default(),
+        context,
+    )?;
+
+    let mut known_textures = KnownTextures::new();
+    let (reference, modded_landmasses, _, _) = create_landmasses(
+        config,
+        &parsed_plugins,
+        &mut known_textures,
+        false,
+        false,
+        context,
+    )?;
+
+    let mut merged = create_merged_lands_from_reference(reference);
+    match threads {
+        None => {
+            for modded_landmass in modded_landmasses.iter() {
+                merge_landmass_into(
+                    &mut merged,
+                    modded_landmass,
+                    None,
+                    &Resolutions::default(),
+                    context,
+                )?;
+            }
+        }
+        Some(threads) => {
+            merge_chunks(
+                &mut merged,
+                &modded_landmasses,
+                1,
+                threads,
+                &config.seams,
+                &mut Report::default(),
+                None,
+                |merged_chunk, chunk_landmass, _, _| {
+                    merge_landmass_into(
+                        merged_chunk,
+                        chunk_landmass,
+                        None,
+                        &Resolutions::default(),
+                        context,
+                    )?;
+                    Ok(())
+                },
+                |_, _| Vec::new(),
+                context,
+            )?;
+        }
+    }
+
+    clean_landmass_diff(&mut merged, &modded_landmasses, &config.seams)?;
+    let remapped_textures = clean_known_textures(&parsed_plugins, &merged, &mut known_textures);
+    let landmass =
+        convert_landmass_diff_to_landmass(&merged, &remapped_textures, config.normal_blend_radius);
+
+    save_plugin(
+        files,
+        data_files,
+        data_files,
+        OUTPUT,
+        SortOrder::None,
+        &landmass,
+        &known_textures,
+        None,
+        &PluginSignature::from_plugins(&parsed_plugins),
+        true,
+        &|_| Ok(()),
+    )?;
+
+    files.read(&data_files.join(OUTPUT))
+}
+
+/// Merges small synthetic plugins in memory and checks the invariants of the merge, so that a
+/// broken build or environment can be told apart from a problem with a load order.
+/// No files are read or saved. Returns an error if any check fails.
+pub fn self_test(context: &RunContext) -> Result<()> {
+    let start = Instant::now();
+
+    let files = create_fixtures()?;
+    let config = GlobalConfig::default();
+    let database = MetaDatabase::bundled()?;
+    let plugin_names = [MASTER, PLUGIN_A, PLUGIN_B].map(str::to_string);
+    let parsed_plugins = ParsedPlugins::new(
+        &files,
+        Path::new(DATA_FILES),
+        Some(&plugin_names),
+        SortOrder::None,
+        &config,
+        &database,
+        context,
+    )?;
+
+    let mut failed = Vec::new();
+    let mut record = |name: &'static str, passed: bool| {
+        if !check(name, passed) {
+            failed.push(name);
+        }
+    };
+
+    record(
+        "Fixtures are parsed",
+        parsed_plugins.masters.len() == 1 && parsed_plugins.plugins.len() == 2,
+    );
+
+    let mut known_textures = KnownTextures::new();
+    let (reference, modded_landmasses, _, _) = create_landmasses(
+        &config,
+        &parsed_plugins,
+        &mut known_textures,
+        false,
+        false,
+        context,
+    )?;
+
+    record(
+        "Reference land is created",
+        reference.land.len() == (GRID_SIZE * GRID_SIZE) as usize,
+    );
+    record(
+        "Plugins differ from the reference",
+        modded_landmasses.len() == 2
+            && modded_landmasses
+                .iter()
+                .all(|landmass| landmass.land.len() == 2),
+    );
+    record("Textures are merged", known_textures.len() == 2);
+
+    let mut merged = create_merged_lands_from_reference(reference);
+    for modded_landmass in modded_landmasses.iter() {
+        merge_landmass_into(
+            &mut merged,
+            modded_landmass,
+            None,
+            &Resolutions::default(),
+            context,
+        )?;
+    }
+
+    record(
+        "Unconflicted changes are kept",
+        merged_heights(&merged, Vec2::new(0, 1)) == Some(hill_heights(Vec2::new(0, 1), 32))
+            && merged_heights(&merged, Vec2::new(2, 1)) == Some(hill_heights(Vec2::new(2, 1), -32))
+            && merged_heights(&merged, Vec2::new(1, 0)) == Some(master_heights(Vec2::new(1, 0))),
+    );
+
+    let rock = known_textures
+        .get("SelfTest_Rock")
+        .map(|texture| IndexVTEX::from(texture.index()));
+    record(
+        "New textures are remapped",
+        rock.is_some()
+            && merged
+                .land
+                .get(&Vec2::new(2, 1))
+                .and_then(|land| land.texture_indices.as_ref())
+                .map(|texture_indices| texture_indices.to_terrain()[0][0])
+                == rock,
+    );
+
+    let seams_converged = repair_landmass_seams_until_fixed(&mut merged, &config.seams).is_ok()
+        && repair_landmass_seams(&mut merged, &config.seams, &mut Vec::new())
+            .map_or(false, |num_seams| num_seams == 0);
+    record("Seams are repaired", seams_converged);
+
+    let mut distant = merged.clone();
+    if let Some(land) = merged.land.get(&Vec2::new(0, 0)) {
+        distant.land.insert(Vec2::new(1000, 1000), land.clone());
+    }
+    record(
+        "Far away cells are outliers",
+        find_outlier_cells(&merged, config.outlier_distance).is_empty()
+            && find_outlier_cells(&distant, config.outlier_distance)
+                == vec![vec![Vec2::new(1000, 1000)]],
+    );
+
+    // Every known texture keeps its index, since the merged land is not cleaned.
+    let landmass = convert_landmass_diff_to_landmass(
+        &merged,
+        &RemappedTextures::from(&vec![true; known_textures.len() + 1]),
+        config.normal_blend_radius,
+    );
+    let round_trip = landmass.land.iter().all(|(coords, land)| {
+        let mut plugin = Plugin::new();
+        plugin.objects.push(TES3Object::Landscape(land.clone()));
+
+        let mut loaded = Plugin::new();
+        let Ok(()) = plugin
+            .save_bytes()
+            .and_then(|bytes| loaded.load_bytes(&bytes))
+        else {
+            return false;
+        };
+
+        let loaded_heights = loaded
+            .objects_of_type::<Landscape>()
+            .next()
+            .and_then(try_calculate_height_map);
+
+        match (loaded_heights, merged_heights(&merged, *coords)) {
+            (Some(actual), Some(expected)) => {
+                max_height_difference(&actual, &expected) <= HEIGHT_TOLERANCE
+            }
+            _ => false,
+        }
+    });
+    record("Merged land is saved and loaded", round_trip);
+
+    let cliff_loss = height_map_encoding_loss(&hill_heights(Vec2::new(0, 0), 4000));
+    record(
+        "Steep heights are reported as clamped",
+        height_map_encoding_loss(&master_heights(Vec2::new(0, 0))).is_none()
+            && cliff_loss.map_or(false, |loss| loss.num_affected_vertices > 0),
+    );
+
+    let report = analyze_conflicts(&parsed_plugins, PresetSettings::default(), context)?;
+    record(
+        "Conflicts are detected",
+        report
+            .conflicts
+            .iter()
+            .any(|conflict| conflict.plugin == PLUGIN_B && conflict.coords == [1, 1])
+            && report
+                .conflicts
+                .iter()
+                .filter(|conflict| conflict.num_minor + conflict.num_major > 0)
+                .all(|conflict| conflict.coords == [1, 1]),
+    );
+    record(
+        "Height conflicts are sized in meters",
+        format_height(64) == "64 units (~0.9 m)"
+            && report
+                .conflicts
+                .iter()
+                .filter(|conflict| conflict.value == "height_map")
+                .all(|conflict| conflict.max_difference_meters.map_or(false, |m| m > 0.)),
+    );
+
+    // Each merge iterates new hash maps in a different order, which must not change the output.
+    let first = save_merged_fixtures(&files, &config, None, context)?;
+    let second = save_merged_fixtures(&files, &config, None, context)?;
+    record(
+        "Merged output is deterministic",
+        !first.is_empty() && first == second,
+    );
+
+    // Chunks finish in any order on many threads, which must not change the output either.
+    let single_thread = save_merged_fixtures(&files, &config, Some(1), context)?;
+    let mut same_for_threads = !single_thread.is_empty();
+    for threads in [2, 8] {
+        same_for_threads &=
+            save_merged_fixtures(&files, &config, Some(threads), context)? == single_thread;
+    }
+    record("Same output on 1, 2, and 8 threads", same_for_threads);
+
+    info!("Time Elapsed: {:?}", Instant::now().duration_since(start));
+
+    if !failed.is_empty() {
+        bail!(MergeError::SelfTestFailed(failed.join(", ")));
+    }
+
+    info!("{}", "Self-test passed".green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::with_main_thread_stack;
+
+    #[test]
+    fn merged_fixtures_are_saved_the_same_way_twice() {
+        with_main_thread_stack(|| {
+            let files = create_fixtures().unwrap();
+            let config = GlobalConfig::default();
+            let context = RunContext::default();
+
+            let first = save_merged_fixtures(&files, &config, None, &context).unwrap();
+            let second = save_merged_fixtures(&files, &config, None, &context).unwrap();
+            assert!(!first.is_empty());
+            assert_eq!(first, second);
+        });
+    }
+
+    #[test]
+    fn merged_fixtures_are_saved_the_same_way_on_any_number_of_threads() {
+        // With a single thread, the chunks are merged on the current thread.
+        with_main_thread_stack(|| {
+            let files = create_fixtures().unwrap();
+            let config = GlobalConfig::default();
+
+            let single_thread =
+                save_merged_fixtures(&files, &config, Some(1), &RunContext::default()).unwrap();
+            assert!(!single_thread.is_empty());
+            for threads in [2, 8] {
+                let output =
+                    save_merged_fixtures(&files, &config, Some(threads), &RunContext::default())
+                        .unwrap();
+                assert_eq!(output, single_thread, "{} threads", threads);
+            }
+        });
+    }
+}
@@ -0,0 +1,147 @@
+//! Builders of the plugins and land used by the tests of each module.
+//! The land is generated with the same functions as the fixtures of the [crate::self_test].
+
+use crate::io::meta_schema::PluginMeta;
+use crate::io::parsed_plugins::{ParsedPlugin, ParsedPlugins};
+use crate::io::report::{LoadOrderEntry, OrderSource};
+use crate::land::landscape_diff::LandscapeDiff;
+use crate::land::terrain_map::{LandData, TerrainMap, Vec2};
+use crate::{Landmass, LandmassDiff};
+use std::sync::Arc;
+use tes3::esp::{Landscape, TES3Object};
+
+pub(crate) use crate::self_test::{hill_heights, landscape, landscape_texture, master_heights};
+
+/// The cell used by tests that only need one cell.
+pub(crate) const CELL: Vec2<i32> = Vec2 { x: 0, y: 0 };
+
+/// Calls `f` on a thread with the stack size of a main thread and returns its result. Debug
+/// builds of the merge use more stack than the threads of tests have.
+pub(crate) fn with_main_thread_stack<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn_scoped(scope, f)
+            .expect("safe")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Builds a [ParsedPlugin] with the records and [PluginMeta] of a test.
+pub(crate) struct PluginBuilder {
+    plugin: ParsedPlugin,
+}
+
+impl PluginBuilder {
+    /// Creates a [PluginBuilder] of an empty plugin named `name`.
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            plugin: ParsedPlugin::empty(name),
+        }
+    }
+
+    /// Uses the `meta` instead of the default [PluginMeta].
+    pub(crate) fn meta(mut self, meta: PluginMeta) -> Self {
+        self.plugin.meta = meta;
+        self
+    }
+
+    /// Adds the `record` after the records already added.
+    pub(crate) fn record(mut self, record: TES3Object) -> Self {
+        self.plugin.records.objects.push(record);
+        self
+    }
+
+    pub(crate) fn build(self) -> Arc<ParsedPlugin> {
+        Arc::new(self.plugin)
+    }
+}
+
+/// Returns an empty [ParsedPlugin] named `name`.
+pub(crate) fn plugin(name: &str) -> Arc<ParsedPlugin> {
+    PluginBuilder::new(name).build()
+}
+
+/// Returns [ParsedPlugins] of the `masters` and `plugins`, with each listed in that load order.
+pub(crate) fn parsed_plugins(
+    masters: Vec<Arc<ParsedPlugin>>,
+    plugins: Vec<Arc<ParsedPlugin>>,
+) -> ParsedPlugins {
+    ParsedPlugins {
+        load_order: masters
+            .iter()
+            .chain(plugins.iter())
+            .map(|plugin| LoadOrderEntry {
+                plugin: plugin.name.clone(),
+                source: OrderSource::Listed,
+            })
+            .collect(),
+        masters,
+        plugins,
+    }
+}
+
+/// Builds a [Landmass] or [LandmassDiff] with the LAND records of a test.
+pub(crate) struct LandmassBuilder {
+    plugin: Arc<ParsedPlugin>,
+    land: Vec<Landscape>,
+}
+
+impl LandmassBuilder {
+    /// Creates a [LandmassBuilder] of the land of the `plugin`.
+    pub(crate) fn new(plugin: Arc<ParsedPlugin>) -> Self {
+        Self {
+            plugin,
+            land: Vec::new(),
+        }
+    }
+
+    /// Adds the `land`, replacing any land already added at its coordinates.
+    pub(crate) fn land(mut self, land: Landscape) -> Self {
+        self.land.push(land);
+        self
+    }
+
+    /// Adds land at `coords` with the `height_map` and a single `texture`.
+    pub(crate) fn heights(
+        self,
+        coords: Vec2<i32>,
+        height_map: &TerrainMap<i32, 65>,
+        texture: u16,
+    ) -> Self {
+        self.land(landscape(coords, height_map, texture))
+    }
+
+    /// Adds land at `coords` with a hill of `scale`, or a pit if `scale` is negative.
+    /// See [hill_heights].
+    pub(crate) fn hill(self, coords: Vec2<i32>, scale: i32, texture: u16) -> Self {
+        self.heights(coords, &hill_heights(coords, scale), texture)
+    }
+
+    /// Returns the [Landmass] of the LAND records.
+    pub(crate) fn build(self) -> Landmass {
+        let mut landmass = Landmass::new(self.plugin.clone());
+        for land in self.land.iter() {
+            landmass.insert_land(Vec2::new(land.grid.0, land.grid.1), &self.plugin, land);
+        }
+
+        landmass
+    }
+
+    /// Returns the [LandmassDiff] of the LAND records against no reference, with only the
+    /// `included_data`.
+    pub(crate) fn build_diff(self, included_data: LandData) -> LandmassDiff {
+        let mut landmass = LandmassDiff::new(self.plugin);
+        for land in self.land.iter() {
+            landmass.insert_land(LandscapeDiff::from_difference(
+                land,
+                None,
+                included_data,
+                None,
+            ));
+        }
+
+        landmass
+    }
+}
@@ -0,0 +1,241 @@
+use hashbrown::HashMap;
+use itertools::Itertools;
+use log::info;
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+/// The number of plugins listed by [Timings::log] as the slowest plugins.
+const NUM_SLOWEST_PLUGINS: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A phase of a run timed by [Timings::time_phase], in the order the phases are logged.
+pub enum TimedPhase {
+    /// The plugins are parsed and checked for problems.
+    Parsing,
+    /// The LAND of each plugin is compared to the LAND of the masters.
+    Diffing,
+    /// The plugins are merged in load order.
+    Merging,
+    /// The seams of the merged land are repaired.
+    SeamRepair,
+    /// The conflicts between each plugin and the merged land are found.
+    Summarizing,
+    /// The images of the conflicts and the maps are saved.
+    Imaging,
+    /// Unchanged cells and unused textures are removed from the merged land.
+    Cleaning,
+    /// The merged land is saved to the output and validated.
+    Saving,
+}
+
+impl TimedPhase {
+    /// Each [TimedPhase], in the order the phases are logged.
+    const ALL: [TimedPhase; 8] = [
+        TimedPhase::Parsing,
+        TimedPhase::Diffing,
+        TimedPhase::Merging,
+        TimedPhase::SeamRepair,
+        TimedPhase::Summarizing,
+        TimedPhase::Imaging,
+        TimedPhase::Cleaning,
+        TimedPhase::Saving,
+    ];
+
+    /// Returns the name of the [TimedPhase] in the log.
+    fn name(&self) -> &'static str {
+        match self {
+            TimedPhase::Parsing => "Parsing",
+            TimedPhase::Diffing => "Diffing",
+            TimedPhase::Merging => "Merging",
+            TimedPhase::SeamRepair => "Seam Repair",
+            TimedPhase::Summarizing => "Summarizing",
+            TimedPhase::Imaging => "Imaging",
+            TimedPhase::Cleaning => "Cleaning",
+            TimedPhase::Saving => "Saving",
+        }
+    }
+}
+
+/// The time spent in each [TimedPhase] of a run, and on each plugin in each [TimedPhase].
+/// The time of a phase does not include the time of any phase timed while it was running, so
+/// that the phases add up to the time of the run. Phases timed on other threads than the thread
+/// of the run, e.g. by [map_in_order], are nested in the phases running on the thread of the run.
+/// While phases run on several threads at once, the time is added to the most nested of them.
+///
+/// [map_in_order]: crate::merge::parallel::map_in_order
+pub struct Timings {
+    /// The thread of the run.
+    thread: ThreadId,
+    recorded: Mutex<RecordedTimings>,
+}
+
+#[derive(Default)]
+/// The times recorded by [Timings] so far.
+struct RecordedTimings {
+    phases: HashMap<TimedPhase, Duration>,
+    plugins: HashMap<(TimedPhase, String), Duration>,
+    /// The phases running on each thread, from the outermost to the innermost phase.
+    running: HashMap<ThreadId, Vec<TimedPhase>>,
+    /// When the time of the running phases was last added to the `phases`.
+    since: Option<Instant>,
+}
+
+impl Default for Timings {
+    /// Creates empty [Timings] of a run on the current thread.
+    fn default() -> Self {
+        Self {
+            thread: std::thread::current().id(),
+            recorded: Mutex::new(RecordedTimings::default()),
+        }
+    }
+}
+
+impl RecordedTimings {
+    /// Adds the time since it was last added to the innermost running phase. The phases
+    /// running on other threads than the `thread` of the run are nested in the phases running on
+    /// the `thread`.
+    fn add_elapsed(&mut self, thread: ThreadId) {
+        let now = Instant::now();
+        let depth = self.running.get(&thread).map_or(0, Vec::len);
+        let innermost = self
+            .running
+            .iter()
+            .filter_map(|(id, running)| {
+                let nested = if *id == thread { 0 } else { depth };
+                running.last().map(|phase| (nested + running.len(), *phase))
+            })
+            .max();
+
+        if let (Some(since), Some((_, phase))) = (self.since, innermost) {
+            *self.phases.entry(phase).or_default() += now.duration_since(since);
+        }
+
+        self.since = Some(now);
+    }
+}
+
+impl Timings {
+    /// Runs `f` and adds the time it took to the `phase`, without the time of any phase timed by
+    /// `f`, including the phases timed on other threads while `f` runs.
+    pub fn time_phase<T>(&self, phase: TimedPhase, f: impl FnOnce() -> T) -> T {
+        let thread = std::thread::current().id();
+        {
+            let mut recorded = self.recorded.lock().expect("safe");
+            recorded.add_elapsed(self.thread);
+            recorded.running.entry(thread).or_default().push(phase);
+        }
+
+        let result = f();
+
+        let mut recorded = self.recorded.lock().expect("safe");
+        recorded.add_elapsed(self.thread);
+        if let Some(running) = recorded.running.get_mut(&thread) {
+            running.pop();
+            if running.is_empty() {
+                recorded.running.remove(&thread);
+            }
+        }
+
+        result
+    }
+
+    /// Runs `f` and adds the time it took to the `plugin` in the `phase`. This does not add to the
+    /// time of the `phase` itself, which must be timed by [Timings::time_phase].
+    pub fn time_plugin<T>(&self, phase: TimedPhase, plugin: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+
+        *self
+            .recorded
+            .lock()
+            .expect("safe")
+            .plugins
+            .entry((phase, plugin.to_string()))
+            .or_default() += start.elapsed();
+
+        result
+    }
+
+    /// Logs a table of the time of each [TimedPhase] as a part of the `total` time of the run,
+    /// followed by the plugins that took the longest in any phase.
+    pub fn log(&self, total: Duration) {
+        let recorded = self.recorded.lock().expect("safe");
+        let percent = |duration: Duration| {
+            100. * duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON)
+        };
+
+        info!("{:<15} | {:>10} | {:>6}", "Phase", "Seconds", "%");
+        let mut timed = Duration::ZERO;
+        for phase in TimedPhase::ALL {
+            let Some(duration) = recorded.phases.get(&phase) else {
+                continue;
+            };
+
+            timed += *duration;
+            info!(
+                "{:<15} | {:>10.3} | {:>5.1}%",
+                phase.name(),
+                duration.as_secs_f64(),
+                percent(*duration)
+            );
+        }
+
+        let other = total.saturating_sub(timed);
+        info!(
+            "{:<15} | {:>10.3} | {:>5.1}%",
+            "Other",
+            other.as_secs_f64(),
+            percent(other)
+        );
+
+        if recorded.plugins.is_empty() {
+            return;
+        }
+
+        info!("Slowest plugins:");
+        for ((phase, plugin), duration) in recorded
+            .plugins
+            .iter()
+            .sorted_by(|(lhs_key, lhs), (rhs_key, rhs)| rhs.cmp(lhs).then(lhs_key.cmp(rhs_key)))
+            .take(NUM_SLOWEST_PLUGINS)
+        {
+            info!(
+                "{:<15} | {:>10.3} | {}",
+                phase.name(),
+                duration.as_secs_f64(),
+                plugin
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::parallel::map_in_order;
+
+    #[test]
+    fn phases_on_worker_threads_are_nested_in_the_phases_of_the_run() {
+        let timings = Timings::default();
+        let start = Instant::now();
+        timings.time_phase(TimedPhase::Merging, || {
+            map_in_order(vec![(); 4], 4, |_| {
+                timings.time_phase(TimedPhase::SeamRepair, || {
+                    std::thread::sleep(Duration::from_millis(20))
+                })
+            })
+        });
+        let total = start.elapsed();
+
+        let recorded = timings.recorded.lock().expect("safe");
+        let seam_repair = recorded.phases[&TimedPhase::SeamRepair];
+        let merging = recorded
+            .phases
+            .get(&TimedPhase::Merging)
+            .copied()
+            .unwrap_or_default();
+        assert!(seam_repair >= Duration::from_millis(20));
+        assert!(seam_repair + merging <= total);
+    }
+}
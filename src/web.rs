@@ -0,0 +1,78 @@
+use crate::analyze_conflicts;
+use crate::cli::SortOrder;
+use crate::events::RunContext;
+use crate::io::file_provider::MemoryFileProvider;
+use crate::io::meta_schema::{GlobalConfig, MetaDatabase};
+use crate::io::parsed_plugins::{is_esm, is_esp, ParsedPlugins};
+use crate::io::preset::PresetSettings;
+use filetime::FileTime;
+use std::path::Path;
+use wasm_bindgen::prelude::*;
+
+/// The directory the files dropped onto the web conflict viewer are stored in.
+const DATA_FILES: &str = "Data Files";
+
+#[wasm_bindgen]
+#[derive(Default)]
+/// The files dropped onto the web conflict viewer. Files are kept in a [MemoryFileProvider],
+/// since a web page cannot read the `Data Files` directory.
+pub struct ConflictViewer {
+    /// The plugin files and any meta files next to them.
+    files: MemoryFileProvider,
+    /// The name of each plugin file, in load order.
+    plugin_names: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl ConflictViewer {
+    #[wasm_bindgen(constructor)]
+    /// Creates a [ConflictViewer] without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file `name` with the `bytes`. Plugins are added in load order.
+    /// A plugin with the same name, ignoring case, is replaced and keeps its place in the load order.
+    /// Other files, e.g. meta files, are read while parsing the plugin they belong to.
+    pub fn add_file(&mut self, name: &str, bytes: Vec<u8>) {
+        let data_files = Path::new(DATA_FILES);
+        if is_esm(name) || is_esp(name) {
+            match self
+                .plugin_names
+                .iter_mut()
+                .find(|plugin_name| plugin_name.eq_ignore_ascii_case(name))
+            {
+                Some(plugin_name) => {
+                    self.files.remove(&data_files.join(&plugin_name));
+                    *plugin_name = name.to_string();
+                }
+                None => self.plugin_names.push(name.to_string()),
+            }
+        }
+
+        self.files
+            .insert(data_files.join(name), bytes, FileTime::zero());
+    }
+
+    /// Returns the [crate::io::report::Report] of the conflicts between the plugin files as JSON.
+    pub fn analyze(&self) -> Result<String, JsValue> {
+        let to_js_error = |e: anyhow::Error| JsValue::from_str(&format!("{:?}", e));
+
+        let context = RunContext::default();
+        let database = MetaDatabase::bundled().map_err(to_js_error)?;
+        let parsed_plugins = ParsedPlugins::new(
+            &self.files,
+            Path::new(DATA_FILES),
+            Some(&self.plugin_names),
+            SortOrder::None,
+            &GlobalConfig::default(),
+            &database,
+            &context,
+        )
+        .map_err(to_js_error)?;
+
+        let report = analyze_conflicts(&parsed_plugins, PresetSettings::default(), &context)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        serde_json::to_string(&report).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}